@@ -0,0 +1,51 @@
+use ark_std::rand::SeedableRng;
+use ark_std::UniformRand;
+use circle_plonk::field::{plain_from_m31, plain_to_m31, to_m31, to_m31_slice, FM31};
+use std::time::Instant;
+
+// cargo bench --bench field
+fn main() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let values = (0..1 << 22)
+        .map(|_| FM31::rand(&mut prng))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let expected = values.iter().map(to_m31).collect::<Vec<_>>();
+    println!(
+        "to_m31 for {} elements: {:?}",
+        values.len(),
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let converted = to_m31_slice(&values);
+    println!(
+        "to_m31_slice for {} elements: {:?}",
+        values.len(),
+        start.elapsed()
+    );
+
+    assert_eq!(converted, expected);
+
+    let plain = expected
+        .iter()
+        .map(|&v| plain_from_m31(v))
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+    let converted = plain.iter().map(plain_to_m31).collect::<Vec<_>>();
+    println!(
+        "plain_to_m31 for {} elements: {:?}",
+        values.len(),
+        start.elapsed()
+    );
+    assert_eq!(converted, expected);
+
+    let start = Instant::now();
+    let product = values.iter().product::<FM31>();
+    println!("FM31 product: {:?}", start.elapsed());
+    let start = Instant::now();
+    let plain_product = plain.iter().product();
+    println!("FM31Plain product: {:?}", start.elapsed());
+    assert_eq!(plain_to_m31(&plain_product), to_m31(&product));
+}
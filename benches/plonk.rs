@@ -0,0 +1,52 @@
+use circle_plonk::bench_utils::{bench_circuit, bench_r1cs, log_sizes};
+use circle_plonk::circuit::Mode;
+use circle_plonk::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use circle_plonk::proof_system::{prove_plonk, verify_plonk};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use stwo_prover::core::prover::StarkProof;
+use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
+use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+// cargo bench --features bench-utils --bench plonk
+fn bench_plonk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plonk");
+    group.sample_size(10);
+
+    for log_size in log_sizes() {
+        group.bench_function(BenchmarkId::new("circuit_generation", log_size), |b| {
+            b.iter_batched(
+                || bench_r1cs(log_size, 0),
+                |r1cs| generate_circuit(r1cs, Mode::PROVE).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+
+        let circuit = bench_circuit(log_size, 0);
+        group.bench_function(BenchmarkId::new("trace_conversion", log_size), |b| {
+            b.iter(|| PlonkCircuitTrace::try_from(&circuit).unwrap())
+        });
+
+        group.bench_function(BenchmarkId::new("prove", log_size), |b| {
+            b.iter_batched(
+                || PlonkCircuitTrace::try_from(&circuit).unwrap(),
+                prove_plonk,
+                BatchSize::PerIteration,
+            )
+        });
+
+        // the verifier consumes the proof, which is decoded afresh for each run
+        let (_, proof) = prove_plonk(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        let json = serde_json::to_vec(&proof).unwrap();
+        group.bench_function(BenchmarkId::new("verify", log_size), |b| {
+            b.iter_batched(
+                || serde_json::from_slice::<StarkProof<BWSSha256MerkleHasher>>(&json).unwrap(),
+                |proof| verify_plonk(log_size, &circuit.input_maps, proof).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_plonk);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+#ifndef CIRCLE_PLONK_H
+#define CIRCLE_PLONK_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum CirclePlonkStatus {
+  CIRCLE_PLONK_STATUS_OK = 0,
+  // a null pointer, or an input value that is not in M31
+  CIRCLE_PLONK_STATUS_INVALID_ARGUMENT = 1,
+  CIRCLE_PLONK_STATUS_INVALID_INDEX = 2,
+  // malformed, not for the index, or not satisfying the constraints
+  CIRCLE_PLONK_STATUS_INVALID_WITNESS = 3,
+  CIRCLE_PLONK_STATUS_INVALID_KEY = 4,
+  CIRCLE_PLONK_STATUS_INVALID_PROOF = 5,
+  // the proof is well-formed but does not verify against the key and the inputs
+  CIRCLE_PLONK_STATUS_VERIFICATION_FAILED = 6,
+  CIRCLE_PLONK_STATUS_INTERNAL_ERROR = 7,
+} CirclePlonkStatus;
+
+// A buffer allocated by this library, to be released with `circle_plonk_buffer_free`.
+typedef struct CirclePlonkBuffer {
+  uint8_t *data;
+  size_t len;
+} CirclePlonkBuffer;
+
+// Proves a witness of a circuit, given as the compressed index written by `circle-plonk setup`
+// and a `Witness`, and stores the proof in `proof_out`.
+//
+// # Safety
+// `index` and `witness` must point to `index_len` and `witness_len` readable bytes, and
+// `proof_out` to a writable `CirclePlonkBuffer`.
+CirclePlonkStatus circle_plonk_prove(const uint8_t *index,
+                                     size_t index_len,
+                                     const uint8_t *witness,
+                                     size_t witness_len,
+                                     CirclePlonkBuffer *proof_out);
+
+// Verifies a proof written by `circle_plonk_prove` against a verification key and the values of
+// the inputs, in the order of the key.
+//
+// # Safety
+// `key`, `proof` and `values` must point to `key_len` and `proof_len` readable bytes and
+// `values_len` readable values.
+CirclePlonkStatus circle_plonk_verify(const uint8_t *key,
+                                      size_t key_len,
+                                      const uint8_t *proof,
+                                      size_t proof_len,
+                                      const uint32_t *values,
+                                      size_t values_len);
+
+// Releases a buffer returned by this library.
+//
+// # Safety
+// `buffer` must have been returned by this library and not been freed yet.
+void circle_plonk_buffer_free(CirclePlonkBuffer buffer);
+
+#endif /* CIRCLE_PLONK_H */
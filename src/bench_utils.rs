@@ -0,0 +1,59 @@
+use crate::circuit::{Circuit, Mode};
+use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use crate::testing::{BenchCircuit, BenchCircuitConfig};
+use std::ops::RangeInclusive;
+
+// The synthetic circuits of the benchmarks in `benches/`, for other harnesses to measure the same
+// workloads:
+//
+//     cargo bench --features bench-utils --bench plonk
+//
+// The sizes are set with `CIRCLE_PLONK_BENCH_LOG_SIZES`, as `N` or `A..B` with B included.
+
+pub const DEFAULT_LOG_SIZES: RangeInclusive<u32> = 10..=14;
+
+pub fn log_sizes() -> RangeInclusive<u32> {
+    let Ok(value) = std::env::var("CIRCLE_PLONK_BENCH_LOG_SIZES") else {
+        return DEFAULT_LOG_SIZES;
+    };
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .unwrap_or_else(|_| panic!("invalid log size {}", v))
+    };
+    match value.split_once("..") {
+        Some((start, end)) => parse(start)..=parse(end),
+        None => parse(&value)..=parse(&value),
+    }
+}
+
+// The R1CS of a circuit of `2^log_size` rows or one less, with the default mix of native
+// multiplications and additions.
+pub fn bench_r1cs(log_size: u32, seed: u64) -> BenchCircuit {
+    let config = BenchCircuitConfig::default().with_target_rows(1 << log_size);
+    BenchCircuit { config, seed }
+}
+
+// The converted circuit of `bench_r1cs`, with its witness, padded to `2^log_size` rows.
+pub fn bench_circuit(log_size: u32, seed: u64) -> Circuit {
+    let mut circuit = generate_circuit(bench_r1cs(log_size, seed), Mode::PROVE)
+        .unwrap()
+        .circuit;
+    circuit.pad_to_next_power_of_2();
+    circuit
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bench_utils::{bench_circuit, log_sizes, DEFAULT_LOG_SIZES};
+
+    #[test]
+    fn test_bench_utils() {
+        if std::env::var("CIRCLE_PLONK_BENCH_LOG_SIZES").is_err() {
+            assert_eq!(log_sizes(), DEFAULT_LOG_SIZES);
+        }
+        let circuit = bench_circuit(10, 0);
+        assert_eq!(circuit.num_rows, 1 << 10);
+        assert!(circuit.is_constraint_satisfied());
+    }
+}
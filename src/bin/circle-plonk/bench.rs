@@ -0,0 +1,121 @@
+use crate::CliResult;
+use circle_plonk::circuit::Mode;
+use circle_plonk::config::ProverConfig;
+use circle_plonk::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use circle_plonk::proof_system::{verify_plonk, PlonkProof};
+use circle_plonk::testing::{BenchCircuit, BenchCircuitConfig};
+use clap::Args;
+use serde::Serialize;
+use std::ops::RangeInclusive;
+use std::time::Instant;
+
+#[derive(Args)]
+pub struct BenchArgs {
+    #[arg(
+        long,
+        default_value = "16..20",
+        value_parser = parse_log_sizes,
+        help = "The log sizes of the circuits, as `N` or `A..B` with B included"
+    )]
+    log_size: RangeInclusive<u32>,
+    #[arg(long, help = "Prints the results as JSON instead of a table")]
+    json: bool,
+}
+
+fn parse_log_sizes(s: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse = |v: &str| v.trim().parse::<u32>().map_err(|err| err.to_string());
+    let range = match s.split_once("..") {
+        Some((start, end)) => parse(start)?..=parse(end)?,
+        None => parse(s)?..=parse(s)?,
+    };
+    if range.is_empty() || *range.end() >= 30 {
+        return Err(format!("invalid range of log sizes {}", s));
+    }
+    Ok(range)
+}
+
+#[derive(Serialize)]
+struct BenchResult {
+    log_size: u32,
+    num_rows: usize,
+    convert_ms: u128,
+    prove_ms: u128,
+    verify_ms: u128,
+    proof_bytes: u64,
+    // the peak resident memory of the process so far, where the system reports it
+    peak_memory_mib: Option<u64>,
+}
+
+fn peak_memory_mib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kib >> 10)
+}
+
+fn bench(log_size: u32) -> Result<BenchResult, Box<dyn std::error::Error>> {
+    let config = BenchCircuitConfig::default().with_target_rows(1 << log_size);
+    let circuit = BenchCircuit { config, seed: 0 };
+
+    let start = Instant::now();
+    let mut circuit = generate_circuit(circuit, Mode::PROVE)?.circuit;
+    let convert_ms = start.elapsed().as_millis();
+
+    let start = Instant::now();
+    let proof = PlonkProof::prove(&mut circuit)?;
+    let prove_ms = start.elapsed().as_millis();
+
+    let proof_bytes = bincode::serialized_size(&proof)?;
+
+    let start = Instant::now();
+    verify_plonk(proof.log_n_rows, &proof.inputs, proof.proof)?;
+    let verify_ms = start.elapsed().as_millis();
+
+    Ok(BenchResult {
+        log_size,
+        num_rows: circuit.num_rows,
+        convert_ms,
+        prove_ms,
+        verify_ms,
+        proof_bytes,
+        peak_memory_mib: peak_memory_mib(),
+    })
+}
+
+pub fn run(args: BenchArgs, config: &ProverConfig) -> CliResult {
+    for log_size in args.log_size.clone() {
+        config.check_memory(log_size)?;
+    }
+    if !args.json {
+        println!(
+            "{:>8} {:>10} {:>12} {:>10} {:>10} {:>12} {:>10}",
+            "log size", "rows", "convert ms", "prove ms", "verify ms", "proof bytes", "peak MiB"
+        );
+    }
+
+    let mut results = vec![];
+    for log_size in args.log_size {
+        let result = bench(log_size)?;
+        if !args.json {
+            let peak = result
+                .peak_memory_mib
+                .map_or_else(|| "-".to_string(), |v| v.to_string());
+            println!(
+                "{:>8} {:>10} {:>12} {:>10} {:>10} {:>12} {:>10}",
+                result.log_size,
+                result.num_rows,
+                result.convert_ms,
+                result.prove_ms,
+                result.verify_ms,
+                result.proof_bytes,
+                peak
+            );
+        }
+        results.push(result);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::{CliResult, PublicValues};
+use circle_plonk::proof_system::bitcoin::{BitcoinArtifacts, ScriptLimits, VerifierHints};
+use circle_plonk::proof_system::{PlonkProof, VerificationKey};
+use clap::Args;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct BitcoinArgs {
+    #[arg(long, help = "The verification key of the circuit")]
+    vk: PathBuf,
+    #[arg(long, help = "The proof, as written by `prove`")]
+    proof: PathBuf,
+    #[arg(long, help = "The values of the inputs as a JSON array")]
+    public: PathBuf,
+    #[arg(
+        long,
+        default_value_t = ScriptLimits::default().max_items_per_chunk,
+        help = "The number of stack items of each input"
+    )]
+    max_items: usize,
+    #[arg(long, help = "Where to write the chunks, as JSON")]
+    out: PathBuf,
+    #[arg(
+        long,
+        help = "Where to write the transcript and the query positions of the verifier, as JSON"
+    )]
+    hints: Option<PathBuf>,
+}
+
+// The chunks with every byte string in hex.
+#[derive(Serialize)]
+struct HexChunk {
+    state_in: String,
+    items: Vec<String>,
+    state_out: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn run(args: BitcoinArgs) -> CliResult {
+    let key: VerificationKey = bincode::deserialize_from(BufReader::new(File::open(&args.vk)?))?;
+    let proof: PlonkProof = bincode::deserialize_from(BufReader::new(File::open(&args.proof)?))?;
+    let PublicValues(values) = serde_json::from_reader(BufReader::new(File::open(&args.public)?))?;
+    if values.len() != key.input_wires.len() || proof.log_n_rows != key.log_n_rows {
+        return Err("the proof and the inputs do not match the verification key".into());
+    }
+
+    let limits = ScriptLimits::default().with_max_items_per_chunk(args.max_items);
+    let artifacts = BitcoinArtifacts::new(&key, &values, &proof, limits)?;
+    let chunks = artifacts
+        .chunks
+        .iter()
+        .map(|chunk| HexChunk {
+            state_in: to_hex(&chunk.state_in),
+            items: chunk.items.iter().map(|item| to_hex(item)).collect(),
+            state_out: to_hex(&chunk.state_out),
+        })
+        .collect::<Vec<_>>();
+
+    let mut writer = BufWriter::new(File::create(&args.out)?);
+    serde_json::to_writer_pretty(&mut writer, &chunks)?;
+    writer.flush()?;
+    eprintln!("split the proof over {} inputs", chunks.len());
+
+    if let Some(path) = args.hints {
+        let hints = VerifierHints::new(&key, &values, proof.proof)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &hints)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::CliResult;
+use circle_plonk::circuit::FastHashMap;
+use circle_plonk::field::{to_m31, FM31};
+use circle_plonk::from_r1cs::circom::{load_r1cs_and_witness, sym_read};
+use circle_plonk::from_r1cs::r1cs_constraint_processor::check_circuit;
+use clap::Args;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    #[arg(long, help = "The constraint system, as written by `circom --r1cs`")]
+    r1cs: PathBuf,
+    #[arg(
+        long,
+        help = "The witness, as computed by the witness generator of the circuit"
+    )]
+    wtns: PathBuf,
+    #[arg(long, help = "The signal names, as written by `circom --sym`")]
+    sym: Option<PathBuf>,
+}
+
+// Prints the terms of one side of a constraint as `coeff * signal (= value)`.
+fn print_terms(
+    side: &str,
+    terms: &[(usize, FM31)],
+    witness: &[FM31],
+    names: &FastHashMap<usize, String>,
+) {
+    println!("  {}:", side);
+    for &(wire, coeff) in terms.iter() {
+        let name = names
+            .get(&wire)
+            .cloned()
+            .unwrap_or_else(|| format!("wire {}", wire));
+        println!(
+            "    {} * {} (= {})",
+            to_m31(&coeff),
+            name,
+            witness.get(wire).map(to_m31).unwrap_or_default()
+        );
+    }
+}
+
+pub fn run(args: CheckArgs) -> CliResult {
+    let r1cs = BufReader::new(File::open(&args.r1cs)?);
+    let wtns = BufReader::new(File::open(&args.wtns)?);
+    let circom = load_r1cs_and_witness(r1cs, wtns)?;
+    let names = match &args.sym {
+        Some(path) => sym_read(BufReader::new(File::open(path)?))?,
+        None => FastHashMap::default(),
+    };
+
+    let constraints = circom.r1cs.constraints.clone();
+    let witness = circom.witness.clone().unwrap_or_default();
+    let (_, report) = check_circuit(circom)?;
+    println!("{}", report);
+
+    if let Some(first) = report.unsatisfied_constraints.first() {
+        if let Some((a, b, c)) = constraints.get(first.constraint) {
+            println!("first unsatisfied constraint:");
+            print_terms("A", a, &witness, &names);
+            print_terms("B", b, &witness, &names);
+            print_terms("C", c, &witness, &names);
+        }
+    }
+
+    if report.is_satisfied() {
+        Ok(())
+    } else {
+        Err("the witness does not satisfy the circuit".into())
+    }
+}
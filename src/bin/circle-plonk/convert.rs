@@ -0,0 +1,70 @@
+use crate::{CliResult, PublicValues};
+use circle_plonk::proof_system::stwo_json::{plonk_proof_from_stwo_json, to_stwo_json};
+use circle_plonk::proof_system::{PlonkProof, VerificationKey};
+use clap::{Args, ValueEnum};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ProofFormat {
+    // the proofs of `prove`, with their statement
+    Bincode,
+    // the JSON of the stwo tooling, with the STARK proof only
+    StwoJson,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    #[arg(long, help = "The proof to convert")]
+    proof: PathBuf,
+    #[arg(long, value_enum, help = "The format to convert the proof to")]
+    to: ProofFormat,
+    #[arg(long, help = "Where to write the converted proof")]
+    out: PathBuf,
+    #[arg(
+        long,
+        required_if_eq("to", "bincode"),
+        help = "The verification key, for the statement of a proof from stwo"
+    )]
+    vk: Option<PathBuf>,
+    #[arg(
+        long,
+        required_if_eq("to", "bincode"),
+        help = "The values of the inputs as a JSON array, for the statement of a proof from stwo"
+    )]
+    public: Option<PathBuf>,
+}
+
+pub fn run(args: ConvertArgs) -> CliResult {
+    match args.to {
+        ProofFormat::StwoJson => {
+            let proof: PlonkProof =
+                bincode::deserialize_from(BufReader::new(File::open(&args.proof)?))?;
+            std::fs::write(&args.out, to_stwo_json(&proof.proof)?)?;
+        }
+        ProofFormat::Bincode => {
+            let (Some(vk), Some(public)) = (&args.vk, &args.public) else {
+                return Err("--vk and --public are required".into());
+            };
+            let key: VerificationKey = bincode::deserialize_from(BufReader::new(File::open(vk)?))?;
+            let PublicValues(values) =
+                serde_json::from_reader(BufReader::new(File::open(public)?))?;
+            if values.len() != key.input_wires.len() {
+                return Err(format!(
+                    "expected {} input values, got {}",
+                    key.input_wires.len(),
+                    values.len()
+                )
+                .into());
+            }
+            let json = std::fs::read_to_string(&args.proof)?;
+            let proof = plonk_proof_from_stwo_json(&key, &values, &json)?;
+
+            let mut writer = BufWriter::new(File::create(&args.out)?);
+            bincode::serialize_into(&mut writer, &proof)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
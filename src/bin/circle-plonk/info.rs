@@ -0,0 +1,99 @@
+use crate::CliResult;
+use circle_plonk::circuit::compressed::CompressedIndex;
+use circle_plonk::circuit::{Circuit, Mode};
+use circle_plonk::from_r1cs::circom::load_r1cs;
+use circle_plonk::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use circle_plonk::proof_system::{estimated_prover_memory, PlonkProof};
+use circle_plonk::simd::SimdLevel;
+use clap::Args;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+use stwo_prover::core::fields::m31::M31;
+
+#[derive(Args)]
+pub struct InfoArgs {
+    #[arg(help = "A circom `.r1cs` file, or a circuit index written by `setup`")]
+    path: PathBuf,
+}
+
+// The encoded size and the proving time of a chain of `2^log_n_rows` multiplications, proven on
+// this host to scale the estimates from.
+fn sample_proof(log_n_rows: u32) -> Result<(u64, Duration), Box<dyn Error>> {
+    let mut circuit = Circuit::new();
+    circuit.new_input(M31::from(1));
+    let mut x = circuit.new_witness(M31::from(3));
+    while circuit.num_rows < 1 << log_n_rows {
+        x = circuit.mul(x, x);
+    }
+    let start = Instant::now();
+    let proof = PlonkProof::prove(&mut circuit)?;
+    let elapsed = start.elapsed();
+    Ok((bincode::serialized_size(&proof)?, elapsed))
+}
+
+// Extrapolates the size and the time of sample proofs of `2^8` and `2^10` rows to a trace of
+// `2^log_n_rows` rows. A doubling of the trace adds about as much to the proof, a Merkle path
+// and a FRI layer per query, and the prover is quasi-linear in the rows.
+fn print_estimates(log_n_rows: u32) -> CliResult {
+    let (small, large) = (LOG_N_LANES + 4, LOG_N_LANES + 6);
+    let (small_size, _) = sample_proof(small)?;
+    let (large_size, large_time) = sample_proof(large)?;
+
+    let growth = (large_size as f64 - small_size as f64) / (large - small) as f64;
+    let size = large_size as f64 + growth * (log_n_rows as f64 - large as f64);
+    println!(
+        "estimated proof size: {} KiB",
+        (size.max(0.0) as u64).div_ceil(1 << 10)
+    );
+
+    let work = |log_n_rows: u32| (log_n_rows as f64) * (1u64 << log_n_rows) as f64;
+    let time = large_time.as_secs_f64() * work(log_n_rows) / work(large);
+    println!(
+        "estimated proving time: {:.2} s, from a proof of 2^{} rows on this host",
+        time, large
+    );
+    Ok(())
+}
+
+pub fn run(args: InfoArgs) -> CliResult {
+    let mut reader = BufReader::new(File::open(&args.path)?);
+    let mut magic = [0u8; 4];
+    let is_r1cs = reader.read_exact(&mut magic).is_ok() && &magic == b"r1cs";
+    reader.seek(SeekFrom::Start(0))?;
+
+    let index = if is_r1cs {
+        let (circom, num_pub_out) = load_r1cs(reader)?;
+        let mut circuit = generate_circuit(circom, Mode::INDEX)?.circuit;
+        println!("public outputs: {}", num_pub_out);
+        println!("rows before padding: {}", circuit.num_rows);
+        // the same padding as `prove_circuit`
+        circuit.pad_minimal(LOG_N_LANES);
+        circuit.index()
+    } else {
+        bincode::deserialize_from::<_, CompressedIndex>(reader)?.expand()?
+    };
+
+    let stats = index.stats();
+    println!("{}", stats);
+    if index.num_rows.is_power_of_two() {
+        let log_n_rows = index.num_rows.ilog2();
+        println!("log size: {}", log_n_rows);
+        println!(
+            "estimated prover memory: {} MiB",
+            estimated_prover_memory(log_n_rows) >> 20
+        );
+        print_estimates(log_n_rows)?;
+    } else {
+        println!("the circuit is not padded");
+    }
+    println!(
+        "SIMD: {} detected, {} compiled",
+        SimdLevel::detect().as_str(),
+        SimdLevel::compiled().as_str()
+    );
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use circle_plonk::circuit::serialization::m31_vec;
+use circle_plonk::config::ProverConfig;
+use circle_plonk::from_r1cs::circom::PublicSignals;
+#[cfg(feature = "memory-metrics")]
+use circle_plonk::metrics::TrackingAllocator;
+use circle_plonk::metrics::{Metrics, METRICS_TARGET};
+use circle_plonk::simd::warn_if_not_native;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use stwo_prover::core::fields::m31::M31;
+use tracing_subscriber::EnvFilter;
+
+mod bench;
+mod bitcoin;
+mod check;
+mod convert;
+mod info;
+mod prove;
+mod serve;
+mod setup;
+mod solidity;
+mod verify;
+
+// Adds the heap to the phase events, at the cost of counting every allocation.
+#[cfg(feature = "memory-metrics")]
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+// cargo run --release --features cli -- prove --r1cs foo.r1cs --wtns foo.wtns --out proof.bin
+#[derive(Parser)]
+#[command(
+    name = "circle-plonk",
+    about = "Plonk over Circle STARK for circom circuits"
+)]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "The configuration file, `circle-plonk.toml` in the working directory by default"
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "The format of the logs on stderr, filtered by RUST_LOG"
+    )]
+    log_format: LogFormat,
+    #[arg(
+        long,
+        global = true,
+        help = "Where to write the counters of the prover phases, in the Prometheus text format"
+    )]
+    metrics: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Proves a circom circuit and writes the proof with its public inputs")]
+    Prove(prove::ProveArgs),
+    #[command(about = "Verifies a proof against a verification key and public inputs")]
+    Verify(verify::VerifyArgs),
+    #[command(about = "Converts a circom circuit into its index and verification key")]
+    Setup(setup::SetupArgs),
+    #[command(about = "Prints statistics about a circuit")]
+    Info(info::InfoArgs),
+    #[command(about = "Checks a witness against a circom circuit without proving")]
+    Check(check::CheckArgs),
+    #[command(about = "Proves and verifies synthetic circuits of several sizes")]
+    Bench(bench::BenchArgs),
+    #[command(about = "Converts a proof to and from the JSON encoding of stwo")]
+    ConvertProof(convert::ConvertArgs),
+    #[command(about = "Runs a prover daemon with an HTTP interface")]
+    Serve(serve::ServeArgs),
+    #[command(about = "Generates a Solidity verifier for a verification key")]
+    ExportSolidity(solidity::SolidityArgs),
+    #[command(about = "Splits a proof into the tapleaf chunks of a Bitcoin script verifier")]
+    ExportBitcoin(bitcoin::BitcoinArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    // one JSON object per line, including an event for every prover phase
+    Json,
+}
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+// The values of the inputs of a proof, in the order of `input_maps`, as a JSON array.
+#[derive(Serialize, Deserialize)]
+struct PublicValues(#[serde(with = "m31_vec")] Vec<M31>);
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PublicFormat {
+    // `PublicValues`, including the constant one
+    Values,
+    // the `public.json` of snarkjs, which leaves out the constant one
+    Snarkjs,
+}
+
+fn read_public(path: &Path, format: PublicFormat) -> Result<Vec<M31>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(match format {
+        PublicFormat::Values => serde_json::from_reader::<_, PublicValues>(reader)?.0,
+        PublicFormat::Snarkjs => serde_json::from_reader::<_, PublicSignals>(reader)?.to_values(),
+    })
+}
+
+fn write_public(path: &Path, format: PublicFormat, values: Vec<M31>) -> CliResult {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        PublicFormat::Values => serde_json::to_writer(&mut writer, &PublicValues(values))?,
+        PublicFormat::Snarkjs => {
+            let signals = PublicSignals::from_values(&values)
+                .ok_or("the statement does not start with the constant one")?;
+            serde_json::to_writer_pretty(&mut writer, &signals)?
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn init_logging(format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder
+            .with_env_filter(EnvFilter::from_default_env())
+            .init(),
+        LogFormat::Json => {
+            // the phase events are what the JSON logs are for, so they are on by default
+            let filter = EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}=info", METRICS_TARGET)));
+            builder.json().with_env_filter(filter).init()
+        }
+    }
+}
+
+fn run(cli: Cli) -> CliResult {
+    init_logging(cli.log_format);
+    warn_if_not_native();
+    let config = ProverConfig::load(cli.config.as_deref())?;
+    config.apply()?;
+    let result = match cli.command {
+        Command::Prove(args) => prove::run(args, &config),
+        Command::Verify(args) => verify::run(args),
+        Command::Setup(args) => setup::run(args, &config),
+        Command::Info(args) => info::run(args),
+        Command::Check(args) => check::run(args),
+        Command::Bench(args) => bench::run(args, &config),
+        Command::ConvertProof(args) => convert::run(args),
+        Command::Serve(args) => serve::run(args, &config),
+        Command::ExportSolidity(args) => solidity::run(args),
+        Command::ExportBitcoin(args) => bitcoin::run(args),
+    };
+    // also written when the command fails, with the phases that did run
+    if let Some(path) = cli.metrics {
+        std::fs::write(path, Metrics::global().to_prometheus())?;
+    }
+    result
+}
@@ -0,0 +1,155 @@
+use crate::solidity::write_calldata;
+use crate::{write_public, CliResult, PublicFormat};
+use circle_plonk::circuit::compressed::CompressedIndex;
+use circle_plonk::circuit::index::Witness;
+use circle_plonk::circuit::{Circuit, Mode};
+use circle_plonk::config::ProverConfig;
+use circle_plonk::from_r1cs::circom::{load_r1cs_and_witness_with_outputs, CircomStatement};
+use circle_plonk::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use circle_plonk::proof_system::keccak::Keccak256Config;
+use circle_plonk::proof_system::solidity::encode_calldata;
+use circle_plonk::proof_system::{prove_plonk_with, PlonkProof};
+use clap::Args;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+#[derive(Args)]
+pub struct ProveArgs {
+    #[arg(
+        long,
+        required_unless_present = "witness",
+        requires = "wtns",
+        help = "The constraint system, as written by `circom --r1cs`"
+    )]
+    r1cs: Option<PathBuf>,
+    #[arg(
+        long,
+        required_unless_present = "witness",
+        requires = "r1cs",
+        help = "The witness, as computed by the witness generator of the circuit"
+    )]
+    wtns: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["r1cs", "wtns"],
+        requires = "index",
+        help = "Instead of `--r1cs` and `--wtns`, the `Witness` of the rows of `--index`, as taken \
+                by the C and mobile interfaces"
+    )]
+    witness: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "The padded circuit index from `setup`, which the witness is then proved against"
+    )]
+    index: Option<PathBuf>,
+    #[arg(long, help = "Where to write the proof")]
+    out: PathBuf,
+    #[arg(
+        long,
+        help = "Where to also write the values of the inputs, as a JSON array"
+    )]
+    out_public: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PublicFormat::Values,
+        help = "The format of `--out-public`, where `snarkjs` writes the `public.json` of snarkjs"
+    )]
+    public_format: PublicFormat,
+    #[arg(
+        long,
+        help = "Where to also write the calldata of `export-solidity`, from a keccak256 proof"
+    )]
+    out_calldata: Option<PathBuf>,
+}
+
+fn read_index(path: &Path) -> Result<CompressedIndex, Box<dyn Error>> {
+    let index: CompressedIndex = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+    index.validate()?;
+    Ok(index)
+}
+
+pub fn run(args: ProveArgs, config: &ProverConfig) -> CliResult {
+    // without the R1CS, there is no statement to print the outputs of
+    let (mut circuit, statement) = match (&args.r1cs, &args.wtns, &args.witness, &args.index) {
+        (_, _, Some(witness), Some(index)) => {
+            let index = read_index(index)?;
+            let witness: Witness = bincode::deserialize_from(BufReader::new(File::open(witness)?))?;
+            if !index.is_compatible(&witness) {
+                return Err("the witness does not match the index".into());
+            }
+            (Circuit::from_parts(index.expand()?, witness), None)
+        }
+        (Some(r1cs), Some(wtns), None, index) => {
+            let r1cs = BufReader::new(File::open(r1cs)?);
+            let wtns = BufReader::new(File::open(wtns)?);
+            let (circom, num_pub_out) = load_r1cs_and_witness_with_outputs(r1cs, wtns)?;
+
+            let result = generate_circuit(circom, Mode::PROVE)?;
+            let statement = CircomStatement::new(&result, num_pub_out);
+            let mut circuit = result.circuit;
+            if let Some(path) = index {
+                let index = read_index(path)?.expand()?;
+                // the same padding as `setup`
+                circuit.pad_minimal(LOG_N_LANES);
+                let witness = circuit.witness();
+                if !index.is_compatible(&witness) {
+                    return Err("the witness does not match the index".into());
+                }
+                circuit = Circuit::from_parts(index, witness);
+            }
+            (circuit, Some(statement))
+        }
+        _ => return Err("either --r1cs and --wtns, or --witness and --index, are required".into()),
+    };
+    // the prover does not check the witness, and would otherwise produce a proof that fails
+    if !circuit.is_constraint_satisfied() {
+        return Err("the witness does not satisfy the constraints".into());
+    }
+
+    let log_n_rows = circuit
+        .num_rows
+        .next_power_of_two()
+        .ilog2()
+        .max(LOG_N_LANES);
+    config.check_memory(log_n_rows)?;
+    let proof = PlonkProof::prove(&mut circuit)?;
+
+    let mut writer = BufWriter::new(File::create(&args.out)?);
+    bincode::serialize_into(&mut writer, &proof)?;
+    writer.flush()?;
+
+    if let Some(path) = &args.out_public {
+        write_public(path, args.public_format, proof.inputs.values())?;
+    }
+    if let Some(path) = &args.out_calldata {
+        // the circuit is already padded as in `setup`, which the contract's key is made from
+        let (_, keccak_proof) =
+            prove_plonk_with::<Keccak256Config>(PlonkCircuitTrace::try_from(&circuit)?);
+        write_calldata(
+            path,
+            &encode_calldata(&keccak_proof, &proof.inputs.values()),
+        )?;
+    }
+
+    eprintln!(
+        "proved {} rows with {} public inputs",
+        circuit.num_rows,
+        proof.inputs.len()
+    );
+    if let Some(statement) = statement {
+        eprintln!(
+            "outputs: {:?}",
+            statement
+                .output_values(&circuit)
+                .iter()
+                .map(|v| v.0)
+                .collect::<Vec<_>>()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::CliResult;
+use circle_plonk::circuit::compressed::CompressedIndex;
+use circle_plonk::config::ProverConfig;
+use circle_plonk::proof_system::registry::IndexRegistry;
+use circle_plonk::service::http::{serve, HttpConfig};
+use circle_plonk::service::{ProverService, ServiceConfig};
+use clap::Args;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(
+        long,
+        default_value = "127.0.0.1:8080",
+        help = "The address to listen on"
+    )]
+    addr: String,
+    #[arg(
+        long,
+        default_value_t = ServiceConfig::default().workers,
+        help = "The number of proofs in parallel"
+    )]
+    workers: usize,
+    #[arg(
+        long,
+        default_value_t = ServiceConfig::default().max_queued,
+        help = "The number of jobs that can wait for a worker"
+    )]
+    max_queued: usize,
+    #[arg(
+        long,
+        default_value_t = ServiceConfig::default().finished_ttl.as_secs(),
+        help = "The seconds a finished proof is kept before it is forgotten"
+    )]
+    finished_ttl: u64,
+    #[arg(
+        long,
+        default_value_t = ServiceConfig::default().max_finished,
+        help = "The finished proofs kept before the oldest is forgotten"
+    )]
+    max_finished: usize,
+    #[arg(
+        long,
+        default_value_t = ServiceConfig::default().max_indexes,
+        help = "The indexes kept before the oldest is forgotten"
+    )]
+    max_indexes: usize,
+    #[arg(
+        long,
+        default_value_t = HttpConfig::default().max_body_size >> 20,
+        help = "The largest request body, in MiB"
+    )]
+    max_body_mib: usize,
+    #[arg(
+        long,
+        help = "A file with the bearer token that requests must carry, without which anyone who \
+                reaches the address can use the service"
+    )]
+    token_file: Option<PathBuf>,
+    #[arg(long, help = "Indexes to register at startup, as written by `setup`")]
+    index: Vec<PathBuf>,
+}
+
+pub fn run(args: ServeArgs, config: &ProverConfig) -> CliResult {
+    if args.workers == 0 {
+        return Err("the service needs at least one worker".into());
+    }
+    let token = match &args.token_file {
+        Some(path) => {
+            let token = std::fs::read_to_string(path)?.trim().to_string();
+            if token.is_empty() {
+                return Err(format!("the token file {} is empty", path.display()).into());
+            }
+            Some(token)
+        }
+        None => {
+            eprintln!("warning: no --token-file, requests are not authenticated");
+            None
+        }
+    };
+    let http_config = HttpConfig::default()
+        .with_max_body_size(args.max_body_mib << 20)
+        .with_token(token);
+    let service_config = ServiceConfig::default()
+        .with_workers(args.workers)
+        .with_max_queued(args.max_queued)
+        .with_finished_ttl(Duration::from_secs(args.finished_ttl))
+        .with_max_finished(args.max_finished)
+        .with_max_indexes(args.max_indexes);
+    let mut service = ProverService::new(service_config);
+    if let Some(dir) = &config.registry_dir {
+        service = service.with_registry(IndexRegistry::open(dir));
+    }
+    for path in args.index.iter() {
+        let index: CompressedIndex = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+        let id = service.register_index(index.expand()?)?;
+        eprintln!("registered {} as {}", path.display(), id);
+    }
+
+    eprintln!("listening on {}", args.addr);
+    serve(&service, &http_config, &args.addr)?;
+    Ok(())
+}
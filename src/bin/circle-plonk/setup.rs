@@ -0,0 +1,64 @@
+use crate::CliResult;
+use circle_plonk::circuit::cache::{cache_key, IndexCache};
+use circle_plonk::circuit::{Circuit, Mode};
+use circle_plonk::config::ProverConfig;
+use circle_plonk::from_r1cs::circom::load_r1cs;
+use circle_plonk::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use circle_plonk::proof_system::registry::IndexRegistry;
+use circle_plonk::proof_system::VerificationKey;
+use clap::Args;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+
+#[derive(Args)]
+pub struct SetupArgs {
+    #[arg(long, help = "The constraint system, as written by `circom --r1cs`")]
+    r1cs: PathBuf,
+    #[arg(long, help = "Where to write the padded circuit index, compressed")]
+    out_index: PathBuf,
+    #[arg(long, help = "Where to write the verification key")]
+    out_vk: PathBuf,
+}
+
+fn write_bincode<T: Serialize>(path: &Path, value: &T) -> CliResult {
+    let mut writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_index(r1cs: &Path) -> Result<Circuit, Box<dyn Error>> {
+    let (circom, _) = load_r1cs(BufReader::new(File::open(r1cs)?))?;
+    let mut circuit = generate_circuit(circom, Mode::INDEX)?.circuit;
+    // the same padding as `prove_circuit`
+    circuit.pad_minimal(LOG_N_LANES);
+    Ok(circuit)
+}
+
+pub fn run(args: SetupArgs, config: &ProverConfig) -> CliResult {
+    let index = match &config.cache_dir {
+        Some(dir) => {
+            let key = cache_key(BufReader::new(File::open(&args.r1cs)?))?;
+            IndexCache::new(dir).get_or_insert_with(&key, || generate_index(&args.r1cs))?
+        }
+        None => generate_index(&args.r1cs)?.index(),
+    };
+    // the registry keeps the key, which otherwise takes committing to the constant columns
+    let key = match &config.registry_dir {
+        Some(dir) => IndexRegistry::open(dir).get_or_insert(index.clone()).key,
+        None => VerificationKey::new(&index),
+    };
+    write_bincode(&args.out_index, &index.compress())?;
+    write_bincode(&args.out_vk, &key)?;
+
+    eprintln!(
+        "set up 2^{} rows with {} public inputs",
+        key.log_n_rows,
+        key.input_wires.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use crate::CliResult;
+use circle_plonk::circuit::compressed::CompressedIndex;
+use circle_plonk::proof_system::keccak::Keccak256Config;
+use circle_plonk::proof_system::solidity::solidity_verifier;
+use circle_plonk::proof_system::VerificationKey;
+use clap::Args;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct SolidityArgs {
+    #[arg(long, help = "The padded circuit index from `setup`")]
+    index: PathBuf,
+    #[arg(long, help = "Where to write the Solidity verifier")]
+    out: PathBuf,
+    #[arg(long, help = "Where to also write the keccak256 verification key")]
+    out_vk: Option<PathBuf>,
+}
+
+// The calldata of `verify` of the generated contract, in hex.
+pub fn write_calldata(path: &Path, calldata: &[u8]) -> CliResult {
+    let hex = calldata
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    std::fs::write(path, format!("0x{}\n", hex))?;
+    Ok(())
+}
+
+pub fn run(args: SolidityArgs) -> CliResult {
+    // the key of `setup` commits with BWS-SHA256, which the contract would have to emulate, so
+    // the constant columns are committed again with keccak256
+    let index: CompressedIndex =
+        bincode::deserialize_from(BufReader::new(File::open(&args.index)?))?;
+    let key = VerificationKey::<Keccak256Config>::new_with_hasher(&index.expand()?);
+    std::fs::write(&args.out, solidity_verifier(&key))?;
+
+    if let Some(path) = &args.out_vk {
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, &key)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
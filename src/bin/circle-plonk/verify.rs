@@ -0,0 +1,46 @@
+use crate::{read_public, CliResult, PublicFormat};
+use circle_plonk::proof_system::{verify_with_key, PlonkProof, VerificationKey};
+use clap::Args;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    #[arg(long, help = "The verification key of the circuit")]
+    vk: PathBuf,
+    #[arg(long, help = "The proof, as written by `prove`")]
+    proof: PathBuf,
+    #[arg(
+        long,
+        help = "The values of the inputs as a JSON array, in the order of the verification key"
+    )]
+    public: PathBuf,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PublicFormat::Values,
+        help = "The format of `--public`, where `snarkjs` reads the `public.json` of snarkjs"
+    )]
+    public_format: PublicFormat,
+}
+
+pub fn run(args: VerifyArgs) -> CliResult {
+    let key: VerificationKey = bincode::deserialize_from(BufReader::new(File::open(&args.vk)?))?;
+    let proof: PlonkProof = bincode::deserialize_from(BufReader::new(File::open(&args.proof)?))?;
+    let values = read_public(&args.public, args.public_format)?;
+
+    if proof.log_n_rows != key.log_n_rows {
+        return Err(format!(
+            "the proof has 2^{} rows, but the circuit has 2^{}",
+            proof.log_n_rows, key.log_n_rows
+        )
+        .into());
+    }
+    if !proof.is_num_rows_consistent() {
+        return Err("the proof claims more rows than its trace has".into());
+    }
+    verify_with_key(&key, &values, proof.proof)?;
+    eprintln!("the proof is valid");
+    Ok(())
+}
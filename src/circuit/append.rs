@@ -0,0 +1,137 @@
+use crate::circuit::hint::Hint;
+use crate::circuit::public_inputs::PublicInput;
+use crate::circuit::{Circuit, Wire};
+
+impl Circuit {
+    // Splices `other` in after the rows of this circuit, for subsystems that are built separately
+    // and combined before padding. Both circuits must hold the constant one as their first input
+    // at `Wire::ONE`, which the two then share together with the zero row and the constants they
+    // have in common. The other rows of `other` are appended in order, and its statement, less
+    // the constant one, follows that of this circuit. Returns where each row of `other` ended up.
+    pub fn append(&mut self, other: &Circuit) -> Vec<Wire> {
+        assert_eq!(
+            self.mode, other.mode,
+            "the circuits are built in different modes"
+        );
+        for circuit in [&*self, other] {
+            assert_eq!(
+                circuit.input_maps.first().map(|&(row, _)| row),
+                Some(1),
+                "the circuit does not start with the constant one"
+            );
+        }
+
+        let mut map = vec![0usize; other.num_rows];
+        map[1] = 1;
+        let mut dropped = vec![0, 1];
+        self.mult[0] += other.mult[0];
+        self.mult[1] += other.mult[1];
+
+        for row in 2..other.num_rows {
+            let shared = other
+                .get_constant(Wire(row))
+                .and_then(|constant| self.constant_maps.get(&constant).copied());
+            if let Some(wire) = shared {
+                map[row] = wire.0;
+                dropped.push(row);
+                self.mult[wire.0] += other.mult[row];
+                continue;
+            }
+
+            let idx = self.next_row();
+            map[row] = idx;
+            self.output_wires.push(other.output_wires[row]);
+            self.op.push(other.op[row]);
+            self.idx_a.push(map[other.idx_a[row]]);
+            self.idx_b.push(map[other.idx_b[row]]);
+            self.mult.push(other.mult[row]);
+            if let Some(constant) = other.get_constant(Wire(row)) {
+                self.constant_maps.insert(constant, Wire(idx));
+            }
+        }
+
+        // the rows that are not copied no longer read anything, while the constant one of `other`
+        // no longer has its entry in the statement
+        for &row in dropped.iter() {
+            self.mult[map[other.idx_a[row]]] -= 1;
+            self.mult[map[other.idx_b[row]]] -= 1;
+        }
+        self.mult[1] += 1;
+
+        for entry in other.input_maps.entries().skip(1) {
+            self.input_maps.push_entry(PublicInput {
+                wire: map[entry.wire],
+                ..entry
+            });
+        }
+        self.hints.extend(other.hints.iter().map(|hint| Hint {
+            row: map[hint.row],
+            inputs: hint.inputs.iter().map(|&idx| map[idx]).collect(),
+            compute: hint.compute.clone(),
+        }));
+        self.debug_info
+            .extend(&other.debug_info, |row| (row > 1).then_some(map[row]));
+
+        map.into_iter().map(Wire).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    #[test]
+    fn test_append() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let build = |x: u32, y: u32| {
+            let mut circuit = Circuit::new();
+            circuit.mode = Mode::PROVE;
+            circuit.new_input(M31::one());
+            let x = circuit.new_input(M31::from(x));
+            circuit.label_public(x, "x");
+            let y = circuit.new_witness(M31::from(y));
+            let five = circuit.new_constant(M31::from(5));
+            let inv = circuit.hint(&[y], |v| v[0].inverse());
+            let product = circuit.mul(x.wire(), inv);
+            let sum = circuit.add(product, five);
+            circuit.expose(sum);
+            circuit
+        };
+
+        let mut circuit = build(6, 3);
+        let other = build(8, 2);
+        let num_rows = circuit.num_rows;
+        let map = circuit.append(&other);
+
+        // the zero row, the constant one and the constant five are shared
+        assert_eq!(circuit.num_rows, num_rows + other.num_rows - 3);
+        assert_eq!(circuit.constant_maps.len(), 1);
+        assert_eq!(circuit.input_maps.len(), 5);
+        assert_eq!(circuit.input_maps.label(3), Some("x"));
+        assert_eq!(circuit.input_maps[4].1, M31::from(9));
+        assert_eq!(circuit.hints.len(), 2);
+        assert_eq!(circuit.hints[1].row, map[other.hints[0].row].0);
+        assert_eq!(circuit.validate(), Ok(()));
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let other = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.append(&other);
+        assert_eq!(circuit.validate(), Ok(()));
+        assert!(circuit.is_constraint_satisfied());
+        circuit.pad_to_next_power_of_2();
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+}
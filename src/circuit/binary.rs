@@ -0,0 +1,265 @@
+use crate::circuit::{check_size, Circuit, FastHashMap, Mode, Wire, MAX_NUM_ROWS};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use stwo_prover::core::fields::m31::{M31, P};
+
+pub const BINARY_MAGIC: [u8; 4] = *b"cplk";
+pub const BINARY_VERSION: u64 = 1;
+
+// Compact binary circuit format, version 1. Unless stated otherwise, integers are unsigned LEB128
+// varints.
+//
+// - magic "cplk", version, num_rows, mode (0 = INDEX, 1 = PROVE, 2 = CHECK)
+// - op palette: the number of distinct op values, followed by the values in order of first use
+// - rows: for every row, the palette index of its op, `zigzag(row - idx_a)`,
+//   `zigzag(row - idx_b)` and its multiplicity
+// - inputs: the number of inputs, followed by the delta-encoded input rows
+// - constants: the number of constants, followed by `(value, row)` pairs sorted by value
+// - witness: a byte that is 1 if the output wires follow as 4-byte little-endian values, 0
+//   otherwise (index-only files, which leave every output wire at zero)
+//
+// References almost always point to nearby, earlier rows, so most rows take 4 to 5 bytes.
+pub fn write_circuit<W: Write>(circuit: &Circuit, with_witness: bool, mut writer: W) -> Result<()> {
+    writer.write_all(&BINARY_MAGIC)?;
+    write_varint(&mut writer, BINARY_VERSION)?;
+    write_varint(&mut writer, circuit.num_rows as u64)?;
+    writer.write_u8(match circuit.mode {
+        Mode::INDEX => 0,
+        Mode::PROVE => 1,
+        Mode::CHECK => 2,
+    })?;
+
+    let mut palette = FastHashMap::<M31, u64>::default();
+    let mut palette_values = vec![];
+    for &op in circuit.op.iter() {
+        palette.entry(op).or_insert_with(|| {
+            palette_values.push(op);
+            palette_values.len() as u64 - 1
+        });
+    }
+    write_varint(&mut writer, palette_values.len() as u64)?;
+    for op in palette_values.iter() {
+        write_varint(&mut writer, op.0 as u64)?;
+    }
+
+    for row in 0..circuit.num_rows {
+        write_varint(&mut writer, palette[&circuit.op[row]])?;
+        write_varint(&mut writer, zigzag(row, circuit.idx_a[row]))?;
+        write_varint(&mut writer, zigzag(row, circuit.idx_b[row]))?;
+        write_varint(&mut writer, circuit.mult[row] as u64)?;
+    }
+
+    write_varint(&mut writer, circuit.input_maps.len() as u64)?;
+    let mut last = 0;
+    for &(idx, _) in circuit.input_maps.iter() {
+        write_varint(&mut writer, zigzag(last, idx))?;
+        last = idx;
+    }
+
+    let constants = circuit
+        .constant_maps
+        .iter()
+        .map(|(constant, wire)| (constant.0, wire.0))
+        .collect::<Vec<_>>();
+    write_varint(&mut writer, constants.len() as u64)?;
+    for (constant, idx) in constants {
+        write_varint(&mut writer, constant as u64)?;
+        write_varint(&mut writer, idx as u64)?;
+    }
+
+    writer.write_u8(with_witness as u8)?;
+    if with_witness {
+        for v in circuit.output_wires.iter() {
+            writer.write_u32::<LittleEndian>(v.0)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn read_circuit<R: Read>(mut reader: R) -> Result<Circuit> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != BINARY_MAGIC {
+        return Err(invalid_data("Invalid magic number"));
+    }
+    if read_varint(&mut reader)? != BINARY_VERSION {
+        return Err(invalid_data("Unsupported version"));
+    }
+
+    let num_rows = read_usize(&mut reader)?;
+    if num_rows > MAX_NUM_ROWS {
+        return Err(invalid_data("Too many rows"));
+    }
+    let mode = match reader.read_u8()? {
+        0 => Mode::INDEX,
+        1 => Mode::PROVE,
+        2 => Mode::CHECK,
+        _ => return Err(invalid_data("Unexpected mode")),
+    };
+
+    let palette_len = read_usize(&mut reader)?;
+    let mut palette = Vec::with_capacity(palette_len.min(num_rows));
+    for _ in 0..palette_len {
+        palette.push(read_m31(&mut reader)?);
+    }
+
+    let mut circuit = Circuit {
+        num_rows,
+        mode,
+        ..Default::default()
+    };
+    for row in 0..num_rows {
+        let op = *palette
+            .get(read_usize(&mut reader)?)
+            .ok_or_else(|| invalid_data("Op palette index out of range"))?;
+        circuit.op.push(op);
+        circuit
+            .idx_a
+            .push(unzigzag(row, read_varint(&mut reader)?, num_rows)?);
+        circuit
+            .idx_b
+            .push(unzigzag(row, read_varint(&mut reader)?, num_rows)?);
+        circuit.mult.push(read_u32(&mut reader)?);
+    }
+    check_size(num_rows, &circuit.mult).map_err(|err| invalid_data(&err.to_string()))?;
+
+    let num_inputs = read_usize(&mut reader)?;
+    let mut inputs = Vec::with_capacity(num_inputs.min(num_rows));
+    let mut last = 0;
+    for _ in 0..num_inputs {
+        last = unzigzag(last, read_varint(&mut reader)?, num_rows)?;
+        inputs.push(last);
+    }
+
+    let num_constants = read_usize(&mut reader)?;
+    circuit.reserve_constants(num_constants.min(num_rows));
+    for _ in 0..num_constants {
+        let constant = read_m31(&mut reader)?;
+        let idx = read_usize(&mut reader)?;
+        if idx >= num_rows {
+            return Err(invalid_data("Constant row out of range"));
+        }
+        circuit.constant_maps.insert(constant, Wire(idx));
+    }
+
+    match reader.read_u8()? {
+        0 => circuit.output_wires = (0..num_rows).map(|_| M31::from(0)).collect(),
+        1 => {
+            for _ in 0..num_rows {
+                let v = reader.read_u32::<LittleEndian>()?;
+                if v >= P {
+                    return Err(invalid_data("Non-canonical output wire"));
+                }
+                circuit.output_wires.push(M31::from(v));
+            }
+        }
+        _ => return Err(invalid_data("Unexpected witness flag")),
+    }
+
+    circuit.input_maps = inputs
+        .into_iter()
+        .map(|idx| (idx, circuit.output_wires[idx]))
+        .collect();
+
+    Ok(circuit)
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn zigzag(row: usize, idx: usize) -> u64 {
+    let delta = row as i64 - idx as i64;
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(row: usize, v: u64, num_rows: usize) -> Result<usize> {
+    let delta = (v >> 1) as i64 ^ -((v & 1) as i64);
+    let idx = row as i64 - delta;
+    if idx < 0 || idx >= num_rows as i64 {
+        return Err(invalid_data("Row reference out of range"));
+    }
+    Ok(idx as usize)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut v: u64) -> Result<()> {
+    while v >= 0x80 {
+        writer.write_u8((v as u8) | 0x80)?;
+        v >>= 7;
+    }
+    writer.write_u8(v as u8)
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8()?;
+        v |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid_data("Varint is too long"))
+}
+
+fn read_usize<R: Read>(reader: &mut R) -> Result<usize> {
+    usize::try_from(read_varint(reader)?).map_err(|_| invalid_data("Integer out of range"))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    u32::try_from(read_varint(reader)?).map_err(|_| invalid_data("Integer out of range"))
+}
+
+fn read_m31<R: Read>(reader: &mut R) -> Result<M31> {
+    let v = read_varint(reader)?;
+    if v >= P as u64 {
+        return Err(invalid_data("Non-canonical field element"));
+    }
+    Ok(M31::from(v as u32))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::binary::{read_circuit, write_circuit};
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_std::io::Cursor;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+        let circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+
+        let mut bytes = vec![];
+        write_circuit(&circuit, true, &mut bytes).unwrap();
+        assert!(bytes.len() < serde_json::to_vec(&circuit).unwrap().len() / 2);
+
+        let recovered = read_circuit(Cursor::new(&bytes)).unwrap();
+        assert_eq!(recovered.num_rows, circuit.num_rows);
+        assert!(recovered.mode == circuit.mode);
+        assert_eq!(recovered.output_wires, circuit.output_wires);
+        assert_eq!(recovered.op, circuit.op);
+        assert_eq!(recovered.idx_a, circuit.idx_a);
+        assert_eq!(recovered.idx_b, circuit.idx_b);
+        assert_eq!(recovered.mult, circuit.mult);
+        assert_eq!(recovered.input_maps, circuit.input_maps);
+        assert_eq!(recovered.constant_maps, circuit.constant_maps);
+        assert!(recovered.is_constraint_satisfied());
+
+        let mut index_bytes = vec![];
+        write_circuit(&circuit, false, &mut index_bytes).unwrap();
+        assert_eq!(index_bytes.len(), bytes.len() - 4 * circuit.num_rows);
+
+        let index = read_circuit(Cursor::new(&index_bytes)).unwrap();
+        assert_eq!(index.idx_a, circuit.idx_a);
+        assert!(index.output_wires.iter().all(|&v| v == M31::from(0)));
+
+        assert!(read_circuit(Cursor::new(&bytes[..bytes.len() - 1])).is_err());
+    }
+}
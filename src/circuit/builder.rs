@@ -0,0 +1,338 @@
+use crate::circuit::{Circuit, Wire};
+use ark_ff::{One, Zero};
+use std::cell::{Ref, RefCell};
+use std::ops::{Add, Mul, Neg, Sub};
+use std::rc::Rc;
+use stwo_prover::core::fields::m31::M31;
+
+#[derive(Clone)]
+pub struct CircuitBuilder {
+    circuit: Rc<RefCell<Circuit>>,
+}
+
+impl Default for CircuitBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        let mut circuit = Circuit::new();
+
+        // `Circuit::new_constant` expects the constant one right after the zero row.
+        let one = circuit.new_input(M31::one());
+        assert_eq!(one.wire(), Wire::ONE);
+
+        Self {
+            circuit: Rc::new(RefCell::new(circuit)),
+        }
+    }
+
+    pub fn input(&self, value: M31) -> Var {
+        let wire = self.circuit.borrow_mut().new_input(value).wire();
+        self.var(wire)
+    }
+
+    pub fn witness(&self, value: M31) -> Var {
+        let wire = self.circuit.borrow_mut().new_witness(value);
+        self.var(wire)
+    }
+
+    pub fn constant(&self, value: M31) -> Var {
+        Var {
+            circuit: self.circuit.clone(),
+            terms: vec![],
+            constant: value,
+        }
+    }
+
+    pub fn var(&self, wire: Wire) -> Var {
+        if let Some(constant) = self.circuit.borrow().get_constant(wire) {
+            return self.constant(constant);
+        }
+
+        Var {
+            circuit: self.circuit.clone(),
+            terms: vec![(wire, M31::one())],
+            constant: M31::zero(),
+        }
+    }
+
+    pub fn wire(&self, var: &Var) -> Wire {
+        var.lower()
+    }
+
+    pub fn assert_zero(&self, var: &Var) {
+        var.assert_zero();
+    }
+
+    pub fn assert_equal(&self, a: &Var, b: &Var) {
+        a.assert_equal(b);
+    }
+
+    pub fn circuit(&self) -> Ref<'_, Circuit> {
+        self.circuit.borrow()
+    }
+
+    pub fn finalize(self) -> Circuit {
+        self.circuit.replace(Circuit::default())
+    }
+}
+
+// A variable is kept as a linear combination of wires plus a constant, and is only turned into
+// gates when a wire is actually needed (a multiplication, an assertion, or an explicit request).
+#[derive(Clone)]
+pub struct Var {
+    circuit: Rc<RefCell<Circuit>>,
+    terms: Vec<(Wire, M31)>,
+    constant: M31,
+}
+
+impl Var {
+    pub fn value(&self) -> M31 {
+        let circuit = self.circuit.borrow();
+
+        let mut sum = self.constant;
+        for &(wire, coeff) in self.terms.iter() {
+            sum += coeff * circuit.get_output_wire(wire);
+        }
+        sum
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn assert_zero(&self) {
+        if self.terms.is_empty() && self.constant.is_zero() {
+            return;
+        }
+
+        // a single scaled term is zero if and only if the wire itself is zero
+        let wire = if self.terms.len() == 1 && self.constant.is_zero() {
+            self.terms[0].0
+        } else {
+            self.lower()
+        };
+        self.circuit.borrow_mut().zero_test(wire);
+    }
+
+    pub fn assert_equal(&self, other: &Var) {
+        self.sub_var(other).assert_zero();
+    }
+
+    fn lower(&self) -> Wire {
+        let mut circuit = self.circuit.borrow_mut();
+
+        if self.terms.is_empty() {
+            return if self.constant.is_zero() {
+                Wire::ZERO
+            } else {
+                circuit.new_constant(self.constant)
+            };
+        }
+
+        let mut sum = None;
+        for &(wire, coeff) in self.terms.iter() {
+            let v = if coeff.is_one() {
+                wire
+            } else {
+                circuit.mul_by_constant(wire, coeff)
+            };
+            sum = Some(match sum {
+                None => v,
+                Some(sum) => circuit.add(sum, v),
+            });
+        }
+        let mut sum = sum.unwrap();
+
+        if !self.constant.is_zero() {
+            let constant = circuit.new_constant(self.constant);
+            sum = circuit.add(sum, constant);
+        }
+
+        sum
+    }
+
+    fn with_terms(&self, terms: Vec<(Wire, M31)>, constant: M31) -> Var {
+        let mut terms = terms;
+        terms.sort_unstable_by_key(|&(wire, _)| wire);
+
+        let mut merged: Vec<(Wire, M31)> = Vec::with_capacity(terms.len());
+        for (wire, coeff) in terms {
+            match merged.last_mut() {
+                Some(last) if last.0 == wire => last.1 += coeff,
+                _ => merged.push((wire, coeff)),
+            }
+        }
+        merged.retain(|(_, coeff)| !coeff.is_zero());
+
+        Var {
+            circuit: self.circuit.clone(),
+            terms: merged,
+            constant,
+        }
+    }
+
+    fn scale(&self, factor: M31) -> Var {
+        self.with_terms(
+            self.terms
+                .iter()
+                .map(|&(wire, coeff)| (wire, coeff * factor))
+                .collect(),
+            self.constant * factor,
+        )
+    }
+
+    fn add_var(&self, other: &Var) -> Var {
+        debug_assert!(Rc::ptr_eq(&self.circuit, &other.circuit));
+        let mut terms = self.terms.clone();
+        terms.extend_from_slice(&other.terms);
+        self.with_terms(terms, self.constant + other.constant)
+    }
+
+    fn sub_var(&self, other: &Var) -> Var {
+        self.add_var(&other.scale(-M31::one()))
+    }
+
+    fn mul_var(&self, other: &Var) -> Var {
+        debug_assert!(Rc::ptr_eq(&self.circuit, &other.circuit));
+        if self.is_constant() {
+            return other.scale(self.constant);
+        }
+        if other.is_constant() {
+            return self.scale(other.constant);
+        }
+
+        let a = self.lower();
+        let b = other.lower();
+        let wire = self.circuit.borrow_mut().mul(a, b);
+        self.with_terms(vec![(wire, M31::one())], M31::zero())
+    }
+
+    fn constant_like(&self, value: M31) -> Var {
+        Var {
+            circuit: self.circuit.clone(),
+            terms: vec![],
+            constant: value,
+        }
+    }
+}
+
+macro_rules! impl_var_binop {
+    ($trait:ident, $method:ident, $func:ident) => {
+        impl $trait<&Var> for &Var {
+            type Output = Var;
+
+            fn $method(self, rhs: &Var) -> Var {
+                self.$func(rhs)
+            }
+        }
+
+        impl $trait<Var> for &Var {
+            type Output = Var;
+
+            fn $method(self, rhs: Var) -> Var {
+                self.$func(&rhs)
+            }
+        }
+
+        impl $trait<&Var> for Var {
+            type Output = Var;
+
+            fn $method(self, rhs: &Var) -> Var {
+                (&self).$func(rhs)
+            }
+        }
+
+        impl $trait<Var> for Var {
+            type Output = Var;
+
+            fn $method(self, rhs: Var) -> Var {
+                (&self).$func(&rhs)
+            }
+        }
+
+        impl $trait<M31> for &Var {
+            type Output = Var;
+
+            fn $method(self, rhs: M31) -> Var {
+                self.$func(&self.constant_like(rhs))
+            }
+        }
+
+        impl $trait<M31> for Var {
+            type Output = Var;
+
+            fn $method(self, rhs: M31) -> Var {
+                (&self).$func(&self.constant_like(rhs))
+            }
+        }
+    };
+}
+
+impl_var_binop!(Add, add, add_var);
+impl_var_binop!(Sub, sub, sub_var);
+impl_var_binop!(Mul, mul, mul_var);
+
+impl Neg for &Var {
+    type Output = Var;
+
+    fn neg(self) -> Var {
+        self.scale(-M31::one())
+    }
+}
+
+impl Neg for Var {
+    type Output = Var;
+
+    fn neg(self) -> Var {
+        self.scale(-M31::one())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::builder::CircuitBuilder;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_builder() {
+        let builder = CircuitBuilder::new();
+
+        let a = builder.input(M31::from(3));
+        let b = builder.witness(M31::from(5));
+        let c = builder.witness(M31::from(7));
+
+        // (a + 2) * b - c * 3 + a - a
+        let t = (&a + M31::from(2)) * &b - &c * M31::from(3) + &a - &a;
+        assert_eq!(t.value(), M31::from(4));
+
+        let expected = builder.constant(M31::from(4));
+        builder.assert_equal(&t, &expected);
+
+        let num_rows_before = builder.circuit().num_rows;
+        let zero = &a - &a;
+        assert!(zero.is_constant());
+        builder.assert_zero(&zero);
+        assert_eq!(builder.circuit().num_rows, num_rows_before);
+
+        let circuit = builder.finalize();
+        assert!(circuit.is_constraint_satisfied());
+
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_builder_unsatisfied() {
+        let builder = CircuitBuilder::new();
+
+        let a = builder.witness(M31::from(3));
+        let b = builder.witness(M31::from(5));
+        builder.assert_equal(&(&a * &b), &builder.constant(M31::from(16)));
+
+        let circuit = builder.finalize();
+        assert!(!circuit.is_constraint_satisfied());
+    }
+}
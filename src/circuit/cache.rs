@@ -0,0 +1,166 @@
+use crate::circuit::binary::{read_circuit, write_circuit};
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::Circuit;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+
+const CACHE_KEY_DOMAIN: &[u8] = b"circle-plonk index cache v1";
+
+// A directory of circuit indexes, each in a file named after its key, which identifies what the
+// circuit was generated from (such as the hash of a `.r1cs` file, as in `cache_key`), so that a
+// large fixed circuit does not have to be converted again in every process.
+//
+// A file holds the fingerprint of the index followed by the index in the binary format without
+// witness, and is only used if the fingerprint matches. The binary format has no hints, so a
+// circuit with hints is not cached, rather than read back with their rows as witnesses.
+pub struct IndexCache {
+    dir: PathBuf,
+}
+
+// Hashes what a circuit is generated from, such as the contents of a `.r1cs` file. The domain
+// includes a version, to be bumped whenever the conversion changes the circuits it generates.
+pub fn cache_key<R: Read>(mut reader: R) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(CACHE_KEY_DOMAIN);
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn to_hex(key: &[u8; 32]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl IndexCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &[u8; 32]) -> PathBuf {
+        self.dir.join(format!("{}.cplk", to_hex(key)))
+    }
+
+    fn read(&self, key: &[u8; 32]) -> Result<CircuitIndex> {
+        let mut reader = BufReader::new(File::open(self.path(key))?);
+        let mut fingerprint = [0u8; 32];
+        reader.read_exact(&mut fingerprint)?;
+        let index = read_circuit(reader)?.index();
+        if index.fingerprint() != fingerprint {
+            return Err(Error::new(ErrorKind::InvalidData, "Fingerprint mismatch"));
+        }
+        Ok(index)
+    }
+
+    // A missing or unreadable file is a miss.
+    pub fn get(&self, key: &[u8; 32]) -> Option<CircuitIndex> {
+        match self.read(key) {
+            Ok(index) => Some(index),
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    tracing::debug!("ignoring cached index {}: {}", to_hex(key), err);
+                }
+                None
+            }
+        }
+    }
+
+    // The file is written under a temporary name and then renamed, so that other processes
+    // never read a partial one.
+    pub fn insert(&self, key: &[u8; 32], circuit: &Circuit) -> Result<()> {
+        if !circuit.hints.is_empty() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the hints of the circuit cannot be cached",
+            ));
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(&circuit.fingerprint())?;
+        write_circuit(circuit, false, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&tmp, &path)
+    }
+
+    // Returns the cached index, or builds the circuit and caches its index. Failing to write the
+    // cache does not fail the conversion.
+    pub fn get_or_insert_with<E, F>(
+        &self,
+        key: &[u8; 32],
+        build: F,
+    ) -> std::result::Result<CircuitIndex, E>
+    where
+        F: FnOnce() -> std::result::Result<Circuit, E>,
+    {
+        if let Some(index) = self.get(key) {
+            return Ok(index);
+        }
+
+        let circuit = build()?;
+        if let Err(err) = self.insert(key, &circuit) {
+            tracing::warn!("cannot cache index {}: {}", to_hex(key), err);
+        }
+        Ok(circuit.index())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::cache::{cache_key, IndexCache};
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_std::io::Cursor;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use std::fs;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_index_cache() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+        let circuit = generate_circuit(test_circuit, Mode::INDEX).unwrap().circuit;
+
+        let dir = std::env::temp_dir().join(format!("circle-plonk-cache-{}", std::process::id()));
+        let cache = IndexCache::new(&dir);
+        let key = cache_key(Cursor::new(b"test circuit")).unwrap();
+        assert_ne!(key, cache_key(Cursor::new(b"other circuit")).unwrap());
+        assert!(cache.get(&key).is_none());
+
+        let index = cache
+            .get_or_insert_with::<(), _>(&key, || Ok(circuit))
+            .unwrap();
+        let cached = cache
+            .get_or_insert_with::<(), _>(&key, || panic!("the index should be cached"))
+            .unwrap();
+        assert_eq!(cached, index);
+
+        // a corrupted file is rebuilt
+        let path = cache.path(&key);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[0] ^= 1;
+        fs::write(&path, bytes).unwrap();
+        assert!(cache.get(&key).is_none());
+        assert!(cache.get_or_insert_with(&key, || Err(())).is_err());
+
+        // a circuit with hints is converted every time
+        let mut hinted = Circuit::new();
+        let x = hinted.new_input(M31::from(3)).wire();
+        hinted.hint(&[x], |values| values[0] + values[0]);
+        let key = cache_key(Cursor::new(b"hinted circuit")).unwrap();
+        assert!(cache.insert(&key, &hinted).is_err());
+        let index = cache
+            .get_or_insert_with::<(), _>(&key, || Ok(hinted))
+            .unwrap();
+        assert_eq!(index.hints.len(), 1);
+        assert!(cache.get(&key).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
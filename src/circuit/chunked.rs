@@ -0,0 +1,173 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::{Index, IndexMut};
+
+pub const LOG_CHUNK_SIZE: u32 = 16;
+pub const CHUNK_SIZE: usize = 1 << LOG_CHUNK_SIZE;
+
+// A column stored as fixed-size chunks. Apart from the first one, which grows like a `Vec` so that
+// small circuits stay small, chunks are allocated at their full size once and never reallocated,
+// so growing a column to tens of millions of rows neither copies the existing rows nor needs
+// twice their memory at the moment the buffer grows.
+#[derive(Clone)]
+pub struct ChunkedVec<T> {
+    chunks: Vec<Vec<T>>,
+    len: usize,
+}
+
+impl<T> Default for ChunkedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ChunkedVec<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len % CHUNK_SIZE == 0 {
+            let capacity = if self.chunks.is_empty() {
+                0
+            } else {
+                CHUNK_SIZE
+            };
+            self.chunks.push(Vec::with_capacity(capacity));
+        }
+        self.chunks.last_mut().unwrap().push(value);
+        self.len += 1;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.chunks
+            .get(index >> LOG_CHUNK_SIZE)
+            .and_then(|chunk| chunk.get(index & (CHUNK_SIZE - 1)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + Clone + '_ {
+        self.chunks.iter().flatten()
+    }
+
+    // The rows in order, one full chunk at a time except for the last one.
+    pub fn chunks(&self) -> impl Iterator<Item = &[T]> + '_ {
+        self.chunks.iter().map(|chunk| chunk.as_slice())
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut vec = Vec::with_capacity(self.len);
+        for chunk in self.chunks.iter() {
+            vec.extend_from_slice(chunk);
+        }
+        vec
+    }
+}
+
+impl<T> Index<usize> for ChunkedVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(
+            index < self.len,
+            "index {} is out of range for a column of {} rows",
+            index,
+            self.len
+        );
+        &self.chunks[index >> LOG_CHUNK_SIZE][index & (CHUNK_SIZE - 1)]
+    }
+}
+
+impl<T> IndexMut<usize> for ChunkedVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(
+            index < self.len,
+            "index {} is out of range for a column of {} rows",
+            index,
+            self.len
+        );
+        &mut self.chunks[index >> LOG_CHUNK_SIZE][index & (CHUNK_SIZE - 1)]
+    }
+}
+
+impl<T> Extend<T> for ChunkedVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for ChunkedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T> From<Vec<T>> for ChunkedVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ChunkedVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::iter::Flatten<std::slice::Iter<'a, Vec<T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter().flatten()
+    }
+}
+
+impl<T: PartialEq> PartialEq for ChunkedVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for ChunkedVec<T> {}
+
+impl<T: Debug> Debug for ChunkedVec<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::chunked::{ChunkedVec, CHUNK_SIZE};
+
+    #[test]
+    fn test_chunked_vec() {
+        let mut vec = ChunkedVec::new();
+        for i in 0..(2 * CHUNK_SIZE + 3) {
+            vec.push(i);
+        }
+        assert_eq!(vec.len(), 2 * CHUNK_SIZE + 3);
+        assert_eq!(vec.chunks().count(), 3);
+        assert_eq!(vec.chunks().last().unwrap().len(), 3);
+
+        assert_eq!(vec[CHUNK_SIZE + 1], CHUNK_SIZE + 1);
+        vec[CHUNK_SIZE + 1] = 0;
+        assert_eq!(vec.get(CHUNK_SIZE + 1), Some(&0));
+        assert_eq!(vec.get(2 * CHUNK_SIZE + 3), None);
+
+        let flat = vec.to_vec();
+        assert!(vec.iter().eq(flat.iter()));
+        assert_eq!(ChunkedVec::from(flat), vec);
+    }
+}
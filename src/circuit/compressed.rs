@@ -0,0 +1,294 @@
+use crate::circuit::index::{is_compatible, CircuitIndex, Witness};
+use crate::circuit::{MAX_MULT, MAX_NUM_ROWS};
+use ark_ff::One;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result};
+use stwo_prover::core::fields::m31::{M31, P};
+
+// A column stored as the differences between consecutive entries, in runs of equal differences,
+// each run written as the zigzag-encoded difference and the length of the run, both as LEB128
+// varints. The columns of converted circuits are mostly long runs of the same gate and of rows
+// read one after the other, which take a few bytes per run instead of eight bytes per row.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedColumn {
+    len: usize,
+    bytes: Vec<u8>,
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        bytes.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    bytes.push(v as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        v |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+impl CompressedColumn {
+    pub fn new(values: impl IntoIterator<Item = usize>) -> Self {
+        let mut column = Self::default();
+        let mut prev = 0usize;
+        let mut run: Option<(u64, u64)> = None;
+        for value in values {
+            let delta = value.wrapping_sub(prev) as i64;
+            let zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+            prev = value;
+            column.len += 1;
+            run = match run {
+                Some((d, count)) if d == zigzag => Some((d, count + 1)),
+                Some((d, count)) => {
+                    write_varint(&mut column.bytes, d);
+                    write_varint(&mut column.bytes, count);
+                    Some((zigzag, 1))
+                }
+                None => Some((zigzag, 1)),
+            };
+        }
+        if let Some((d, count)) = run {
+            write_varint(&mut column.bytes, d);
+            write_varint(&mut column.bytes, count);
+        }
+        column
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.bytes.len()
+    }
+
+    // Decodes the entries one at a time. A malformed column, which can only come from
+    // deserialization, ends early or runs past `len`, which `to_vec` checks.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut pos = 0;
+        let mut prev = 0usize;
+        let mut run = (0u64, 0u64);
+        std::iter::from_fn(move || {
+            if run.1 == 0 {
+                run = (
+                    read_varint(&self.bytes, &mut pos)?,
+                    read_varint(&self.bytes, &mut pos)?,
+                );
+                if run.1 == 0 {
+                    return None;
+                }
+            }
+            run.1 -= 1;
+            let delta = ((run.0 >> 1) as i64) ^ -((run.0 & 1) as i64);
+            prev = prev.wrapping_add(delta as usize);
+            Some(prev)
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<usize>> {
+        let values = self.iter().take(self.len + 1).collect::<Vec<_>>();
+        if values.len() != self.len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the column does not decode to its length",
+            ));
+        }
+        Ok(values)
+    }
+}
+
+// A `CircuitIndex` with compressed columns, to keep on disk or in memory between proofs and to
+// expand right before proving.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedIndex {
+    pub num_rows: usize,
+    pub op: CompressedColumn,
+    pub idx_a: CompressedColumn,
+    pub idx_b: CompressedColumn,
+    pub mult: CompressedColumn,
+    pub input_wires: Vec<usize>,
+}
+
+impl CircuitIndex {
+    pub fn compress(&self) -> CompressedIndex {
+        CompressedIndex {
+            num_rows: self.num_rows,
+            op: CompressedColumn::new(self.op.iter().map(|v| v.0 as usize)),
+            idx_a: CompressedColumn::new(self.idx_a.iter().copied()),
+            idx_b: CompressedColumn::new(self.idx_b.iter().copied()),
+            mult: CompressedColumn::new(self.mult.iter().map(|&m| m as usize)),
+            input_wires: self.input_wires.clone(),
+        }
+    }
+}
+
+impl CompressedIndex {
+    // The same as `CircuitIndex::is_compatible`, without expanding the columns.
+    pub fn is_compatible(&self, witness: &Witness) -> bool {
+        is_compatible(self.num_rows, &self.input_wires, witness)
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        [&self.op, &self.idx_a, &self.idx_b, &self.mult]
+            .iter()
+            .map(|c| c.size_in_bytes())
+            .sum::<usize>()
+            + self.input_wires.len() * std::mem::size_of::<usize>()
+    }
+
+    // Checks the columns without expanding them: each decodes to `num_rows` entries, `op` is
+    // canonical, the sizes pass `check_size`, and `idx_a` and `idx_b` only read their own row or an
+    // earlier one, so that the prover never reads past the trace.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+        if self.num_rows > MAX_NUM_ROWS {
+            return Err(invalid("the index has too many rows"));
+        }
+        let columns = [&self.op, &self.idx_a, &self.idx_b, &self.mult];
+        if columns.iter().any(|c| c.len() != self.num_rows) {
+            return Err(invalid("the columns do not have num_rows entries"));
+        }
+        if columns
+            .iter()
+            .any(|c| c.iter().take(self.num_rows + 1).count() != self.num_rows)
+        {
+            return Err(invalid("a column does not decode to its length"));
+        }
+
+        if self.op.iter().any(|v| v >= P as usize)
+            || self.mult.iter().any(|m| m > MAX_MULT as usize)
+        {
+            return Err(invalid("an entry is out of range"));
+        }
+        for column in [&self.idx_a, &self.idx_b] {
+            if column.iter().enumerate().any(|(row, idx)| idx > row) {
+                return Err(invalid("a row reads a later row"));
+            }
+        }
+        if self.input_wires.iter().any(|&wire| wire >= self.num_rows) {
+            return Err(invalid("an input is out of range"));
+        }
+        Ok(())
+    }
+
+    // The first row whose gate the witness does not satisfy, if any, decoding the columns as it
+    // goes. The witness must be compatible with a validated index.
+    pub fn unsatisfied_row(&self, witness: &Witness) -> Option<usize> {
+        let wires = &witness.output_wires;
+        let rows = self.op.iter().zip(self.idx_a.iter()).zip(self.idx_b.iter());
+        rows.enumerate().position(|(row, ((op, idx_a), idx_b))| {
+            let op = M31::from(op as u32);
+            let (w_a, w_b) = (wires[idx_a], wires[idx_b]);
+            op * (w_a + w_b) + (M31::one() - op) * w_a * w_b != wires[row]
+        })
+    }
+
+    // The compressed index keeps no hints, so the expanded one expects their rows among the
+    // witnesses, which is how a `Witness` already holds them.
+    pub fn expand(&self) -> Result<CircuitIndex> {
+        self.validate()?;
+        Ok(CircuitIndex {
+            num_rows: self.num_rows,
+            op: self.op.iter().map(|v| M31::from(v as u32)).collect(),
+            idx_a: self.idx_a.to_vec()?,
+            idx_b: self.idx_b.to_vec()?,
+            mult: self.mult.iter().map(|m| m as u32).collect(),
+            input_wires: self.input_wires.clone(),
+            hints: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::compressed::{CompressedColumn, CompressedIndex};
+    use crate::circuit::index::CircuitIndex;
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_compressed_column() {
+        let values = vec![0, 1, 2, 3, 3, 3, 100, 7, usize::MAX, 0];
+        let column = CompressedColumn::new(values.iter().copied());
+        assert_eq!(column.len(), values.len());
+        assert_eq!(column.to_vec().unwrap(), values);
+
+        // consecutive rows take a run after the first one
+        let column = CompressedColumn::new(0..1 << 20);
+        assert!(column.size_in_bytes() < 8);
+        assert_eq!(column.iter().nth(12345), Some(12345));
+
+        let mut truncated = CompressedColumn::new(values.iter().copied());
+        truncated.bytes.pop();
+        assert!(truncated.to_vec().is_err());
+        let mut too_long = CompressedColumn::new(values.iter().copied());
+        too_long.len -= 1;
+        assert!(too_long.to_vec().is_err());
+    }
+
+    #[test]
+    fn test_compressed_index() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_to_next_power_of_2();
+        let (index, witness) = circuit.split();
+
+        let compressed = index.compress();
+        let expanded = compressed.expand().unwrap();
+        assert_eq!(
+            expanded,
+            CircuitIndex {
+                hints: vec![],
+                ..index.clone()
+            }
+        );
+        assert!(compressed.is_compatible(&witness));
+        // against four bytes per entry of each column
+        assert!(compressed.size_in_bytes() < index.num_rows * 4 * 4);
+
+        let mut invalid = compressed.clone();
+        invalid.num_rows += 1;
+        assert!(invalid.expand().is_err());
+        assert_eq!(compressed.unsatisfied_row(&witness), None);
+
+        // a row that reads a later row, and one that reads past the end
+        for later in [index.num_rows - 1, index.num_rows] {
+            let mut idx_a = index.idx_a.clone();
+            idx_a[1] = later;
+            let invalid = CompressedIndex {
+                idx_a: CompressedColumn::new(idx_a),
+                ..compressed.clone()
+            };
+            assert!(invalid.validate().is_err());
+        }
+
+        let mut unsatisfied = witness.clone();
+        let row = (0..index.num_rows)
+            .rev()
+            .find(|&row| {
+                index.idx_a[row] != row && !witness.input_maps.iter().any(|&(idx, _)| idx == row)
+            })
+            .unwrap();
+        unsatisfied.output_wires[row] += M31::one();
+        assert_eq!(compressed.unsatisfied_row(&unsatisfied), Some(row));
+    }
+}
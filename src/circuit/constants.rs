@@ -0,0 +1,103 @@
+use crate::circuit::{FastHashMap, Wire};
+use std::fmt::{Debug, Formatter};
+use stwo_prover::core::fields::m31::M31;
+
+// The rows created by `Circuit::new_constant`, by value. Lookups go through a hash map, while
+// iteration is in increasing order of the values, so that whatever a pass or an encoding derives
+// from the constants is the same across runs and platforms, whatever order they were added in.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ConstantMap {
+    map: FastHashMap<M31, Wire>,
+}
+
+impl ConstantMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            map: FastHashMap::with_capacity_and_hasher(capacity, Default::default()),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, constant: &M31) -> Option<&Wire> {
+        self.map.get(constant)
+    }
+
+    pub fn contains_key(&self, constant: &M31) -> bool {
+        self.map.contains_key(constant)
+    }
+
+    pub fn insert(&mut self, constant: M31, wire: Wire) -> Option<Wire> {
+        self.map.insert(constant, wire)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (M31, Wire)> {
+        let mut entries = self
+            .map
+            .iter()
+            .map(|(&constant, &wire)| (constant, wire))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(constant, _)| constant.0);
+        entries.into_iter()
+    }
+}
+
+impl Debug for ConstantMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<(M31, Wire)> for ConstantMap {
+    fn from_iter<I: IntoIterator<Item = (M31, Wire)>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::constants::ConstantMap;
+    use crate::circuit::Wire;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_constant_map_order() {
+        let values = [7u32, 3, 1 << 20, 5, 2];
+        let forward = values
+            .iter()
+            .enumerate()
+            .map(|(row, &v)| (M31::from(v), Wire(row + 2)))
+            .collect::<ConstantMap>();
+        let backward = values
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(row, &v)| (M31::from(v), Wire(row + 2)))
+            .collect::<ConstantMap>();
+
+        assert_eq!(forward, backward);
+        let order = forward.iter().map(|(v, _)| v.0).collect::<Vec<_>>();
+        assert_eq!(order, vec![2, 3, 5, 7, 1 << 20]);
+        assert_eq!(
+            forward.iter().collect::<Vec<_>>(),
+            backward.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(format!("{:?}", forward), format!("{:?}", backward));
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(feature = "debug")]
+use std::collections::HashMap;
+
+// Labels and namespaces attached to rows for diagnostics. Without the `debug` feature, this is
+// a zero-sized type and every operation is a no-op.
+#[cfg(feature = "debug")]
+#[derive(Clone, Debug, Default)]
+pub struct DebugInfo {
+    labels: HashMap<usize, String>,
+    namespaces: Vec<String>,
+    // (first row, namespace path), in increasing order of rows
+    spans: Vec<(usize, String)>,
+}
+
+#[cfg(feature = "debug")]
+impl DebugInfo {
+    pub fn push_namespace(&mut self, row: usize, name: &str) {
+        self.namespaces.push(name.to_string());
+        self.spans.push((row, self.namespaces.join(".")));
+    }
+
+    pub fn pop_namespace(&mut self, row: usize) {
+        assert!(self.namespaces.pop().is_some());
+        self.spans.push((row, self.namespaces.join(".")));
+    }
+
+    pub fn set_label(&mut self, row: usize, label: &str) {
+        let label = if self.namespaces.is_empty() {
+            label.to_string()
+        } else {
+            format!("{}.{}", self.namespaces.join("."), label)
+        };
+        self.labels.insert(row, label);
+    }
+
+    // Explicit labels follow their rows, while namespace spans cannot survive a reordering and
+    // are dropped.
+    pub fn remap<F: Fn(usize) -> Option<usize>>(&self, new_index: F) -> DebugInfo {
+        DebugInfo {
+            labels: self
+                .labels
+                .iter()
+                .filter_map(|(&row, label)| new_index(row).map(|row| (row, label.clone())))
+                .collect(),
+            namespaces: self.namespaces.clone(),
+            spans: vec![],
+        }
+    }
+
+    // Takes over the explicit labels of `other`, whose rows are moved by `new_index`.
+    pub fn extend<F: Fn(usize) -> Option<usize>>(&mut self, other: &DebugInfo, new_index: F) {
+        self.labels.extend(other.remap(new_index).labels);
+    }
+
+    pub fn label(&self, row: usize) -> Option<String> {
+        if let Some(label) = self.labels.get(&row) {
+            return Some(label.clone());
+        }
+
+        let pos = self.spans.partition_point(|(start, _)| *start <= row);
+        if pos == 0 || self.spans[pos - 1].1.is_empty() {
+            None
+        } else {
+            Some(format!("{} (row {})", self.spans[pos - 1].1, row))
+        }
+    }
+}
+
+#[cfg(not(feature = "debug"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DebugInfo;
+
+#[cfg(not(feature = "debug"))]
+impl DebugInfo {
+    pub fn push_namespace(&mut self, _row: usize, _name: &str) {}
+
+    pub fn pop_namespace(&mut self, _row: usize) {}
+
+    pub fn set_label(&mut self, _row: usize, _label: &str) {}
+
+    pub fn remap<F: Fn(usize) -> Option<usize>>(&self, _new_index: F) -> DebugInfo {
+        DebugInfo
+    }
+
+    pub fn extend<F: Fn(usize) -> Option<usize>>(&mut self, _other: &DebugInfo, _new_index: F) {}
+
+    pub fn label(&self, _row: usize) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_labels() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_witness(M31::from(3));
+        circuit.set_label(a, "a");
+
+        circuit.push_namespace("sha256");
+        circuit.push_namespace("round[12]");
+        let b = circuit.mul(a, a);
+        let ch = circuit.add(a, b);
+        circuit.set_label(ch, "ch");
+        circuit.pop_namespace();
+        circuit.pop_namespace();
+        let c = circuit.add(ch, a);
+
+        if cfg!(feature = "debug") {
+            assert_eq!(circuit.describe_row(a.0), "a");
+            assert_eq!(circuit.describe_row(b.0), "sha256.round[12] (row 2)");
+            assert_eq!(circuit.describe_row(ch.0), "sha256.round[12].ch");
+        } else {
+            assert_eq!(circuit.describe_row(ch.0), "row 3");
+        }
+        assert_eq!(circuit.describe_row(c.0), "row 4");
+    }
+}
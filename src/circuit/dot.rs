@@ -0,0 +1,97 @@
+use crate::circuit::{Circuit, RowKind, Wire};
+use ark_ff::{One, Zero};
+use std::fmt::Write;
+
+impl Circuit {
+    // Renders the first `max_rows` rows as a Graphviz digraph, with an edge from every row to the
+    // rows that read it. References to the zero row are left out, as almost every row has one.
+    pub fn to_dot(&self, max_rows: usize) -> String {
+        let kinds = self.row_kinds();
+        let num_rows = self.num_rows.min(max_rows);
+
+        let mut dot = String::new();
+        writeln!(dot, "digraph circuit {{").unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+
+        for (row, &kind) in kinds.iter().enumerate().take(num_rows) {
+            let op = self.op[row];
+            let kind = match kind {
+                RowKind::Zero => "zero".to_string(),
+                RowKind::Input => format!("input = {}", self.output_wires[row]),
+                RowKind::Witness => "witness".to_string(),
+                RowKind::Boolean => "boolean".to_string(),
+                RowKind::ZeroTest => "assert_zero".to_string(),
+                RowKind::Gate => {
+                    if self.get_constant(Wire(row)).is_some() {
+                        format!("const {}", op)
+                    } else if self.idx_b[row] == 0 {
+                        format!("mul by {}", op)
+                    } else if op.is_one() {
+                        "add".to_string()
+                    } else if op.is_zero() {
+                        "mul".to_string()
+                    } else {
+                        format!("op {}", op)
+                    }
+                }
+            };
+
+            let mut label = format!("{}\\n{}", self.describe_row(row), kind);
+            if self.get_label(row).is_some() {
+                write!(label, "\\nrow {}", row).unwrap();
+            }
+            writeln!(
+                dot,
+                "    r{} [label=\"{}\"];",
+                row,
+                label.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+
+        for (row, &kind) in kinds.iter().enumerate().take(num_rows) {
+            if matches!(
+                kind,
+                RowKind::Zero | RowKind::Input | RowKind::Witness | RowKind::Boolean
+            ) {
+                continue;
+            }
+            for (name, idx) in [("a", self.idx_a[row]), ("b", self.idx_b[row])] {
+                if idx != 0 && idx != row && idx < num_rows {
+                    writeln!(dot, "    r{} -> r{} [label=\"{}\"];", idx, row, name).unwrap();
+                }
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_to_dot() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(3));
+        let b = circuit.new_constant(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.set_label(c, "product");
+        circuit.zero_test(c);
+
+        let dot = circuit.to_dot(usize::MAX);
+        assert!(dot.starts_with("digraph circuit {"));
+        assert!(dot.contains("r3 [label=\"row 3\\nconst 5\"];"));
+        assert!(dot.contains(&format!("r{} -> r{} [label=\"a\"];", a.0, c.0)));
+        assert!(dot.contains(&format!("r{} -> r{} [label=\"b\"];", b.0, c.0)));
+        assert!(dot.contains("assert_zero"));
+
+        let dot = circuit.to_dot(3);
+        assert!(!dot.contains("r3"));
+    }
+}
@@ -0,0 +1,60 @@
+// A small statement language over `Var`s, e.g.
+//
+// circuit! {
+//     let t = a * b + c;
+//     assert_zero!(t - d);
+//     assert_equal!(t, e);
+// }
+//
+// Every statement expands to the corresponding `Var` operations, so the usual ownership rules
+// apply: use references (`&a * &b`) for variables that are used more than once.
+#[macro_export]
+macro_rules! circuit {
+    () => {};
+    (assert_zero!($e:expr); $($rest:tt)*) => {
+        $crate::circuit::builder::Var::assert_zero(&($e));
+        $crate::circuit!($($rest)*);
+    };
+    (assert_equal!($a:expr, $b:expr); $($rest:tt)*) => {
+        $crate::circuit::builder::Var::assert_equal(&($a), &($b));
+        $crate::circuit!($($rest)*);
+    };
+    (let $name:ident = $e:expr; $($rest:tt)*) => {
+        let $name: $crate::circuit::builder::Var = $e;
+        $crate::circuit!($($rest)*);
+    };
+    ($s:stmt; $($rest:tt)*) => {
+        $s;
+        $crate::circuit!($($rest)*);
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::builder::CircuitBuilder;
+    use stwo_prover::core::fields::m31::M31;
+
+    fn build(d: u32) -> bool {
+        let builder = CircuitBuilder::new();
+
+        let a = builder.input(M31::from(3));
+        let b = builder.witness(M31::from(5));
+        let c = builder.witness(M31::from(7));
+        let d = builder.witness(M31::from(d));
+
+        circuit! {
+            let t = &a * &b + c;
+            assert_zero!(&t - &d);
+            let u = t * M31::from(2) - a;
+            assert_equal!(u, d + &b * M31::from(4) - M31::from(1));
+        }
+
+        builder.finalize().is_constraint_satisfied()
+    }
+
+    #[test]
+    fn test_circuit_macro() {
+        assert!(build(22));
+        assert!(!build(23));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::Circuit;
+use sha2::{Digest, Sha256};
+use stwo_prover::core::fields::m31::M31;
+
+const FINGERPRINT_DOMAIN: &[u8] = b"circle-plonk circuit v1";
+
+// A hash of the geometry of a circuit: the number of rows, every row's `op`, `idx_a`, `idx_b`
+// and `mult`, and the rows of the inputs in order. It does not depend on the assignment, so a
+// circuit and its index agree on it in both modes.
+fn fingerprint<'a>(
+    num_rows: usize,
+    rows: impl Iterator<Item = (&'a M31, &'a usize, &'a usize, &'a u32)>,
+    input_rows: impl ExactSizeIterator<Item = usize>,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+
+    hasher.update((num_rows as u64).to_le_bytes());
+    for (op, &idx_a, &idx_b, mult) in rows {
+        hasher.update(op.0.to_le_bytes());
+        hasher.update((idx_a as u64).to_le_bytes());
+        hasher.update((idx_b as u64).to_le_bytes());
+        hasher.update(mult.to_le_bytes());
+    }
+
+    hasher.update((input_rows.len() as u64).to_le_bytes());
+    for idx in input_rows {
+        hasher.update((idx as u64).to_le_bytes());
+    }
+
+    hasher.finalize().into()
+}
+
+impl Circuit {
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(
+            self.num_rows,
+            self.op
+                .iter()
+                .zip(self.idx_a.iter())
+                .zip(self.idx_b.iter())
+                .zip(self.mult.iter())
+                .map(|(((op, idx_a), idx_b), mult)| (op, idx_a, idx_b, mult)),
+            self.input_maps.iter().map(|&(idx, _)| idx),
+        )
+    }
+}
+
+impl CircuitIndex {
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(
+            self.num_rows,
+            self.op
+                .iter()
+                .zip(self.idx_a.iter())
+                .zip(self.idx_b.iter())
+                .zip(self.mult.iter())
+                .map(|(((op, idx_a), idx_b), mult)| (op, idx_a, idx_b, mult)),
+            self.input_wires.iter().copied(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+
+    #[test]
+    fn test_fingerprint() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let index = generate_circuit(test_circuit.clone(), Mode::INDEX)
+            .unwrap()
+            .circuit;
+        let mut circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        assert_eq!(index.fingerprint(), circuit.fingerprint());
+        assert_eq!(index.index().fingerprint(), circuit.fingerprint());
+
+        let fingerprint = circuit.fingerprint();
+        circuit.pad_to_next_power_of_2();
+        assert_ne!(circuit.fingerprint(), fingerprint);
+
+        let fingerprint = circuit.fingerprint();
+        circuit.mult[0] += 1;
+        assert_ne!(circuit.fingerprint(), fingerprint);
+    }
+}
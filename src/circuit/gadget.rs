@@ -0,0 +1,148 @@
+use crate::circuit::{Circuit, RowKind, Wire};
+use ark_ff::One;
+use std::collections::HashMap;
+use stwo_prover::core::fields::m31::M31;
+
+// A sub-circuit built once against formal input wires, which can then be spliced into other
+// circuits any number of times through `Circuit::instantiate`.
+//
+// Gate rows and hints are re-evaluated for every instance. Other free witness rows keep the
+// values they were built with, so witnesses that depend on the inputs should be hints.
+pub struct Gadget {
+    pub circuit: Circuit,
+    pub inputs: Vec<Wire>,
+    pub outputs: Vec<Wire>,
+}
+
+impl Gadget {
+    pub fn new<F>(input_values: &[M31], build: F) -> Self
+    where
+        F: FnOnce(&mut Circuit, &[Wire]) -> Vec<Wire>,
+    {
+        let mut circuit = Circuit::new();
+
+        // keep the constant one at `Wire::ONE` so that constants can be shared with the host
+        circuit.new_input(M31::one());
+
+        let inputs = input_values
+            .iter()
+            .map(|&v| circuit.new_witness(v))
+            .collect::<Vec<_>>();
+        let outputs = build(&mut circuit, &inputs);
+
+        Self {
+            circuit,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+impl Circuit {
+    // The host circuit is expected to hold the constant one at `Wire::ONE`, as the R1CS converter
+    // and the builder do.
+    pub fn instantiate(&mut self, gadget: &Gadget, inputs: &[Wire]) -> Vec<Wire> {
+        assert_eq!(inputs.len(), gadget.inputs.len());
+
+        let g = &gadget.circuit;
+        let kinds = g.row_kinds();
+        let hints = g
+            .hints
+            .iter()
+            .map(|hint| (hint.row, hint))
+            .collect::<HashMap<_, _>>();
+
+        let mut map = vec![None; g.num_rows];
+        map[0] = Some(Wire::ZERO);
+        map[Wire::ONE.0] = Some(Wire::ONE);
+        for (&formal, &actual) in gadget.inputs.iter().zip(inputs.iter()) {
+            map[formal.0] = Some(actual);
+        }
+
+        for row in 2..g.num_rows {
+            if map[row].is_some() {
+                continue;
+            }
+
+            let get = |idx: usize| map[idx].expect("the gadget refers to a later row");
+
+            let wire = match kinds[row] {
+                RowKind::Zero | RowKind::Input => {
+                    panic!("gadgets cannot declare their own public inputs")
+                }
+                RowKind::Witness => match hints.get(&row) {
+                    Some(hint) => {
+                        let inputs = hint.inputs.iter().map(|&idx| get(idx)).collect::<Vec<_>>();
+                        self.hint_with(&inputs, hint.compute.clone())
+                    }
+                    None => self.new_witness(g.output_wires[row]),
+                },
+                RowKind::Boolean => self.new_boolean(g.output_wires[row]).wire(),
+                RowKind::ZeroTest => {
+                    self.zero_test(get(g.idx_a[row]));
+                    Wire(self.num_rows - 1)
+                }
+                RowKind::Gate => {
+                    let op = g.op[row];
+                    if g.idx_a[row] == 1
+                        && g.idx_b[row] == 0
+                        && g.constant_maps.get(&op) == Some(&Wire(row))
+                    {
+                        self.new_constant(op)
+                    } else {
+                        self.new_row(op, get(g.idx_a[row]), get(g.idx_b[row]))
+                    }
+                }
+            };
+            map[row] = Some(wire);
+        }
+
+        gadget
+            .outputs
+            .iter()
+            .map(|w| map[w.0].unwrap())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::gadget::Gadget;
+    use crate::circuit::Circuit;
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_instantiate() {
+        // (x * y + 5, x - y)
+        let gadget = Gadget::new(&[M31::from(0), M31::from(0)], |circuit, inputs| {
+            let xy = circuit.mul(inputs[0], inputs[1]);
+            let five = circuit.new_constant(M31::from(5));
+            let first = circuit.add(xy, five);
+            let y_neg = circuit.neg(inputs[1]);
+            let second = circuit.add(inputs[0], y_neg);
+            let xy_neg = circuit.neg(xy);
+            let zero = circuit.add(xy, xy_neg);
+            circuit.zero_test(zero);
+            vec![first, second]
+        });
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let x = circuit.new_input(M31::from(3)).wire();
+        let y = circuit.new_witness(M31::from(4));
+
+        let outputs = circuit.instantiate(&gadget, &[x, y]);
+        assert_eq!(circuit.get_output_wire(outputs[0]), M31::from(17));
+        assert_eq!(circuit.get_output_wire(outputs[1]), -M31::one());
+
+        let outputs = circuit.instantiate(&gadget, &[outputs[0], x]);
+        assert_eq!(circuit.get_output_wire(outputs[0]), M31::from(56));
+        assert_eq!(circuit.get_output_wire(outputs[1]), M31::from(14));
+
+        assert_eq!(circuit.constant_maps.len(), 1);
+        assert!(circuit.is_constraint_satisfied());
+
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+}
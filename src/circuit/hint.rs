@@ -0,0 +1,137 @@
+use crate::circuit::{Circuit, Wire};
+use std::fmt::{Debug, Formatter};
+use std::ops::Index;
+use std::sync::Arc;
+use stwo_prover::core::fields::m31::M31;
+
+pub type HintFn = Arc<dyn Fn(&[M31]) -> M31 + Send + Sync>;
+
+// A witness row whose value is computed from other rows instead of being supplied, such as an
+// inverse, a square root or a limb of a decomposition. The value is not constrained by the hint
+// itself; the circuit must still check it with gates.
+#[derive(Clone)]
+pub struct Hint {
+    pub row: usize,
+    pub inputs: Vec<usize>,
+    pub compute: HintFn,
+}
+
+impl Debug for Hint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hint")
+            .field("row", &self.row)
+            .field("inputs", &self.inputs)
+            .finish()
+    }
+}
+
+// Hints compare by the rows they read and write, since closures cannot be compared.
+impl PartialEq for Hint {
+    fn eq(&self, other: &Self) -> bool {
+        self.row == other.row && self.inputs == other.inputs
+    }
+}
+
+impl Eq for Hint {}
+
+impl Hint {
+    pub fn evaluate<W: Index<usize, Output = M31> + ?Sized>(&self, output_wires: &W) -> M31 {
+        let values = self
+            .inputs
+            .iter()
+            .map(|&idx| output_wires[idx])
+            .collect::<Vec<_>>();
+        (self.compute)(&values)
+    }
+}
+
+impl Circuit {
+    // Allocates a witness row computed by `compute` from the values of `inputs`. The closure also
+    // runs in `Mode::INDEX`, where the values are placeholders, so it must not panic on them
+    // (e.g. an inverse should map zero to zero).
+    pub fn hint<F>(&mut self, inputs: &[Wire], compute: F) -> Wire
+    where
+        F: Fn(&[M31]) -> M31 + Send + Sync + 'static,
+    {
+        self.hint_with(inputs, Arc::new(compute))
+    }
+
+    pub(crate) fn hint_with(&mut self, inputs: &[Wire], compute: HintFn) -> Wire {
+        let values = inputs
+            .iter()
+            .map(|&wire| self.get_output_wire(wire))
+            .collect::<Vec<_>>();
+        let wire = self.new_witness(compute(&values));
+        self.hints.push(Hint {
+            row: wire.0,
+            inputs: inputs.iter().map(|wire| wire.0).collect(),
+            compute,
+        });
+        wire
+    }
+
+    pub fn get_hint(&self, row: usize) -> Option<&Hint> {
+        self.hints.iter().find(|hint| hint.row == row)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::index::CircuitIndex;
+    use crate::circuit::{Circuit, Mode, Wire};
+    use ark_ff::{One, Zero};
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    fn build(x: M31, mode: Mode) -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.mode = mode;
+        circuit.new_input(M31::one());
+        let x = circuit.new_input(x).wire();
+
+        // x * x^{-1} = 1
+        let inv = circuit.hint(&[x], |values| {
+            if values[0].is_zero() {
+                M31::zero()
+            } else {
+                values[0].inverse()
+            }
+        });
+        let prod = circuit.mul(x, inv);
+        let one_neg = circuit.new_constant(-M31::one());
+        let zero = circuit.add(prod, one_neg);
+        circuit.zero_test(zero);
+        circuit
+    }
+
+    #[test]
+    fn test_hint() {
+        let circuit = build(M31::from(7), Mode::PROVE);
+        assert!(circuit.is_constraint_satisfied());
+        assert_eq!(circuit.hints.len(), 1);
+        assert_eq!(
+            circuit.get_output_wire(Wire(circuit.hints[0].row)),
+            M31::from(7).inverse()
+        );
+
+        // the index re-evaluates the hint, so it is not part of the supplied witnesses
+        let index = build(M31::zero(), Mode::INDEX).index();
+        let (inputs, witnesses) = circuit.assignment();
+        assert!(witnesses.is_empty());
+        assert_eq!(
+            index.evaluate(&inputs, &witnesses).unwrap(),
+            circuit.witness()
+        );
+
+        // the function of the hint is not serialized, so the index cannot be read back
+        let json = serde_json::to_string(&index).unwrap();
+        let err = serde_json::from_str::<CircuitIndex>(&json).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the index has 1 hints, starting at row"));
+
+        let witness = index.evaluate(&[M31::one(), M31::from(9)], &[]).unwrap();
+        let circuit = Circuit::from_parts(index, witness);
+        assert!(circuit.is_constraint_satisfied());
+    }
+}
@@ -0,0 +1,449 @@
+use crate::circuit::hint::Hint;
+use crate::circuit::public_inputs::PublicInputs;
+use crate::circuit::serialization::{hint_rows, m31_vec};
+use crate::circuit::{check_size, classify_rows, Circuit, FastHashMap, Mode, RowKind, SizeError};
+use ark_ff::{One, Zero};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use stwo_prover::core::backend::simd::m31::{PackedM31, N_LANES};
+use stwo_prover::core::fields::m31::M31;
+
+// The geometry of a circuit, which does not depend on the witness and can be generated once in
+// `Mode::INDEX`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitIndex {
+    pub num_rows: usize,
+    #[serde(with = "m31_vec")]
+    pub op: Vec<M31>,
+    pub idx_a: Vec<usize>,
+    pub idx_b: Vec<usize>,
+    pub mult: Vec<u32>,
+    pub input_wires: Vec<usize>,
+    #[serde(with = "hint_rows")]
+    pub hints: Vec<Hint>,
+}
+
+// The per-proof assignment of a circuit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Witness {
+    #[serde(with = "m31_vec")]
+    pub output_wires: Vec<M31>,
+    pub input_maps: PublicInputs,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvaluationError {
+    MissingInput,
+    MissingWitness,
+    TooManyInputs,
+    TooManyWitnesses,
+    ForwardReference { row: usize },
+}
+
+impl Display for EvaluationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvaluationError::MissingInput => write!(f, "not enough input values"),
+            EvaluationError::MissingWitness => write!(f, "not enough witness values"),
+            EvaluationError::TooManyInputs => write!(f, "too many input values"),
+            EvaluationError::TooManyWitnesses => write!(f, "too many witness values"),
+            EvaluationError::ForwardReference { row } => {
+                write!(
+                    f,
+                    "row {} refers to a row that has not been evaluated yet",
+                    row
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvaluationError {}
+
+// Whether a witness fits an index with this number of rows and these input rows.
+pub(crate) fn is_compatible(num_rows: usize, input_wires: &[usize], witness: &Witness) -> bool {
+    witness.output_wires.len() == num_rows
+        && witness.input_maps.len() == input_wires.len()
+        && witness
+            .input_maps
+            .iter()
+            .zip(input_wires.iter())
+            .all(|(&(idx, v), &expected)| idx == expected && witness.output_wires[idx] == v)
+}
+
+impl CircuitIndex {
+    pub fn check_size(&self) -> Result<(), SizeError> {
+        check_size(self.num_rows, &self.mult)
+    }
+
+    pub fn row_kinds(&self) -> Vec<RowKind> {
+        classify_rows(&self.idx_a, &self.idx_b, self.input_wires.iter().copied())
+    }
+
+    // Recomputes every output wire from the gate structure and the hints, given the values of
+    // the input rows and of the free witness rows that are not computed by hints, both in row
+    // order.
+    //
+    // Gates are evaluated level by level, where the level of a row is one more than the highest
+    // level among the rows it reads, so that the rows of a level only depend on earlier levels
+    // and are computed `N_LANES` at a time.
+    pub fn evaluate(&self, inputs: &[M31], witnesses: &[M31]) -> Result<Witness, EvaluationError> {
+        let kinds = self.row_kinds();
+        let hints = self
+            .hints
+            .iter()
+            .map(|hint| (hint.row, hint))
+            .collect::<FastHashMap<_, _>>();
+
+        let mut output_wires = vec![M31::zero(); self.num_rows];
+        let mut levels = vec![0usize; self.num_rows];
+        // the gate rows and the hints of every level above zero
+        let mut layers: Vec<(Vec<usize>, Vec<&Hint>)> = vec![];
+        let mut inputs_iter = inputs.iter();
+        let mut witnesses_iter = witnesses.iter();
+
+        for (row, kind) in kinds.into_iter().enumerate() {
+            let (level, hint) = match kind {
+                RowKind::Zero | RowKind::ZeroTest => continue,
+                RowKind::Input => {
+                    output_wires[row] = *inputs_iter.next().ok_or(EvaluationError::MissingInput)?;
+                    continue;
+                }
+                RowKind::Boolean => {
+                    output_wires[row] = *witnesses_iter
+                        .next()
+                        .ok_or(EvaluationError::MissingWitness)?;
+                    continue;
+                }
+                RowKind::Witness => match hints.get(&row) {
+                    Some(&hint) => {
+                        if hint.inputs.iter().any(|&idx| idx >= row) {
+                            return Err(EvaluationError::ForwardReference { row });
+                        }
+                        let level = hint.inputs.iter().map(|&idx| levels[idx]).max();
+                        (level.unwrap_or(0) + 1, Some(hint))
+                    }
+                    None => {
+                        output_wires[row] = *witnesses_iter
+                            .next()
+                            .ok_or(EvaluationError::MissingWitness)?;
+                        continue;
+                    }
+                },
+                RowKind::Gate => {
+                    let (idx_a, idx_b) = (self.idx_a[row], self.idx_b[row]);
+                    if idx_a >= row || idx_b >= row {
+                        return Err(EvaluationError::ForwardReference { row });
+                    }
+                    (levels[idx_a].max(levels[idx_b]) + 1, None)
+                }
+            };
+
+            levels[row] = level;
+            if layers.len() < level {
+                layers.resize_with(level, Default::default);
+            }
+            match hint {
+                Some(hint) => layers[level - 1].1.push(hint),
+                None => layers[level - 1].0.push(row),
+            }
+        }
+
+        if inputs_iter.next().is_some() {
+            return Err(EvaluationError::TooManyInputs);
+        }
+        if witnesses_iter.next().is_some() {
+            return Err(EvaluationError::TooManyWitnesses);
+        }
+
+        for (gates, hints) in layers.iter() {
+            self.evaluate_gates(gates, &mut output_wires);
+            for hint in hints.iter() {
+                output_wires[hint.row] = hint.evaluate(&output_wires);
+            }
+        }
+
+        let input_maps = self
+            .input_wires
+            .iter()
+            .map(|&idx| (idx, output_wires[idx]))
+            .collect();
+
+        Ok(Witness {
+            output_wires,
+            input_maps,
+        })
+    }
+
+    // Gates whose inputs are already known, with the last batch padded by zero lanes.
+    fn evaluate_gates(&self, rows: &[usize], output_wires: &mut [M31]) {
+        let one = PackedM31::broadcast(M31::one());
+        for batch in rows.chunks(N_LANES) {
+            let mut op = [M31::zero(); N_LANES];
+            let mut w_a = [M31::zero(); N_LANES];
+            let mut w_b = [M31::zero(); N_LANES];
+            for (i, &row) in batch.iter().enumerate() {
+                op[i] = self.op[row];
+                w_a[i] = output_wires[self.idx_a[row]];
+                w_b[i] = output_wires[self.idx_b[row]];
+            }
+
+            let op = PackedM31::from_array(op);
+            let w_a = PackedM31::from_array(w_a);
+            let w_b = PackedM31::from_array(w_b);
+            let values = (op * (w_a + w_b) + (one - op) * w_a * w_b).to_array();
+
+            for (&row, &value) in batch.iter().zip(values.iter()) {
+                output_wires[row] = value;
+            }
+        }
+    }
+
+    pub fn is_compatible(&self, witness: &Witness) -> bool {
+        is_compatible(self.num_rows, &self.input_wires, witness)
+    }
+}
+
+impl Circuit {
+    pub fn index(&self) -> CircuitIndex {
+        CircuitIndex {
+            num_rows: self.num_rows,
+            op: self.op.to_vec(),
+            idx_a: self.idx_a.to_vec(),
+            idx_b: self.idx_b.to_vec(),
+            mult: self.mult.to_vec(),
+            input_wires: self.input_maps.iter().map(|&(idx, _)| idx).collect(),
+            hints: self.hints.clone(),
+        }
+    }
+
+    // The values of the input rows and of the free witness rows that are not computed by hints,
+    // as expected by `CircuitIndex::evaluate`.
+    pub fn assignment(&self) -> (Vec<M31>, Vec<M31>) {
+        let hinted = self
+            .hints
+            .iter()
+            .map(|hint| hint.row)
+            .collect::<HashSet<_>>();
+
+        let mut inputs = vec![];
+        let mut witnesses = vec![];
+        for (row, kind) in self.row_kinds().into_iter().enumerate() {
+            match kind {
+                RowKind::Input => inputs.push(self.output_wires[row]),
+                RowKind::Witness if !hinted.contains(&row) => {
+                    witnesses.push(self.output_wires[row])
+                }
+                RowKind::Boolean => witnesses.push(self.output_wires[row]),
+                _ => {}
+            }
+        }
+        (inputs, witnesses)
+    }
+
+    pub fn witness(&self) -> Witness {
+        Witness {
+            output_wires: self.output_wires.to_vec(),
+            input_maps: self.input_maps.clone(),
+        }
+    }
+
+    pub fn split(self) -> (CircuitIndex, Witness) {
+        let index = self.index();
+        let witness = Witness {
+            output_wires: self.output_wires.to_vec(),
+            input_maps: self.input_maps,
+        };
+        (index, witness)
+    }
+
+    // Pairs an index with a witness. Constants are not part of the index, so the resulting
+    // circuit should not be extended with further constants.
+    pub fn from_parts(index: CircuitIndex, witness: Witness) -> Circuit {
+        assert!(
+            index.is_compatible(&witness),
+            "the witness does not match the index"
+        );
+
+        Circuit {
+            num_rows: index.num_rows,
+            mode: Mode::PROVE,
+            output_wires: witness.output_wires.into(),
+            op: index.op.into(),
+            idx_a: index.idx_a.into(),
+            idx_b: index.idx_b.into(),
+            mult: index.mult.into(),
+            input_maps: witness.input_maps,
+            hints: index.hints,
+            ..Default::default()
+        }
+    }
+}
+
+// Checks that a circuit generated in `Mode::INDEX` has the same geometry as one generated by the
+// same frontend in `Mode::PROVE`, which is not the case when the constraint generation depends on
+// the witness values. Panics at the first row where they differ.
+pub fn assert_same_geometry(index_circuit: &Circuit, prove_circuit: &Circuit) {
+    let row_of = |circuit: &Circuit, row: usize| {
+        (
+            circuit.op[row],
+            circuit.idx_a[row],
+            circuit.idx_b[row],
+            circuit.mult[row],
+        )
+    };
+
+    for row in 0..index_circuit.num_rows.min(prove_circuit.num_rows) {
+        assert_eq!(
+            row_of(index_circuit, row),
+            row_of(prove_circuit, row),
+            "{} differs between the index and the prove circuit in (op, idx_a, idx_b, mult)",
+            prove_circuit.describe_row(row)
+        );
+    }
+    assert_eq!(
+        index_circuit.num_rows, prove_circuit.num_rows,
+        "the index and the prove circuit have different numbers of rows"
+    );
+
+    let input_rows = |circuit: &Circuit| {
+        circuit
+            .input_maps
+            .iter()
+            .map(|&(idx, _)| idx)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        input_rows(index_circuit),
+        input_rows(prove_circuit),
+        "the index and the prove circuit have different input rows"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::index::{assert_same_geometry, CircuitIndex, EvaluationError};
+    use crate::circuit::{Circuit, Mode, Wire};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_index_and_witness() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let index = generate_circuit(test_circuit.clone(), Mode::INDEX)
+            .unwrap()
+            .circuit
+            .index();
+        let index: CircuitIndex =
+            serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+
+        let (prove_index, witness) = generate_circuit(test_circuit, Mode::PROVE)
+            .unwrap()
+            .circuit
+            .split();
+        assert_eq!(index, prove_index);
+        assert!(index.is_compatible(&witness));
+
+        let circuit = Circuit::from_parts(index, witness);
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let index = generate_circuit(test_circuit.clone(), Mode::INDEX)
+            .unwrap()
+            .circuit
+            .index();
+        let circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        let (inputs, witnesses) = circuit.assignment();
+
+        let witness = index.evaluate(&inputs, &witnesses).unwrap();
+        assert_eq!(witness, circuit.witness());
+
+        assert_eq!(
+            index.evaluate(&inputs[1..], &witnesses),
+            Err(EvaluationError::MissingInput)
+        );
+        assert_eq!(
+            index.evaluate(&inputs, &[witnesses.as_slice(), &[M31::from(0)]].concat()),
+            Err(EvaluationError::TooManyWitnesses)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_levels() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let x = circuit.new_input(M31::from(3)).wire();
+        let y = circuit.new_witness(M31::from(5));
+
+        // wide levels that take several batches, a hint in the middle and a chain on top of it
+        let mut wires = vec![x, y];
+        for i in 0..50 {
+            let k = circuit.new_constant(M31::from(i + 2));
+            let a = circuit.mul(wires[i % 2], k);
+            wires.push(circuit.add(a, wires[(i + 1) % 2]));
+        }
+        let sum = wires[2..]
+            .iter()
+            .fold(Wire::ZERO, |acc, &w| circuit.add(acc, w));
+        let h = circuit.hint(&[sum, y], |v| v[0] * v[1]);
+        let hy = circuit.mul(h, y);
+        let hy_neg = circuit.neg(hy);
+        let zero = circuit.add(hy, hy_neg);
+        circuit.zero_test(zero);
+
+        let index = circuit.index();
+        let (inputs, witnesses) = circuit.assignment();
+        assert_eq!(witnesses.len(), 1);
+        assert_eq!(
+            index.evaluate(&inputs, &witnesses).unwrap(),
+            circuit.witness()
+        );
+
+        let witness = index.evaluate(&inputs, &[M31::from(6)]).unwrap();
+        assert_ne!(witness, circuit.witness());
+        assert!(Circuit::from_parts(index, witness).is_constraint_satisfied());
+    }
+
+    #[test]
+    fn test_same_geometry() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let index_circuit = generate_circuit(test_circuit.clone(), Mode::INDEX)
+            .unwrap()
+            .circuit;
+        let prove_circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        assert_same_geometry(&index_circuit, &prove_circuit);
+    }
+
+    #[test]
+    #[should_panic(expected = "row 3 differs")]
+    fn test_same_geometry_value_dependent() {
+        // a frontend that only multiplies by a witness when it is not zero
+        let build = |value: u32| {
+            let mut circuit = Circuit::new();
+            circuit.new_input(M31::one());
+            let x = circuit.new_witness(M31::from(value));
+            if value != 0 {
+                circuit.mul(x, x);
+            } else {
+                circuit.add(x, x);
+            }
+            circuit
+        };
+        assert_same_geometry(&build(0), &build(7));
+    }
+}
@@ -0,0 +1,944 @@
+use crate::circuit::chunked::ChunkedVec;
+use crate::circuit::constants::ConstantMap;
+use crate::circuit::debug::DebugInfo;
+use crate::circuit::hint::Hint;
+use crate::circuit::packed::PackedM31Vec;
+use crate::circuit::public_inputs::PublicInputs;
+use ark_ff::{One, Zero};
+use rayon::prelude::*;
+use rustc_hash::FxBuildHasher;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::ops::Neg;
+use stwo_prover::core::channel::{BWSSha256Channel, Channel};
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::fields::FieldExpOps;
+use stwo_prover::core::vcs::bws_sha256_hash::BWSSha256Hasher;
+
+pub mod append;
+
+pub mod binary;
+
+pub mod builder;
+
+pub mod cache;
+
+pub mod chunked;
+
+pub mod compressed;
+
+pub mod constants;
+
+pub mod debug;
+
+pub mod dsl;
+
+pub mod dot;
+
+pub mod fingerprint;
+
+pub mod gadget;
+
+pub mod hint;
+
+pub mod index;
+
+pub mod packed;
+
+pub mod parallel;
+
+pub mod permutation;
+
+pub mod public_inputs;
+
+pub mod serialization;
+
+pub mod stats;
+
+pub mod update;
+
+pub mod validate;
+
+// Maps keyed by field elements and row indices, which need no protection against collision
+// attacks. Swapping the hasher only takes changing these aliases.
+pub type FastHashMap<K, V> = HashMap<K, V, FxBuildHasher>;
+pub type FastHashSet<K> = HashSet<K, FxBuildHasher>;
+
+// Row indices and multiplicities end up as M31 elements in the trace, so they must stay below
+// the modulus. The row count is also kept a power of two that padding can reach.
+pub const MAX_NUM_ROWS: usize = 1 << 30;
+pub const MAX_MULT: u32 = P - 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeError {
+    TooManyRows { num_rows: usize },
+    MultOverflow { row: usize, mult: u32 },
+}
+
+impl Display for SizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeError::TooManyRows { num_rows } => write!(
+                f,
+                "the circuit has {} rows, more than the maximum of {}",
+                num_rows, MAX_NUM_ROWS
+            ),
+            SizeError::MultOverflow { row, mult } => write!(
+                f,
+                "row {} is used {} times, more than the maximum of {}",
+                row, mult, MAX_MULT
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SizeError {}
+
+// The first row over the budget of `Circuit::with_max_log_size`, described by `describe_row`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudgetError {
+    pub log_size: u32,
+    pub row: String,
+}
+
+impl Display for BudgetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the circuit exceeds its budget of 2^{} rows at {}",
+            self.log_size, self.row
+        )
+    }
+}
+
+impl std::error::Error for BudgetError {}
+
+pub fn check_size<'a>(
+    num_rows: usize,
+    mult: impl IntoIterator<Item = &'a u32>,
+) -> Result<(), SizeError> {
+    if num_rows > MAX_NUM_ROWS {
+        return Err(SizeError::TooManyRows { num_rows });
+    }
+    for (row, &mult) in mult.into_iter().enumerate() {
+        if mult > MAX_MULT {
+            return Err(SizeError::MultOverflow { row, mult });
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    INDEX,
+    PROVE,
+    // generates the witness as in `PROVE`, to check the circuit without proving it
+    CHECK,
+}
+
+impl Mode {
+    pub fn has_witness(self) -> bool {
+        self != Mode::INDEX
+    }
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::INDEX
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Wire(pub usize);
+
+impl Wire {
+    pub const ZERO: Wire = Wire(0);
+    // The first input row, which holds the constant one that constants are made from.
+    pub const ONE: Wire = Wire(1);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+// A wire whose value has been constrained to be either zero or one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoolWire(pub(crate) Wire);
+
+// A wire whose value is supplied by the verifier as part of the statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PublicWire(pub(crate) Wire);
+
+impl BoolWire {
+    pub fn wire(self) -> Wire {
+        self.0
+    }
+}
+
+impl PublicWire {
+    pub fn wire(self) -> Wire {
+        self.0
+    }
+}
+
+impl From<BoolWire> for Wire {
+    fn from(value: BoolWire) -> Self {
+        value.0
+    }
+}
+
+impl From<PublicWire> for Wire {
+    fn from(value: PublicWire) -> Self {
+        value.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowKind {
+    Zero,
+    Input,
+    Witness,
+    // a witness that reads itself twice with `op = 0`, which constrains it to `c * c = c`
+    Boolean,
+    ZeroTest,
+    Gate,
+}
+
+pub fn classify_rows<'a>(
+    idx_a: impl IntoIterator<Item = &'a usize>,
+    idx_b: impl IntoIterator<Item = &'a usize>,
+    input_rows: impl Iterator<Item = usize>,
+) -> Vec<RowKind> {
+    let mut kinds = idx_a
+        .into_iter()
+        .zip(idx_b)
+        .enumerate()
+        .map(|(row, (&idx_a, &idx_b))| {
+            if row == 0 {
+                RowKind::Zero
+            } else if idx_a == row && idx_b == row {
+                RowKind::Boolean
+            } else if idx_b == row {
+                RowKind::ZeroTest
+            } else if idx_a == row {
+                RowKind::Witness
+            } else {
+                RowKind::Gate
+            }
+        })
+        .collect::<Vec<_>>();
+    for idx in input_rows {
+        if kinds[idx] == RowKind::Witness {
+            kinds[idx] = RowKind::Input;
+        }
+    }
+    kinds
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsatisfiedRow {
+    pub row: usize,
+    pub op: M31,
+    pub idx_a: usize,
+    pub idx_b: usize,
+    pub w_a: M31,
+    pub w_b: M31,
+    pub w_c: M31,
+    pub label: Option<String>,
+}
+
+impl Display for UnsatisfiedRow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{} (row {}) unsatisfied", label, self.row)?,
+            None => write!(f, "row {} unsatisfied", self.row)?,
+        }
+        write!(
+            f,
+            ": op = {}, a = {} (wire {}), b = {} (wire {}), c = {}",
+            self.op, self.w_a, self.idx_a, self.w_b, self.idx_b, self.w_c
+        )
+    }
+}
+
+impl std::error::Error for UnsatisfiedRow {}
+
+#[derive(Default)]
+pub struct Circuit {
+    pub num_rows: usize,
+    pub mode: Mode,
+    pub output_wires: PackedM31Vec,
+
+    pub op: PackedM31Vec,
+    pub idx_a: ChunkedVec<usize>,
+    pub idx_b: ChunkedVec<usize>,
+    pub mult: ChunkedVec<u32>,
+
+    pub input_maps: PublicInputs,
+    pub constant_maps: ConstantMap,
+
+    pub hints: Vec<Hint>,
+
+    pub debug_info: DebugInfo,
+
+    // the rows that the circuit may take, as set by `with_max_log_size`
+    pub max_log_size: Option<u32>,
+}
+
+impl Circuit {
+    pub fn new() -> Circuit {
+        let mut circuit = Self::default();
+
+        circuit.num_rows += 1;
+        circuit.output_wires.push(M31::zero());
+        circuit.op.push(M31::one());
+        circuit.idx_a.push(0);
+        circuit.idx_b.push(0);
+        circuit.mult.push(2);
+
+        circuit
+    }
+
+    // Caps the circuit at `2^log_size` rows, so that building a circuit too large to prove panics
+    // at the first row over the budget, instead of once the whole circuit is built. The message
+    // names the row, which with the `debug` feature includes the namespace of the gadget that
+    // creates it. Padding is not limited.
+    pub fn with_max_log_size(mut self, log_size: u32) -> Self {
+        assert!(
+            self.num_rows <= 1 << log_size,
+            "the circuit already has {} rows, more than 2^{}",
+            self.num_rows,
+            log_size
+        );
+        self.max_log_size = Some(log_size);
+        self
+    }
+
+    // Whether the circuit fits in `2^log_size` rows, with the first row over it otherwise.
+    pub fn check_log_size(&self, log_size: u32) -> Result<(), BudgetError> {
+        if self.num_rows <= 1 << log_size {
+            return Ok(());
+        }
+        Err(BudgetError {
+            log_size,
+            row: self.describe_row(1 << log_size),
+        })
+    }
+
+    // Takes the index of a new row, within the budget of `with_max_log_size`.
+    pub(crate) fn next_row(&mut self) -> usize {
+        let idx = self.num_rows;
+        self.num_rows += 1;
+        if let Some(log_size) = self.max_log_size {
+            if let Err(err) = self.check_log_size(log_size) {
+                panic!("{}", err);
+            }
+        }
+        idx
+    }
+
+    // Like `new_row`, with an error instead of the panic when the row is over the budget of
+    // `with_max_log_size`, for builders that report running out of rows.
+    pub fn try_new_row(&mut self, op: M31, idx_a: Wire, idx_b: Wire) -> Result<Wire, BudgetError> {
+        if let Some(log_size) = self.max_log_size {
+            if self.num_rows >= 1 << log_size {
+                return Err(BudgetError {
+                    log_size,
+                    row: self.describe_row(self.num_rows),
+                });
+            }
+        }
+        Ok(self.new_row(op, idx_a, idx_b))
+    }
+
+    pub fn new_row(&mut self, op: M31, idx_a: Wire, idx_b: Wire) -> Wire {
+        // fold rows over constants, with the zero wire only counting as a constant next to
+        // another constant so that rows over the zero wire alone are left untouched
+        let folded = match (self.get_constant(idx_a), self.get_constant(idx_b)) {
+            (Some(a), Some(b)) => Some((a, b)),
+            (Some(a), None) if idx_b == Wire::ZERO => Some((a, M31::zero())),
+            (None, Some(b)) if idx_a == Wire::ZERO => Some((M31::zero(), b)),
+            _ => None,
+        };
+        if let Some((a, b)) = folded {
+            let value = op * (a + b) + (M31::one() - op) * a * b;
+            return if value.is_zero() {
+                Wire::ZERO
+            } else {
+                self.new_constant(value)
+            };
+        }
+
+        let value = op * (self.get_output_wire(idx_a) + self.get_output_wire(idx_b))
+            + (M31::one() - op) * self.get_output_wire(idx_a) * self.get_output_wire(idx_b);
+
+        let idx = self.next_row();
+        self.output_wires.push(value);
+        self.op.push(op);
+        self.idx_a.push(idx_a.0);
+        self.idx_b.push(idx_b.0);
+        self.mult.push(0);
+
+        self.increase_output_count(idx_a);
+        self.increase_output_count(idx_b);
+
+        Wire(idx)
+    }
+
+    // Makes room for `additional` more constants, for frontends that know roughly how many distinct
+    // constants they are about to create.
+    pub fn reserve_constants(&mut self, additional: usize) {
+        self.constant_maps.reserve(additional);
+    }
+
+    pub fn new_constant(&mut self, constant: M31) -> Wire {
+        if self.constant_maps.contains_key(&constant) {
+            *self.constant_maps.get(&constant).unwrap()
+        } else {
+            let idx = self.new_row(constant, Wire::ONE, Wire::ZERO);
+            self.constant_maps.insert(constant, idx);
+            idx
+        }
+    }
+
+    // Returns the value of a row created by `new_constant`.
+    pub fn get_constant(&self, wire: Wire) -> Option<M31> {
+        let row = wire.0;
+        let op = self.op[row];
+        if self.idx_a[row] == Wire::ONE.0
+            && self.idx_b[row] == Wire::ZERO.0
+            && self.constant_maps.get(&op) == Some(&wire)
+        {
+            Some(op)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&mut self, idx_a: Wire, idx_b: Wire) -> Wire {
+        self.new_row(M31::one(), idx_a, idx_b)
+    }
+
+    pub fn mul(&mut self, idx_a: Wire, idx_b: Wire) -> Wire {
+        self.new_row(M31::zero(), idx_a, idx_b)
+    }
+
+    pub fn neg(&mut self, idx: Wire) -> Wire {
+        self.mul_by_constant(idx, M31::one().neg())
+    }
+
+    pub fn zero_test(&mut self, idx: Wire) {
+        let helper = self.next_row();
+        self.output_wires.push(M31::zero()); // it can be any value
+        self.op.push(M31::one());
+        self.idx_a.push(idx.0);
+        self.idx_b.push(helper);
+        self.mult.push(1);
+
+        self.increase_output_count(idx);
+    }
+
+    pub fn mul_by_constant(&mut self, idx: Wire, constant: M31) -> Wire {
+        self.new_row(constant, idx, Wire::ZERO)
+    }
+
+    pub fn new_input(&mut self, input: M31) -> PublicWire {
+        let idx = self.next_row();
+        self.output_wires.push(input);
+        self.op.push(M31::one());
+        self.idx_a.push(idx);
+        self.idx_b.push(0);
+        self.mult.push(0); // input is done by intentionally reducing the mult by one causing the need to externally supply it
+
+        self.input_maps.push(idx, input);
+
+        self.increase_output_count(Wire::ZERO);
+
+        PublicWire(Wire(idx))
+    }
+
+    pub fn new_witness(&mut self, witness: M31) -> Wire {
+        let idx = self.next_row();
+        self.output_wires.push(witness);
+        self.op.push(M31::one());
+        self.idx_a.push(idx);
+        self.idx_b.push(0);
+        self.mult.push(1);
+
+        self.increase_output_count(Wire::ZERO);
+
+        Wire(idx)
+    }
+
+    // A witness constrained to be zero or one by its own row, in place of a witness followed by
+    // the rows of `enforce_bool`.
+    pub fn new_boolean(&mut self, value: M31) -> BoolWire {
+        let idx = self.next_row();
+        self.output_wires.push(value);
+        self.op.push(M31::zero());
+        self.idx_a.push(idx);
+        self.idx_b.push(idx);
+        self.mult.push(2);
+
+        BoolWire(Wire(idx))
+    }
+
+    // Makes the value of an existing wire part of the statement. Like an input, one of its uses
+    // is then supplied externally through `input_maps` instead of by the row itself.
+    pub fn expose(&mut self, idx: Wire) -> PublicWire {
+        if self.mult[idx.0] > 0 {
+            self.mult[idx.0] -= 1;
+        } else {
+            // nothing consumes the wire yet, so add a copy row that does
+            self.next_row();
+            self.output_wires.push(self.output_wires[idx.0]);
+            self.op.push(M31::one());
+            self.idx_a.push(idx.0);
+            self.idx_b.push(0);
+            self.mult.push(0);
+
+            self.increase_output_count(Wire::ZERO);
+        }
+
+        self.input_maps.push(idx.0, self.output_wires[idx.0]);
+
+        PublicWire(idx)
+    }
+
+    // Labels the last entry of the statement for an input or exposed wire, which is carried along
+    // with its value into witnesses and proofs.
+    pub fn label_public(&mut self, wire: PublicWire, label: &str) {
+        let index = self
+            .input_maps
+            .iter()
+            .rposition(|&(idx, _)| idx == wire.0 .0)
+            .expect("the wire is not public");
+        self.input_maps.set_label(index, label);
+    }
+
+    pub fn get_output_wire(&self, idx: Wire) -> M31 {
+        self.output_wires[idx.0]
+    }
+
+    pub fn increase_output_count(&mut self, idx: Wire) {
+        let mult = &mut self.mult[idx.0];
+        *mult = mult.checked_add(1).expect("multiplicity overflows u32");
+    }
+
+    pub fn push_namespace(&mut self, name: &str) {
+        self.debug_info.push_namespace(self.num_rows, name);
+    }
+
+    pub fn pop_namespace(&mut self) {
+        self.debug_info.pop_namespace(self.num_rows);
+    }
+
+    pub fn set_label(&mut self, wire: Wire, label: &str) {
+        self.debug_info.set_label(wire.0, label);
+    }
+
+    pub fn get_label(&self, row: usize) -> Option<String> {
+        self.debug_info.label(row)
+    }
+
+    pub fn describe_row(&self, row: usize) -> String {
+        self.get_label(row).unwrap_or_else(|| format!("row {}", row))
+    }
+
+    // Every reference to a row counts towards its multiplicity, except the self-reference of
+    // an input row, which is supplied externally through `input_maps`.
+    pub fn recompute_mult(&mut self) {
+        let mut mult = vec![0u32; self.num_rows];
+        for &idx in self.idx_a.iter().chain(self.idx_b.iter()) {
+            mult[idx] = mult[idx]
+                .checked_add(1)
+                .expect("multiplicity overflows u32");
+        }
+        for &(idx, _) in self.input_maps.iter() {
+            mult[idx] = mult[idx]
+                .checked_sub(1)
+                .expect("input row is not used by the circuit");
+        }
+        self.mult = mult.into();
+    }
+
+    pub fn check_size(&self) -> Result<(), SizeError> {
+        check_size(self.num_rows, &self.mult)
+    }
+
+    pub fn row_kinds(&self) -> Vec<RowKind> {
+        classify_rows(
+            &self.idx_a,
+            &self.idx_b,
+            self.input_maps.iter().map(|&(idx, _)| idx),
+        )
+    }
+
+    pub fn is_constraint_satisfied(&self) -> bool {
+        match self.check_satisfied() {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::debug!("{}", err);
+                false
+            }
+        }
+    }
+
+    pub fn check_satisfied(&self) -> Result<(), UnsatisfiedRow> {
+        assert_eq!(self.num_rows, self.output_wires.len());
+        assert_eq!(self.num_rows, self.op.len());
+        assert_eq!(self.num_rows, self.idx_a.len());
+        assert_eq!(self.num_rows, self.idx_b.len());
+        assert_eq!(self.num_rows, self.mult.len());
+
+        // rows are checked in parallel, and `find_first` still reports the lowest failing row
+        let failing = (0..self.num_rows).into_par_iter().find_first(|&row| {
+            let op = self.op[row];
+            let w_a = self.output_wires[self.idx_a[row]];
+            let w_b = self.output_wires[self.idx_b[row]];
+            let w_c = self.output_wires[row];
+
+            let mut sum = M31::zero();
+            sum += op * (w_a + w_b);
+            sum += (M31::one() - op) * w_a * w_b;
+            sum -= w_c;
+
+            !sum.is_zero()
+        });
+
+        match failing {
+            None => Ok(()),
+            Some(row) => {
+                let (idx_a, idx_b) = (self.idx_a[row], self.idx_b[row]);
+                Err(UnsatisfiedRow {
+                    row,
+                    op: self.op[row],
+                    idx_a,
+                    idx_b,
+                    w_a: self.output_wires[idx_a],
+                    w_b: self.output_wires[idx_b],
+                    w_c: self.output_wires[row],
+                    label: self.get_label(row),
+                })
+            }
+        }
+    }
+
+    pub fn pad_to_next_power_of_2(&mut self) {
+        self.pad_to_log_size(self.num_rows.next_power_of_two().ilog2());
+    }
+
+    // Pads only the shortfall up to the smallest power of two that holds the circuit and is at
+    // least `2^min_log_size`, and returns the resulting log size.
+    pub fn pad_minimal(&mut self, min_log_size: u32) -> u32 {
+        let log_size = self.num_rows.next_power_of_two().ilog2().max(min_log_size);
+        self.pad_to_log_size(log_size);
+        log_size
+    }
+
+    pub fn pad_to_log_size(&mut self, log_size: u32) {
+        let size = 1usize << log_size;
+        assert!(
+            self.num_rows <= size,
+            "the circuit has {} rows, more than 2^{}",
+            self.num_rows,
+            log_size
+        );
+
+        for _ in self.num_rows..size {
+            self.num_rows += 1;
+            self.output_wires.push(M31::zero());
+            self.op.push(M31::zero());
+            self.idx_a.push(0);
+            self.idx_b.push(0);
+            self.mult.push(0);
+
+            self.increase_output_count(Wire::ZERO);
+            self.increase_output_count(Wire::ZERO);
+        }
+    }
+
+    // Draws `alpha` and `z` over QM31 from `logup_channel`, so that the check has the soundness
+    // of the interaction trace and gives the same answer on every run.
+    pub fn is_logup_satisfied(&self, inputs: &[(usize, M31)]) -> bool {
+        self.is_logup_satisfied_with_channel(&mut self.logup_channel(inputs), inputs)
+    }
+
+    // Draws `alpha` and `z` from the channel, like the lookup elements of the interaction trace.
+    pub fn is_logup_satisfied_with_channel<C: Channel>(
+        &self,
+        channel: &mut C,
+        inputs: &[(usize, M31)],
+    ) -> bool {
+        let alpha = channel.draw_felt();
+        let z = channel.draw_felt();
+        self.is_logup_satisfied_at(alpha, z, inputs)
+    }
+
+    // A channel seeded with the fingerprint of the circuit and the statement, so that the check
+    // is reproducible and its randomness depends on everything being checked.
+    pub fn logup_channel(&self, inputs: &[(usize, M31)]) -> BWSSha256Channel {
+        let mut channel = BWSSha256Channel::new(BWSSha256Hasher::hash(&self.fingerprint()));
+        let statement = inputs
+            .iter()
+            .map(|&(idx, v)| QM31::from_m31(M31::from(idx), v, M31::zero(), M31::zero()))
+            .collect::<Vec<_>>();
+        channel.mix_felts(&statement);
+        channel
+    }
+
+    pub fn is_logup_satisfied_at(&self, alpha: QM31, z: QM31, inputs: &[(usize, M31)]) -> bool {
+        let mut sum = QM31::zero();
+
+        if self.num_rows > 0 {
+            let mut denominators = vec![];
+            for (idx_c, (&idx_a, &idx_b)) in self.idx_a.iter().zip(self.idx_b.iter()).enumerate() {
+                denominators.push(M31::from(idx_a) + alpha * self.output_wires[idx_a] - z);
+                denominators.push(M31::from(idx_b) + alpha * self.output_wires[idx_b] - z);
+                denominators.push(M31::from(idx_c) + alpha * self.output_wires[idx_c] - z);
+            }
+
+            let mut denominator_inverses = vec![QM31::zero(); denominators.len()];
+            QM31::batch_inverse(&denominators, &mut denominator_inverses);
+
+            for (group, &mult) in denominator_inverses.chunks_exact(3).zip(self.mult.iter()) {
+                sum += group[0];
+                sum += group[1];
+                sum -= M31::from(mult) * group[2];
+            }
+        }
+
+        if !inputs.is_empty() {
+            let mut denominators = vec![];
+            for &(id, v) in inputs.iter() {
+                denominators.push(M31::from(id) + alpha * v - z);
+            }
+
+            let mut denominator_inverses = vec![QM31::zero(); denominators.len()];
+            QM31::batch_inverse(&denominators, &mut denominator_inverses);
+
+            for &v in denominator_inverses.iter() {
+                sum -= v;
+            }
+        }
+
+        sum.is_zero()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{check_size, Circuit, SizeError, Wire, MAX_MULT, MAX_NUM_ROWS};
+    use ark_ff::{One, Zero};
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_constant_folding() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(2));
+
+        let five = circuit.new_constant(M31::from(5));
+        let three = circuit.new_constant(M31::from(3));
+        let num_rows = circuit.num_rows;
+
+        let minus_five = circuit.neg(five);
+        assert_eq!(circuit.get_constant(minus_five), Some(-M31::from(5)));
+        assert_eq!(circuit.neg(minus_five), five);
+
+        let fifteen = circuit.mul(five, three);
+        assert_eq!(circuit.get_constant(fifteen), Some(M31::from(15)));
+        assert_eq!(circuit.add(five, minus_five), Wire::ZERO);
+        assert_eq!(
+            circuit.add(fifteen, three),
+            circuit.new_constant(M31::from(18))
+        );
+        assert_eq!(circuit.num_rows, num_rows + 3);
+
+        // rows involving a variable are not folded
+        let b = circuit.mul(a, five);
+        assert_eq!(circuit.get_constant(b), None);
+        assert_eq!(circuit.get_output_wire(b), M31::from(10));
+
+        assert!(circuit.is_constraint_satisfied());
+    }
+
+    #[test]
+    fn test_check_satisfied() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_witness(M31::from(3));
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        assert!(circuit.check_satisfied().is_ok());
+
+        circuit.output_wires[c.0] = M31::from(16);
+        let err = circuit.check_satisfied().unwrap_err();
+        assert_eq!(err.row, c.0);
+        assert_eq!(err.op, M31::from(0));
+        assert_eq!((err.idx_a, err.idx_b), (a.0, b.0));
+        assert_eq!(
+            (err.w_a, err.w_b, err.w_c),
+            (M31::from(3), M31::from(5), M31::from(16))
+        );
+        assert_eq!(
+            err.to_string(),
+            "row 3 unsatisfied: op = 0, a = 3 (wire 1), b = 5 (wire 2), c = 16"
+        );
+    }
+
+    #[test]
+    fn test_check_satisfied_reports_lowest_row() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_witness(M31::from(3));
+        let mut rows = vec![];
+        let mut acc = a;
+        for _ in 0..1000 {
+            acc = circuit.add(acc, a);
+            rows.push(acc);
+        }
+        assert!(circuit.check_satisfied().is_ok());
+
+        circuit.output_wires[rows[700].0] += M31::one();
+        circuit.output_wires[rows[300].0] += M31::one();
+        assert_eq!(circuit.check_satisfied().unwrap_err().row, rows[300].0);
+    }
+
+    #[test]
+    fn test_logup_with_channel() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+
+        let inputs = circuit.input_maps.clone();
+        assert!(
+            circuit.is_logup_satisfied_with_channel(&mut circuit.logup_channel(&inputs), &inputs)
+        );
+
+        let mut wrong = inputs.clone();
+        wrong.set_value(2, M31::from(16));
+        assert!(
+            !circuit.is_logup_satisfied_with_channel(&mut circuit.logup_channel(&wrong), &wrong)
+        );
+
+        // a wrong statement fails whatever the channel was seeded with
+        assert!(
+            !circuit.is_logup_satisfied_with_channel(&mut circuit.logup_channel(&inputs), &wrong)
+        );
+    }
+
+    #[test]
+    fn test_expose() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(3));
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+
+        // `a` is already consumed by the multiplication, `c` needs a copy row
+        let num_rows = circuit.num_rows;
+        let public_a = circuit.expose(a);
+        assert_eq!(circuit.num_rows, num_rows);
+        let public_c = circuit.expose(c);
+        assert_eq!(circuit.num_rows, num_rows + 1);
+        assert_eq!((public_a.wire(), public_c.wire()), (a, c));
+
+        let mult = circuit.mult.clone();
+        circuit.recompute_mult();
+        assert_eq!(circuit.mult, mult);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        // the statement must contain the exposed values
+        let mut inputs = circuit.input_maps.clone();
+        inputs.set_value(inputs.len() - 1, M31::from(16));
+        assert!(!circuit.is_logup_satisfied(&inputs));
+        inputs.pop();
+        assert!(!circuit.is_logup_satisfied(&inputs));
+    }
+
+    #[test]
+    #[should_panic(expected = "the circuit exceeds its budget of 2^3 rows at row 8")]
+    fn test_max_log_size() {
+        let mut circuit = Circuit::new().with_max_log_size(3);
+        let mut a = circuit.new_witness(M31::from(3));
+        for _ in 0..6 {
+            a = circuit.mul(a, a);
+        }
+        assert_eq!(circuit.num_rows, 8);
+        circuit.mul(a, a);
+    }
+
+    #[test]
+    fn test_try_new_row() {
+        let mut circuit = Circuit::new().with_max_log_size(2);
+        let a = circuit.new_witness(M31::from(3));
+        let b = circuit.try_new_row(M31::zero(), a, a).unwrap();
+        circuit.try_new_row(M31::zero(), b, b).unwrap();
+        assert_eq!(circuit.check_log_size(2), Ok(()));
+        let err = circuit.try_new_row(M31::zero(), b, b).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the circuit exceeds its budget of 2^2 rows at row 4"
+        );
+        assert_eq!(circuit.num_rows, 4);
+        assert!(circuit.check_log_size(1).is_err());
+    }
+
+    #[test]
+    fn test_check_size() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_witness(M31::from(3));
+        circuit.mul(a, a);
+        assert_eq!(circuit.check_size(), Ok(()));
+
+        circuit.mult[a.0] = MAX_MULT + 1;
+        assert_eq!(
+            circuit.check_size(),
+            Err(SizeError::MultOverflow {
+                row: a.0,
+                mult: MAX_MULT + 1
+            })
+        );
+
+        assert_eq!(
+            check_size(MAX_NUM_ROWS + 1, std::iter::empty()),
+            Err(SizeError::TooManyRows {
+                num_rows: MAX_NUM_ROWS + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(3));
+        circuit.mul(a, a);
+        assert_eq!(circuit.num_rows, 4);
+
+        // already a power of two
+        circuit.pad_to_next_power_of_2();
+        assert_eq!(circuit.num_rows, 4);
+
+        assert_eq!(circuit.pad_minimal(2), 2);
+        assert_eq!(circuit.num_rows, 4);
+        assert_eq!(circuit.pad_minimal(4), 4);
+        assert_eq!(circuit.num_rows, 16);
+
+        circuit.pad_to_log_size(5);
+        assert_eq!(circuit.num_rows, 32);
+        assert!(circuit.is_constraint_satisfied());
+
+        let mult = circuit.mult.clone();
+        circuit.recompute_mult();
+        assert_eq!(circuit.mult, mult);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::circuit::chunked::{CHUNK_SIZE, LOG_CHUNK_SIZE};
+use crate::simd::multiversion;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Index, IndexMut};
+use stwo_prover::core::backend::simd::column::BaseColumn;
+use stwo_prover::core::backend::simd::m31::{PackedM31, N_LANES};
+use stwo_prover::core::fields::m31::M31;
+
+const PACKED_CHUNK_SIZE: usize = CHUNK_SIZE / N_LANES;
+
+// A column of field elements in the chunked layout of `ChunkedVec`, where every chunk is already
+// in the packed layout of the SIMD backend. The trace columns are then made of the chunks as they
+// are instead of being converted element by element.
+#[derive(Clone, Default)]
+pub struct PackedM31Vec {
+    chunks: Vec<Vec<PackedM31>>,
+    len: usize,
+}
+
+// A packed word is `N_LANES` consecutive `M31`s, so a slice of packed words can be read as a
+// slice of field elements.
+fn lanes(chunk: &[PackedM31]) -> &[M31] {
+    // SAFETY: `PackedM31` is a `#[repr(transparent)]` wrapper of `Simd<u32, N_LANES>`, which is
+    // laid out as `N_LANES` contiguous `u32`s with no padding, and `M31` is a
+    // `#[repr(transparent)]` `u32`. The pointer is aligned for `M31`, as `PackedM31` is aligned to
+    // at least the alignment of `u32`, and the length covers exactly the words of `chunk`, whose
+    // borrow the returned slice keeps. The lanes only ever hold reduced values, written as `M31`s
+    // or broadcast from one.
+    unsafe { std::slice::from_raw_parts(chunk.as_ptr() as *const M31, chunk.len() * N_LANES) }
+}
+
+fn lanes_mut(chunk: &mut [PackedM31]) -> &mut [M31] {
+    // SAFETY: as in `lanes`, and the returned slice holds the unique borrow of `chunk`. A
+    // `PackedM31` is valid for any reduced lanes, so writing an `M31` through the slice keeps the
+    // packed word valid.
+    unsafe { std::slice::from_raw_parts_mut(chunk.as_mut_ptr() as *mut M31, chunk.len() * N_LANES) }
+}
+
+impl PackedM31Vec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: M31) {
+        if self.len % CHUNK_SIZE == 0 {
+            let capacity = if self.chunks.is_empty() {
+                0
+            } else {
+                PACKED_CHUNK_SIZE
+            };
+            self.chunks.push(Vec::with_capacity(capacity));
+        }
+        let chunk = self.chunks.last_mut().unwrap();
+        if self.len % N_LANES == 0 {
+            chunk.push(PackedM31::broadcast(M31::from(0)));
+        }
+        lanes_mut(chunk)[self.len & (CHUNK_SIZE - 1)] = value;
+        self.len += 1;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&M31> {
+        (index < self.len).then(|| &self[index])
+    }
+
+    // The rows in order, one full chunk at a time except for the last one.
+    pub fn chunks(&self) -> impl Iterator<Item = &[M31]> + Clone + '_ {
+        self.chunks.iter().enumerate().map(|(i, chunk)| {
+            let len = (self.len - (i << LOG_CHUNK_SIZE)).min(CHUNK_SIZE);
+            &lanes(chunk)[..len]
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &M31> + Clone + '_ {
+        self.chunks().flatten()
+    }
+
+    pub fn to_vec(&self) -> Vec<M31> {
+        let mut vec = Vec::with_capacity(self.len);
+        for chunk in self.chunks() {
+            vec.extend_from_slice(chunk);
+        }
+        vec
+    }
+
+    pub fn to_base_column(&self) -> BaseColumn {
+        let mut data = Vec::with_capacity(self.len.div_ceil(N_LANES));
+        for chunk in self.chunks.iter() {
+            data.extend_from_slice(chunk);
+        }
+        BaseColumn {
+            data,
+            length: self.len,
+        }
+    }
+
+    pub fn into_base_column(mut self) -> BaseColumn {
+        if self.chunks.len() == 1 {
+            return BaseColumn {
+                data: self.chunks.pop().unwrap(),
+                length: self.len,
+            };
+        }
+        self.to_base_column()
+    }
+}
+
+// A column of `len` rows written in order by `fill(first_row, rows)`, `CHUNK_SIZE` rows at a
+// time, straight into the packed words of the column.
+pub(crate) fn fill_base_column(len: usize, mut fill: impl FnMut(usize, &mut [M31])) -> BaseColumn {
+    let mut data = vec![PackedM31::broadcast(M31::from(0)); len.div_ceil(N_LANES)];
+    for (i, rows) in lanes_mut(&mut data)[..len]
+        .chunks_mut(CHUNK_SIZE)
+        .enumerate()
+    {
+        fill(i << LOG_CHUNK_SIZE, rows);
+    }
+    BaseColumn { data, length: len }
+}
+
+// Extends a column to `len` rows of `value`, in place.
+pub(crate) fn pad_base_column(mut column: BaseColumn, len: usize, value: M31) -> BaseColumn {
+    assert!(column.length <= len);
+    let start = column.length;
+    column
+        .data
+        .resize(len.div_ceil(N_LANES), PackedM31::broadcast(value));
+    lanes_mut(&mut column.data)[start..len].fill(value);
+    column.length = len;
+    column
+}
+
+// The rows of a column, for updating single cells in place.
+pub(crate) fn base_column_rows(column: &mut BaseColumn) -> &mut [M31] {
+    let len = column.length;
+    &mut lanes_mut(&mut column.data)[..len]
+}
+
+multiversion! {
+    // Converts wire indices, which `check_size` keeps below P, in batches of `N_LANES`.
+    pub(crate) fn indices_into(values: &[usize], out: &mut [M31]) {
+        assert_eq!(values.len(), out.len());
+        for (values, out) in values.chunks(N_LANES).zip(out.chunks_mut(N_LANES)) {
+            for (v, o) in values.iter().zip(out.iter_mut()) {
+                *o = M31::from(*v as u32);
+            }
+        }
+    }
+}
+
+multiversion! {
+    // The same for multiplicities, which `check_size` keeps at most `MAX_MULT`.
+    pub(crate) fn mults_into(values: &[u32], out: &mut [M31]) {
+        assert_eq!(values.len(), out.len());
+        for (values, out) in values.chunks(N_LANES).zip(out.chunks_mut(N_LANES)) {
+            for (v, o) in values.iter().zip(out.iter_mut()) {
+                *o = M31::from(*v);
+            }
+        }
+    }
+}
+
+multiversion! {
+    // The indices `first_row..` of the rows themselves, for the `c_wire` column.
+    pub(crate) fn row_indices_into(first_row: usize, out: &mut [M31]) {
+        let first_row = first_row as u32;
+        for (i, out) in out.chunks_mut(N_LANES).enumerate() {
+            let base = first_row + (i * N_LANES) as u32;
+            for (j, o) in out.iter_mut().enumerate() {
+                *o = M31::from(base + j as u32);
+            }
+        }
+    }
+}
+
+impl Index<usize> for PackedM31Vec {
+    type Output = M31;
+
+    fn index(&self, index: usize) -> &M31 {
+        assert!(
+            index < self.len,
+            "index {} is out of range for a column of {} rows",
+            index,
+            self.len
+        );
+        &lanes(&self.chunks[index >> LOG_CHUNK_SIZE])[index & (CHUNK_SIZE - 1)]
+    }
+}
+
+impl IndexMut<usize> for PackedM31Vec {
+    fn index_mut(&mut self, index: usize) -> &mut M31 {
+        assert!(
+            index < self.len,
+            "index {} is out of range for a column of {} rows",
+            index,
+            self.len
+        );
+        &mut lanes_mut(&mut self.chunks[index >> LOG_CHUNK_SIZE])[index & (CHUNK_SIZE - 1)]
+    }
+}
+
+impl Extend<M31> for PackedM31Vec {
+    fn extend<I: IntoIterator<Item = M31>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl FromIterator<M31> for PackedM31Vec {
+    fn from_iter<I: IntoIterator<Item = M31>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl From<Vec<M31>> for PackedM31Vec {
+    fn from(vec: Vec<M31>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a PackedM31Vec {
+    type Item = &'a M31;
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<&'a [M31]>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks().collect::<Vec<_>>().into_iter().flatten()
+    }
+}
+
+impl PartialEq for PackedM31Vec {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for PackedM31Vec {}
+
+impl Debug for PackedM31Vec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::chunked::CHUNK_SIZE;
+    use crate::circuit::packed::{
+        fill_base_column, indices_into, pad_base_column, row_indices_into, PackedM31Vec,
+    };
+    use stwo_prover::core::backend::Column;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_packed_vec() {
+        let mut vec = PackedM31Vec::new();
+        for i in 0..(CHUNK_SIZE + 21) {
+            vec.push(M31::from(i));
+        }
+        assert_eq!(vec.len(), CHUNK_SIZE + 21);
+        assert_eq!(vec.chunks().last().unwrap().len(), 21);
+        assert_eq!(vec[CHUNK_SIZE + 20], M31::from(CHUNK_SIZE + 20));
+
+        vec[17] = M31::from(0);
+        assert_eq!(vec.get(17), Some(&M31::from(0)));
+        assert_eq!(vec.get(CHUNK_SIZE + 21), None);
+
+        let column = vec.to_base_column();
+        assert_eq!(column.length, vec.len());
+        assert_eq!(column.to_cpu(), vec.to_vec());
+
+        let small = (0..20u32).map(M31::from).collect::<PackedM31Vec>();
+        assert_eq!(small.clone().into_base_column().to_cpu(), small.to_vec());
+    }
+
+    #[test]
+    fn test_fill_base_column() {
+        let len = CHUNK_SIZE + 21;
+        let indices = (0..len).rev().collect::<Vec<_>>();
+        let column = fill_base_column(len, |first_row, out| {
+            indices_into(&indices[first_row..first_row + out.len()], out)
+        });
+        let expected = indices.iter().map(|&i| M31::from(i)).collect::<Vec<_>>();
+        assert_eq!(column.to_cpu(), expected);
+
+        let rows = fill_base_column(len, row_indices_into);
+        assert_eq!(rows.to_cpu(), (0..len).map(M31::from).collect::<Vec<_>>());
+
+        // the padding fills the rest of the last packed word and then whole words
+        let padded = pad_base_column(column, CHUNK_SIZE * 2, M31::from(7));
+        assert_eq!(padded.length, CHUNK_SIZE * 2);
+        let padded = padded.to_cpu();
+        assert_eq!(padded[..len], expected[..]);
+        assert!(padded[len..].iter().all(|&v| v == M31::from(7)));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::circuit::gadget::Gadget;
+use crate::circuit::hint::Hint;
+use crate::circuit::{Circuit, RowKind, Wire};
+use std::num::NonZeroUsize;
+use std::thread;
+
+impl Circuit {
+    // Builds independent regions on separate threads and appends them to this circuit in region
+    // order. Region `i` is built by `build(i, circuit, inputs)` into a local segment whose formal
+    // inputs take the values of `regions[i]`, exactly like a `Gadget`. The result does not depend
+    // on the number of threads: segments are stitched in order, with each one's rows moved by the
+    // number of rows before it.
+    //
+    // Debug labels set inside a segment are not carried over.
+    pub fn build_parallel<F>(&mut self, regions: &[Vec<Wire>], build: F) -> Vec<Vec<Wire>>
+    where
+        F: Fn(usize, &mut Circuit, &[Wire]) -> Vec<Wire> + Sync,
+    {
+        let values = regions
+            .iter()
+            .map(|inputs| {
+                inputs
+                    .iter()
+                    .map(|&wire| self.get_output_wire(wire))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let num_threads = thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
+        let per_thread = regions.len().div_ceil(num_threads).max(1);
+
+        let build = &build;
+        let segments = thread::scope(|s| {
+            let handles = values
+                .chunks(per_thread)
+                .enumerate()
+                .map(|(chunk, values)| {
+                    s.spawn(move || {
+                        values
+                            .iter()
+                            .enumerate()
+                            .map(|(i, values)| {
+                                let region = chunk * per_thread + i;
+                                Gadget::new(values, |circuit, inputs| {
+                                    build(region, circuit, inputs)
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        segments
+            .iter()
+            .zip(regions.iter())
+            .map(|(segment, inputs)| self.append_segment(segment, inputs))
+            .collect()
+    }
+
+    // Unlike `instantiate`, rows are copied as they are instead of being re-evaluated, since the
+    // segment was built against the actual input values.
+    fn append_segment(&mut self, segment: &Gadget, inputs: &[Wire]) -> Vec<Wire> {
+        assert_eq!(inputs.len(), segment.inputs.len());
+
+        let g = &segment.circuit;
+        let kinds = g.row_kinds();
+
+        let mut map = vec![Wire::ZERO; g.num_rows];
+        map[Wire::ONE.0] = Wire::ONE;
+        for (&formal, &actual) in segment.inputs.iter().zip(inputs.iter()) {
+            map[formal.0] = actual;
+        }
+        let mut is_mapped = vec![false; g.num_rows];
+        is_mapped[0] = true;
+        is_mapped[1] = true;
+        for &formal in segment.inputs.iter() {
+            is_mapped[formal.0] = true;
+        }
+
+        for row in 2..g.num_rows {
+            if is_mapped[row] {
+                continue;
+            }
+
+            let op = g.op[row];
+            if matches!(kinds[row], RowKind::Zero | RowKind::Input) {
+                panic!("segments cannot declare their own public inputs");
+            }
+            if kinds[row] == RowKind::Gate
+                && g.idx_a[row] == 1
+                && g.idx_b[row] == 0
+                && g.constant_maps.get(&op) == Some(&Wire(row))
+            {
+                map[row] = self.new_constant(op);
+                continue;
+            }
+
+            let idx = self.next_row();
+            map[row] = Wire(idx);
+            let idx_a = map[g.idx_a[row]];
+            let idx_b = map[g.idx_b[row]];
+
+            self.output_wires.push(g.output_wires[row]);
+            self.op.push(op);
+            self.idx_a.push(idx_a.0);
+            self.idx_b.push(idx_b.0);
+            self.mult.push(0);
+
+            self.increase_output_count(idx_a);
+            self.increase_output_count(idx_b);
+        }
+
+        for hint in g.hints.iter() {
+            self.hints.push(Hint {
+                row: map[hint.row].0,
+                inputs: hint.inputs.iter().map(|&idx| map[idx].0).collect(),
+                compute: hint.compute.clone(),
+            });
+        }
+
+        segment.outputs.iter().map(|w| map[w.0]).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Wire};
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_build_parallel() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let regions = (0..10)
+            .map(|i| vec![circuit.new_witness(M31::from(i))])
+            .collect::<Vec<_>>();
+
+        // x^3 + region + 7, with a zero test on the way
+        let outputs = circuit.build_parallel(&regions, |region, circuit, inputs| {
+            let x2 = circuit.mul(inputs[0], inputs[0]);
+            let x3 = circuit.mul(x2, inputs[0]);
+            let k = circuit.new_constant(M31::from(region as u32 + 7));
+            let y = circuit.add(x3, k);
+            let x3_neg = circuit.neg(x3);
+            let zero = circuit.add(x3, x3_neg);
+            circuit.zero_test(zero);
+            vec![y]
+        });
+
+        for (i, output) in outputs.iter().enumerate() {
+            let i = i as u32;
+            assert_eq!(
+                circuit.get_output_wire(output[0]),
+                M31::from(i * i * i + i + 7)
+            );
+        }
+        assert!(circuit.is_constraint_satisfied());
+
+        let mult = circuit.mult.clone();
+        circuit.recompute_mult();
+        assert_eq!(circuit.mult, mult);
+
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        // the same regions built sequentially give the same rows
+        let mut sequential = Circuit::new();
+        sequential.new_input(M31::one());
+        for i in 0..10 {
+            sequential.new_witness(M31::from(i));
+        }
+        for (region, inputs) in regions.iter().enumerate() {
+            let x2 = sequential.mul(inputs[0], inputs[0]);
+            let x3 = sequential.mul(x2, inputs[0]);
+            let k = sequential.new_constant(M31::from(region as u32 + 7));
+            let y = sequential.add(x3, k);
+            let x3_neg = sequential.neg(x3);
+            let zero = sequential.add(x3, x3_neg);
+            sequential.zero_test(zero);
+            assert_eq!(y, outputs[region][0]);
+        }
+        assert_eq!(sequential.idx_a, circuit.idx_a);
+        assert_eq!(sequential.idx_b, circuit.idx_b);
+        assert_eq!(circuit.get_output_wire(Wire(1)), M31::one());
+    }
+}
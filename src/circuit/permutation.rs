@@ -0,0 +1,130 @@
+use crate::circuit::Circuit;
+use ark_ff::{One, Zero};
+use ark_std::rand::RngCore;
+use ark_std::UniformRand;
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+
+// The classic Plonk copy constraint, as an alternative to the logup wiring check: the cells
+// holding the same wire form a cycle of a permutation `sigma`, and the grand product over
+// `(v + beta * id + gamma)` must match the one over `(v + beta * sigma(id) + gamma)`. It needs no
+// `mult` column and no inverses, but one more constant column per wire column.
+//
+// The component in the stwo fork only checks the wiring with logup, so this only provides the
+// constant columns and a reference check that a permutation component can be tested against.
+
+impl Circuit {
+    // The copy permutation over the cells `a[0..n], b[0..n], c[0..n]`, numbered in this order.
+    // All cells that carry the value of the same row form one cycle, which starts at the c cell
+    // of that row.
+    pub fn copy_permutation(&self) -> Vec<usize> {
+        let n = self.num_rows;
+
+        let mut cycles = (0..n).map(|row| vec![2 * n + row]).collect::<Vec<_>>();
+        for (row, (&idx_a, &idx_b)) in self.idx_a.iter().zip(self.idx_b.iter()).enumerate() {
+            cycles[idx_a].push(row);
+            cycles[idx_b].push(n + row);
+        }
+
+        let mut sigma = vec![0; 3 * n];
+        for cycle in cycles.iter() {
+            for (i, &cell) in cycle.iter().enumerate() {
+                sigma[cell] = cycle[(i + 1) % cycle.len()];
+            }
+        }
+        sigma
+    }
+
+    // The values of the a, b and c cells, as they appear in the trace.
+    pub fn cell_values(&self) -> [Vec<M31>; 3] {
+        [
+            self.idx_a
+                .iter()
+                .map(|&idx| self.output_wires[idx])
+                .collect(),
+            self.idx_b
+                .iter()
+                .map(|&idx| self.output_wires[idx])
+                .collect(),
+            self.output_wires.to_vec(),
+        ]
+    }
+
+    // The grand product argument over the copy permutation for the given cell values, plus the
+    // boundary check that the input rows hold the public values.
+    pub fn is_permutation_satisfied<R: RngCore>(
+        &self,
+        prng: &mut R,
+        cells: &[Vec<M31>; 3],
+        inputs: &[(usize, M31)],
+    ) -> bool {
+        let n = self.num_rows;
+        if cells.iter().any(|column| column.len() != n) {
+            return false;
+        }
+        for &(idx, v) in inputs.iter() {
+            if idx >= n || cells[2][idx] != v {
+                return false;
+            }
+        }
+
+        let beta = QM31::rand(prng);
+        let gamma = QM31::rand(prng);
+
+        let sigma = self.copy_permutation();
+
+        let mut numerator = QM31::one();
+        let mut denominator = QM31::one();
+        for ((cell, &v), &sigma) in cells.iter().flatten().enumerate().zip(sigma.iter()) {
+            numerator *= beta * M31::from(cell) + gamma + v;
+            denominator *= beta * M31::from(sigma) + gamma + v;
+        }
+
+        !denominator.is_zero() && numerator == denominator
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_permutation_wiring() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        let d = circuit.add(c, a);
+        circuit.expose(d);
+        circuit.pad_to_next_power_of_2();
+
+        let sigma = circuit.copy_permutation();
+        let mut sorted = sigma.clone();
+        sorted.sort_unstable();
+        assert!(sorted.into_iter().eq(0..3 * circuit.num_rows));
+
+        let cells = circuit.cell_values();
+        assert!(circuit.is_permutation_satisfied(&mut prng, &cells, &circuit.input_maps));
+
+        // a wrong public value
+        let mut inputs = circuit.input_maps.clone();
+        inputs.set_value(1, M31::from(4));
+        assert!(!circuit.is_permutation_satisfied(&mut prng, &cells, &inputs));
+
+        // an a cell that disagrees with the row it copies
+        let mut tampered = cells.clone();
+        tampered[0][d.0] = M31::from(16);
+        assert!(!circuit.is_permutation_satisfied(&mut prng, &tampered, &circuit.input_maps));
+
+        // a c cell that disagrees with its copies
+        let mut tampered = cells;
+        tampered[2][c.0] = M31::from(16);
+        assert!(!circuit.is_permutation_satisfied(&mut prng, &tampered, &circuit.input_maps));
+    }
+}
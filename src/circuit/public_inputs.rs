@@ -0,0 +1,204 @@
+use std::ops::Deref;
+use stwo_prover::core::fields::m31::M31;
+
+// The statement of a proof: the rows whose values are given to the verifier, which are the
+// inputs and the exposed outputs, with their values and optional labels.
+//
+// The order is the one in which they were added to the circuit. It is the order of
+// `CircuitIndex::input_wires` and of the values given with a verification key, and the only one
+// in which the entries are read, written or compared. The encoding is the sequence of
+// `(row, value, label)` triples, of which human-readable formats also accept `(row, value)`.
+//
+// It dereferences to the `(row, value)` pairs, which is all that the logup sum reads. Entries are
+// only changed through the methods below, which keep every label with its entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PublicInputs {
+    entries: Vec<(usize, M31)>,
+    labels: Vec<Option<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicInput {
+    pub wire: usize,
+    pub value: M31,
+    pub label: Option<String>,
+}
+
+impl PublicInputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Pairs the rows of a verification key with the values given for them.
+    pub fn from_wires(wires: &[usize], values: &[M31]) -> Self {
+        assert_eq!(wires.len(), values.len());
+        wires.iter().copied().zip(values.iter().copied()).collect()
+    }
+
+    pub fn push(&mut self, wire: usize, value: M31) {
+        self.entries.push((wire, value));
+        self.labels.push(None);
+    }
+
+    pub fn push_entry(&mut self, entry: PublicInput) {
+        self.entries.push((entry.wire, entry.value));
+        self.labels.push(entry.label);
+    }
+
+    pub fn pop(&mut self) -> Option<PublicInput> {
+        let (wire, value) = self.entries.pop()?;
+        let label = self.labels.pop().unwrap();
+        Some(PublicInput { wire, value, label })
+    }
+
+    pub fn set_value(&mut self, index: usize, value: M31) {
+        self.entries[index].1 = value;
+    }
+
+    pub fn set_wire(&mut self, index: usize, wire: usize) {
+        self.entries[index].0 = wire;
+    }
+
+    // Reads the value of every entry again from its row.
+    pub fn update_values(&mut self, value: impl Fn(usize) -> M31) {
+        for entry in self.entries.iter_mut() {
+            entry.1 = value(entry.0);
+        }
+    }
+
+    pub fn set_label(&mut self, index: usize, label: &str) {
+        self.labels[index] = Some(label.to_string());
+    }
+
+    pub fn label(&self, index: usize) -> Option<&str> {
+        self.labels[index].as_deref()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = PublicInput> + '_ {
+        self.entries
+            .iter()
+            .zip(self.labels.iter())
+            .map(|(&(wire, value), label)| PublicInput {
+                wire,
+                value,
+                label: label.clone(),
+            })
+    }
+
+    pub fn wires(&self) -> Vec<usize> {
+        self.entries.iter().map(|&(wire, _)| wire).collect()
+    }
+
+    pub fn values(&self) -> Vec<M31> {
+        self.entries.iter().map(|&(_, value)| value).collect()
+    }
+}
+
+impl Deref for PublicInputs {
+    type Target = [(usize, M31)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl FromIterator<(usize, M31)> for PublicInputs {
+    fn from_iter<I: IntoIterator<Item = (usize, M31)>>(iter: I) -> Self {
+        let entries = iter.into_iter().collect::<Vec<_>>();
+        let labels = vec![None; entries.len()];
+        Self { entries, labels }
+    }
+}
+
+impl FromIterator<PublicInput> for PublicInputs {
+    fn from_iter<I: IntoIterator<Item = PublicInput>>(iter: I) -> Self {
+        let mut inputs = Self::new();
+        for entry in iter {
+            inputs.push_entry(entry);
+        }
+        inputs
+    }
+}
+
+impl From<Vec<(usize, M31)>> for PublicInputs {
+    fn from(entries: Vec<(usize, M31)>) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a PublicInputs {
+    type Item = &'a (usize, M31);
+    type IntoIter = std::slice::Iter<'a, (usize, M31)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::public_inputs::{PublicInput, PublicInputs};
+    use crate::circuit::Circuit;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_public_inputs() {
+        let mut circuit = Circuit::new();
+        let x = circuit.new_input(M31::from(3));
+        let y = circuit.new_input(M31::from(4));
+        let z = circuit.mul(x.wire(), y.wire());
+        circuit.expose(z);
+        circuit.label_public(y, "y");
+
+        let inputs = &circuit.input_maps;
+        assert_eq!(inputs.wires(), vec![1, 2, z.0]);
+        assert_eq!(
+            inputs.values(),
+            vec![M31::from(3), M31::from(4), M31::from(12)]
+        );
+        assert_eq!(inputs.label(0), None);
+        assert_eq!(inputs.label(1), Some("y"));
+        assert_eq!(
+            PublicInputs::from_wires(&inputs.wires(), &inputs.values())[..],
+            inputs[..]
+        );
+
+        // the label stays with its entry
+        let mut changed = inputs.clone();
+        changed.set_value(1, M31::from(5));
+        changed.set_wire(1, 3);
+        assert_eq!(changed[1], (3, M31::from(5)));
+        assert_eq!(changed.label(1), Some("y"));
+        changed.update_values(|wire| M31::from(wire as u32));
+        assert_eq!(
+            changed.values(),
+            vec![M31::from(1), M31::from(3), M31::from(z.0 as u32)]
+        );
+        assert_eq!(changed.label(1), Some("y"));
+
+        let json = serde_json::to_string(inputs).unwrap();
+        assert_eq!(json, format!(r#"[[1,3,null],[2,4,"y"],[{},12,null]]"#, z.0));
+        let decoded: PublicInputs = serde_json::from_str(&json).unwrap();
+        assert_eq!(&decoded, inputs);
+
+        // pairs are read as entries without a label
+        let decoded: PublicInputs = serde_json::from_str(r#"[[1, 3], [2, "0x4", "y"]]"#).unwrap();
+        assert_eq!(
+            decoded.entries().collect::<Vec<_>>(),
+            vec![
+                PublicInput {
+                    wire: 1,
+                    value: M31::from(3),
+                    label: None
+                },
+                PublicInput {
+                    wire: 2,
+                    value: M31::from(4),
+                    label: Some("y".to_string())
+                },
+            ]
+        );
+        assert!(serde_json::from_str::<PublicInputs>("[[1]]").is_err());
+        assert!(serde_json::from_str::<PublicInputs>(r#"[[1, 3, "x", 4]]"#).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::circuit::constants::ConstantMap;
+use crate::circuit::public_inputs::{PublicInput, PublicInputs};
+use crate::circuit::{check_size, Circuit, Mode, Wire};
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+use stwo_prover::core::fields::m31::{M31, P};
+
+pub const CIRCUIT_LAYOUT_VERSION: u32 = 1;
+
+// The serialized layout of a circuit, version 1:
+// - `version`: the layout version, currently 1
+// - `num_rows`, `mode`
+// - `op`, `idx_a`, `idx_b`, `mult`, `output_wires`: the columns, one entry per row, with field
+//   elements stored as their canonical `u32` representation
+// - `input_maps`: `(row, value)` pairs in the order in which the inputs were created, without
+//   the labels of `PublicInputs`
+// - `constant_maps`: `(value, row)` pairs sorted by value
+//
+// Debug labels and hints are not part of the layout, so the rows computed by hints are read back
+// as plain witness rows.
+#[derive(Serialize, Deserialize)]
+struct CircuitLayout {
+    version: u32,
+    num_rows: usize,
+    mode: Mode,
+    op: Vec<u32>,
+    idx_a: Vec<usize>,
+    idx_b: Vec<usize>,
+    mult: Vec<u32>,
+    output_wires: Vec<u32>,
+    input_maps: Vec<(usize, u32)>,
+    constant_maps: Vec<(u32, usize)>,
+}
+
+impl From<&Circuit> for CircuitLayout {
+    fn from(circuit: &Circuit) -> Self {
+        let constant_maps = circuit
+            .constant_maps
+            .iter()
+            .map(|(constant, wire)| (constant.0, wire.0))
+            .collect::<Vec<_>>();
+
+        Self {
+            version: CIRCUIT_LAYOUT_VERSION,
+            num_rows: circuit.num_rows,
+            mode: circuit.mode,
+            op: circuit.op.iter().map(|v| v.0).collect(),
+            idx_a: circuit.idx_a.to_vec(),
+            idx_b: circuit.idx_b.to_vec(),
+            mult: circuit.mult.to_vec(),
+            output_wires: circuit.output_wires.iter().map(|v| v.0).collect(),
+            input_maps: circuit
+                .input_maps
+                .iter()
+                .map(|&(idx, v)| (idx, v.0))
+                .collect(),
+            constant_maps,
+        }
+    }
+}
+
+fn to_m31<E: Error>(v: u32) -> Result<M31, E> {
+    if v >= P {
+        return Err(E::custom(format!("{} is not a canonical M31 element", v)));
+    }
+    Ok(M31::from(v))
+}
+
+// A field element, serialized as its canonical `u32`. In human-readable formats such as JSON, it
+// can also be read from a decimal or `0x`-prefixed hexadecimal string, as witness generators
+// often write them.
+struct M31Value(M31);
+
+impl Serialize for M31Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0 .0)
+    }
+}
+
+struct M31Visitor;
+
+impl Visitor<'_> for M31Visitor {
+    type Value = M31;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a canonical M31 element")
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<M31, E> {
+        let v = u32::try_from(v).map_err(|_| E::custom(format!("{} is not an M31 element", v)))?;
+        to_m31(v)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<M31, E> {
+        let v = u64::try_from(v).map_err(|_| E::custom(format!("{} is not an M31 element", v)))?;
+        self.visit_u64(v)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<M31, E> {
+        let parsed = match v.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => v.parse::<u32>(),
+        };
+        to_m31(parsed.map_err(|_| E::custom(format!("{} is not an M31 element", v)))?)
+    }
+}
+
+impl<'de> Deserialize<'de> for M31Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(M31Visitor).map(M31Value)
+        } else {
+            deserializer.deserialize_u32(M31Visitor).map(M31Value)
+        }
+    }
+}
+
+// Serde helpers for fields holding M31 elements, as in `M31Value`.
+pub mod m31_vec {
+    use crate::circuit::serialization::M31Value;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use stwo_prover::core::fields::m31::M31;
+
+    pub fn serialize<S: Serializer>(values: &[M31], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|&v| M31Value(v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<M31>, D::Error> {
+        Ok(Vec::<M31Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|v| v.0)
+            .collect())
+    }
+}
+
+// As `m31_vec`, but written as decimal strings, as snarkjs and circom tools write field elements.
+pub mod m31_decimal_vec {
+    use crate::circuit::serialization::m31_vec;
+    use serde::{Deserializer, Serializer};
+    use stwo_prover::core::fields::m31::M31;
+
+    pub fn serialize<S: Serializer>(values: &[M31], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| v.0.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<M31>, D::Error> {
+        m31_vec::deserialize(deserializer)
+    }
+}
+
+// The hints of an index, as the `(row, inputs)` of each. Their functions cannot be serialized, so
+// an index with any hint fails to deserialize, instead of reading their rows back as witnesses
+// that `CircuitIndex::evaluate` would then expect to be supplied.
+pub mod hint_rows {
+    use crate::circuit::hint::Hint;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hints: &[Hint], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hints.iter().map(|hint| (hint.row, &hint.inputs)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Hint>, D::Error> {
+        let hints = Vec::<(usize, Vec<usize>)>::deserialize(deserializer)?;
+        match hints.first() {
+            None => Ok(vec![]),
+            Some((row, _)) => Err(D::Error::custom(format!(
+                "the index has {} hints, starting at row {}, whose functions cannot be \
+                 deserialized; generate the index again from its circuit",
+                hints.len(),
+                row
+            ))),
+        }
+    }
+}
+
+// A public input as a `(row, value, label)` triple, which human-readable formats may shorten to
+// `(row, value)` when there is no label.
+impl Serialize for PublicInputs {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.entries()
+                .map(|entry| (entry.wire, M31Value(entry.value), entry.label)),
+        )
+    }
+}
+
+struct PublicInputVisitor;
+
+impl<'de> Visitor<'de> for PublicInputVisitor {
+    type Value = PublicInput;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a (row, value, label) triple")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PublicInput, A::Error> {
+        let wire = seq
+            .next_element::<usize>()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let value = seq
+            .next_element::<M31Value>()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?
+            .0;
+        let label = seq.next_element::<Option<String>>()?.flatten();
+        Ok(PublicInput { wire, value, label })
+    }
+}
+
+struct PublicInputEntry(PublicInput);
+
+impl<'de> Deserialize<'de> for PublicInputEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_tuple(3, PublicInputVisitor)
+            .map(PublicInputEntry)
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicInputs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<PublicInputEntry>::deserialize(deserializer)?
+            .into_iter()
+            .map(|entry| entry.0)
+            .collect())
+    }
+}
+
+// The same for `FM31`, which is stored as the `u32` of its canonical representative rather than
+// in Montgomery form.
+pub mod fm31 {
+    use crate::circuit::serialization::M31Value;
+    use crate::field::{from_m31, to_m31, FM31};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &FM31, serializer: S) -> Result<S::Ok, S::Error> {
+        M31Value(to_m31(value)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FM31, D::Error> {
+        Ok(from_m31(M31Value::deserialize(deserializer)?.0))
+    }
+}
+
+pub mod fm31_vec {
+    use crate::circuit::serialization::M31Value;
+    use crate::field::{IntoFM31Iter, IntoM31Iter, FM31};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[FM31], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().into_m31().map(M31Value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<FM31>, D::Error> {
+        Ok(Vec::<M31Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|v| v.0)
+            .into_fm31()
+            .collect())
+    }
+}
+
+impl Serialize for Circuit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CircuitLayout::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Circuit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let layout = CircuitLayout::deserialize(deserializer)?;
+
+        if layout.version != CIRCUIT_LAYOUT_VERSION {
+            return Err(D::Error::custom(format!(
+                "unsupported circuit layout version {}",
+                layout.version
+            )));
+        }
+
+        let num_rows = layout.num_rows;
+        if layout.op.len() != num_rows
+            || layout.idx_a.len() != num_rows
+            || layout.idx_b.len() != num_rows
+            || layout.mult.len() != num_rows
+            || layout.output_wires.len() != num_rows
+        {
+            return Err(D::Error::custom("the columns do not have num_rows entries"));
+        }
+        check_size(num_rows, &layout.mult).map_err(D::Error::custom)?;
+
+        let rows = layout
+            .idx_a
+            .iter()
+            .chain(layout.idx_b.iter())
+            .chain(layout.input_maps.iter().map(|(idx, _)| idx))
+            .chain(layout.constant_maps.iter().map(|(_, idx)| idx));
+        for &idx in rows {
+            if idx >= num_rows {
+                return Err(D::Error::custom(format!("row {} is out of range", idx)));
+            }
+        }
+
+        let mut constant_maps = ConstantMap::with_capacity(layout.constant_maps.len());
+        for (constant, idx) in layout.constant_maps {
+            constant_maps.insert(to_m31::<D::Error>(constant)?, Wire(idx));
+        }
+
+        Ok(Circuit {
+            num_rows,
+            mode: layout.mode,
+            output_wires: layout
+                .output_wires
+                .into_iter()
+                .map(to_m31::<D::Error>)
+                .collect::<Result<_, _>>()?,
+            op: layout
+                .op
+                .into_iter()
+                .map(to_m31::<D::Error>)
+                .collect::<Result<_, _>>()?,
+            idx_a: layout.idx_a.into(),
+            idx_b: layout.idx_b.into(),
+            mult: layout.mult.into(),
+            input_maps: layout
+                .input_maps
+                .into_iter()
+                .map(|(idx, v)| to_m31::<D::Error>(v).map(|v| (idx, v)))
+                .collect::<Result<_, _>>()?,
+            constant_maps,
+            hints: vec![],
+            debug_info: Default::default(),
+            max_log_size: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::index::Witness;
+    use crate::circuit::serialization::fm31_vec;
+    use crate::circuit::{Circuit, Mode};
+    use crate::field::FM31;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use serde::{Deserialize, Serialize};
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+        let circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+
+        let json = serde_json::to_string(&circuit).unwrap();
+        let recovered: Circuit = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(recovered.num_rows, circuit.num_rows);
+        assert!(recovered.mode == circuit.mode);
+        assert_eq!(recovered.output_wires, circuit.output_wires);
+        assert_eq!(recovered.op, circuit.op);
+        assert_eq!(recovered.idx_a, circuit.idx_a);
+        assert_eq!(recovered.idx_b, circuit.idx_b);
+        assert_eq!(recovered.mult, circuit.mult);
+        assert_eq!(recovered.input_maps, circuit.input_maps);
+        assert_eq!(recovered.constant_maps, circuit.constant_maps);
+
+        // the serialization is canonical
+        assert_eq!(serde_json::to_string(&recovered).unwrap(), json);
+
+        let mut layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+        layout["idx_a"][3] = serde_json::Value::from(circuit.num_rows);
+        assert!(serde_json::from_value::<Circuit>(layout).is_err());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Assignment {
+        #[serde(with = "fm31_vec")]
+        values: Vec<FM31>,
+    }
+
+    #[test]
+    fn test_m31_serde() {
+        let json = r#"{"output_wires": [1, "2", "0x7ffffffe"], "input_maps": [[0, "0x10"]]}"#;
+        let witness: Witness = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            witness.output_wires,
+            vec![M31::from(1), M31::from(2), -M31::from(1)]
+        );
+        assert_eq!(witness.input_maps[..], [(0, M31::from(16))]);
+        let recovered: Witness =
+            serde_json::from_str(&serde_json::to_string(&witness).unwrap()).unwrap();
+        assert_eq!(recovered, witness);
+
+        for invalid in ["2147483647", r#""0x7fffffff""#, "-1", r#""1.5""#] {
+            let json = format!(r#"{{"output_wires": [{}], "input_maps": []}}"#, invalid);
+            assert!(serde_json::from_str::<Witness>(&json).is_err());
+        }
+
+        let assignment = Assignment {
+            values: vec![FM31::from(3u32), -FM31::from(1u32)],
+        };
+        let json = serde_json::to_string(&assignment).unwrap();
+        assert_eq!(json, r#"{"values":[3,2147483646]}"#);
+        assert_eq!(
+            serde_json::from_str::<Assignment>(&json).unwrap(),
+            assignment
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::{Circuit, RowKind};
+use ark_ff::{One, Zero};
+use std::fmt::{Display, Formatter};
+
+// A breakdown of the rows of a circuit by what they do. Gates are additions when `op` is one,
+// multiplications when it is zero, and mixed otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitStats {
+    pub num_rows: usize,
+    pub num_inputs: usize,
+    pub num_witnesses: usize,
+    pub num_booleans: usize,
+    pub num_zero_tests: usize,
+    pub num_additions: usize,
+    pub num_multiplications: usize,
+    pub num_mixed: usize,
+    // the trailing rows that look like those added by `pad_to_log_size`, which are otherwise
+    // counted as multiplications
+    pub num_padding: usize,
+    pub max_mult: u32,
+}
+
+impl CircuitStats {
+    pub fn num_rows_before_padding(&self) -> usize {
+        self.num_rows - self.num_padding
+    }
+}
+
+impl CircuitIndex {
+    pub fn stats(&self) -> CircuitStats {
+        let mut stats = CircuitStats {
+            num_rows: self.num_rows,
+            max_mult: self.mult.iter().copied().max().unwrap_or(0),
+            ..Default::default()
+        };
+        for (row, kind) in self.row_kinds().into_iter().enumerate() {
+            match kind {
+                RowKind::Zero => {}
+                RowKind::Input => stats.num_inputs += 1,
+                RowKind::Witness => stats.num_witnesses += 1,
+                RowKind::Boolean => stats.num_booleans += 1,
+                RowKind::ZeroTest => stats.num_zero_tests += 1,
+                RowKind::Gate if self.op[row].is_one() => stats.num_additions += 1,
+                RowKind::Gate if self.op[row].is_zero() => stats.num_multiplications += 1,
+                RowKind::Gate => stats.num_mixed += 1,
+            }
+        }
+
+        let is_padding =
+            |row: usize| self.idx_a[row] == 0 && self.idx_b[row] == 0 && self.op[row].is_zero();
+        stats.num_padding = (1..self.num_rows)
+            .rev()
+            .take_while(|&row| is_padding(row))
+            .count();
+        stats
+    }
+}
+
+impl Circuit {
+    pub fn stats(&self) -> CircuitStats {
+        self.index().stats()
+    }
+}
+
+impl Display for CircuitStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "rows: {}", self.num_rows)?;
+        writeln!(f, "  inputs: {}", self.num_inputs)?;
+        writeln!(f, "  witnesses: {}", self.num_witnesses)?;
+        writeln!(f, "  booleans: {}", self.num_booleans)?;
+        writeln!(f, "  assert zero: {}", self.num_zero_tests)?;
+        writeln!(f, "  additions: {}", self.num_additions)?;
+        writeln!(
+            f,
+            "  multiplications: {}",
+            self.num_multiplications - self.num_padding
+        )?;
+        writeln!(f, "  mixed gates: {}", self.num_mixed)?;
+        writeln!(f, "  padding: {}", self.num_padding)?;
+        write!(f, "max multiplicity: {}", self.max_mult)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_circuit_stats() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        let d = circuit.add(c, a);
+        let e = circuit.new_witness(M31::from(18));
+        let neg_e = circuit.neg(e);
+        let diff = circuit.add(d, neg_e);
+        circuit.zero_test(diff);
+
+        let stats = circuit.stats();
+        assert_eq!(stats.num_rows, circuit.num_rows);
+        assert_eq!(stats.num_inputs, 1);
+        assert_eq!(stats.num_witnesses, 2);
+        assert_eq!(stats.num_zero_tests, 1);
+        assert_eq!(stats.num_additions, 2);
+        assert_eq!(stats.num_multiplications, 1);
+        assert_eq!(stats.num_mixed, 1);
+        assert_eq!(stats.num_padding, 0);
+
+        circuit.pad_to_next_power_of_2();
+        let padded = circuit.stats();
+        assert_eq!(padded.num_rows, circuit.num_rows);
+        assert_eq!(padded.num_rows_before_padding(), stats.num_rows);
+        assert_eq!(
+            padded.num_multiplications - padded.num_padding,
+            stats.num_multiplications
+        );
+        assert_eq!(padded.num_additions, stats.num_additions);
+        assert_eq!(padded.num_mixed, stats.num_mixed);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::circuit::index::EvaluationError;
+use crate::circuit::{Circuit, FastHashMap, RowKind};
+use ark_ff::One;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use stwo_prover::core::fields::m31::M31;
+
+// The rows that read each row of a circuit, for `Circuit::update_inputs` to re-evaluate only
+// what a change of inputs reaches. It is built once for a circuit of a given shape, after any
+// padding, and stays valid as long as the rows are not changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DependencyIndex {
+    num_rows: usize,
+    // the readers of row `r` are `readers[offsets[r]..offsets[r + 1]]`
+    offsets: Vec<usize>,
+    readers: Vec<usize>,
+    input_rows: Vec<usize>,
+    // the position in `Circuit::hints` of the hint of a row
+    hints: FastHashMap<usize, usize>,
+}
+
+impl DependencyIndex {
+    pub fn num_inputs(&self) -> usize {
+        self.input_rows.len()
+    }
+
+    fn readers(&self, row: usize) -> &[usize] {
+        &self.readers[self.offsets[row]..self.offsets[row + 1]]
+    }
+}
+
+impl Circuit {
+    // Only gates and hints compute their values from other rows, so these are the only readers
+    // recorded. Zero tests have no value of their own.
+    pub fn dependency_index(&self) -> DependencyIndex {
+        let kinds = self.row_kinds();
+        let hints = self
+            .hints
+            .iter()
+            .enumerate()
+            .map(|(i, hint)| (hint.row, i))
+            .collect::<FastHashMap<_, _>>();
+
+        let mut edges = vec![];
+        for (row, kind) in kinds.iter().enumerate() {
+            match kind {
+                RowKind::Gate => {
+                    edges.push((self.idx_a[row], row));
+                    edges.push((self.idx_b[row], row));
+                }
+                RowKind::Witness => {
+                    if let Some(&i) = hints.get(&row) {
+                        edges.extend(self.hints[i].inputs.iter().map(|&idx| (idx, row)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut offsets = vec![0; self.num_rows + 1];
+        for &(idx, _) in edges.iter() {
+            offsets[idx + 1] += 1;
+        }
+        for row in 0..self.num_rows {
+            offsets[row + 1] += offsets[row];
+        }
+
+        DependencyIndex {
+            num_rows: self.num_rows,
+            offsets,
+            readers: edges.into_iter().map(|(_, row)| row).collect(),
+            input_rows: (0..self.num_rows)
+                .filter(|&row| kinds[row] == RowKind::Input)
+                .collect(),
+            hints,
+        }
+    }
+
+    // Sets the input rows to `inputs`, given in row order as in `Circuit::assignment`, and
+    // re-evaluates the gates and hints that they reach, stopping wherever a value does not
+    // change. The values in the statement follow. Free witness rows keep their values, so
+    // witnesses that depend on the inputs should be hints. Returns the number of rows that were
+    // recomputed, with the inputs that changed.
+    pub fn update_inputs(
+        &mut self,
+        index: &DependencyIndex,
+        inputs: &[M31],
+    ) -> Result<usize, EvaluationError> {
+        assert_eq!(
+            self.num_rows, index.num_rows,
+            "the dependency index is not the one of this circuit"
+        );
+        if inputs.len() < index.input_rows.len() {
+            return Err(EvaluationError::MissingInput);
+        }
+        if inputs.len() > index.input_rows.len() {
+            return Err(EvaluationError::TooManyInputs);
+        }
+
+        let mut queued = vec![false; self.num_rows];
+        let mut queue = BinaryHeap::new();
+        let mut updated = 0;
+        let mut set = |circuit: &mut Circuit, queue: &mut BinaryHeap<_>, row: usize, value| {
+            if circuit.output_wires[row] == value {
+                return false;
+            }
+            circuit.output_wires[row] = value;
+            for &reader in index.readers(row) {
+                if !queued[reader] {
+                    queued[reader] = true;
+                    queue.push(Reverse(reader));
+                }
+            }
+            true
+        };
+
+        for (&row, &value) in index.input_rows.iter().zip(inputs.iter()) {
+            if set(self, &mut queue, row, value) {
+                updated += 1;
+            }
+        }
+        // rows only read earlier rows, so popping them in order sees every input final
+        while let Some(Reverse(row)) = queue.pop() {
+            let value = match index.hints.get(&row) {
+                Some(&i) => self.hints[i].evaluate(&self.output_wires),
+                None => {
+                    let op = self.op[row];
+                    let a = self.output_wires[self.idx_a[row]];
+                    let b = self.output_wires[self.idx_b[row]];
+                    op * (a + b) + (M31::one() - op) * a * b
+                }
+            };
+            updated += 1;
+            set(self, &mut queue, row, value);
+        }
+
+        let output_wires = &self.output_wires;
+        self.input_maps.update_values(|wire| output_wires[wire]);
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::index::EvaluationError;
+    use crate::circuit::{Circuit, Mode};
+    use crate::gadgets::std::inverse;
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    // (x^-1 * w + x, y^2)
+    fn build(x: u32, y: u32) -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.mode = Mode::PROVE;
+        circuit.new_input(M31::one());
+        let x = circuit.new_input(M31::from(x)).wire();
+        let y = circuit.new_input(M31::from(y)).wire();
+        let w = circuit.new_witness(M31::from(5));
+
+        let inv = inverse(&mut circuit, x);
+        let scaled = circuit.mul(inv, w);
+        let sum = circuit.add(scaled, x);
+        circuit.expose(sum);
+        let square = circuit.mul(y, y);
+        circuit.expose(square);
+        circuit.pad_to_next_power_of_2();
+        circuit
+    }
+
+    #[test]
+    fn test_update_inputs() {
+        let mut circuit = build(3, 4);
+        let index = circuit.dependency_index();
+        assert_eq!(index.num_inputs(), 3);
+
+        let expected = build(7, 4);
+        let (inputs, _) = expected.assignment();
+        let updated = circuit.update_inputs(&index, &inputs).unwrap();
+        assert_eq!(
+            circuit.output_wires.to_vec(),
+            expected.output_wires.to_vec()
+        );
+        assert_eq!(circuit.input_maps, expected.input_maps);
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        // `y` and its square are untouched
+        assert!(updated < circuit.num_rows / 2);
+        assert_eq!(circuit.update_inputs(&index, &inputs), Ok(0));
+
+        assert_eq!(
+            circuit.update_inputs(&index, &inputs[1..]),
+            Err(EvaluationError::MissingInput)
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::circuit::constants::ConstantMap;
+use crate::circuit::public_inputs::PublicInputs;
+use crate::circuit::{Circuit, Mode, SizeError, Wire};
+use ark_ff::{One, Zero};
+use std::fmt::{Display, Formatter};
+use stwo_prover::core::fields::m31::M31;
+
+// The ways in which a circuit can be malformed, as opposed to unsatisfied: these break the
+// assumptions of the checks, the padding and the prover, which then panic or produce proofs that
+// fail for no apparent reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    ColumnLength {
+        column: &'static str,
+        len: usize,
+        num_rows: usize,
+    },
+    // the first row is not `op = 1, a = b = 0`, which every zero test and padding row reads
+    ZeroRow,
+    // row 1 is not the input of the constant one, which every constant row reads
+    OneRow,
+    // a row reads from a later row or from outside the circuit
+    InvalidReference {
+        row: usize,
+        column: &'static str,
+        idx: usize,
+        label: Option<String>,
+    },
+    InputOutOfRange {
+        index: usize,
+        row: usize,
+    },
+    // the value in the statement is not the one in the row
+    InputValueMismatch {
+        index: usize,
+        row: usize,
+        label: Option<String>,
+    },
+    ConstantOutOfRange {
+        row: usize,
+    },
+    HintOutOfRange {
+        row: usize,
+    },
+    // the multiplicity is not the number of uses of the row, less its entries in the statement
+    MultMismatch {
+        row: usize,
+        expected: i64,
+        actual: u32,
+        label: Option<String>,
+    },
+    Size(SizeError),
+}
+
+fn describe(row: usize, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} (row {})", label, row),
+        None => format!("row {}", row),
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::ColumnLength {
+                column,
+                len,
+                num_rows,
+            } => write!(
+                f,
+                "the column {} has {} entries for {} rows",
+                column, len, num_rows
+            ),
+            ValidationError::ZeroRow => write!(f, "the first row is not the zero row"),
+            ValidationError::OneRow => write!(f, "row 1 is not the input of the constant one"),
+            ValidationError::InvalidReference {
+                row,
+                column,
+                idx,
+                label,
+            } => write!(
+                f,
+                "{} refers to row {} in {}, which is not an earlier row",
+                describe(*row, label),
+                idx,
+                column
+            ),
+            ValidationError::InputOutOfRange { index, row } => {
+                write!(f, "input {} is row {}, which is out of range", index, row)
+            }
+            ValidationError::InputValueMismatch { index, row, label } => write!(
+                f,
+                "input {} does not have the value of {}",
+                index,
+                describe(*row, label)
+            ),
+            ValidationError::ConstantOutOfRange { row } => {
+                write!(
+                    f,
+                    "a constant is mapped to row {}, which is out of range",
+                    row
+                )
+            }
+            ValidationError::HintOutOfRange { row } => write!(
+                f,
+                "the hint of row {} writes or reads a row out of range",
+                row
+            ),
+            ValidationError::MultMismatch {
+                row,
+                expected,
+                actual,
+                label,
+            } => write!(
+                f,
+                "{} has multiplicity {}, but is used {} times",
+                describe(*row, label),
+                actual,
+                expected
+            ),
+            ValidationError::Size(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Circuit {
+    // Checks the structure of the circuit, whatever its values: the columns have one entry per
+    // row, every row reads only from itself and earlier rows, the statement, the constants and
+    // the hints point to rows of the circuit, and the multiplicities count the uses of each row.
+    // Circuits built through `Circuit` pass by construction, while hand-built or deserialized
+    // ones may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let num_rows = self.num_rows;
+        let columns = [
+            ("output_wires", self.output_wires.len()),
+            ("op", self.op.len()),
+            ("idx_a", self.idx_a.len()),
+            ("idx_b", self.idx_b.len()),
+            ("mult", self.mult.len()),
+        ];
+        for (column, len) in columns {
+            if len != num_rows {
+                return Err(ValidationError::ColumnLength {
+                    column,
+                    len,
+                    num_rows,
+                });
+            }
+        }
+        self.check_size().map_err(ValidationError::Size)?;
+        if num_rows == 0 || self.op[0] != M31::one() || self.idx_a[0] != 0 || self.idx_b[0] != 0 {
+            return Err(ValidationError::ZeroRow);
+        }
+
+        let mut uses = vec![0i64; num_rows];
+        for (column, indices) in [("idx_a", &self.idx_a), ("idx_b", &self.idx_b)] {
+            for (row, &idx) in indices.iter().enumerate() {
+                if idx > row {
+                    return Err(ValidationError::InvalidReference {
+                        row,
+                        column,
+                        idx,
+                        label: self.get_label(row),
+                    });
+                }
+                uses[idx] += 1;
+            }
+        }
+
+        for (index, &(row, value)) in self.input_maps.iter().enumerate() {
+            if row >= num_rows {
+                return Err(ValidationError::InputOutOfRange { index, row });
+            }
+            if self.mode.has_witness() && self.output_wires[row] != value {
+                return Err(ValidationError::InputValueMismatch {
+                    index,
+                    row,
+                    label: self.get_label(row),
+                });
+            }
+            uses[row] -= 1;
+        }
+
+        if let Some((_, wire)) = self
+            .constant_maps
+            .iter()
+            .find(|(_, wire)| wire.0 >= num_rows)
+        {
+            return Err(ValidationError::ConstantOutOfRange { row: wire.0 });
+        }
+        for hint in self.hints.iter() {
+            if hint.row >= num_rows || hint.inputs.iter().any(|&idx| idx >= num_rows) {
+                return Err(ValidationError::HintOutOfRange { row: hint.row });
+            }
+        }
+
+        for (row, (&expected, &actual)) in uses.iter().zip(self.mult.iter()).enumerate() {
+            if expected != actual as i64 {
+                return Err(ValidationError::MultMismatch {
+                    row,
+                    expected,
+                    actual,
+                    label: self.get_label(row),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Takes the columns of a trace built elsewhere, such as by another compiler or a VM, for the
+    // prover of this crate. The columns are those of the `Circuit` fields of the same names, with
+    // `inputs` the statement, and must pass `validate`. Row 1 must be the input of the constant
+    // one, as `Circuit::new_input` makes it, and rows of the shape of `new_constant` over it are
+    // registered as constants, so that gadgets added afterwards reuse them. Whether the values
+    // satisfy the rows is left to `check_satisfied`.
+    pub fn from_columns(
+        op: Vec<M31>,
+        idx_a: Vec<usize>,
+        idx_b: Vec<usize>,
+        mult: Vec<u32>,
+        output_wires: Vec<M31>,
+        inputs: PublicInputs,
+    ) -> Result<Circuit, ValidationError> {
+        // otherwise the rows that look like constants hold multiples of whatever row 1 holds
+        let is_one_row = op.get(1) == Some(&M31::one())
+            && idx_a.get(1) == Some(&1)
+            && idx_b.get(1) == Some(&0)
+            && output_wires.get(1) == Some(&M31::one())
+            && inputs
+                .iter()
+                .any(|&(row, value)| row == 1 && value.is_one());
+        if !is_one_row {
+            return Err(ValidationError::OneRow);
+        }
+
+        let mut constant_maps = ConstantMap::new();
+        for (row, (&op, (&a, &b))) in op.iter().zip(idx_a.iter().zip(idx_b.iter())).enumerate() {
+            if row > 1 && a == 1 && b == 0 && !op.is_zero() && !constant_maps.contains_key(&op) {
+                constant_maps.insert(op, Wire(row));
+            }
+        }
+
+        let circuit = Circuit {
+            num_rows: op.len(),
+            mode: Mode::PROVE,
+            output_wires: output_wires.into(),
+            op: op.into(),
+            idx_a: idx_a.into(),
+            idx_b: idx_b.into(),
+            mult: mult.into(),
+            input_maps: inputs,
+            constant_maps,
+            ..Default::default()
+        };
+        circuit.validate()?;
+        Ok(circuit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::validate::ValidationError;
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_validate() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert_eq!(circuit.validate(), Ok(()));
+        circuit.pad_to_next_power_of_2();
+        assert_eq!(circuit.validate(), Ok(()));
+
+        let mut circuit = Circuit::new();
+        circuit.mode = Mode::PROVE;
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.zero_test(c);
+        circuit.expose(c);
+        assert_eq!(circuit.validate(), Ok(()));
+
+        circuit.idx_b[c.0] = circuit.num_rows - 1;
+        assert!(matches!(
+            circuit.validate(),
+            Err(ValidationError::InvalidReference {
+                column: "idx_b",
+                ..
+            })
+        ));
+        circuit.idx_b[c.0] = b.0;
+
+        circuit.mult[b.0] += 1;
+        let err = circuit.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("row {} has multiplicity 3, but is used 2 times", b.0)
+        );
+        circuit.mult[b.0] -= 1;
+
+        circuit.input_maps.set_value(1, M31::from(4));
+        assert!(matches!(
+            circuit.validate(),
+            Err(ValidationError::InputValueMismatch { index: 1, .. })
+        ));
+        circuit.input_maps.set_wire(1, circuit.num_rows);
+        assert!(matches!(
+            circuit.validate(),
+            Err(ValidationError::InputOutOfRange { index: 1, .. })
+        ));
+
+        circuit.mult.push(0);
+        assert!(matches!(
+            circuit.validate(),
+            Err(ValidationError::ColumnLength { column: "mult", .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_columns() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let columns = || {
+            (
+                circuit.op.iter().copied().collect::<Vec<_>>(),
+                circuit.idx_a.iter().copied().collect::<Vec<_>>(),
+                circuit.idx_b.iter().copied().collect::<Vec<_>>(),
+                circuit.mult.iter().copied().collect::<Vec<_>>(),
+                circuit.output_wires.iter().copied().collect::<Vec<_>>(),
+            )
+        };
+
+        let (op, idx_a, idx_b, mult, output_wires) = columns();
+        let mut recovered = Circuit::from_columns(
+            op,
+            idx_a,
+            idx_b,
+            mult,
+            output_wires,
+            circuit.input_maps.clone(),
+        )
+        .unwrap();
+        assert!(recovered.is_constraint_satisfied());
+        assert!(recovered.is_logup_satisfied(&recovered.input_maps));
+        assert!(circuit
+            .constant_maps
+            .iter()
+            .all(|(constant, _)| recovered.constant_maps.contains_key(&constant)));
+        for (constant, wire) in recovered.constant_maps.iter() {
+            assert_eq!(recovered.get_output_wire(wire), constant);
+        }
+
+        // gadgets can be added to the recovered circuit
+        let x = recovered.new_witness(M31::from(6));
+        let y = recovered.mul_by_constant(x, M31::from(7));
+        recovered.expose(y);
+        assert!(recovered.is_constraint_satisfied());
+        assert_eq!(recovered.validate(), Ok(()));
+
+        let (mut op, idx_a, idx_b, mult, output_wires) = columns();
+        op[0] = M31::from(2);
+        assert_eq!(
+            Circuit::from_columns(
+                op,
+                idx_a,
+                idx_b,
+                mult,
+                output_wires,
+                circuit.input_maps.clone()
+            )
+            .unwrap_err(),
+            ValidationError::ZeroRow
+        );
+
+        // row 1 holds another value than one
+        let (op, idx_a, idx_b, mult, mut output_wires) = columns();
+        let mut inputs = circuit.input_maps.clone();
+        output_wires[1] = M31::from(2);
+        inputs.set_value(0, M31::from(2));
+        assert_eq!(
+            Circuit::from_columns(op, idx_a, idx_b, mult, output_wires, inputs).unwrap_err(),
+            ValidationError::OneRow
+        );
+
+        let (op, idx_a, idx_b, mut mult, output_wires) = columns();
+        mult[1] += 1;
+        assert!(matches!(
+            Circuit::from_columns(
+                op,
+                idx_a,
+                idx_b,
+                mult,
+                output_wires,
+                circuit.input_maps.clone()
+            ),
+            Err(ValidationError::MultMismatch { row: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::proof_system::estimated_prover_memory;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use stwo_prover::core::prover::{LOG_BLOWUP_FACTOR, N_QUERIES};
+
+// The file read from the working directory when no other is given.
+pub const CONFIG_FILE: &str = "circle-plonk.toml";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum HasherKind {
+    #[default]
+    #[serde(rename = "bws-sha256")]
+    BwsSha256,
+}
+
+// The parameters of the prover, from a TOML file such as
+//
+//     log_blowup_factor = 2
+//     threads = 8
+//     memory_budget_mib = 16384
+//     cache_dir = "/var/cache/circle-plonk"
+//     registry_dir = "/var/lib/circle-plonk/registry"
+//
+// where every key is optional and can be overridden by the environment variable of the same name
+// in upper case, prefixed with `CIRCLE_PLONK_` (for example, `CIRCLE_PLONK_THREADS`).
+//
+// The blowup factor, the number of queries and the hasher are fixed when the prover is compiled
+// (the blowup factor through the `*_blowup` features), so they are only checked against it, for
+// deployments to fail early rather than produce proofs their verifiers reject.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProverConfig {
+    pub log_blowup_factor: Option<u32>,
+    pub n_queries: Option<usize>,
+    pub hasher: HasherKind,
+    // the size of the global thread pool, all the cores by default
+    pub threads: Option<usize>,
+    // checked against `estimated_prover_memory` before proving
+    pub memory_budget_mib: Option<u64>,
+    // where circuit indexes are cached, see `IndexCache`
+    pub cache_dir: Option<PathBuf>,
+    // where set-up indexes and their keys are kept, see `IndexRegistry`
+    pub registry_dir: Option<PathBuf>,
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+impl ProverConfig {
+    pub fn from_toml(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|err| invalid_data(err.to_string()))
+    }
+
+    // Reads `path`, or `CONFIG_FILE` if there is one in the working directory, and then applies
+    // the environment variables.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_toml(&std::fs::read_to_string(path)?)?,
+            None if Path::new(CONFIG_FILE).exists() => {
+                Self::from_toml(&std::fs::read_to_string(CONFIG_FILE)?)?
+            }
+            None => Self::default(),
+        };
+        config.apply_env_with(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    // Overrides the parameters with the variables that `var` returns.
+    pub fn apply_env_with<F>(&mut self, var: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        fn parse<T: std::str::FromStr>(name: &str, value: String) -> Result<T> {
+            value
+                .parse()
+                .map_err(|_| invalid_data(format!("invalid value {} for {}", value, name)))
+        }
+
+        let lookup = |key: &str| {
+            let name = format!("CIRCLE_PLONK_{}", key);
+            var(&name).map(|value| (name, value))
+        };
+        if let Some((name, value)) = lookup("LOG_BLOWUP_FACTOR") {
+            self.log_blowup_factor = Some(parse(&name, value)?);
+        }
+        if let Some((name, value)) = lookup("N_QUERIES") {
+            self.n_queries = Some(parse(&name, value)?);
+        }
+        if let Some((name, value)) = lookup("HASHER") {
+            self.hasher = match value.as_str() {
+                "bws-sha256" => HasherKind::BwsSha256,
+                _ => {
+                    return Err(invalid_data(format!(
+                        "unknown hasher {} in {}",
+                        value, name
+                    )))
+                }
+            };
+        }
+        if let Some((name, value)) = lookup("THREADS") {
+            self.threads = Some(parse(&name, value)?);
+        }
+        if let Some((name, value)) = lookup("MEMORY_BUDGET_MIB") {
+            self.memory_budget_mib = Some(parse(&name, value)?);
+        }
+        if let Some((_, value)) = lookup("CACHE_DIR") {
+            self.cache_dir = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = lookup("REGISTRY_DIR") {
+            self.registry_dir = Some(PathBuf::from(value));
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(log_blowup_factor) = self.log_blowup_factor {
+            if log_blowup_factor != LOG_BLOWUP_FACTOR {
+                return Err(invalid_data(format!(
+                    "the prover is compiled with a log blowup factor of {}, not {}",
+                    LOG_BLOWUP_FACTOR, log_blowup_factor
+                )));
+            }
+        }
+        if let Some(n_queries) = self.n_queries {
+            if n_queries != N_QUERIES {
+                return Err(invalid_data(format!(
+                    "the prover is compiled with {} queries, not {}",
+                    N_QUERIES, n_queries
+                )));
+            }
+        }
+        if self.threads == Some(0) {
+            return Err(invalid_data(
+                "the thread count must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Validates the parameters and sets up the global thread pool, which has to happen before
+    // anything else uses it.
+    pub fn apply(&self) -> Result<()> {
+        self.validate()?;
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+                .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        }
+        Ok(())
+    }
+
+    pub fn check_memory(&self, log_n_rows: u32) -> Result<()> {
+        let Some(budget) = self.memory_budget_mib else {
+            return Ok(());
+        };
+        let estimate = (estimated_prover_memory(log_n_rows) >> 20) as u64;
+        if estimate > budget {
+            return Err(Error::new(
+                ErrorKind::OutOfMemory,
+                format!(
+                    "proving 2^{} rows takes about {} MiB, over the budget of {} MiB",
+                    log_n_rows, estimate, budget
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{HasherKind, ProverConfig};
+    use std::path::PathBuf;
+    use stwo_prover::core::prover::LOG_BLOWUP_FACTOR;
+
+    #[test]
+    fn test_prover_config() {
+        let mut config = ProverConfig::from_toml(
+            r#"
+            log_blowup_factor = 2
+            hasher = "bws-sha256"
+            threads = 4
+            cache_dir = "/tmp/circle-plonk"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.log_blowup_factor, Some(2));
+        assert_eq!(config.hasher, HasherKind::BwsSha256);
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/tmp/circle-plonk")));
+        assert_eq!(config.memory_budget_mib, None);
+        assert_eq!(
+            ProverConfig::from_toml("").unwrap(),
+            ProverConfig::default()
+        );
+        assert!(ProverConfig::from_toml("blowup = 2").is_err());
+        assert!(ProverConfig::from_toml("hasher = \"md5\"").is_err());
+
+        let env = |name: &str| match name {
+            "CIRCLE_PLONK_THREADS" => Some("2".to_string()),
+            "CIRCLE_PLONK_MEMORY_BUDGET_MIB" => Some("1".to_string()),
+            _ => None,
+        };
+        config.apply_env_with(env).unwrap();
+        assert_eq!(config.threads, Some(2));
+        assert_eq!(config.memory_budget_mib, Some(1));
+        assert_eq!(config.log_blowup_factor, Some(2));
+        assert!(config.check_memory(8).is_ok());
+        assert!(config.check_memory(24).is_err());
+
+        let env = |name: &str| (name == "CIRCLE_PLONK_THREADS").then(|| "many".to_string());
+        assert!(config.apply_env_with(env).is_err());
+
+        config.log_blowup_factor = Some(LOG_BLOWUP_FACTOR);
+        assert!(config.validate().is_ok());
+        config.log_blowup_factor = Some(LOG_BLOWUP_FACTOR + 1);
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::circuit::compressed::CompressedIndex;
+use crate::circuit::index::Witness;
+use crate::proof_system::{verify_with_key, PlonkProof, VerificationKey};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+use stwo_prover::core::fields::m31::{M31, P};
+
+// The C interface of the prover and the verifier, on the same bincode encodings as the files of
+// the `circle-plonk` tool. `include/circle_plonk.h` is generated from this file with
+// `cbindgen --config cbindgen.toml --output include/circle_plonk.h`.
+//
+// Every function returns a status code, zero on success, and never unwinds into the caller: a
+// panic in the prover is reported as `CIRCLE_PLONK_STATUS_INTERNAL_ERROR`. The items below have
+// doc comments, unlike the rest of the crate, for cbindgen to copy them into the header.
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CirclePlonkStatus {
+    Ok = 0,
+    /// a null pointer, or an input value that is not in M31
+    InvalidArgument = 1,
+    InvalidIndex = 2,
+    /// malformed, not for the index, or not satisfying the constraints
+    InvalidWitness = 3,
+    InvalidKey = 4,
+    InvalidProof = 5,
+    /// the proof is well-formed but does not verify against the key and the inputs
+    VerificationFailed = 6,
+    InternalError = 7,
+}
+
+/// A buffer allocated by this library, to be released with `circle_plonk_buffer_free`.
+#[repr(C)]
+pub struct CirclePlonkBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl CirclePlonkBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        // a null pointer is still a valid empty buffer
+        return (len == 0).then_some(&[][..]);
+    }
+    Some(slice::from_raw_parts(data, len))
+}
+
+fn catch<F: FnOnce() -> CirclePlonkStatus>(f: F) -> CirclePlonkStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(CirclePlonkStatus::InternalError)
+}
+
+fn prove(index: &[u8], witness: &[u8]) -> Result<Vec<u8>, CirclePlonkStatus> {
+    let index: CompressedIndex =
+        bincode::deserialize(index).map_err(|_| CirclePlonkStatus::InvalidIndex)?;
+    index
+        .validate()
+        .map_err(|_| CirclePlonkStatus::InvalidIndex)?;
+    let witness: Witness =
+        bincode::deserialize(witness).map_err(|_| CirclePlonkStatus::InvalidWitness)?;
+    if !index.is_compatible(&witness) {
+        return Err(CirclePlonkStatus::InvalidWitness);
+    }
+
+    // fails on a witness that does not satisfy the gates
+    let proof = PlonkProof::prove_compressed(&index, &witness)
+        .map_err(|_| CirclePlonkStatus::InvalidWitness)?;
+    bincode::serialize(&proof).map_err(|_| CirclePlonkStatus::InternalError)
+}
+
+fn verify(key: &[u8], proof: &[u8], values: &[u32]) -> CirclePlonkStatus {
+    let Ok(key) = bincode::deserialize::<VerificationKey>(key) else {
+        return CirclePlonkStatus::InvalidKey;
+    };
+    let Ok(proof) = bincode::deserialize::<PlonkProof>(proof) else {
+        return CirclePlonkStatus::InvalidProof;
+    };
+    if values.iter().any(|&v| v >= P) {
+        return CirclePlonkStatus::InvalidArgument;
+    }
+    let values = values.iter().map(|&v| M31::from(v)).collect::<Vec<_>>();
+
+    if proof.log_n_rows != key.log_n_rows || !proof.is_num_rows_consistent() {
+        return CirclePlonkStatus::VerificationFailed;
+    }
+    match verify_with_key(&key, &values, proof.proof) {
+        Ok(()) => CirclePlonkStatus::Ok,
+        Err(_) => CirclePlonkStatus::VerificationFailed,
+    }
+}
+
+/// Proves a witness of a circuit, given as the compressed index written by `circle-plonk setup`
+/// and a `Witness`, and stores the proof in `proof_out`.
+///
+/// # Safety
+/// `index` and `witness` must point to `index_len` and `witness_len` readable bytes, and
+/// `proof_out` to a writable `CirclePlonkBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn circle_plonk_prove(
+    index: *const u8,
+    index_len: usize,
+    witness: *const u8,
+    witness_len: usize,
+    proof_out: *mut CirclePlonkBuffer,
+) -> CirclePlonkStatus {
+    let (Some(index), Some(witness)) = (bytes(index, index_len), bytes(witness, witness_len))
+    else {
+        return CirclePlonkStatus::InvalidArgument;
+    };
+    if proof_out.is_null() {
+        return CirclePlonkStatus::InvalidArgument;
+    }
+    catch(|| match prove(index, witness) {
+        Ok(proof) => {
+            *proof_out = CirclePlonkBuffer::new(proof);
+            CirclePlonkStatus::Ok
+        }
+        Err(status) => status,
+    })
+}
+
+/// Verifies a proof written by `circle_plonk_prove` against a verification key and the values of
+/// the inputs, in the order of the key.
+///
+/// # Safety
+/// `key`, `proof` and `values` must point to `key_len` and `proof_len` readable bytes and
+/// `values_len` readable values.
+#[no_mangle]
+pub unsafe extern "C" fn circle_plonk_verify(
+    key: *const u8,
+    key_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+    values: *const u32,
+    values_len: usize,
+) -> CirclePlonkStatus {
+    let values = if values.is_null() {
+        (values_len == 0).then_some(&[][..])
+    } else {
+        Some(slice::from_raw_parts(values, values_len))
+    };
+    let (Some(key), Some(proof), Some(values)) =
+        (bytes(key, key_len), bytes(proof, proof_len), values)
+    else {
+        return CirclePlonkStatus::InvalidArgument;
+    };
+    catch(|| verify(key, proof, values))
+}
+
+/// Releases a buffer returned by this library.
+///
+/// # Safety
+/// `buffer` must have been returned by this library and not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn circle_plonk_buffer_free(buffer: CirclePlonkBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::ffi::{
+        circle_plonk_buffer_free, circle_plonk_prove, circle_plonk_verify, CirclePlonkBuffer,
+        CirclePlonkStatus,
+    };
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::VerificationKey;
+    use ark_std::rand::SeedableRng;
+    use std::ptr;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_ffi() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (index, witness) = circuit.split();
+        let key = bincode::serialize(&VerificationKey::new(&index)).unwrap();
+        let values = witness
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v.0)
+            .collect::<Vec<_>>();
+        let expanded = bincode::serialize(&index).unwrap();
+        // the index as `circle-plonk setup` writes it
+        let index = bincode::serialize(&index.compress()).unwrap();
+        let witness_bytes = bincode::serialize(&witness).unwrap();
+
+        unsafe {
+            let mut proof = CirclePlonkBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let status = circle_plonk_prove(
+                index.as_ptr(),
+                index.len(),
+                witness_bytes.as_ptr(),
+                witness_bytes.len(),
+                &mut proof,
+            );
+            assert_eq!(status, CirclePlonkStatus::Ok);
+
+            let verify = |values: &[u32]| {
+                circle_plonk_verify(
+                    key.as_ptr(),
+                    key.len(),
+                    proof.data,
+                    proof.len,
+                    values.as_ptr(),
+                    values.len(),
+                )
+            };
+            assert_eq!(verify(&values), CirclePlonkStatus::Ok);
+            let mut wrong = values.clone();
+            *wrong.last_mut().unwrap() ^= 1;
+            assert_eq!(verify(&wrong), CirclePlonkStatus::VerificationFailed);
+            *wrong.last_mut().unwrap() = u32::MAX;
+            assert_eq!(verify(&wrong), CirclePlonkStatus::InvalidArgument);
+
+            let status = circle_plonk_verify(
+                key.as_ptr(),
+                key.len(),
+                proof.data,
+                proof.len / 2,
+                values.as_ptr(),
+                values.len(),
+            );
+            assert_eq!(status, CirclePlonkStatus::InvalidProof);
+            circle_plonk_buffer_free(proof);
+
+            let status = circle_plonk_prove(
+                index.as_ptr(),
+                index.len(),
+                ptr::null(),
+                witness_bytes.len(),
+                &mut proof,
+            );
+            assert_eq!(status, CirclePlonkStatus::InvalidArgument);
+            let status = circle_plonk_prove(
+                index.as_ptr(),
+                index.len(),
+                index.as_ptr(),
+                index.len(),
+                &mut proof,
+            );
+            assert_eq!(status, CirclePlonkStatus::InvalidWitness);
+
+            // a witness of the right shape that breaks a gate
+            let mut wrong = witness.clone();
+            *wrong.output_wires.last_mut().unwrap() += M31::from(1);
+            let wrong = bincode::serialize(&wrong).unwrap();
+            let status = circle_plonk_prove(
+                index.as_ptr(),
+                index.len(),
+                wrong.as_ptr(),
+                wrong.len(),
+                &mut proof,
+            );
+            assert_eq!(status, CirclePlonkStatus::InvalidWitness);
+            // the expanded index is not what `setup` writes
+            let status = circle_plonk_prove(
+                expanded.as_ptr(),
+                expanded.len(),
+                witness_bytes.as_ptr(),
+                witness_bytes.len(),
+                &mut proof,
+            );
+            assert_eq!(status, CirclePlonkStatus::InvalidIndex);
+        }
+    }
+}
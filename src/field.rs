@@ -1,5 +1,16 @@
-use ark_ff::{Fp64, MontBackend, MontConfig, PrimeField};
-use stwo_prover::core::fields::m31::M31;
+use crate::simd::multiversion;
+use ark_ff::{
+    BigInt, BigInteger, Field, Fp, Fp2, Fp2Config, Fp64, FpConfig, MontBackend, MontConfig, MontFp,
+    PrimeField, QuadExtConfig, QuadExtField, SqrtPrecomputation,
+};
+use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
+use std::iter::Map;
+use std::marker::PhantomData;
+use stwo_prover::core::backend::simd::m31::N_LANES;
+use stwo_prover::core::fields::cm31::CM31;
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::qm31::QM31;
 
 #[derive(MontConfig)]
 #[modulus = "2147483647"]
@@ -10,14 +21,688 @@ pub type FM31 = Fp64<MontBackend<FM31Config, 1>>;
 pub const FM31_ONE: FM31 = ark_ff::MontFp!("1");
 pub const FM31_ZERO: FM31 = ark_ff::MontFp!("0");
 
+// Elements of `FM31` are always below the modulus, so this never reduces. Values from elsewhere
+// go through `M31Reducer`, and elements that may have been built unchecked through `try_to_m31`.
 pub fn to_m31(v: &FM31) -> M31 {
     M31::reduce(v.into_bigint().0[0])
 }
 
+pub fn from_m31(v: M31) -> FM31 {
+    FM31::from(v.0)
+}
+
+fn borrowed_to_m31<T: Borrow<FM31>>(v: T) -> M31 {
+    to_m31(v.borrow())
+}
+
+fn borrowed_from_m31<T: Borrow<M31>>(v: T) -> FM31 {
+    from_m31(*v.borrow())
+}
+
+// Converts the elements of an iterator, as `values.iter().into_m31()`, keeping it exact-size if
+// it was.
+pub trait IntoM31Iter: Iterator + Sized
+where
+    Self::Item: Borrow<FM31>,
+{
+    fn into_m31(self) -> Map<Self, fn(Self::Item) -> M31> {
+        self.map(borrowed_to_m31 as fn(Self::Item) -> M31)
+    }
+}
+
+impl<I: Iterator> IntoM31Iter for I where I::Item: Borrow<FM31> {}
+
+pub trait IntoFM31Iter: Iterator + Sized
+where
+    Self::Item: Borrow<M31>,
+{
+    fn into_fm31(self) -> Map<Self, fn(Self::Item) -> FM31> {
+        self.map(borrowed_from_m31 as fn(Self::Item) -> FM31)
+    }
+}
+
+impl<I: Iterator> IntoFM31Iter for I where I::Item: Borrow<M31> {}
+
+// An element is stored in Montgomery form as `v * 2^64`, where `2^64 = 4 (mod p)`, and turned back
+// into `v` by multiplying by `2^29`, which is a rotation of its 31 bits.
+#[inline(always)]
+fn from_montgomery(v: &FM31) -> M31 {
+    let m = v.0 .0[0] as u32;
+    M31::from_u32_unchecked(((m >> 2) | (m << 29)) & P)
+}
+
+// The inverse of `from_montgomery`, which multiplies by `4` with the opposite rotation.
+#[inline(always)]
+fn to_montgomery(v: M31) -> FM31 {
+    FM31::new_unchecked(BigInt([(((v.0 << 2) | (v.0 >> 29)) & P) as u64]))
+}
+
+pub fn to_m31_slice(values: &[FM31]) -> Vec<M31> {
+    let mut out = vec![M31::from_u32_unchecked(0); values.len()];
+    to_m31_slice_into(values, &mut out);
+    out
+}
+
+// An element of `FM31` whose Montgomery limb is not below the modulus. arkworks never produces one,
+// but `Fp::new_unchecked`, an unchecked deserialization or a cast buffer can, and the conversions
+// above give some element for it without a word: `to_m31_slice` only reads the low 32 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonCanonicalError {
+    pub limb: u64,
+}
+
+impl Display for NonCanonicalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the Montgomery limb {:#x} is not below the M31 modulus",
+            self.limb
+        )
+    }
+}
+
+impl std::error::Error for NonCanonicalError {}
+
+pub fn is_canonical(v: &FM31) -> bool {
+    v.0 .0[0] < P as u64
+}
+
+pub fn try_to_m31(v: &FM31) -> Result<M31, NonCanonicalError> {
+    if is_canonical(v) {
+        Ok(from_montgomery(v))
+    } else {
+        Err(NonCanonicalError { limb: v.0 .0[0] })
+    }
+}
+
+// Like `to_m31_slice_into`, but fails with the index of the first non-canonical element, before
+// anything is written.
+pub fn try_to_m31_slice_into(
+    values: &[FM31],
+    out: &mut [M31],
+) -> Result<(), (usize, NonCanonicalError)> {
+    if let Some(i) = first_non_canonical(values) {
+        return Err((
+            i,
+            NonCanonicalError {
+                limb: values[i].0 .0[0],
+            },
+        ));
+    }
+    to_m31_slice_into(values, out);
+    Ok(())
+}
+
+multiversion! {
+    // Converts in batches of `N_LANES` elements without branches, which the compiler vectorizes.
+    pub fn to_m31_slice_into(values: &[FM31], out: &mut [M31]) {
+        assert_eq!(values.len(), out.len());
+        let mut values = values.chunks_exact(N_LANES);
+        let mut out = out.chunks_exact_mut(N_LANES);
+        for (values, out) in (&mut values).zip(&mut out) {
+            for (v, o) in values.iter().zip(out.iter_mut()) {
+                *o = from_montgomery(v);
+            }
+        }
+        for (v, o) in values.remainder().iter().zip(out.into_remainder()) {
+            *o = from_montgomery(v);
+        }
+    }
+}
+
+multiversion! {
+    // Checks `N_LANES` elements at a time, and only looks for the index in a batch that fails.
+    fn first_non_canonical(values: &[FM31]) -> Option<usize> {
+        let chunks = values.chunks(N_LANES);
+        for (i, chunk) in chunks.enumerate() {
+            if chunk.iter().fold(false, |acc, v| acc | !is_canonical(v)) {
+                let j = chunk.iter().position(|v| !is_canonical(v)).unwrap();
+                return Some(i * N_LANES + j);
+            }
+        }
+        None
+    }
+}
+
+pub fn from_m31_slice(values: &[M31]) -> Vec<FM31> {
+    let mut out = vec![FM31_ZERO; values.len()];
+    from_m31_slice_into(values, &mut out);
+    out
+}
+
+multiversion! {
+    // The counterpart of `to_m31_slice_into`, for handing witnesses back to arkworks.
+    pub fn from_m31_slice_into(values: &[M31], out: &mut [FM31]) {
+        assert_eq!(values.len(), out.len());
+        let mut values = values.chunks_exact(N_LANES);
+        let mut out = out.chunks_exact_mut(N_LANES);
+        for (values, out) in (&mut values).zip(&mut out) {
+            for (v, o) in values.iter().zip(out.iter_mut()) {
+                *o = to_montgomery(*v);
+            }
+        }
+        for (v, o) in values.remainder().iter().zip(out.into_remainder()) {
+            *o = to_montgomery(*v);
+        }
+    }
+}
+
+// FM31 without the Montgomery form, so that converting to M31 is free, at the cost of a slower
+// multiplication. The representation is the value itself, below the modulus, which also means
+// that `MontFp!` cannot build constants of this type.
+pub struct FM31PlainConfig;
+pub type FM31Plain = Fp64<FM31PlainConfig>;
+
+const fn plain(v: u32) -> FM31Plain {
+    Fp(BigInt([v as u64]), PhantomData)
+}
+
+// Reduces a value below 2^62 by folding the bits above the 31st, as 2^31 = 1 (mod p).
+#[inline(always)]
+fn reduce_plain(x: u64) -> u64 {
+    let x = (x & P as u64) + (x >> 31);
+    let x = (x & P as u64) + (x >> 31);
+    if x >= P as u64 {
+        x - P as u64
+    } else {
+        x
+    }
+}
+
+impl FpConfig<1> for FM31PlainConfig {
+    const MODULUS: BigInt<1> = BigInt([P as u64]);
+    const GENERATOR: FM31Plain = plain(7);
+    const ZERO: FM31Plain = plain(0);
+    const ONE: FM31Plain = plain(1);
+    // p - 1 = 2 * (2^30 - 1)
+    const TWO_ADICITY: u32 = 1;
+    const TWO_ADIC_ROOT_OF_UNITY: FM31Plain = plain(P - 1);
+    const SQRT_PRECOMP: Option<SqrtPrecomputation<FM31Plain>> =
+        Some(SqrtPrecomputation::Case3Mod4 {
+            modulus_plus_one_div_four: &[(P as u64 + 1) / 4],
+        });
+
+    #[inline(always)]
+    fn add_assign(a: &mut FM31Plain, b: &FM31Plain) {
+        let sum = a.0 .0[0] + b.0 .0[0];
+        a.0 .0[0] = if sum >= P as u64 { sum - P as u64 } else { sum };
+    }
+
+    #[inline(always)]
+    fn sub_assign(a: &mut FM31Plain, b: &FM31Plain) {
+        let (a0, b0) = (a.0 .0[0], b.0 .0[0]);
+        a.0 .0[0] = if a0 >= b0 {
+            a0 - b0
+        } else {
+            a0 + P as u64 - b0
+        };
+    }
+
+    #[inline(always)]
+    fn double_in_place(a: &mut FM31Plain) {
+        let b = *a;
+        Self::add_assign(a, &b);
+    }
+
+    #[inline(always)]
+    fn neg_in_place(a: &mut FM31Plain) {
+        if a.0 .0[0] != 0 {
+            a.0 .0[0] = P as u64 - a.0 .0[0];
+        }
+    }
+
+    #[inline(always)]
+    fn mul_assign(a: &mut FM31Plain, b: &FM31Plain) {
+        a.0 .0[0] = reduce_plain(a.0 .0[0] * b.0 .0[0]);
+    }
+
+    fn sum_of_products<const T: usize>(a: &[FM31Plain; T], b: &[FM31Plain; T]) -> FM31Plain {
+        let mut sum = 0u64;
+        for (a, b) in a.iter().zip(b.iter()) {
+            sum = reduce_plain(sum + reduce_plain(a.0 .0[0] * b.0 .0[0]));
+        }
+        plain(sum as u32)
+    }
+
+    #[inline(always)]
+    fn square_in_place(a: &mut FM31Plain) {
+        let b = *a;
+        Self::mul_assign(a, &b);
+    }
+
+    // a^(p - 2)
+    fn inverse(a: &FM31Plain) -> Option<FM31Plain> {
+        if a.0 .0[0] == 0 {
+            return None;
+        }
+        let mut result = 1u64;
+        let mut base = a.0 .0[0];
+        let mut exponent = P - 2;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = reduce_plain(result * base);
+            }
+            base = reduce_plain(base * base);
+            exponent >>= 1;
+        }
+        Some(plain(result as u32))
+    }
+
+    fn from_bigint(other: BigInt<1>) -> Option<FM31Plain> {
+        (other.0[0] < P as u64).then(|| plain(other.0[0] as u32))
+    }
+
+    fn into_bigint(other: FM31Plain) -> BigInt<1> {
+        other.0
+    }
+}
+
+pub fn plain_to_m31(v: &FM31Plain) -> M31 {
+    M31::from_u32_unchecked(v.0 .0[0] as u32)
+}
+
+pub fn plain_from_m31(v: M31) -> FM31Plain {
+    plain(v.0)
+}
+
+// M31[i] / (i^2 + 1), the same extension as stwo's `CM31`, for example for the coordinates of
+// circle points at witness generation.
+pub struct FCM31Config;
+
+impl Fp2Config for FCM31Config {
+    type Fp = FM31;
+
+    const NONRESIDUE: FM31 = MontFp!("-1");
+
+    // NONRESIDUE^((p^k - 1) / 2)
+    const FROBENIUS_COEFF_FP2_C1: &'static [FM31] = &[FM31_ONE, MontFp!("-1")];
+
+    #[inline(always)]
+    fn mul_fp_by_nonresidue_in_place(fe: &mut FM31) -> &mut FM31 {
+        fe.neg_in_place()
+    }
+}
+
+pub type FCM31 = Fp2<FCM31Config>;
+
+pub fn to_cm31(v: &FCM31) -> CM31 {
+    CM31(to_m31(&v.c0), to_m31(&v.c1))
+}
+
+pub fn from_cm31(v: CM31) -> FCM31 {
+    FCM31::new(from_m31(v.0), from_m31(v.1))
+}
+
+// CM31[u] / (u^2 - (2 + i)), the same extension as stwo's `QM31`. As the non-residue is not `i`,
+// this is not an arkworks `Fp4`, whose Frobenius coefficients would have to be in M31.
+pub struct FQM31Config;
+
+impl QuadExtConfig for FQM31Config {
+    type BasePrimeField = FM31;
+    type BaseField = FCM31;
+    type FrobCoeff = FCM31;
+
+    const DEGREE_OVER_BASE_PRIME_FIELD: usize = 4;
+
+    const NONRESIDUE: FCM31 = FCM31::new(MontFp!("2"), MontFp!("1"));
+
+    // NONRESIDUE^((p^k - 1) / 2)
+    const FROBENIUS_COEFF_C1: &'static [FCM31] = &[
+        FCM31::new(FM31_ONE, FM31_ZERO),
+        FCM31::new(MontFp!("21189756"), MontFp!("42379512")),
+        FCM31::new(MontFp!("-1"), FM31_ZERO),
+        FCM31::new(MontFp!("2126293891"), MontFp!("2105104135")),
+    ];
+
+    fn mul_base_field_by_frob_coeff(fe: &mut FCM31, power: usize) {
+        *fe *= &Self::FROBENIUS_COEFF_C1[power % Self::DEGREE_OVER_BASE_PRIME_FIELD];
+    }
+}
+
+pub type FQM31 = QuadExtField<FQM31Config>;
+
+pub fn to_qm31(v: &FQM31) -> QM31 {
+    QM31(to_cm31(&v.c0), to_cm31(&v.c1))
+}
+
+pub fn from_qm31(v: QM31) -> FQM31 {
+    FQM31::new(from_cm31(v.0), from_cm31(v.1))
+}
+
+// What to do with a value that is not a canonical M31 element, such as a witness value computed
+// over another prime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReductionPolicy {
+    // the value is rejected
+    #[default]
+    Strict,
+    // the value is reduced modulo M31 and counted, so that the caller can warn about it
+    Reduce,
+}
+
+// Converts values to M31 under a policy, counting those that had to be reduced.
+#[derive(Clone, Debug, Default)]
+pub struct M31Reducer {
+    pub policy: ReductionPolicy,
+    pub num_reduced: usize,
+}
+
+// The integer of the little-endian `bytes` reduced modulo M31, and whether it was already below
+// the modulus.
+pub fn m31_from_le_bytes(bytes: &[u8]) -> (M31, bool) {
+    let mut low = [0u8; 4];
+    let n = bytes.len().min(4);
+    low[..n].copy_from_slice(&bytes[..n]);
+    let fits = bytes.iter().skip(4).all(|&b| b == 0) && u32::from_le_bytes(low) < P;
+
+    let reduced = bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &b| ((acc << 8) | b as u64) % P as u64);
+    (M31::from(reduced as u32), fits)
+}
+
+impl M31Reducer {
+    pub fn new(policy: ReductionPolicy) -> Self {
+        Self {
+            policy,
+            num_reduced: 0,
+        }
+    }
+
+    fn reduced(&mut self, value: M31) -> Option<M31> {
+        match self.policy {
+            ReductionPolicy::Strict => None,
+            ReductionPolicy::Reduce => {
+                self.num_reduced += 1;
+                Some(value)
+            }
+        }
+    }
+
+    // Returns `None` if the value is not below the modulus and the policy is strict.
+    pub fn convert_le_bytes(&mut self, bytes: &[u8]) -> Option<M31> {
+        let (value, fits) = m31_from_le_bytes(bytes);
+        if fits {
+            return Some(value);
+        }
+        self.reduced(value)
+    }
+
+    pub fn convert_u64(&mut self, value: u64) -> Option<M31> {
+        self.convert_le_bytes(&value.to_le_bytes())
+    }
+
+    // An element of another prime field, such as BN254, taken as the integer it represents.
+    pub fn convert_field<F: PrimeField>(&mut self, value: &F) -> Option<M31> {
+        self.convert_le_bytes(&value.into_bigint().to_bytes_le())
+    }
+
+    // An element of `FM31` that may not be canonical, whose limb is reduced before it is read.
+    pub fn convert_fm31(&mut self, value: &FM31) -> Option<M31> {
+        match try_to_m31(value) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let limb = FM31::new_unchecked(BigInt([err.limb % P as u64]));
+                self.reduced(from_montgomery(&limb))
+            }
+        }
+    }
+
+    pub fn warn(&self, what: &str) {
+        if self.num_reduced > 0 {
+            tracing::warn!("{} {} were reduced modulo M31", self.num_reduced, what);
+        }
+    }
+}
+
+// Splits the elements of any prime field, such as BN254, into a fixed number of limbs of
+// `limb_bits` bits each, least significant first, so that they can be worked on in M31. A limb
+// has at most 30 bits, as 2^31 - 1 does not fit in M31.
+pub trait M31Limbs: PrimeField {
+    fn num_m31_limbs(limb_bits: usize) -> usize {
+        assert!((1..=30).contains(&limb_bits), "Invalid limb size");
+        (Self::MODULUS_BIT_SIZE as usize).div_ceil(limb_bits)
+    }
+
+    fn to_m31_limbs(&self, limb_bits: usize) -> Vec<M31> {
+        let num_limbs = Self::num_m31_limbs(limb_bits);
+        let mut bits = self.into_bigint().to_bits_le();
+        bits.resize(num_limbs * limb_bits, false);
+        bits.chunks(limb_bits)
+            .map(|chunk| {
+                let limb = chunk
+                    .iter()
+                    .rev()
+                    .fold(0u32, |acc, &bit| (acc << 1) | bit as u32);
+                M31::from(limb)
+            })
+            .collect()
+    }
+
+    // Returns `None` unless there are exactly `num_m31_limbs` limbs, each of them fits in
+    // `limb_bits` bits, and the integer they make up is below the modulus.
+    fn from_m31_limbs(limbs: &[M31], limb_bits: usize) -> Option<Self> {
+        if limbs.len() != Self::num_m31_limbs(limb_bits) {
+            return None;
+        }
+        let mut bits = Vec::with_capacity(limbs.len() * limb_bits);
+        for limb in limbs.iter() {
+            if limb.0 >> limb_bits != 0 {
+                return None;
+            }
+            bits.extend((0..limb_bits).map(|i| (limb.0 >> i) & 1 == 1));
+        }
+
+        let bit_size = Self::MODULUS_BIT_SIZE as usize;
+        if bits[bit_size..].iter().any(|&bit| bit) {
+            return None;
+        }
+        Self::from_bigint(Self::BigInt::from_bits_le(&bits[..bit_size]))
+    }
+}
+
+impl<F: PrimeField> M31Limbs for F {}
+
 #[cfg(test)]
 mod test {
-    use super::FM31;
+    use super::{FM31Plain, FCM31, FM31, FQM31};
+    use crate::field::{
+        from_cm31, from_m31, from_m31_slice, from_qm31, plain_from_m31, plain_to_m31, to_cm31,
+        to_m31, to_m31_slice, to_qm31, try_to_m31, try_to_m31_slice_into, IntoFM31Iter,
+        IntoM31Iter, M31Limbs, M31Reducer, NonCanonicalError, ReductionPolicy,
+    };
     use ark_algebra_test_templates::*;
+    use ark_ff::{BigInt, Field, PrimeField};
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::backend::simd::m31::N_LANES;
+    use stwo_prover::core::fields::cm31::CM31;
+    use stwo_prover::core::fields::m31::{M31, P};
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::fields::FieldExpOps;
 
     test_field!(fm31; FM31; mont_prime_field);
+    test_field!(fm31_plain; FM31Plain);
+    test_field!(fcm31; FCM31);
+
+    #[test]
+    fn test_fm31_plain() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        for _ in 0..100 {
+            let a = FM31::rand(&mut prng);
+            let b = FM31::rand(&mut prng);
+            let plain = |v: FM31| plain_from_m31(to_m31(&v));
+            assert_eq!(plain(a * b), plain(a) * plain(b));
+            assert_eq!(plain(a + b), plain(a) + plain(b));
+            assert_eq!(plain(a - b), plain(a) - plain(b));
+            assert_eq!(plain(a.inverse().unwrap()), plain(a).inverse().unwrap());
+            assert_eq!(from_m31(plain_to_m31(&plain(a))), a);
+        }
+        assert_eq!(-FM31Plain::from(1u32), FM31Plain::from(P - 1));
+    }
+    test_field!(fqm31; FQM31);
+
+    #[test]
+    fn test_m31_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let values = (0..10).map(|_| FM31::rand(&mut prng)).collect::<Vec<_>>();
+        for v in values.iter() {
+            assert_eq!(from_m31(to_m31(v)), *v);
+        }
+
+        let converted = values.iter().into_m31();
+        assert_eq!(converted.len(), values.len());
+        let converted = converted.collect::<Vec<_>>();
+        assert_eq!(converted.iter().into_fm31().collect::<Vec<_>>(), values);
+        assert_eq!(
+            converted
+                .clone()
+                .into_iter()
+                .into_fm31()
+                .collect::<Vec<_>>(),
+            values
+        );
+        assert_eq!(values.into_iter().into_m31().collect::<Vec<_>>(), converted);
+    }
+
+    #[test]
+    fn test_to_m31_slice() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut values = (0..100).map(|_| FM31::rand(&mut prng)).collect::<Vec<_>>();
+        values.extend([FM31::from(0u32), FM31::from(1u32), -FM31::from(1u32)]);
+        let converted = to_m31_slice(&values);
+        assert_eq!(converted, values.iter().map(to_m31).collect::<Vec<_>>());
+        assert_eq!(from_m31_slice(&converted), values);
+    }
+
+    #[test]
+    fn test_cm31_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        for _ in 0..10 {
+            let a = FCM31::rand(&mut prng);
+            let b = FCM31::rand(&mut prng);
+            assert_eq!(from_cm31(to_cm31(&a)), a);
+            assert_eq!(to_cm31(&(a * b)), to_cm31(&a) * to_cm31(&b));
+            assert_eq!(to_cm31(&a.inverse().unwrap()), to_cm31(&a).inverse());
+        }
+
+        let i = CM31::from_u32_unchecked(0, 1);
+        assert_eq!(
+            to_cm31(&from_cm31(i).square()),
+            -CM31::from_u32_unchecked(1, 0)
+        );
+    }
+
+    #[test]
+    fn test_qm31_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        for _ in 0..10 {
+            let a = FQM31::rand(&mut prng);
+            let b = FQM31::rand(&mut prng);
+            assert_eq!(from_qm31(to_qm31(&a)), a);
+            assert_eq!(to_qm31(&(a * b)), to_qm31(&a) * to_qm31(&b));
+            assert_eq!(to_qm31(&(a + b)), to_qm31(&a) + to_qm31(&b));
+            assert_eq!(to_qm31(&a.inverse().unwrap()), to_qm31(&a).inverse());
+        }
+
+        let u = QM31::from_u32_unchecked(0, 0, 1, 0);
+        assert_eq!(
+            to_qm31(&from_qm31(u).square()),
+            QM31::from_u32_unchecked(2, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_reduction_policy() {
+        let mut strict = M31Reducer::new(ReductionPolicy::Strict);
+        assert_eq!(strict.convert_u64(5), Some(M31::from(5u32)));
+        assert_eq!(strict.convert_u64(P as u64), None);
+        assert_eq!(
+            strict.convert_field(&ark_bn254::Fr::from(7u64)),
+            Some(M31::from(7u32))
+        );
+        assert_eq!(strict.convert_field(&-ark_bn254::Fr::from(1u64)), None);
+        assert_eq!(strict.num_reduced, 0);
+
+        let mut reduce = M31Reducer::new(ReductionPolicy::Reduce);
+        assert_eq!(reduce.convert_u64(P as u64 + 3), Some(M31::from(3u32)));
+        assert_eq!(
+            reduce.convert_le_bytes(&[1, 0, 0, 0, 1]),
+            Some(M31::from(3u32))
+        );
+        assert_eq!(reduce.convert_u64(2), Some(M31::from(2u32)));
+        assert_eq!(reduce.num_reduced, 2);
+    }
+
+    #[test]
+    fn test_try_to_m31() {
+        let values = [FM31::from(5u32), FM31::from(P - 1)];
+        assert_eq!(try_to_m31(&values[1]), Ok(M31::from(P - 1)));
+
+        // the limb of 3, plus the modulus
+        let limb = FM31::from(3u32).0 .0[0] + P as u64;
+        let non_canonical = FM31::new_unchecked(BigInt([limb]));
+        assert_eq!(try_to_m31(&non_canonical), Err(NonCanonicalError { limb }));
+
+        let mut out = [M31::from(0u32); 3];
+        assert!(try_to_m31_slice_into(&values, &mut out[..2]).is_ok());
+        assert_eq!(out[..2], [M31::from(5u32), M31::from(P - 1)]);
+        let values = [values[0], values[1], non_canonical];
+        let mut out = [M31::from(0u32); 3];
+        assert_eq!(
+            try_to_m31_slice_into(&values, &mut out),
+            Err((2, NonCanonicalError { limb }))
+        );
+        assert_eq!(out, [M31::from(0u32); 3]);
+        // past the first batch
+        let mut values = vec![FM31::from(7u32); 3 * N_LANES];
+        values[2 * N_LANES + 1] = non_canonical;
+        let mut out = vec![M31::from(0u32); values.len()];
+        assert_eq!(
+            try_to_m31_slice_into(&values, &mut out),
+            Err((2 * N_LANES + 1, NonCanonicalError { limb }))
+        );
+
+        assert_eq!(
+            M31Reducer::new(ReductionPolicy::Strict).convert_fm31(&non_canonical),
+            None
+        );
+        let mut reduce = M31Reducer::new(ReductionPolicy::Reduce);
+        assert_eq!(reduce.convert_fm31(&non_canonical), Some(M31::from(3u32)));
+        assert_eq!(reduce.convert_fm31(&values[0]), Some(M31::from(5u32)));
+        assert_eq!(reduce.num_reduced, 1);
+    }
+
+    #[test]
+    fn test_m31_limbs() {
+        type Fr = ark_bn254::Fr;
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        assert_eq!(Fr::num_m31_limbs(30), 9);
+        assert_eq!(Fr::num_m31_limbs(16), 16);
+
+        for limb_bits in [16, 22, 30] {
+            for _ in 0..10 {
+                let v = Fr::rand(&mut prng);
+                let limbs = v.to_m31_limbs(limb_bits);
+                assert_eq!(limbs.len(), Fr::num_m31_limbs(limb_bits));
+                assert!(limbs.iter().all(|limb| limb.0 >> limb_bits == 0));
+                assert_eq!(Fr::from_m31_limbs(&limbs, limb_bits), Some(v));
+            }
+        }
+
+        let limbs = (-Fr::from(1u32)).to_m31_limbs(30);
+        assert_eq!(
+            limbs[0],
+            M31::from((Fr::MODULUS.0[0] - 1) as u32 & ((1 << 30) - 1))
+        );
+        // the modulus itself, a limb that overflows and a wrong number of limbs
+        let mut modulus = limbs.clone();
+        modulus[0] += M31::from(1u32);
+        assert_eq!(Fr::from_m31_limbs(&modulus, 30), None);
+        let mut overflow = Fr::from(1u32).to_m31_limbs(30);
+        overflow[1] = M31::from(1u32 << 30);
+        assert_eq!(Fr::from_m31_limbs(&overflow, 30), None);
+        assert_eq!(Fr::from_m31_limbs(&limbs[1..], 30), None);
+    }
 }
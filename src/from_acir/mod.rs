@@ -0,0 +1,339 @@
+use crate::circuit::builder::{CircuitBuilder, Var};
+use crate::circuit::{Circuit, FastHashMap, Mode};
+use ark_ff::{One, Zero};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+use stwo_prover::core::fields::m31::M31;
+
+#[cfg(feature = "acir")]
+pub mod program;
+
+// Programs compiled by Noir to ACIR over a 31-bit field. The witnesses are numbered from zero,
+// and the public parameters and return values are among them.
+//
+// This takes the opcodes as acvm holds them in memory; `program` reads them from the serialized
+// programs of Noir. There is no range-check or RAM argument in the circuit, so `Range` is lowered
+// to a bit decomposition and the memory opcodes to a one-hot selection over the whole block.
+
+// `sum(q * w_l * w_r) + sum(q * w) + q_c`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcirExpression {
+    pub mul_terms: Vec<(M31, u32, u32)>,
+    pub linear_combinations: Vec<(M31, u32)>,
+    pub q_c: M31,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AcirOpcode {
+    AssertZero(AcirExpression),
+    Range {
+        witness: u32,
+        num_bits: u32,
+    },
+    MemoryInit {
+        block_id: u32,
+        init: Vec<u32>,
+    },
+    // a read constrains `value` to the element at `index`, a write replaces that element by it
+    MemoryOp {
+        block_id: u32,
+        is_write: bool,
+        index: AcirExpression,
+        value: AcirExpression,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcirCircuit {
+    pub current_witness_index: u32,
+    pub opcodes: Vec<AcirOpcode>,
+    pub public_parameters: Vec<u32>,
+    pub return_values: Vec<u32>,
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+struct AcirLowering<'a> {
+    builder: CircuitBuilder,
+    mode: Mode,
+    witness: Option<&'a BTreeMap<u32, M31>>,
+    num_witnesses: u32,
+    vars: FastHashMap<u32, Var>,
+    blocks: FastHashMap<u32, Vec<Var>>,
+}
+
+impl AcirLowering<'_> {
+    fn value(&self, idx: u32) -> Result<M31> {
+        if idx >= self.num_witnesses {
+            return Err(invalid_data("Witness out of range"));
+        }
+        match (self.mode, self.witness) {
+            (Mode::PROVE | Mode::CHECK, Some(witness)) => witness
+                .get(&idx)
+                .copied()
+                .ok_or_else(|| invalid_data("Missing witness")),
+            (Mode::PROVE | Mode::CHECK, None) => Err(invalid_data("Missing witness")),
+            (Mode::INDEX, _) => Ok(M31::zero()),
+        }
+    }
+
+    fn var(&mut self, idx: u32) -> Result<Var> {
+        if let Some(var) = self.vars.get(&idx) {
+            return Ok(var.clone());
+        }
+        let var = self.builder.witness(self.value(idx)?);
+        self.vars.insert(idx, var.clone());
+        Ok(var)
+    }
+
+    fn expression(&mut self, expression: &AcirExpression) -> Result<Var> {
+        let mut sum = self.builder.constant(expression.q_c);
+        for &(coeff, w) in expression.linear_combinations.iter() {
+            sum = sum + self.var(w)? * coeff;
+        }
+        for &(coeff, l, r) in expression.mul_terms.iter() {
+            sum = sum + self.var(l)? * self.var(r)? * coeff;
+        }
+        Ok(sum)
+    }
+
+    fn bit(&self, value: bool) -> Var {
+        let bit = self
+            .builder
+            .witness(if value { M31::one() } else { M31::zero() });
+        self.builder.assert_zero(&(&bit * &bit - &bit));
+        bit
+    }
+
+    fn range(&mut self, idx: u32, num_bits: u32) -> Result<()> {
+        let var = self.var(idx)?;
+        // every M31 element is below 2^31
+        if num_bits >= 31 {
+            return Ok(());
+        }
+
+        let value = var.value().0;
+        let mut sum = self.builder.constant(M31::zero());
+        for i in 0..num_bits {
+            let bit = self.bit((value >> i) & 1 == 1);
+            sum = sum + bit * M31::from(1u32 << i);
+        }
+        self.builder.assert_equal(&sum, &var);
+        Ok(())
+    }
+
+    // Returns flags that are one at `index` and zero elsewhere.
+    fn select(&self, index: &Var, len: usize) -> Result<Vec<Var>> {
+        let position = index.value().0 as usize;
+        if position >= len {
+            return Err(invalid_data("Memory index out of range"));
+        }
+
+        let flags = (0..len)
+            .map(|i| self.bit(i == position))
+            .collect::<Vec<_>>();
+        let mut count = self.builder.constant(M31::zero());
+        let mut selected = self.builder.constant(M31::zero());
+        for (i, flag) in flags.iter().enumerate() {
+            count = count + flag;
+            selected = selected + flag * M31::from(i as u32);
+        }
+        self.builder
+            .assert_equal(&count, &self.builder.constant(M31::one()));
+        self.builder.assert_equal(&selected, index);
+        Ok(flags)
+    }
+
+    fn memory_op(
+        &mut self,
+        block_id: u32,
+        is_write: bool,
+        index: &AcirExpression,
+        value: &AcirExpression,
+    ) -> Result<()> {
+        let index = self.expression(index)?;
+        let value = self.expression(value)?;
+        let len = self
+            .blocks
+            .get(&block_id)
+            .ok_or_else(|| invalid_data("Memory block is not initialized"))?
+            .len();
+
+        // a constant index picks the element directly
+        if index.is_constant() {
+            let position = index.value().0 as usize;
+            if position >= len {
+                return Err(invalid_data("Memory index out of range"));
+            }
+            let block = self.blocks.get_mut(&block_id).unwrap();
+            if is_write {
+                block[position] = value;
+            } else {
+                self.builder.assert_equal(&block[position], &value);
+            }
+            return Ok(());
+        }
+
+        let flags = self.select(&index, len)?;
+        let block = self.blocks.get_mut(&block_id).unwrap();
+        if is_write {
+            for (element, flag) in block.iter_mut().zip(flags.iter()) {
+                *element = &*element + flag * (&value - &*element);
+            }
+        } else {
+            let mut selected = self.builder.constant(M31::zero());
+            for (element, flag) in block.iter().zip(flags.iter()) {
+                selected = selected + flag * element;
+            }
+            self.builder.assert_equal(&selected, &value);
+        }
+        Ok(())
+    }
+}
+
+impl AcirCircuit {
+    // In `Mode::PROVE`, `witness` is the witness map solved by acvm, with a value for every
+    // witness that the opcodes use.
+    pub fn generate_circuit(
+        &self,
+        mode: Mode,
+        witness: Option<&BTreeMap<u32, M31>>,
+    ) -> Result<Circuit> {
+        let mut lowering = AcirLowering {
+            builder: CircuitBuilder::new(),
+            mode,
+            witness,
+            num_witnesses: self.current_witness_index + 1,
+            vars: FastHashMap::default(),
+            blocks: FastHashMap::default(),
+        };
+
+        for &idx in self
+            .public_parameters
+            .iter()
+            .chain(self.return_values.iter())
+        {
+            if !lowering.vars.contains_key(&idx) {
+                let var = lowering.builder.input(lowering.value(idx)?);
+                lowering.vars.insert(idx, var);
+            }
+        }
+
+        for opcode in self.opcodes.iter() {
+            match opcode {
+                AcirOpcode::AssertZero(expression) => {
+                    let var = lowering.expression(expression)?;
+                    lowering.builder.assert_zero(&var);
+                }
+                AcirOpcode::Range { witness, num_bits } => lowering.range(*witness, *num_bits)?,
+                AcirOpcode::MemoryInit { block_id, init } => {
+                    let block = init
+                        .iter()
+                        .map(|&idx| lowering.var(idx))
+                        .collect::<Result<Vec<_>>>()?;
+                    lowering.blocks.insert(*block_id, block);
+                }
+                AcirOpcode::MemoryOp {
+                    block_id,
+                    is_write,
+                    index,
+                    value,
+                } => lowering.memory_op(*block_id, *is_write, index, value)?,
+            }
+        }
+
+        Ok(lowering.builder.finalize())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_acir::{AcirCircuit, AcirExpression, AcirOpcode};
+    use std::collections::BTreeMap;
+    use stwo_prover::core::fields::m31::M31;
+
+    fn witness(values: &[u32]) -> BTreeMap<u32, M31> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (i as u32, M31::from(v)))
+            .collect()
+    }
+
+    fn single(w: u32) -> AcirExpression {
+        AcirExpression {
+            linear_combinations: vec![(M31::from(1u32), w)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_acir() {
+        // x * y = z with z below 2^8, then a block [x, y, z] read at i into u, written with z at
+        // i, and read at i again into v
+        let acir = AcirCircuit {
+            current_witness_index: 5,
+            opcodes: vec![
+                AcirOpcode::AssertZero(AcirExpression {
+                    mul_terms: vec![(M31::from(1u32), 0, 1)],
+                    linear_combinations: vec![(-M31::from(1u32), 2)],
+                    ..Default::default()
+                }),
+                AcirOpcode::Range {
+                    witness: 2,
+                    num_bits: 8,
+                },
+                AcirOpcode::MemoryInit {
+                    block_id: 0,
+                    init: vec![0, 1, 2],
+                },
+                AcirOpcode::MemoryOp {
+                    block_id: 0,
+                    is_write: false,
+                    index: single(3),
+                    value: single(4),
+                },
+                AcirOpcode::MemoryOp {
+                    block_id: 0,
+                    is_write: true,
+                    index: single(3),
+                    value: single(2),
+                },
+                AcirOpcode::MemoryOp {
+                    block_id: 0,
+                    is_write: false,
+                    index: single(3),
+                    value: single(5),
+                },
+            ],
+            public_parameters: vec![0],
+            return_values: vec![5],
+        };
+
+        let values = witness(&[3, 7, 21, 1, 7, 21]);
+        let circuit = acir.generate_circuit(Mode::PROVE, Some(&values)).unwrap();
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        let index = acir.generate_circuit(Mode::INDEX, None).unwrap();
+        assert_eq!(index.index(), circuit.index());
+
+        // reading the wrong element
+        let values = witness(&[3, 7, 21, 1, 3, 21]);
+        let circuit = acir.generate_circuit(Mode::PROVE, Some(&values)).unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        // z = 300 does not fit in 8 bits
+        let values = witness(&[3, 100, 300, 1, 100, 300]);
+        let circuit = acir.generate_circuit(Mode::PROVE, Some(&values)).unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        let values = witness(&[3, 7, 21, 3, 7, 21]);
+        assert!(acir.generate_circuit(Mode::PROVE, Some(&values)).is_err());
+        let values = witness(&[3, 7, 21]);
+        assert!(acir.generate_circuit(Mode::PROVE, Some(&values)).is_err());
+    }
+}
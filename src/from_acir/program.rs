@@ -0,0 +1,379 @@
+use crate::from_acir::{AcirCircuit, AcirExpression, AcirOpcode};
+use bincode::Options;
+use flate2::read::GzDecoder;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Read, Result};
+use stwo_prover::core::fields::m31::{M31, P};
+
+// The serialized programs and witnesses of Noir, as acir 0.46 writes them: a `Program` or a
+// `WitnessStack` encoded with bincode and compressed with gzip. The `bytecode` of a compiled
+// artifact is such a program in base64, and `nargo execute` writes such a witness stack.
+//
+// The types below mirror those of acir field by field, as bincode is not self-describing, and
+// stop after the fields that the lowering reads. Only the `main` function is read: `Call` is
+// rejected, and so are the black box functions other than `RANGE`. `BrilligCall` and `Directive`
+// only compute witnesses, which the solved witness already holds, so they are skipped.
+//
+// ACIR does not name its field, so a program is taken to be over M31 only if every field element
+// in it is a canonical M31 element. The negative coefficients of a program compiled over BN254,
+// such as the `-1` of almost every `AssertZero`, are far above `P`, and such programs are
+// rejected.
+
+// a bound on the decompressed size, as the lengths in the encoding are not checked otherwise
+const MAX_DECODED_BYTES: u64 = 1 << 32;
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_DECODED_BYTES)
+}
+
+// A field element, serialized as the hex of its 32 big-endian bytes.
+struct FieldElement(M31);
+
+fn parse_field(hex: &str) -> std::result::Result<M31, String> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    let significant = digits.trim_start_matches('0');
+    let value = if significant.len() > 8 {
+        None
+    } else if significant.is_empty() {
+        Some(0)
+    } else {
+        u32::from_str_radix(significant, 16).ok()
+    };
+    match value {
+        Some(value) if value < P && digits.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(M31::from(value))
+        }
+        _ => Err(format!("{} is not an M31 element", hex)),
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        parse_field(&hex)
+            .map(FieldElement)
+            .map_err(D::Error::custom)
+    }
+}
+
+// The payload of an opcode that has no lowering, which fails to read.
+enum Unsupported {}
+
+impl<'de> Deserialize<'de> for Unsupported {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> std::result::Result<Self, D::Error> {
+        Err(D::Error::custom("unsupported opcode"))
+    }
+}
+
+#[derive(Deserialize)]
+struct Witness(u32);
+
+#[derive(Deserialize)]
+struct Expression {
+    mul_terms: Vec<(FieldElement, Witness, Witness)>,
+    linear_combinations: Vec<(FieldElement, Witness)>,
+    q_c: FieldElement,
+}
+
+impl From<Expression> for AcirExpression {
+    fn from(expression: Expression) -> Self {
+        AcirExpression {
+            mul_terms: expression
+                .mul_terms
+                .into_iter()
+                .map(|(q, l, r)| (q.0, l.0, r.0))
+                .collect(),
+            linear_combinations: expression
+                .linear_combinations
+                .into_iter()
+                .map(|(q, w)| (q.0, w.0))
+                .collect(),
+            q_c: expression.q_c.0,
+        }
+    }
+}
+
+impl Expression {
+    // The value of an expression without witnesses.
+    fn constant(&self) -> Option<M31> {
+        (self.mul_terms.is_empty() && self.linear_combinations.is_empty()).then_some(self.q_c.0)
+    }
+}
+
+#[derive(Deserialize)]
+struct FunctionInput {
+    witness: Witness,
+    num_bits: u32,
+}
+
+// The black box functions up to `RANGE`, the only one with a lowering. The others fail to read.
+#[allow(clippy::upper_case_acronyms, dead_code)]
+#[derive(Deserialize)]
+enum BlackBoxFuncCall {
+    AES128Encrypt(Unsupported),
+    AND(Unsupported),
+    XOR(Unsupported),
+    RANGE { input: FunctionInput },
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum Directive {
+    ToLeRadix {
+        a: Expression,
+        b: Vec<Witness>,
+        radix: u32,
+    },
+}
+
+#[derive(Deserialize)]
+struct MemOp {
+    // the constant 0 for a read and 1 for a write
+    operation: Expression,
+    index: Expression,
+    value: Expression,
+}
+
+#[derive(Deserialize)]
+enum BlockType {
+    Memory,
+    CallData,
+    ReturnData,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum BrilligInputs {
+    Single(Expression),
+    Array(Vec<Expression>),
+    MemoryArray(u32),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum BrilligOutputs {
+    Simple(Witness),
+    Array(Vec<Witness>),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum Opcode {
+    AssertZero(Expression),
+    BlackBoxFuncCall(BlackBoxFuncCall),
+    Directive(Directive),
+    MemoryOp {
+        block_id: u32,
+        op: MemOp,
+        predicate: Option<Expression>,
+    },
+    MemoryInit {
+        block_id: u32,
+        init: Vec<Witness>,
+        block_type: BlockType,
+    },
+    BrilligCall {
+        id: u32,
+        inputs: Vec<BrilligInputs>,
+        outputs: Vec<BrilligOutputs>,
+        predicate: Option<Expression>,
+    },
+    Call(Unsupported),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum ExpressionWidth {
+    Unbounded,
+    Bounded { width: u64 },
+}
+
+// The fields of a `Circuit` up to its return values.
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct Circuit {
+    current_witness_index: u32,
+    opcodes: Vec<Opcode>,
+    expression_width: ExpressionWidth,
+    private_parameters: Vec<Witness>,
+    public_parameters: Vec<Witness>,
+    return_values: Vec<Witness>,
+}
+
+// The number of functions of a `Program` and then its first one, `main`.
+#[derive(Deserialize)]
+struct Program {
+    num_functions: u64,
+    main: Circuit,
+}
+
+fn opcode(opcode: Opcode) -> Result<Option<AcirOpcode>> {
+    let opcode = match opcode {
+        Opcode::AssertZero(expression) => AcirOpcode::AssertZero(expression.into()),
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE { input }) => AcirOpcode::Range {
+            witness: input.witness.0,
+            num_bits: input.num_bits,
+        },
+        Opcode::MemoryInit { block_id, init, .. } => AcirOpcode::MemoryInit {
+            block_id,
+            init: init.into_iter().map(|w| w.0).collect(),
+        },
+        Opcode::MemoryOp {
+            block_id,
+            op,
+            predicate,
+        } => {
+            if predicate.is_some_and(|predicate| predicate.constant() != Some(M31::from(1))) {
+                return Err(invalid_data(
+                    "Memory operations under a predicate are not supported".to_string(),
+                ));
+            }
+            let is_write = match op.operation.constant() {
+                Some(v) if v == M31::from(0) => false,
+                Some(v) if v == M31::from(1) => true,
+                _ => return Err(invalid_data("Unknown memory operation".to_string())),
+            };
+            AcirOpcode::MemoryOp {
+                block_id,
+                is_write,
+                index: op.index.into(),
+                value: op.value.into(),
+            }
+        }
+        Opcode::BlackBoxFuncCall(
+            BlackBoxFuncCall::AES128Encrypt(unsupported)
+            | BlackBoxFuncCall::AND(unsupported)
+            | BlackBoxFuncCall::XOR(unsupported),
+        )
+        | Opcode::Call(unsupported) => match unsupported {},
+        Opcode::Directive(_) | Opcode::BrilligCall { .. } => return Ok(None),
+    };
+    Ok(Some(opcode))
+}
+
+// Reads the `main` function of a serialized program.
+pub fn acir_program_read<R: Read>(reader: R) -> Result<AcirCircuit> {
+    let program: Program = options()
+        .deserialize_from(GzDecoder::new(reader))
+        .map_err(|err| invalid_data(format!("Invalid ACIR program: {}", err)))?;
+    if program.num_functions == 0 {
+        return Err(invalid_data("The program has no functions".to_string()));
+    }
+
+    let circuit = program.main;
+    let mut opcodes = Vec::with_capacity(circuit.opcodes.len());
+    for op in circuit.opcodes {
+        opcodes.extend(opcode(op)?);
+    }
+    Ok(AcirCircuit {
+        current_witness_index: circuit.current_witness_index,
+        opcodes,
+        public_parameters: circuit.public_parameters.into_iter().map(|w| w.0).collect(),
+        return_values: circuit.return_values.into_iter().map(|w| w.0).collect(),
+    })
+}
+
+#[derive(Deserialize)]
+struct StackItem {
+    index: u32,
+    witness: BTreeMap<u32, FieldElement>,
+}
+
+// Reads the witness of `main` from a serialized witness stack, for `AcirCircuit::generate_circuit`.
+pub fn acir_witness_read<R: Read>(reader: R) -> Result<BTreeMap<u32, M31>> {
+    let stack: Vec<StackItem> = options()
+        .deserialize_from(GzDecoder::new(reader))
+        .map_err(|err| invalid_data(format!("Invalid ACIR witness: {}", err)))?;
+    let main = stack
+        .into_iter()
+        .find(|item| item.index == 0)
+        .ok_or_else(|| invalid_data("The witness stack has no witness for main".to_string()))?;
+    Ok(main.witness.into_iter().map(|(w, v)| (w, v.0)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_acir::program::{acir_program_read, acir_witness_read};
+    use crate::from_acir::AcirOpcode;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+    use std::io::Write;
+    use stwo_prover::core::fields::m31::{M31, P};
+
+    fn gzip<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(&bincode::serialize(value).unwrap())
+            .unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn field(v: u32) -> String {
+        format!("{:064x}", v)
+    }
+
+    // `x * y - z = 0` and `z < 2^8`, in the layout of acir: the number of functions, then
+    // `current_witness_index`, the opcodes (`AssertZero` and the `RANGE` black box function),
+    // `Bounded { width: 4 }`, the private and public parameters and the return values.
+    fn program(minus_one: &str) -> Vec<u8> {
+        let assert_zero = (
+            0u32,
+            vec![(field(1), 0u32, 1u32)],
+            vec![(minus_one.to_string(), 2u32)],
+            field(0),
+        );
+        let range = (1u32, 3u32, 2u32, 8u32);
+        gzip(&(
+            1u64,
+            2u32,
+            2u64,
+            assert_zero,
+            range,
+            (1u32, 4u64),
+            vec![1u32],
+            vec![0u32],
+            Vec::<u32>::new(),
+        ))
+    }
+
+    #[test]
+    fn test_acir_program() {
+        let acir = acir_program_read(&program(&field(P - 1))[..]).unwrap();
+        assert_eq!(acir.current_witness_index, 2);
+        assert_eq!(acir.public_parameters, vec![0]);
+        assert_eq!(acir.opcodes.len(), 2);
+        assert_eq!(
+            acir.opcodes[1],
+            AcirOpcode::Range {
+                witness: 2,
+                num_bits: 8
+            }
+        );
+
+        let witness = (0..3)
+            .map(|w| (w, field([3, 7, 21][w as usize])))
+            .collect::<BTreeMap<u32, String>>();
+        let witness = acir_witness_read(&gzip(&vec![(0u32, witness)])[..]).unwrap();
+        assert_eq!(witness[&2], M31::from(21));
+        let circuit = acir.generate_circuit(Mode::PROVE, Some(&witness)).unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        // the -1 of a program over BN254
+        let bn254_minus_one = "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000";
+        assert!(acir_program_read(&program(bn254_minus_one)[..]).is_err());
+        assert!(acir_program_read(&program(&field(P))[..]).is_err());
+        assert!(acir_program_read(&[0u8; 8][..]).is_err());
+    }
+}
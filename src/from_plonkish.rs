@@ -0,0 +1,436 @@
+use crate::circuit::builder::{CircuitBuilder, Var};
+use crate::circuit::{Circuit, FastHashMap, Mode};
+use ark_ff::Zero;
+use std::io::{Error, ErrorKind, Result};
+use stwo_prover::core::fields::m31::M31;
+
+// Plonkish circuits, as halo2-style frontends describe them over a 31-bit field: a table of fixed,
+// advice and instance columns, gates that are polynomials over cells of the current row and its
+// neighbours and must vanish on every row, and copy constraints between cells.
+//
+// Every row of every gate is lowered on its own, with the fixed cells as constants, so a term
+// whose selector is zero on a row costs nothing. The cells related by copy constraints share one
+// wire, and the rows of instance columns that hold public values become the inputs, in column
+// order, while their other rows are zero. Lookups and shuffles are not supported.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlonkishColumn {
+    Fixed(usize),
+    Advice(usize),
+    Instance(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlonkishCell {
+    pub column: PlonkishColumn,
+    pub row: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlonkishExpression {
+    Constant(M31),
+    // the cell of the column at the current row plus the rotation, wrapping around the table
+    Query(PlonkishColumn, i32),
+    Negated(Box<PlonkishExpression>),
+    Sum(Box<PlonkishExpression>, Box<PlonkishExpression>),
+    Product(Box<PlonkishExpression>, Box<PlonkishExpression>),
+    Scaled(Box<PlonkishExpression>, M31),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlonkishCircuit {
+    pub num_rows: usize,
+    pub num_advice_columns: usize,
+    // the values of every fixed column, including the selectors
+    pub fixed: Vec<Vec<M31>>,
+    // the number of rows holding public values in every instance column
+    pub num_instance_rows: Vec<usize>,
+    pub gates: Vec<PlonkishExpression>,
+    pub copies: Vec<(PlonkishCell, PlonkishCell)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlonkishAssignment {
+    pub advice: Vec<Vec<M31>>,
+    pub instance: Vec<Vec<M31>>,
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+struct PlonkishLowering<'a> {
+    circuit: &'a PlonkishCircuit,
+    builder: CircuitBuilder,
+    mode: Mode,
+    assignment: Option<&'a PlonkishAssignment>,
+    // the parent of every cell in a copy constraint, with the roots standing for their class
+    parents: FastHashMap<PlonkishCell, PlonkishCell>,
+    vars: FastHashMap<PlonkishCell, Var>,
+}
+
+impl PlonkishLowering<'_> {
+    fn find(&mut self, cell: PlonkishCell) -> PlonkishCell {
+        let mut root = cell;
+        while let Some(&parent) = self.parents.get(&root) {
+            if parent == root {
+                break;
+            }
+            root = parent;
+        }
+
+        let mut cell = cell;
+        while cell != root {
+            let parent = self.parents.insert(cell, root).unwrap();
+            cell = parent;
+        }
+        root
+    }
+
+    fn union(&mut self, a: PlonkishCell, b: PlonkishCell) {
+        self.parents.entry(a).or_insert(a);
+        self.parents.entry(b).or_insert(b);
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parents.insert(b, a);
+        }
+    }
+
+    fn cell(&self, column: PlonkishColumn, row: usize, rotation: i32) -> PlonkishCell {
+        let num_rows = self.circuit.num_rows as i64;
+        let row = (row as i64 + rotation as i64).rem_euclid(num_rows) as usize;
+        PlonkishCell { column, row }
+    }
+
+    fn value(&self, cell: PlonkishCell) -> Result<M31> {
+        let column = match (self.mode, self.assignment, cell.column) {
+            (_, _, PlonkishColumn::Fixed(i)) => return Ok(self.circuit.fixed[i][cell.row]),
+            (Mode::INDEX, _, _) => return Ok(M31::zero()),
+            (Mode::PROVE | Mode::CHECK, None, _) => return Err(invalid_data("Missing assignment")),
+            (Mode::PROVE | Mode::CHECK, Some(assignment), PlonkishColumn::Advice(i)) => {
+                &assignment.advice[i]
+            }
+            (Mode::PROVE | Mode::CHECK, Some(assignment), PlonkishColumn::Instance(i)) => {
+                &assignment.instance[i]
+            }
+        };
+        Ok(column.get(cell.row).copied().unwrap_or_default())
+    }
+
+    // The cell on its own: a constant for fixed cells and the rows of instance columns without
+    // public values, and a new input or witness otherwise.
+    fn leaf(&self, cell: PlonkishCell) -> Result<Var> {
+        let value = self.value(cell)?;
+        Ok(match cell.column {
+            PlonkishColumn::Fixed(_) => self.builder.constant(value),
+            PlonkishColumn::Instance(i) if cell.row >= self.circuit.num_instance_rows[i] => {
+                self.builder.constant(M31::zero())
+            }
+            PlonkishColumn::Instance(_) => self.builder.input(value),
+            PlonkishColumn::Advice(_) => self.builder.witness(value),
+        })
+    }
+
+    fn is_constant(&self, cell: PlonkishCell) -> bool {
+        match cell.column {
+            PlonkishColumn::Fixed(_) => true,
+            PlonkishColumn::Instance(i) => cell.row >= self.circuit.num_instance_rows[i],
+            PlonkishColumn::Advice(_) => false,
+        }
+    }
+
+    // Gives the class of the cell the value of `var`, or enforces that it already has it.
+    fn bind(&mut self, cell: PlonkishCell, var: Var) {
+        let root = self.find(cell);
+        match self.vars.get(&root) {
+            Some(existing) => self.builder.assert_equal(existing, &var),
+            None => {
+                self.vars.insert(root, var);
+            }
+        }
+    }
+
+    fn var(&mut self, cell: PlonkishCell) -> Result<Var> {
+        let root = self.find(cell);
+        if let Some(var) = self.vars.get(&root) {
+            return Ok(var.clone());
+        }
+        let var = self.leaf(cell)?;
+        self.vars.insert(root, var.clone());
+        Ok(var)
+    }
+
+    // The value of the expression on the row if it only depends on fixed cells.
+    fn fixed_value(&self, expression: &PlonkishExpression, row: usize) -> Option<M31> {
+        match expression {
+            PlonkishExpression::Constant(c) => Some(*c),
+            PlonkishExpression::Query(column, rotation) => match column {
+                PlonkishColumn::Fixed(i) => {
+                    Some(self.circuit.fixed[*i][self.cell(*column, row, *rotation).row])
+                }
+                _ => None,
+            },
+            PlonkishExpression::Negated(e) => self.fixed_value(e, row).map(|v| -v),
+            PlonkishExpression::Sum(a, b) => {
+                Some(self.fixed_value(a, row)? + self.fixed_value(b, row)?)
+            }
+            PlonkishExpression::Product(a, b) => {
+                match (self.fixed_value(a, row), self.fixed_value(b, row)) {
+                    (Some(a), Some(b)) => Some(a * b),
+                    (Some(v), None) | (None, Some(v)) if v.is_zero() => Some(M31::zero()),
+                    _ => None,
+                }
+            }
+            PlonkishExpression::Scaled(e, c) => self.fixed_value(e, row).map(|v| v * *c),
+        }
+    }
+
+    fn expression(&mut self, expression: &PlonkishExpression, row: usize) -> Result<Var> {
+        if let Some(value) = self.fixed_value(expression, row) {
+            return Ok(self.builder.constant(value));
+        }
+
+        Ok(match expression {
+            PlonkishExpression::Constant(c) => self.builder.constant(*c),
+            PlonkishExpression::Query(column, rotation) => {
+                let cell = self.cell(*column, row, *rotation);
+                self.var(cell)?
+            }
+            PlonkishExpression::Negated(e) => -self.expression(e, row)?,
+            PlonkishExpression::Sum(a, b) => self.expression(a, row)? + self.expression(b, row)?,
+            PlonkishExpression::Product(a, b) => {
+                self.expression(a, row)? * self.expression(b, row)?
+            }
+            PlonkishExpression::Scaled(e, c) => self.expression(e, row)? * *c,
+        })
+    }
+}
+
+impl PlonkishCircuit {
+    fn check_column(&self, column: PlonkishColumn) -> Result<()> {
+        let in_range = match column {
+            PlonkishColumn::Fixed(i) => i < self.fixed.len(),
+            PlonkishColumn::Advice(i) => i < self.num_advice_columns,
+            PlonkishColumn::Instance(i) => i < self.num_instance_rows.len(),
+        };
+        if in_range {
+            Ok(())
+        } else {
+            Err(invalid_data("Column out of range"))
+        }
+    }
+
+    fn check_expression(&self, expression: &PlonkishExpression) -> Result<()> {
+        match expression {
+            PlonkishExpression::Constant(_) => Ok(()),
+            PlonkishExpression::Query(column, _) => self.check_column(*column),
+            PlonkishExpression::Negated(e) | PlonkishExpression::Scaled(e, _) => {
+                self.check_expression(e)
+            }
+            PlonkishExpression::Sum(a, b) | PlonkishExpression::Product(a, b) => {
+                self.check_expression(a)?;
+                self.check_expression(b)
+            }
+        }
+    }
+
+    fn check(&self, assignment: Option<&PlonkishAssignment>) -> Result<()> {
+        if self.num_rows == 0 {
+            return Err(invalid_data("Empty table"));
+        }
+        if self
+            .fixed
+            .iter()
+            .any(|column| column.len() != self.num_rows)
+        {
+            return Err(invalid_data("Unexpected length of a fixed column"));
+        }
+        if self.num_instance_rows.iter().any(|&n| n > self.num_rows) {
+            return Err(invalid_data("Too many instance rows"));
+        }
+        for gate in self.gates.iter() {
+            self.check_expression(gate)?;
+        }
+        for (a, b) in self.copies.iter() {
+            for cell in [a, b] {
+                self.check_column(cell.column)?;
+                if cell.row >= self.num_rows {
+                    return Err(invalid_data("Row out of range"));
+                }
+            }
+        }
+
+        if let Some(assignment) = assignment {
+            if assignment.advice.len() != self.num_advice_columns
+                || assignment.advice.iter().any(|c| c.len() > self.num_rows)
+            {
+                return Err(invalid_data("Unexpected shape of the advice columns"));
+            }
+            let lengths = assignment.instance.iter().map(|c| c.len());
+            if lengths.ne(self.num_instance_rows.iter().copied()) {
+                return Err(invalid_data("Unexpected shape of the instance columns"));
+            }
+        }
+        Ok(())
+    }
+
+    // In `Mode::PROVE`, `assignment` holds the advice columns, where missing rows at the end are
+    // zero, and the public values of the instance columns.
+    pub fn generate_circuit(
+        &self,
+        mode: Mode,
+        assignment: Option<&PlonkishAssignment>,
+    ) -> Result<Circuit> {
+        self.check(assignment)?;
+
+        let mut lowering = PlonkishLowering {
+            circuit: self,
+            builder: CircuitBuilder::new(),
+            mode,
+            assignment,
+            parents: FastHashMap::default(),
+            vars: FastHashMap::default(),
+        };
+        for &(a, b) in self.copies.iter() {
+            lowering.union(a, b);
+        }
+
+        for (i, &n) in self.num_instance_rows.iter().enumerate() {
+            for row in 0..n {
+                let cell = PlonkishCell {
+                    column: PlonkishColumn::Instance(i),
+                    row,
+                };
+                let input = lowering.leaf(cell)?;
+                lowering.bind(cell, input);
+            }
+        }
+        // the constants copied into advice cells
+        for &(a, b) in self.copies.iter() {
+            for cell in [a, b] {
+                if lowering.is_constant(cell) {
+                    let constant = lowering.leaf(cell)?;
+                    lowering.bind(cell, constant);
+                }
+            }
+        }
+
+        for row in 0..self.num_rows {
+            for gate in self.gates.iter() {
+                let var = lowering.expression(gate, row)?;
+                lowering.builder.assert_zero(&var);
+            }
+        }
+
+        Ok(lowering.builder.finalize())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_plonkish::{
+        PlonkishAssignment, PlonkishCell, PlonkishCircuit, PlonkishColumn, PlonkishExpression,
+    };
+    use stwo_prover::core::fields::m31::M31;
+
+    fn query(column: PlonkishColumn, rotation: i32) -> Box<PlonkishExpression> {
+        Box::new(PlonkishExpression::Query(column, rotation))
+    }
+
+    fn cell(column: PlonkishColumn, row: usize) -> PlonkishCell {
+        PlonkishCell { column, row }
+    }
+
+    fn column(values: &[u32]) -> Vec<M31> {
+        values.iter().map(|&v| M31::from(v)).collect()
+    }
+
+    #[test]
+    fn test_plonkish() {
+        use PlonkishColumn::{Advice, Fixed, Instance};
+        use PlonkishExpression::{Negated, Product, Sum};
+
+        // the standard gate `q_add * (a + b - c) + q_mul * (a * b - c)` and `q_next * (a' - c)`,
+        // with rows
+        //   3 * 7 = 21
+        //   21 + 5 = 26, with 5 copied from a fixed column and 26 from the instance
+        //   a = 26 by rotation
+        let c_minus =
+            |e: Box<PlonkishExpression>| Box::new(Sum(e, Box::new(Negated(query(Advice(2), 0)))));
+        let standard = Sum(
+            Box::new(Product(
+                query(Fixed(0), 0),
+                c_minus(Box::new(Sum(query(Advice(0), 0), query(Advice(1), 0)))),
+            )),
+            Box::new(Product(
+                query(Fixed(1), 0),
+                c_minus(Box::new(Product(query(Advice(0), 0), query(Advice(1), 0)))),
+            )),
+        );
+        let next = Product(query(Fixed(2), 0), c_minus(query(Advice(0), 1)));
+
+        let plonkish = PlonkishCircuit {
+            num_rows: 4,
+            num_advice_columns: 3,
+            fixed: vec![
+                column(&[0, 1, 0, 0]),
+                column(&[1, 0, 0, 0]),
+                column(&[0, 1, 0, 0]),
+                column(&[5, 0, 0, 0]),
+            ],
+            num_instance_rows: vec![1],
+            gates: vec![standard, next],
+            copies: vec![
+                (cell(Advice(2), 0), cell(Advice(0), 1)),
+                (cell(Advice(1), 1), cell(Fixed(3), 0)),
+                (cell(Advice(2), 1), cell(Instance(0), 0)),
+            ],
+        };
+
+        let assignment = PlonkishAssignment {
+            advice: vec![column(&[3, 21, 26]), column(&[7, 5]), column(&[21, 26])],
+            instance: vec![column(&[26])],
+        };
+        let circuit = plonkish
+            .generate_circuit(Mode::PROVE, Some(&assignment))
+            .unwrap();
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        let index = plonkish.generate_circuit(Mode::INDEX, None).unwrap();
+        assert_eq!(index.index(), circuit.index());
+
+        // a wrong product
+        let mut wrong = assignment.clone();
+        wrong.advice[2][0] = M31::from(20u32);
+        let circuit = plonkish
+            .generate_circuit(Mode::PROVE, Some(&wrong))
+            .unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        // a wrong public value
+        let mut wrong = assignment.clone();
+        wrong.instance[0][0] = M31::from(27u32);
+        let circuit = plonkish
+            .generate_circuit(Mode::PROVE, Some(&wrong))
+            .unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        // a wrong rotated value
+        let mut wrong = assignment.clone();
+        wrong.advice[0][2] = M31::from(25u32);
+        let circuit = plonkish
+            .generate_circuit(Mode::PROVE, Some(&wrong))
+            .unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        assert!(plonkish.generate_circuit(Mode::PROVE, None).is_err());
+        let mut malformed = plonkish.clone();
+        malformed
+            .copies
+            .push((cell(Advice(3), 0), cell(Advice(0), 0)));
+        assert!(malformed.generate_circuit(Mode::INDEX, None).is_err());
+    }
+}
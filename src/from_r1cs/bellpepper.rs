@@ -0,0 +1,268 @@
+use crate::circuit::{Circuit, FastHashMap, Mode};
+use crate::field::FM31;
+use crate::from_r1cs::circom::element_from_bytes;
+use crate::from_r1cs::r1cs_constraint_processor::{process_r1cs_constraint, OnDemandAllocator};
+use ark_ff::{One, Zero};
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use ff::PrimeField;
+use std::io::{Error, ErrorKind};
+use std::marker::PhantomData;
+use stwo_prover::core::fields::m31::{M31, P};
+
+// Records what a bellpepper circuit allocates and enforces, and then lowers it like the R1CS
+// matrices of arkworks. The scalar field must be M31 with a little-endian representation, which
+// is what `#[derive(PrimeField)]` produces with `PrimeFieldReprEndianness = "little"`.
+pub struct BellpepperConstraintSystem<Scalar: PrimeField> {
+    mode: Mode,
+    // the constant one followed by the inputs
+    inputs: Vec<M31>,
+    aux: Vec<M31>,
+    constraints: Vec<[Vec<(FM31, Index)>; 3]>,
+    names: Vec<(Index, String)>,
+    namespaces: Vec<String>,
+    _marker: PhantomData<Scalar>,
+}
+
+fn scalar_to_m31<Scalar: PrimeField>(value: &Scalar) -> M31 {
+    M31::from(element_from_bytes(value.to_repr().as_ref()).0)
+}
+
+impl<Scalar: PrimeField> BellpepperConstraintSystem<Scalar> {
+    pub fn new(mode: Mode) -> Result<Self, SynthesisError> {
+        let modulus = Scalar::MODULUS
+            .trim_start_matches("0x")
+            .trim_start_matches('0');
+        if u32::from_str_radix(modulus, 16).ok() != Some(P) {
+            return Err(SynthesisError::IoError(Error::new(
+                ErrorKind::InvalidInput,
+                format!("The scalar field of modulus {} is not M31", Scalar::MODULUS),
+            )));
+        }
+
+        Ok(Self {
+            mode,
+            inputs: vec![M31::one()],
+            aux: vec![],
+            constraints: vec![],
+            names: vec![],
+            namespaces: vec![],
+            _marker: PhantomData,
+        })
+    }
+
+    fn value<F>(&self, f: F) -> Result<M31, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+    {
+        match self.mode {
+            Mode::PROVE | Mode::CHECK => Ok(scalar_to_m31(&f()?)),
+            Mode::INDEX => Ok(M31::zero()),
+        }
+    }
+
+    fn push_name(&mut self, idx: Index, annotation: String) {
+        let mut path = self.namespaces.join("/");
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(&annotation);
+        self.names.push((idx, path));
+    }
+
+    pub fn generate_circuit(self) -> Circuit {
+        let num_inputs = self.inputs.len();
+        let wire = |idx: Index| match idx {
+            Index::Input(i) => i,
+            Index::Aux(i) => num_inputs + i,
+        };
+
+        let mut assignments = self.inputs;
+        assignments.extend_from_slice(&self.aux);
+        let names = self
+            .names
+            .into_iter()
+            .map(|(idx, name)| (wire(idx), name))
+            .collect::<FastHashMap<_, _>>();
+
+        let mut allocator = OnDemandAllocator::new(assignments, num_inputs).with_names(names);
+        let mut output = Circuit::new();
+        for i in 0..num_inputs {
+            allocator.get(&mut output, i);
+        }
+
+        for constraint in self.constraints {
+            let [a, b, c] = constraint.map(|terms| {
+                let mut terms = terms
+                    .into_iter()
+                    .map(|(coeff, idx)| (coeff, wire(idx)))
+                    .collect::<Vec<_>>();
+                terms.sort_unstable_by_key(|&(_, wire)| wire);
+                terms
+            });
+            process_r1cs_constraint(&mut output, &mut allocator, &a, &b, &c);
+        }
+        output
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for BellpepperConstraintSystem<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let value = self.value(f)?;
+        let idx = Index::Aux(self.aux.len());
+        self.aux.push(value);
+        self.push_name(idx, annotation().into());
+        Ok(Variable::new_unchecked(idx))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let value = self.value(f)?;
+        let idx = Index::Input(self.inputs.len());
+        self.inputs.push(value);
+        self.push_name(idx, annotation().into());
+        Ok(Variable::new_unchecked(idx))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        let terms = |lc: LinearCombination<Scalar>| {
+            lc.iter()
+                .map(|(var, coeff)| (FM31::from(scalar_to_m31(coeff).0), var.get_unchecked()))
+                .collect::<Vec<_>>()
+        };
+        self.constraints.push([
+            terms(a(LinearCombination::zero())),
+            terms(b(LinearCombination::zero())),
+            terms(c(LinearCombination::zero())),
+        ]);
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.namespaces.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        self.namespaces.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+// The counterpart of `generate_circuit` for circuits written against bellpepper. In
+// `Mode::INDEX`, the closures computing the values are not called.
+pub fn generate_circuit_bellpepper<Scalar, C>(
+    circuit: C,
+    mode: Mode,
+) -> Result<Circuit, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: bellpepper_core::Circuit<Scalar>,
+{
+    let mut cs = BellpepperConstraintSystem::new(mode)?;
+    circuit.synthesize(&mut cs)?;
+    Ok(cs.generate_circuit())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::bellpepper::{generate_circuit_bellpepper, BellpepperConstraintSystem};
+    use bellpepper_core::{Circuit, ConstraintSystem, SynthesisError};
+    use ff::PrimeField;
+
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "2147483647"]
+    #[PrimeFieldGenerator = "7"]
+    #[PrimeFieldReprEndianness = "little"]
+    struct Fm31([u64; 1]);
+
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "2013265921"]
+    #[PrimeFieldGenerator = "31"]
+    #[PrimeFieldReprEndianness = "little"]
+    struct BabyBear([u64; 1]);
+
+    // x * y = z with x public, and z + 5 = w
+    struct Multiply {
+        x: Option<u64>,
+        y: Option<u64>,
+    }
+
+    impl Circuit<Fm31> for Multiply {
+        fn synthesize<CS>(self, cs: &mut CS) -> Result<(), SynthesisError>
+        where
+            CS: ConstraintSystem<Fm31>,
+        {
+            let value = |v: Option<u64>| v.map(Fm31::from).ok_or(SynthesisError::AssignmentMissing);
+            let z_value = self.x.zip(self.y).map(|(x, y)| x * y);
+
+            let x = cs.alloc_input(|| "x", || value(self.x))?;
+            let mut cs = cs.namespace(|| "product");
+            let y = cs.alloc(|| "y", || value(self.y))?;
+            let z = cs.alloc(|| "z", || value(z_value))?;
+            let w = cs.alloc(|| "w", || value(z_value.map(|z| z + 5)))?;
+
+            cs.enforce(|| "x * y = z", |lc| lc + x, |lc| lc + y, |lc| lc + z);
+            cs.enforce(
+                || "z + 5 = w",
+                |lc| lc + z + (Fm31::from(5), CS::one()),
+                |lc| lc + CS::one(),
+                |lc| lc + w,
+            );
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bellpepper() {
+        let multiply = Multiply {
+            x: Some(3),
+            y: Some(7),
+        };
+        let circuit = generate_circuit_bellpepper(multiply, Mode::PROVE).unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        let labels = (0..circuit.num_rows)
+            .filter_map(|row| circuit.get_label(row))
+            .collect::<Vec<_>>();
+        assert!(labels.iter().any(|label| label.ends_with("product/y")));
+
+        let setup = Multiply { x: None, y: None };
+        let index = generate_circuit_bellpepper(setup, Mode::INDEX).unwrap();
+        assert_eq!(index.index(), circuit.index());
+
+        let missing = Multiply {
+            x: Some(3),
+            y: None,
+        };
+        assert!(generate_circuit_bellpepper(missing, Mode::PROVE).is_err());
+    }
+
+    #[test]
+    fn test_bellpepper_other_field() {
+        assert!(BellpepperConstraintSystem::<BabyBear>::new(Mode::PROVE).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::{Circuit, FastHashMap, FastHashSet, RowKind, UnsatisfiedRow};
+use crate::field::to_m31_slice;
+use crate::from_r1cs::circom::stream::{generate_index_streaming, ConstraintReader};
+use crate::from_r1cs::circom::{witness_read, IoResult};
+use crate::proof_system::PlonkProof;
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+
+fn invalid_data(msg: &str) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::InvalidData, msg))
+}
+
+// The index of a `.r1cs` file, converted once to prove any number of its witnesses. Each `.wtns`
+// file only gives the values of the input rows and of the free witness rows, and the other rows
+// are evaluated by `CircuitIndex::evaluate`, without going through the constraints again.
+pub struct BatchProver {
+    pub index: CircuitIndex,
+    num_wires: usize,
+    // the variables whose values are those of the input rows and of the free witness rows, in
+    // the order expected by `CircuitIndex::evaluate`
+    input_variables: Vec<usize>,
+    witness_variables: Vec<usize>,
+}
+
+impl BatchProver {
+    pub fn new<R: Read + Seek>(mut r1cs_data: R) -> IoResult<Self> {
+        let num_wires = ConstraintReader::new(&mut r1cs_data)?.header().num_wires as usize;
+        r1cs_data.seek(SeekFrom::Start(0))?;
+        let result = generate_index_streaming(r1cs_data)?;
+        let index = result.circuit.index();
+
+        // variables with the same wire have the same value in a satisfying witness
+        let mut row_variables = FastHashMap::default();
+        for (&variable, &wire) in result.variable_map.iter() {
+            let entry = row_variables.entry(wire.0).or_insert(variable);
+            *entry = variable.min(*entry);
+        }
+        let hinted = index
+            .hints
+            .iter()
+            .map(|hint| hint.row)
+            .collect::<FastHashSet<_>>();
+
+        let mut input_variables = vec![];
+        let mut witness_variables = vec![];
+        for (row, kind) in index.row_kinds().into_iter().enumerate() {
+            let variables = match kind {
+                RowKind::Input => &mut input_variables,
+                RowKind::Witness if !hinted.contains(&row) => &mut witness_variables,
+                RowKind::Boolean => &mut witness_variables,
+                _ => continue,
+            };
+            let &variable = row_variables.get(&row).ok_or_else(|| {
+                invalid_data(&format!("Row {} is not the value of a variable", row))
+            })?;
+            variables.push(variable);
+        }
+
+        Ok(Self {
+            index,
+            num_wires,
+            input_variables,
+            witness_variables,
+        })
+    }
+
+    pub fn prove<W: Read + Seek>(&self, witness: W) -> IoResult<PlonkProof> {
+        let values = to_m31_slice(&witness_read(witness)?);
+        if values.len() != self.num_wires {
+            return Err(invalid_data(
+                "The witness does not match the number of wires",
+            ));
+        }
+        let inputs = self
+            .input_variables
+            .iter()
+            .map(|&variable| values[variable])
+            .collect::<Vec<_>>();
+        let witnesses = self
+            .witness_variables
+            .iter()
+            .map(|&variable| values[variable])
+            .collect::<Vec<_>>();
+
+        let witness = self
+            .index
+            .evaluate(&inputs, &witnesses)
+            .map_err(|err| invalid_data(&err.to_string()))?;
+        let mut circuit = Circuit::from_parts(self.index.clone(), witness);
+        if let Err(UnsatisfiedRow { row }) = circuit.check_satisfied() {
+            return Err(invalid_data(&format!(
+                "The witness does not satisfy row {}",
+                row
+            )));
+        }
+        PlonkProof::prove(&mut circuit).map_err(|err| invalid_data(&err.to_string()))
+    }
+}
+
+// Proves every witness of the same `.r1cs` file with a `BatchProver`, such as the jobs of a
+// queue, so that the constraints are only read and lowered once. The proofs are produced as the
+// iterator is advanced, and a witness that cannot be read or is not satisfying only fails its own
+// proof.
+pub fn prove_many<R, W, I>(
+    r1cs_data: R,
+    witnesses: I,
+) -> IoResult<impl Iterator<Item = IoResult<PlonkProof>>>
+where
+    R: Read + Seek,
+    W: Read + Seek,
+    I: IntoIterator<Item = W>,
+{
+    let prover = BatchProver::new(r1cs_data)?;
+    Ok(witnesses
+        .into_iter()
+        .map(move |witness| prover.prove(witness)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::FM31;
+    use crate::from_r1cs::circom::batch::prove_many;
+    use crate::from_r1cs::circom::stream::generate_circuit_streaming;
+    use crate::from_r1cs::circom::{witness_read, witness_write};
+    use crate::proof_system::verify_plonk;
+    use ark_ff::One;
+    use ark_std::io::Cursor;
+
+    #[test]
+    fn test_prove_many() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+        let values = witness_read(Cursor::new(witness)).unwrap();
+        let expected =
+            generate_circuit_streaming(Cursor::new(r1cs), Some(values.as_slice())).unwrap();
+
+        let mut wrong = values.clone();
+        *wrong.last_mut().unwrap() += FM31::one();
+        let mut wrong_data = vec![];
+        witness_write(&mut wrong_data, &wrong).unwrap();
+
+        let jobs = [&witness[..], &wrong_data[..], &witness[..]].map(Cursor::new);
+        let proofs = prove_many(Cursor::new(r1cs), jobs)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(proofs.len(), 3);
+        for (i, proof) in proofs.into_iter().enumerate() {
+            if i == 1 {
+                assert!(proof.is_err());
+                continue;
+            }
+            let proof = proof.unwrap();
+            assert_eq!(proof.num_rows, expected.num_rows);
+            assert_eq!(proof.inputs.values(), expected.input_maps.values());
+            verify_plonk(proof.log_n_rows, &proof.inputs, proof.proof).unwrap();
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::circuit::gadget::Gadget;
+use crate::circuit::{Circuit, FastHashMap};
+use crate::from_r1cs::circom::IoResult;
+use crate::from_r1cs::r1cs_constraint_processor::OnDemandAllocator;
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind};
+use stwo_prover::core::fields::m31::M31;
+
+// Custom templates, which circom 2.1 keeps out of the constraints with `--custom_templates`. The
+// `.r1cs` file then lists the custom gates (the template name and its parameters) in section 4
+// and where they are applied (the gate and the wires of its signals) in section 5, and their
+// signals are only related by the witness generator.
+//
+// As nothing in the R1CS constrains them, every custom gate must be mapped onto a native gadget,
+// which takes the first signals as inputs and whose outputs are the remaining signals.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomGate {
+    pub name: String,
+    pub parameters: Vec<M31>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomGateApplication {
+    pub gate: usize,
+    pub signals: Vec<usize>,
+}
+
+type GadgetBuilder = Box<dyn Fn(&[M31]) -> Gadget>;
+
+#[derive(Default)]
+pub struct CustomGateMap {
+    gadgets: FastHashMap<String, GadgetBuilder>,
+}
+
+fn invalid_data(msg: String) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::InvalidData, msg))
+}
+
+impl CustomGateMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `build` receives the parameters of the template, such as the round constants of a
+    // Poseidon round.
+    pub fn register<F>(mut self, name: &str, build: F) -> Self
+    where
+        F: Fn(&[M31]) -> Gadget + 'static,
+    {
+        self.gadgets.insert(name.to_string(), Box::new(build));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.gadgets.contains_key(name)
+    }
+
+    // Outputs that are not allocated yet are taken from the gadget, and the others are enforced
+    // to be equal to it.
+    pub fn apply(
+        &self,
+        circuit: &mut Circuit,
+        allocator: &mut OnDemandAllocator,
+        gates: &[CustomGate],
+        applications: &[CustomGateApplication],
+    ) -> IoResult<()> {
+        let mut gadgets = FastHashMap::default();
+        for application in applications {
+            let gate = &gates[application.gate];
+            if !gadgets.contains_key(&application.gate) {
+                let build = self.gadgets.get(&gate.name).ok_or_else(|| {
+                    invalid_data(format!("Unrecognized custom gate {}", gate.name))
+                })?;
+                gadgets.insert(application.gate, build(&gate.parameters));
+            }
+            let gadget = &gadgets[&application.gate];
+
+            let num_inputs = gadget.inputs.len();
+            if application.signals.len() != num_inputs + gadget.outputs.len() {
+                return Err(invalid_data(format!(
+                    "Unexpected number of signals for custom gate {}",
+                    gate.name
+                )));
+            }
+
+            let inputs = application.signals[..num_inputs]
+                .iter()
+                .map(|&idx| allocator.get(circuit, idx))
+                .collect::<Vec<_>>();
+            let outputs = circuit.instantiate(gadget, &inputs);
+            for (&idx, output) in application.signals[num_inputs..].iter().zip(outputs) {
+                if allocator.is_allocated(idx) {
+                    let expected = allocator.get(circuit, idx);
+                    let expected_neg = circuit.neg(expected);
+                    let diff = circuit.add(output, expected_neg);
+                    circuit.zero_test(diff);
+                } else {
+                    allocator.set_allocated(circuit, idx, output);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use crate::circuit::{Circuit, FastHashMap, RowKind, Wire};
+use crate::field::{from_m31, from_m31_slice, to_m31, FM31};
+use crate::from_r1cs::circom::stream::LinearCombination;
+use crate::from_r1cs::circom::{m31_modulus, IoResult};
+use ark_ff::{One, Zero};
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind, Write};
+use byteorder::{LittleEndian, WriteBytesExt};
+use stwo_prover::core::fields::m31::M31;
+
+// An R1CS equivalent to a circuit, with a variable for every row that carries a value: the
+// public rows (those in `input_maps`) as instance variables, in order, and the other input,
+// witness and gate rows as witness variables. A public `Wire::ONE` holding one, as the converter
+// and the builder create it, becomes the constant one itself.
+//
+// Every gate row `op * (a + b) + (1 - op) * a * b = c` becomes one constraint and every zero test
+// `a * 1 = 0`, while the rows whose value is free give none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedR1CS {
+    pub num_instance_variables: usize,
+    pub num_variables: usize,
+    pub constraints: Vec<[LinearCombination; 3]>,
+    // the variable of every row, if it has one
+    pub variables: Vec<Option<usize>>,
+    // the values of all the variables, including the constant one
+    pub assignment: Vec<M31>,
+}
+
+fn merge(mut terms: LinearCombination) -> LinearCombination {
+    terms.sort_unstable_by_key(|&(_, idx)| idx);
+    let mut merged: LinearCombination = Vec::with_capacity(terms.len());
+    for (coeff, idx) in terms {
+        match merged.last_mut() {
+            Some(last) if last.1 == idx => last.0 += coeff,
+            _ => merged.push((coeff, idx)),
+        }
+    }
+    merged.retain(|(coeff, _)| !coeff.is_zero());
+    merged
+}
+
+impl ExportedR1CS {
+    pub fn from_circuit(circuit: &Circuit) -> Self {
+        let kinds = circuit.row_kinds();
+        let mut variables = vec![None; circuit.num_rows];
+        let mut assignment = vec![M31::one()];
+
+        let mut public_rows = FastHashMap::default();
+        for &(row, value) in circuit.input_maps.iter() {
+            public_rows.entry(row).or_insert(value);
+        }
+        if circuit.num_rows > 1 && public_rows.get(&1) == Some(&M31::one()) {
+            variables[1] = Some(0);
+        }
+        for &(row, _) in circuit.input_maps.iter() {
+            if variables[row].is_none() {
+                variables[row] = Some(assignment.len());
+                assignment.push(circuit.get_output_wire(Wire(row)));
+            }
+        }
+        let num_instance_variables = assignment.len();
+
+        for row in 1..circuit.num_rows {
+            if variables[row].is_none() && kinds[row] != RowKind::ZeroTest {
+                variables[row] = Some(assignment.len());
+                assignment.push(circuit.get_output_wire(Wire(row)));
+            }
+        }
+
+        // the zero row is the empty linear combination
+        let term = |coeff: M31, row: usize| variables[row].map(|idx| (from_m31(coeff), idx));
+        let one = vec![(FM31::one(), 0)];
+
+        let mut constraints = vec![];
+        for row in 1..circuit.num_rows {
+            let (a, b) = (circuit.idx_a[row], circuit.idx_b[row]);
+            match kinds[row] {
+                RowKind::Zero | RowKind::Input | RowKind::Witness => {}
+                RowKind::Boolean => {
+                    let c = term(M31::one(), row).into_iter().collect::<Vec<_>>();
+                    constraints.push([c.clone(), c.clone(), c]);
+                }
+                RowKind::ZeroTest => {
+                    if a != 0 {
+                        let a = term(M31::one(), a).into_iter().collect();
+                        constraints.push([a, one.clone(), vec![]]);
+                    }
+                }
+                RowKind::Gate => {
+                    let op = circuit.op[row];
+                    let c = term(M31::one(), row).into_iter().collect::<Vec<_>>();
+                    // with a zero operand, the product vanishes and only `op * (a + b)` is left
+                    let constraint = if a == 0 || b == 0 || op.is_one() {
+                        let lin = term(op, a).into_iter().chain(term(op, b)).collect();
+                        [merge(lin), one.clone(), c]
+                    } else if op.is_zero() {
+                        let a = term(M31::one(), a).into_iter().collect();
+                        let b = term(M31::one(), b).into_iter().collect();
+                        [a, b, c]
+                    } else {
+                        let a_scaled = term(M31::one() - op, a).into_iter().collect();
+                        let b = term(M31::one(), b).into_iter().collect();
+                        let c = c
+                            .into_iter()
+                            .chain(term(-op, a))
+                            .chain(term(-op, b))
+                            .collect();
+                        [a_scaled, b, merge(c)]
+                    };
+                    constraints.push(constraint);
+                }
+            }
+        }
+
+        Self {
+            num_instance_variables,
+            num_variables: assignment.len(),
+            constraints,
+            variables,
+            assignment,
+        }
+    }
+
+    pub fn witness(&self) -> Vec<FM31> {
+        from_m31_slice(&self.assignment)
+    }
+
+    // Writes the constraints as a `.r1cs` file over M31 with elements of 8 bytes, as circom does,
+    // with the instance variables as public inputs and every label equal to its variable.
+    pub fn write<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let too_large = || IoError(Error::new(ErrorKind::InvalidInput, "Too many variables"));
+        let n8 = 8u32;
+        let num_wires = u32::try_from(self.num_variables).map_err(|_| too_large())?;
+        let num_constraints = u32::try_from(self.constraints.len()).map_err(|_| too_large())?;
+
+        let mut constraints = vec![];
+        for constraint in self.constraints.iter() {
+            for terms in constraint.iter() {
+                constraints.write_u32::<LittleEndian>(terms.len() as u32)?;
+                for (coeff, idx) in terms.iter() {
+                    constraints.write_u32::<LittleEndian>(*idx as u32)?;
+                    constraints.write_u64::<LittleEndian>(to_m31(coeff).0 as u64)?;
+                }
+            }
+        }
+
+        writer.write_all(b"r1cs")?;
+        writer.write_u32::<LittleEndian>(1)?;
+        writer.write_u32::<LittleEndian>(3)?;
+
+        writer.write_u32::<LittleEndian>(1)?;
+        writer.write_u64::<LittleEndian>(4 + n8 as u64 + 4 * 4 + 8 + 4)?;
+        writer.write_u32::<LittleEndian>(n8)?;
+        writer.write_all(&m31_modulus(n8))?;
+        writer.write_u32::<LittleEndian>(num_wires)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u32::<LittleEndian>(self.num_instance_variables as u32 - 1)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u64::<LittleEndian>(num_wires as u64)?;
+        writer.write_u32::<LittleEndian>(num_constraints)?;
+
+        writer.write_u32::<LittleEndian>(2)?;
+        writer.write_u64::<LittleEndian>(constraints.len() as u64)?;
+        writer.write_all(&constraints)?;
+
+        writer.write_u32::<LittleEndian>(3)?;
+        writer.write_u64::<LittleEndian>(8 * num_wires as u64)?;
+        for label in 0..num_wires as u64 {
+            writer.write_u64::<LittleEndian>(label)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::builder::CircuitBuilder;
+    use crate::circuit::Mode;
+    use crate::from_r1cs::circom::export::ExportedR1CS;
+    use crate::from_r1cs::circom::{load_r1cs_and_witness, witness_write};
+    use crate::from_r1cs::r1cs_constraint_processor::{evaluate_r1cs_constraint, generate_circuit};
+    use ark_std::io::Cursor;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_export() {
+        // (x + 2) * y - 3 * x = 5 * z, with x public
+        let builder = CircuitBuilder::new();
+        let x = builder.input(M31::from(4u32));
+        let y = builder.witness(M31::from(7u32));
+        let t = (&x + M31::from(2u32)) * &y - &x * M31::from(3u32);
+        let z = builder.witness(M31::from(6u32));
+        let z_wire = builder.wire(&z);
+        builder.assert_equal(&t, &(&z * M31::from(5u32)));
+        let circuit = builder.finalize();
+        assert!(circuit.is_constraint_satisfied());
+
+        let r1cs = ExportedR1CS::from_circuit(&circuit);
+        assert_eq!(r1cs.num_instance_variables, 2);
+        assert_eq!(r1cs.variables[1], Some(0));
+        for [a, b, c] in r1cs.constraints.iter() {
+            assert!(
+                evaluate_r1cs_constraint(&r1cs.assignment.clone().into(), a, b, c)
+                    == M31::from(0u32)
+            );
+        }
+
+        let mut r1cs_data = vec![];
+        r1cs.write(&mut r1cs_data).unwrap();
+        let mut witness_data = vec![];
+        witness_write(&mut witness_data, &r1cs.witness()).unwrap();
+
+        let circom_circuit =
+            load_r1cs_and_witness(Cursor::new(&r1cs_data), Cursor::new(&witness_data)).unwrap();
+        let converted = generate_circuit(circom_circuit, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(converted.is_constraint_satisfied());
+
+        // a wrong value for z
+        let mut wrong = r1cs.clone();
+        let z = wrong.variables[z_wire.0].unwrap();
+        wrong.assignment[z] += M31::from(1u32);
+        assert!(wrong.constraints.iter().any(|[a, b, c]| {
+            evaluate_r1cs_constraint(&wrong.assignment.clone().into(), a, b, c) != M31::from(0u32)
+        }));
+    }
+}
@@ -0,0 +1,580 @@
+use crate::circuit::FastHashMap;
+use crate::field::{try_to_m31, ReductionPolicy, FM31};
+use crate::from_r1cs::circom::{load_r1cs_with, IoResult};
+use ark_circom::CircomCircuit;
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind, Read, Seek};
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::FieldExpOps;
+
+// The graph files compiled by `circom-witnesscalc`, which compute the witness without running
+// the `.wasm` witness calculator. The file is the magic `wtns.graph.001`, the number of nodes as
+// a u64, the nodes as length-delimited protobuf messages in topological order, and finally the
+// length-delimited metadata: which nodes are the witness signals and where every input signal
+// starts in the input buffer.
+
+const MAGIC: &[u8] = b"wtns.graph.001";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnoOp {
+    Neg,
+    Id,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuoOp {
+    Mul,
+    Div,
+    Add,
+    Sub,
+    Pow,
+    Idiv,
+    Mod,
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Leq,
+    Geq,
+    Land,
+    Lor,
+    Shl,
+    Shr,
+    Bor,
+    Band,
+    Bxor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphNode {
+    // a position in the input buffer, whose first entry is the constant one
+    Input(usize),
+    Constant(M31),
+    Uno(UnoOp, usize),
+    Duo(DuoOp, usize, usize),
+    // `a ? b : c`
+    TernCond(usize, usize, usize),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WitnessGraph {
+    pub nodes: Vec<GraphNode>,
+    pub witness_signals: Vec<usize>,
+    // (offset in the input buffer, number of values) of every input signal
+    pub inputs: FastHashMap<String, (usize, usize)>,
+}
+
+fn invalid_data(msg: &str) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::InvalidData, msg))
+}
+
+struct ProtoReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn varint(&mut self) -> IoResult<u64> {
+        let mut value = 0u64;
+        for i in 0..10 {
+            let (&byte, rest) = self
+                .data
+                .split_first()
+                .ok_or_else(|| invalid_data("Truncated varint"))?;
+            self.data = rest;
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("Varint is too long"))
+    }
+
+    fn bytes(&mut self) -> IoResult<&'a [u8]> {
+        let len = self.varint()?;
+        if len > self.data.len() as u64 {
+            return Err(invalid_data("Truncated message"));
+        }
+        let (bytes, rest) = self.data.split_at(len as usize);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn message(&mut self) -> IoResult<ProtoReader<'a>> {
+        Ok(ProtoReader {
+            data: self.bytes()?,
+        })
+    }
+
+    // (field number, wire type)
+    fn field(&mut self) -> IoResult<(u64, u64)> {
+        let tag = self.varint()?;
+        Ok((tag >> 3, tag & 7))
+    }
+
+    fn skip(&mut self, wire_type: u64) -> IoResult<()> {
+        let len = match wire_type {
+            0 => return self.varint().map(|_| ()),
+            1 => 8,
+            2 => return self.bytes().map(|_| ()),
+            5 => 4,
+            _ => return Err(invalid_data("Unsupported wire type")),
+        };
+        if self.data.len() < len {
+            return Err(invalid_data("Truncated message"));
+        }
+        self.data = &self.data[len..];
+        Ok(())
+    }
+
+    // The varint fields of a message, by field number, with absent fields being zero.
+    fn varint_fields<const N: usize>(mut self) -> IoResult<[u64; N]> {
+        let mut fields = [0u64; N];
+        while !self.is_empty() {
+            let (field, wire_type) = self.field()?;
+            if wire_type == 0 && (1..=N as u64).contains(&field) {
+                fields[field as usize - 1] = self.varint()?;
+            } else {
+                self.skip(wire_type)?;
+            }
+        }
+        Ok(fields)
+    }
+}
+
+fn parse_duo_op(op: u64) -> IoResult<DuoOp> {
+    use DuoOp::*;
+    const OPS: [DuoOp; 20] = [
+        Mul, Div, Add, Sub, Pow, Idiv, Mod, Eq, Neq, Lt, Gt, Leq, Geq, Land, Lor, Shl, Shr, Bor,
+        Band, Bxor,
+    ];
+    OPS.get(op as usize)
+        .copied()
+        .ok_or_else(|| invalid_data("Unknown binary operation"))
+}
+
+// A `BigUInt` message, which holds the little-endian bytes of a constant.
+fn parse_constant(mut message: ProtoReader) -> IoResult<M31> {
+    let mut value = 0u64;
+    while !message.is_empty() {
+        let (field, wire_type) = message.field()?;
+        if field != 1 || wire_type != 2 {
+            message.skip(wire_type)?;
+            continue;
+        }
+        let bytes = message.bytes()?;
+        let len = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        if len > 4 {
+            return Err(invalid_data("Constant is not an M31 element"));
+        }
+        value = bytes[..len]
+            .iter()
+            .rev()
+            .fold(0, |acc, &b| (acc << 8) | b as u64);
+    }
+    if value >= P as u64 {
+        return Err(invalid_data("Constant is not an M31 element"));
+    }
+    Ok(M31::from(value as u32))
+}
+
+fn parse_node(mut message: ProtoReader, index: usize) -> IoResult<GraphNode> {
+    let (kind, wire_type) = message.field()?;
+    if wire_type != 2 {
+        return Err(invalid_data("Invalid node"));
+    }
+    let body = message.message()?;
+
+    let node = match kind {
+        1 => GraphNode::Input(body.varint_fields::<1>()?[0] as usize),
+        2 => {
+            let mut body = body;
+            let mut value = M31::from(0);
+            while !body.is_empty() {
+                let (field, wire_type) = body.field()?;
+                if field == 1 && wire_type == 2 {
+                    value = parse_constant(body.message()?)?;
+                } else {
+                    body.skip(wire_type)?;
+                }
+            }
+            GraphNode::Constant(value)
+        }
+        3 => {
+            let [op, a] = body.varint_fields::<2>()?;
+            let op = match op {
+                0 => UnoOp::Neg,
+                1 => UnoOp::Id,
+                _ => return Err(invalid_data("Unknown unary operation")),
+            };
+            GraphNode::Uno(op, a as usize)
+        }
+        4 => {
+            let [op, a, b] = body.varint_fields::<3>()?;
+            GraphNode::Duo(parse_duo_op(op)?, a as usize, b as usize)
+        }
+        5 => {
+            let [op, a, b, c] = body.varint_fields::<4>()?;
+            if op != 0 {
+                return Err(invalid_data("Unknown ternary operation"));
+            }
+            GraphNode::TernCond(a as usize, b as usize, c as usize)
+        }
+        _ => return Err(invalid_data("Unknown node type")),
+    };
+
+    // nodes only read earlier nodes
+    let refs = match node {
+        GraphNode::Input(_) | GraphNode::Constant(_) => vec![],
+        GraphNode::Uno(_, a) => vec![a],
+        GraphNode::Duo(_, a, b) => vec![a, b],
+        GraphNode::TernCond(a, b, c) => vec![a, b, c],
+    };
+    if refs.iter().any(|&idx| idx >= index) {
+        return Err(invalid_data("Node refers to a later node"));
+    }
+    Ok(node)
+}
+
+pub fn graph_read<R: Read>(mut reader: R) -> IoResult<WitnessGraph> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+
+    if !data.starts_with(MAGIC) {
+        return Err(invalid_data("Invalid magic number"));
+    }
+    let data = &data[MAGIC.len()..];
+    if data.len() < 8 {
+        return Err(invalid_data("Truncated graph"));
+    }
+    let num_nodes = u64::from_le_bytes(data[..8].try_into().unwrap());
+    let mut reader = ProtoReader { data: &data[8..] };
+
+    // every node takes at least two bytes
+    let mut nodes = Vec::with_capacity((num_nodes as usize).min(reader.data.len() / 2));
+    for index in 0..num_nodes as usize {
+        nodes.push(parse_node(reader.message()?, index)?);
+    }
+
+    let mut graph = WitnessGraph {
+        nodes,
+        ..Default::default()
+    };
+
+    let mut metadata = reader.message()?;
+    while !metadata.is_empty() {
+        let (field, wire_type) = metadata.field()?;
+        match (field, wire_type) {
+            // packed
+            (1, 2) => {
+                let mut packed = metadata.message()?;
+                while !packed.is_empty() {
+                    graph.witness_signals.push(packed.varint()? as usize);
+                }
+            }
+            (1, 0) => graph.witness_signals.push(metadata.varint()? as usize),
+            (2, 2) => {
+                let mut entry = metadata.message()?;
+                let mut name = String::new();
+                let mut signal = (0, 0);
+                while !entry.is_empty() {
+                    match entry.field()? {
+                        (1, 2) => {
+                            name = String::from_utf8(entry.bytes()?.to_vec())
+                                .map_err(|_| invalid_data("Invalid signal name"))?;
+                        }
+                        (2, 2) => {
+                            let [offset, len] = entry.message()?.varint_fields::<2>()?;
+                            signal = (offset as usize, len as usize);
+                        }
+                        (_, wire_type) => entry.skip(wire_type)?,
+                    }
+                }
+                graph.inputs.insert(name, signal);
+            }
+            (_, wire_type) => metadata.skip(wire_type)?,
+        }
+    }
+
+    if graph
+        .witness_signals
+        .iter()
+        .any(|&idx| idx >= graph.nodes.len())
+    {
+        return Err(invalid_data("Witness signal out of range"));
+    }
+    Ok(graph)
+}
+
+// Values above P / 2 are negative in circom's comparisons.
+fn to_signed(v: M31) -> i64 {
+    if v.0 > P / 2 {
+        v.0 as i64 - P as i64
+    } else {
+        v.0 as i64
+    }
+}
+
+fn from_bool(b: bool) -> M31 {
+    M31::from(b as u32)
+}
+
+fn reduce(v: u64) -> M31 {
+    M31::from((v % P as u64) as u32)
+}
+
+// Shifts follow circom: a negative amount shifts the other way, and bits beyond the bit length
+// of the modulus are dropped.
+fn shift(a: M31, b: M31, left: bool) -> M31 {
+    let (left, amount) = if b.0 > P / 2 {
+        (!left, P - b.0)
+    } else {
+        (left, b.0)
+    };
+    if amount >= 31 {
+        return M31::from(0);
+    }
+    if left {
+        reduce(((a.0 as u64) << amount) & P as u64)
+    } else {
+        M31::from(a.0 >> amount)
+    }
+}
+
+fn evaluate_duo(op: DuoOp, a: M31, b: M31) -> M31 {
+    let zero = M31::from(0);
+    match op {
+        DuoOp::Mul => a * b,
+        DuoOp::Div => {
+            if b == zero {
+                zero
+            } else {
+                a * b.inverse()
+            }
+        }
+        DuoOp::Add => a + b,
+        DuoOp::Sub => a - b,
+        DuoOp::Pow => a.pow(b.0 as u128),
+        DuoOp::Idiv => M31::from(a.0.checked_div(b.0).unwrap_or(0)),
+        DuoOp::Mod => M31::from(a.0.checked_rem(b.0).unwrap_or(0)),
+        DuoOp::Eq => from_bool(a == b),
+        DuoOp::Neq => from_bool(a != b),
+        DuoOp::Lt => from_bool(to_signed(a) < to_signed(b)),
+        DuoOp::Gt => from_bool(to_signed(a) > to_signed(b)),
+        DuoOp::Leq => from_bool(to_signed(a) <= to_signed(b)),
+        DuoOp::Geq => from_bool(to_signed(a) >= to_signed(b)),
+        DuoOp::Land => from_bool(a != zero && b != zero),
+        DuoOp::Lor => from_bool(a != zero || b != zero),
+        DuoOp::Shl => shift(a, b, true),
+        DuoOp::Shr => shift(a, b, false),
+        DuoOp::Bor => reduce((a.0 | b.0) as u64),
+        DuoOp::Band => reduce((a.0 & b.0) as u64),
+        DuoOp::Bxor => reduce((a.0 ^ b.0) as u64),
+    }
+}
+
+impl WitnessGraph {
+    // Computes the full assignment, in the order of the R1CS wires, from the values of every
+    // input signal.
+    pub fn calculate(&self, inputs: &FastHashMap<String, Vec<FM31>>) -> IoResult<Vec<FM31>> {
+        let buffer_len = self
+            .inputs
+            .values()
+            .map(|&(offset, len)| offset + len)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut buffer = vec![M31::from(0); buffer_len];
+        buffer[0] = M31::from(1);
+
+        for (name, values) in inputs.iter() {
+            let &(offset, len) = self
+                .inputs
+                .get(name)
+                .ok_or_else(|| invalid_data(&format!("Unknown input signal {}", name)))?;
+            if values.len() != len {
+                return Err(invalid_data(&format!(
+                    "Input signal {} expects {} values",
+                    name, len
+                )));
+            }
+            let slots = buffer[offset..offset + len].iter_mut();
+            for (i, (slot, value)) in slots.zip(values.iter()).enumerate() {
+                *slot = try_to_m31(value).map_err(|err| {
+                    invalid_data(&format!("Input signal {}[{}]: {}", name, i, err))
+                })?;
+            }
+        }
+        if let Some(name) = self.inputs.keys().find(|&name| !inputs.contains_key(name)) {
+            return Err(invalid_data(&format!("Missing input signal {}", name)));
+        }
+
+        let mut values = Vec::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            let value = match *node {
+                GraphNode::Input(idx) => *buffer
+                    .get(idx)
+                    .ok_or_else(|| invalid_data("Input out of range"))?,
+                GraphNode::Constant(c) => c,
+                GraphNode::Uno(UnoOp::Neg, a) => -values[a],
+                GraphNode::Uno(UnoOp::Id, a) => values[a],
+                GraphNode::Duo(op, a, b) => evaluate_duo(op, values[a], values[b]),
+                GraphNode::TernCond(a, b, c) => {
+                    if values[a] != M31::from(0) {
+                        values[b]
+                    } else {
+                        values[c]
+                    }
+                }
+            };
+            values.push(value);
+        }
+
+        Ok(self
+            .witness_signals
+            .iter()
+            .map(|&idx| FM31::from(values[idx].0))
+            .collect())
+    }
+}
+
+pub fn load_r1cs_and_graph_witness(
+    r1cs_data: impl Read + Seek,
+    graph_data: impl Read,
+    inputs: &FastHashMap<String, Vec<FM31>>,
+) -> IoResult<CircomCircuit<FM31>> {
+    let (circuit, _) = load_r1cs_with(r1cs_data, ReductionPolicy::Strict)?;
+
+    let witness = graph_read(graph_data)?.calculate(inputs)?;
+    Ok(CircomCircuit::<FM31> {
+        witness: Some(witness),
+        ..circuit
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{FastHashMap, Mode};
+    use crate::field::FM31;
+    use crate::from_r1cs::circom::graph::{graph_read, load_r1cs_and_graph_witness, MAGIC};
+    use crate::from_r1cs::circom::test::with_non_canonical_coefficient;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use ark_std::io::Cursor;
+
+    fn varint(mut v: u64, out: &mut Vec<u8>) {
+        while v >= 0x80 {
+            out.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        varint((field << 3) | 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn varints(fields: &[u64]) -> Vec<u8> {
+        let mut out = vec![];
+        for (i, &v) in fields.iter().enumerate() {
+            varint((i as u64 + 1) << 3, &mut out);
+            varint(v, &mut out);
+        }
+        out
+    }
+
+    fn node(kind: u64, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        field(kind, body, &mut out);
+        out
+    }
+
+    // The graph of multiplier2: the witness is (1, c, a, b) with c = a * b.
+    fn multiplier2_graph() -> Vec<u8> {
+        let nodes = [
+            node(2, &{
+                let mut big = vec![];
+                field(1, &[1], &mut big);
+                let mut constant = vec![];
+                field(1, &big, &mut constant);
+                constant
+            }),
+            node(1, &varints(&[1])),
+            node(1, &varints(&[2])),
+            node(4, &varints(&[0, 1, 2])),
+        ];
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+        for n in nodes.iter() {
+            varint(n.len() as u64, &mut data);
+            data.extend_from_slice(n);
+        }
+
+        let mut metadata = vec![];
+        let mut packed = vec![];
+        for idx in [0, 3, 1, 2] {
+            varint(idx, &mut packed);
+        }
+        field(1, &packed, &mut metadata);
+        for (name, offset) in [("main.a", 1), ("main.b", 2)] {
+            let mut entry = vec![];
+            field(1, name.as_bytes(), &mut entry);
+            field(2, &varints(&[offset, 1]), &mut entry);
+            field(2, &entry, &mut metadata);
+        }
+        varint(metadata.len() as u64, &mut data);
+        data.extend_from_slice(&metadata);
+        data
+    }
+
+    #[test]
+    fn test_graph_witness() {
+        let graph = graph_read(Cursor::new(multiplier2_graph())).unwrap();
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.inputs["main.b"], (2, 1));
+
+        let mut inputs = FastHashMap::default();
+        inputs.insert("main.a".to_string(), vec![FM31::from(3u32)]);
+        inputs.insert("main.b".to_string(), vec![FM31::from(11u32)]);
+        assert_eq!(
+            graph.calculate(&inputs).unwrap(),
+            [1u32, 33, 3, 11].map(FM31::from).to_vec()
+        );
+
+        // the same circuit and witness as `output.wtns`
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let circom_circuit = load_r1cs_and_graph_witness(
+            Cursor::new(r1cs),
+            Cursor::new(multiplier2_graph()),
+            &inputs,
+        )
+        .unwrap();
+        let circuit = generate_circuit(circom_circuit, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(circuit.is_constraint_satisfied());
+
+        let err = load_r1cs_and_graph_witness(
+            Cursor::new(with_non_canonical_coefficient(r1cs)),
+            Cursor::new(multiplier2_graph()),
+            &inputs,
+        )
+        .err()
+        .unwrap();
+        assert!(err
+            .to_string()
+            .contains("Coefficient 0 of A in constraint 0 is not an M31 element"));
+
+        inputs.remove("main.b");
+        assert!(graph.calculate(&inputs).is_err());
+
+        let mut data = multiplier2_graph();
+        data[0] = b'x';
+        assert!(graph_read(Cursor::new(data)).is_err());
+    }
+}
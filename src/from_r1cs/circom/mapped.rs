@@ -0,0 +1,139 @@
+use crate::field::{M31Reducer, ReductionPolicy};
+use crate::from_r1cs::circom::{element_from_bytes, m31_modulus, read_witness_header, IoResult};
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Cursor, Error, ErrorKind};
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+use stwo_prover::core::fields::m31::M31;
+
+// A `.wtns` file mapped into memory, whose values are only converted to M31 when they are read,
+// so that a large witness is not held twice (once as the file and once as a vector).
+pub struct MappedWitness {
+    data: Mmap,
+    n8: usize,
+    start: usize,
+    len: usize,
+}
+
+impl MappedWitness {
+    // Accepts the same encodings as `witness_read_with`. Every value is checked against the policy
+    // once here, so that reading them later cannot fail.
+    pub fn open<P: AsRef<Path>>(path: P, policy: ReductionPolicy) -> IoResult<Self> {
+        let file = File::open(path)?;
+        // the file must not be modified while it is mapped
+        let data = unsafe { Mmap::map(&file)? };
+
+        let mut reader = Cursor::new(&data[..]);
+        let (n8, modulus, len) = read_witness_header(&mut reader)?;
+        if policy == ReductionPolicy::Strict && modulus != m31_modulus(n8) {
+            return Err(IoError(Error::new(
+                ErrorKind::InvalidData,
+                "Witness is not generated for M31",
+            )));
+        }
+        let start = reader.position() as usize;
+        let (n8, len) = (n8 as usize, len as usize);
+        if data.len() < start + n8 * len {
+            return Err(IoError(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The witness file is truncated",
+            )));
+        }
+
+        let witness = Self {
+            data,
+            n8,
+            start,
+            len,
+        };
+        let mut reducer = M31Reducer::new(policy);
+        let values = &witness.data[start..start + n8 * len];
+        if let Some(i) = values
+            .chunks(n8)
+            .position(|bytes| reducer.convert_le_bytes(bytes).is_none())
+        {
+            return Err(IoError(Error::new(
+                ErrorKind::InvalidData,
+                format!("Witness {} is not an M31 element", i),
+            )));
+        }
+        reducer.warn("witness values");
+        Ok(witness)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<M31> {
+        (index < self.len).then(|| {
+            let offset = self.start + index * self.n8;
+            M31::from(element_from_bytes(&self.data[offset..offset + self.n8]).0)
+        })
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = M31> + '_ {
+        (0..self.len).map(|i| self.get(i).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::{IntoM31Iter, ReductionPolicy};
+    use crate::from_r1cs::circom::mapped::MappedWitness;
+    use crate::from_r1cs::circom::stream::{
+        generate_circuit_streaming, generate_circuit_streaming_from,
+        generate_circuit_streaming_mapped,
+    };
+    use crate::from_r1cs::circom::witness_read;
+    use crate::from_r1cs::r1cs_constraint_processor::Assignments;
+    use ark_std::io::Cursor;
+    use std::path::Path;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_mapped_witness() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/from_r1cs/circom/output.wtns");
+        let witness = MappedWitness::open(&path, ReductionPolicy::Strict).unwrap();
+
+        let expected = witness_read(Cursor::new(include_bytes!("./output.wtns"))).unwrap();
+        assert_eq!(witness.len(), expected.len());
+        assert_eq!(
+            witness.iter().collect::<Vec<_>>(),
+            expected.iter().into_m31().collect::<Vec<_>>()
+        );
+        assert_eq!(witness.get(expected.len()), None);
+
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let circuit = generate_circuit_streaming_from(Cursor::new(r1cs), Some(witness.iter()));
+        let circuit = circuit.unwrap();
+        assert!(circuit.is_constraint_satisfied());
+        assert_eq!(
+            circuit.witness(),
+            generate_circuit_streaming(Cursor::new(r1cs), Some(expected.as_slice()))
+                .unwrap()
+                .witness()
+        );
+
+        // the values are read from the mapping as the wires are allocated
+        let mapped = generate_circuit_streaming_mapped(Cursor::new(r1cs), witness).unwrap();
+        assert_eq!(mapped.index(), circuit.index());
+        assert_eq!(mapped.witness(), circuit.witness());
+
+        // variables added during conversion go past the end of the mapping
+        let witness = MappedWitness::open(&path, ReductionPolicy::Strict).unwrap();
+        let mut assignments = Assignments::from(witness);
+        assignments.push(M31::from(7));
+        assert_eq!(assignments.len(), expected.len() + 1);
+        assert_eq!(assignments.get(expected.len()), M31::from(7));
+        assert_eq!(
+            assignments.get(1),
+            expected.iter().into_m31().nth(1).unwrap()
+        );
+    }
+}
@@ -1,16 +1,145 @@
-use crate::field::FM31;
+use crate::circuit::serialization::m31_decimal_vec;
+use crate::circuit::{Circuit, FastHashMap, PublicWire};
+use crate::field::{from_m31, m31_from_le_bytes, to_m31, M31Reducer, ReductionPolicy, FM31};
+use crate::from_r1cs::circom::stream::ConstraintReader;
+use crate::from_r1cs::emulated::EmulatedR1CS;
+use crate::from_r1cs::r1cs_constraint_processor::ConversionResult;
 use ark_circom::{CircomCircuit, R1CSFile, R1CS};
+use ark_ff::{BigInteger, One, PrimeField};
 use ark_serialize::SerializationError;
 use ark_serialize::SerializationError::IoError;
-use ark_std::io::{Error, ErrorKind, Read, Seek};
-use byteorder::{LittleEndian, ReadBytesExt};
+use ark_std::io::{BufRead, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Deserializer, Serialize};
+use stwo_prover::core::fields::m31::{M31, P};
 
 type IoResult<T> = Result<T, SerializationError>;
 
+pub mod batch;
+
+pub mod custom;
+
+pub mod export;
+
+pub mod graph;
+
+pub mod mapped;
+
+pub mod stream;
+
+#[cfg(feature = "circom-witness")]
+pub mod wasm;
+
 // This implementation is based on the R1CS reader in `arkworks-rs/circom-compat`,
 // originally by Georgios Konstantopoulos.
 
-pub fn witness_read<R: Read + Seek>(mut reader: R) -> IoResult<Vec<FM31>> {
+pub fn witness_read<R: Read + Seek>(reader: R) -> IoResult<Vec<FM31>> {
+    witness_read_with(reader, ReductionPolicy::Strict)
+}
+
+// Accepts field elements of 4, 8 or 32 bytes. Under `ReductionPolicy::Strict`, the file must be
+// generated for M31 and hold elements below the modulus. Under `ReductionPolicy::Reduce`, any
+// prime is accepted and the values are reduced modulo M31, with a warning if any had to be.
+pub fn witness_read_with<R: Read + Seek>(
+    mut reader: R,
+    policy: ReductionPolicy,
+) -> IoResult<Vec<FM31>> {
+    let (n8, modulus, num_witnesses) = read_witness_header(&mut reader)?;
+    if policy == ReductionPolicy::Strict && modulus != m31_modulus(n8) {
+        return Err(IoError(Error::new(
+            ErrorKind::InvalidData,
+            "Witness is not generated for M31",
+        )));
+    }
+
+    let mut reducer = M31Reducer::new(policy);
+    let mut witnesses = vec![];
+    let mut bytes = vec![0u8; n8 as usize];
+    for i in 0..num_witnesses {
+        reader.read_exact(&mut bytes)?;
+        let value = reducer.convert_le_bytes(&bytes).ok_or_else(|| {
+            IoError(Error::new(
+                ErrorKind::InvalidData,
+                format!("Witness {} is not an M31 element", i),
+            ))
+        })?;
+        witnesses.push(from_m31(value));
+    }
+    reducer.warn("witness values");
+    Ok(witnesses)
+}
+
+// Writes the values in the `.wtns` format that `witness_read` reads, with elements of 8 bytes as
+// circom uses for M31.
+pub fn witness_write<W: Write>(writer: W, witness: &[FM31]) -> IoResult<()> {
+    witness_write_with(writer, witness, 8)
+}
+
+pub fn witness_write_with<W: Write>(mut writer: W, witness: &[FM31], n8: u32) -> IoResult<()> {
+    if ![4, 8, 32].contains(&n8) {
+        return Err(IoError(Error::new(
+            ErrorKind::InvalidInput,
+            "Unexpected n8",
+        )));
+    }
+    let num_witnesses = u32::try_from(witness.len())
+        .map_err(|_| IoError(Error::new(ErrorKind::InvalidInput, "Too many values")))?;
+
+    writer.write_all(b"wtns")?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u32::<LittleEndian>(2)?;
+
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u64::<LittleEndian>(8 + n8 as u64)?;
+    writer.write_u32::<LittleEndian>(n8)?;
+    writer.write_all(&m31_modulus(n8))?;
+    writer.write_u32::<LittleEndian>(num_witnesses)?;
+
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u64::<LittleEndian>(n8 as u64 * num_witnesses as u64)?;
+    let mut bytes = vec![0u8; n8 as usize];
+    for value in witness.iter() {
+        bytes[..4].copy_from_slice(&to_m31(value).0.to_le_bytes());
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+// Reads a witness generated for another prime field, such as BN254, whose modulus must be the one
+// in the file.
+pub fn witness_read_field<F: PrimeField, R: Read>(mut reader: R) -> IoResult<Vec<F>> {
+    let (n8, modulus, num_witnesses) = read_witness_header(&mut reader)?;
+    let to_bytes = |v: F::BigInt| {
+        let mut bytes = v.to_bytes_le();
+        bytes.resize(n8 as usize, 0);
+        bytes
+    };
+    if modulus != to_bytes(F::MODULUS) {
+        return Err(IoError(Error::new(
+            ErrorKind::InvalidData,
+            "Witness is not generated for this field",
+        )));
+    }
+
+    let mut witnesses = vec![];
+    let mut bytes = vec![0u8; n8 as usize];
+    for _ in 0..num_witnesses {
+        reader.read_exact(&mut bytes)?;
+        let value = F::from_le_bytes_mod_order(&bytes);
+        if to_bytes(value.into_bigint()) != bytes {
+            return Err(IoError(Error::new(
+                ErrorKind::InvalidData,
+                "Witness is not reduced",
+            )));
+        }
+        witnesses.push(value);
+    }
+    Ok(witnesses)
+}
+
+// Reads everything up to the values of the witness, and returns their size, the modulus and their
+// number.
+fn read_witness_header<R: Read>(reader: &mut R) -> IoResult<(u32, Vec<u8>, u32)> {
     let mut magic = [0u8; 4];
     reader.read_exact(&mut magic)?;
     if magic != [0x77, 0x74, 0x6e, 0x73] {
@@ -46,26 +175,20 @@ pub fn witness_read<R: Read + Seek>(mut reader: R) -> IoResult<Vec<FM31>> {
     }
 
     let id_section1_length = reader.read_u64::<LittleEndian>()?;
-    if id_section1_length != 16 {
-        return Err(IoError(Error::new(
-            ErrorKind::InvalidData,
-            "Unexpected length of the first section",
-        )));
-    }
-
     let n8 = reader.read_u32::<LittleEndian>()?;
-    if n8 != 8 {
+    if ![4, 8, 32].contains(&n8) {
         return Err(IoError(Error::new(ErrorKind::InvalidData, "Unexpected n8")));
     }
-
-    let fr_q = reader.read_u64::<LittleEndian>()?;
-    if fr_q != 2147483647 {
+    if id_section1_length != 8 + n8 as u64 {
         return Err(IoError(Error::new(
             ErrorKind::InvalidData,
-            "Witness is not generated for M31",
+            "Unexpected length of the first section",
         )));
     }
 
+    let mut fr_q = vec![0u8; n8 as usize];
+    reader.read_exact(&mut fr_q)?;
+
     let num_witnesses = reader.read_u32::<LittleEndian>()?;
 
     let id_section2 = reader.read_u32::<LittleEndian>()?;
@@ -77,41 +200,351 @@ pub fn witness_read<R: Read + Seek>(mut reader: R) -> IoResult<Vec<FM31>> {
     }
 
     let id_section2_length = reader.read_u64::<LittleEndian>()?;
-    if id_section2_length != 8 * num_witnesses as u64 {
+    if id_section2_length != n8 as u64 * num_witnesses as u64 {
         return Err(IoError(Error::new(
             ErrorKind::InvalidData,
             "Unexpected length of the second section",
         )));
     }
 
-    let mut witnesses = vec![];
-    for _ in 0..num_witnesses {
-        witnesses.push(FM31::from(reader.read_u64::<LittleEndian>()? as u32));
+    Ok((n8, fr_q, num_witnesses))
+}
+
+// The modulus in `n8` little-endian bytes, as it appears in the headers.
+fn m31_modulus(n8: u32) -> Vec<u8> {
+    let mut bytes = vec![0u8; n8 as usize];
+    bytes[..4].copy_from_slice(&P.to_le_bytes());
+    bytes
+}
+
+// Reads the file with `R1CSFile`, which skips the custom gates of sections 4 and 5. As nothing else
+// constrains their signals, a file that applies any is an error here, and must be converted with
+// `generate_circuit_streaming_with_custom_gates` and a gadget for each of them.
+pub(crate) fn read_r1cs_file<F: PrimeField, R: Read + Seek>(
+    mut reader: R,
+) -> IoResult<R1CSFile<F>> {
+    let start = reader.stream_position()?;
+    reject_custom_gates(&mut reader)?;
+    reader.seek(SeekFrom::Start(start))?;
+    R1CSFile::<F>::new(reader)
+}
+
+fn reject_custom_gates<R: Read + Seek>(reader: &mut R) -> IoResult<()> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    let _version = reader.read_u32::<LittleEndian>()?;
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+    for _ in 0..num_sections {
+        let section_type = reader.read_u32::<LittleEndian>()?;
+        let section_size = reader.read_u64::<LittleEndian>()?;
+        let start = reader.stream_position()?;
+        // both sections start with their number of entries
+        if (section_type == 4 || section_type == 5) && reader.read_u32::<LittleEndian>()? != 0 {
+            return Err(IoError(Error::new(
+                ErrorKind::InvalidData,
+                "Custom gates are not mapped to gadgets",
+            )));
+        }
+        let end = start
+            .checked_add(section_size)
+            .ok_or_else(|| IoError(Error::new(ErrorKind::InvalidData, "Invalid section size")))?;
+        reader.seek(SeekFrom::Start(end))?;
     }
-    Ok(witnesses)
+    Ok(())
+}
+
+// An element of `n8` little-endian bytes, reduced modulo M31, and whether it was already below
+// the modulus.
+fn read_element<R: Read>(reader: &mut R, n8: u32) -> IoResult<(u32, bool)> {
+    let mut bytes = vec![0u8; n8 as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(element_from_bytes(&bytes))
+}
+
+pub(crate) fn element_from_bytes(bytes: &[u8]) -> (u32, bool) {
+    let (value, fits) = m31_from_le_bytes(bytes);
+    (value.0, fits)
+}
+
+// Reads the signal names from a `.sym` file, whose lines are `label,wire,component,name` with a
+// wire of -1 for signals removed by the optimizer. A wire with several names (for example, a
+// signal passed to a subcomponent) keeps the first one.
+pub fn sym_read<R: BufRead>(reader: R) -> IoResult<FastHashMap<usize, String>> {
+    let invalid = || IoError(Error::new(ErrorKind::InvalidData, "Invalid symbol line"));
+
+    let mut names = FastHashMap::default();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields = line.splitn(4, ',').collect::<Vec<_>>();
+        if fields.len() != 4 {
+            return Err(invalid());
+        }
+        let wire = fields[1].parse::<i64>().map_err(|_| invalid())?;
+        if wire < 0 {
+            continue;
+        }
+        names
+            .entry(wire as usize)
+            .or_insert_with(|| fields[3].to_string());
+    }
+    Ok(names)
+}
+
+// Reads the signal names from a `.sym` file by label, the first column, which the wire-to-label
+// section of the `.r1cs` file refers to. Unlike `sym_read`, this also keeps the signals removed by
+// the optimizer.
+pub fn sym_read_labels<R: BufRead>(reader: R) -> IoResult<FastHashMap<u64, String>> {
+    let invalid = || IoError(Error::new(ErrorKind::InvalidData, "Invalid symbol line"));
+
+    let mut names = FastHashMap::default();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields = line.splitn(4, ',').collect::<Vec<_>>();
+        if fields.len() != 4 {
+            return Err(invalid());
+        }
+        let label = fields[0].parse::<u64>().map_err(|_| invalid())?;
+        names.entry(label).or_insert_with(|| fields[3].to_string());
+    }
+    Ok(names)
+}
+
+// The names of the wires, given their labels as in `ConstraintReader::wire_labels`, for
+// `generate_circuit_with_names`. A label without a name in `symbols` is kept as a number.
+pub fn wire_names(
+    wire_labels: &[u64],
+    symbols: &FastHashMap<u64, String>,
+) -> FastHashMap<usize, String> {
+    wire_labels
+        .iter()
+        .enumerate()
+        .map(|(wire, label)| {
+            let name = match symbols.get(label) {
+                Some(name) => name.clone(),
+                None => format!("label {}", label),
+            };
+            (wire, name)
+        })
+        .collect()
 }
 
 pub fn load_r1cs_and_witness(
     r1cs_data: impl Read + Seek,
     witness_data: impl Read + Seek,
 ) -> IoResult<CircomCircuit<FM31>> {
-    let r1cs_file = R1CSFile::<FM31>::new(r1cs_data)?;
-    let r1cs: R1CS<FM31> = r1cs_file.into();
+    Ok(load_r1cs_and_witness_with_outputs(r1cs_data, witness_data)?.0)
+}
+
+// Like `load_r1cs_and_witness`, also returning the number of public outputs from the header, to
+// be passed to `CircomStatement::new`.
+pub fn load_r1cs_and_witness_with_outputs(
+    r1cs_data: impl Read + Seek,
+    witness_data: impl Read + Seek,
+) -> IoResult<(CircomCircuit<FM31>, usize)> {
+    let (mut circuit, num_pub_out) = load_r1cs(r1cs_data)?;
+    circuit.witness = Some(witness_read(witness_data)?);
+    Ok((circuit, num_pub_out))
+}
 
-    let witness = witness_read(witness_data)?;
-    Ok(CircomCircuit::<FM31> {
+// Reads the constraints alone, to generate the circuit in `Mode::INDEX`, with the number of
+// public outputs from the header.
+pub fn load_r1cs(r1cs_data: impl Read + Seek) -> IoResult<(CircomCircuit<FM31>, usize)> {
+    load_r1cs_with(r1cs_data, ReductionPolicy::Strict)
+}
+
+// Under `ReductionPolicy::Strict`, a coefficient that is not a canonical M31 element is an error
+// naming its constraint and term. Under `ReductionPolicy::Reduce`, it is reduced, with a warning.
+//
+// The file is read once by `ConstraintReader`, which sees the bytes of every coefficient, rather
+// than by `R1CSFile`, whose field elements are already reduced.
+pub fn load_r1cs_with(
+    r1cs_data: impl Read + Seek,
+    policy: ReductionPolicy,
+) -> IoResult<(CircomCircuit<FM31>, usize)> {
+    let mut reader = ConstraintReader::new_with(r1cs_data, policy)?;
+    // as nothing else constrains their signals, custom gates must be converted with
+    // `generate_circuit_streaming_with_custom_gates` and a gadget for each of them
+    if !reader.custom_gates().is_empty() || !reader.custom_gate_applications().is_empty() {
+        return Err(IoError(Error::new(
+            ErrorKind::InvalidData,
+            "Custom gates are not mapped to gadgets",
+        )));
+    }
+
+    let header = *reader.header();
+    let wire_mapping = match reader.wire_labels() {
+        [] => None,
+        labels => Some(labels.iter().map(|&label| label as usize).collect()),
+    };
+    let mut constraints = Vec::with_capacity(header.num_constraints.min(1 << 20) as usize);
+    for constraint in reader.by_ref() {
+        let [a, b, c] = constraint?.map(|lc| {
+            lc.into_iter()
+                .map(|(coeff, wire)| (wire, coeff))
+                .collect::<Vec<_>>()
+        });
+        constraints.push((a, b, c));
+    }
+    reader.reducer().warn("coefficients");
+
+    let num_inputs = header.num_instance_variables();
+    let num_variables = header.num_wires as usize;
+    let r1cs = R1CS {
+        num_inputs,
+        num_aux: num_variables - num_inputs,
+        num_variables,
+        constraints,
+        wire_mapping,
+    };
+    let circuit = CircomCircuit::<FM31> {
         r1cs,
-        witness: Some(witness),
+        witness: None,
+    };
+    Ok((circuit, header.num_pub_out as usize))
+}
+
+// The statement of a converted circom circuit. circom puts the outputs first among the instance
+// signals, right after the constant one, and then the public inputs. Both are supplied to the
+// verifier the same way, through `input_maps`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircomStatement {
+    pub outputs: Vec<PublicWire>,
+    pub inputs: Vec<PublicWire>,
+}
+
+impl CircomStatement {
+    pub fn new(result: &ConversionResult, num_pub_out: usize) -> Self {
+        let signals = &result.public_wires[1..];
+        assert!(num_pub_out <= signals.len());
+        Self {
+            outputs: signals[..num_pub_out].to_vec(),
+            inputs: signals[num_pub_out..].to_vec(),
+        }
+    }
+
+    pub fn output_values(&self, circuit: &Circuit) -> Vec<M31> {
+        self.outputs
+            .iter()
+            .map(|w| circuit.get_output_wire(w.wire()))
+            .collect()
+    }
+
+    pub fn public_signals(&self, circuit: &Circuit) -> PublicSignals {
+        PublicSignals(
+            self.outputs
+                .iter()
+                .chain(self.inputs.iter())
+                .map(|w| circuit.get_output_wire(w.wire()))
+                .collect(),
+        )
+    }
+}
+
+// The public signals of a circom circuit as snarkjs writes them to `public.json`: the outputs and
+// then the public inputs, as decimal strings, without the constant one. The verifier takes the
+// same values after the constant one, as they are the instance signals of the statement.
+//
+// snarkjs writes elements of the field of the circuit, which for the default BN254 go up to 254
+// bits. Only the signals of circuits over M31 are below its modulus, so the others are rejected
+// rather than reduced, which would change the statement.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PublicSignals(#[serde(serialize_with = "m31_decimal_vec::serialize")] pub Vec<M31>);
+
+impl<'de> Deserialize<'de> for PublicSignals {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        m31_decimal_vec::deserialize(deserializer)
+            .map(Self)
+            .map_err(|err| {
+                serde::de::Error::custom(format!(
+                    "{}, as public signals must come from a circuit over M31 rather than BN254",
+                    err
+                ))
+            })
+    }
+}
+
+impl PublicSignals {
+    // Drops the constant one that starts the values given to the verifier.
+    pub fn from_values(values: &[M31]) -> Option<Self> {
+        match values.split_first() {
+            Some((first, signals)) if first.is_one() => Some(Self(signals.to_vec())),
+            _ => None,
+        }
+    }
+
+    // The values in the order of the statement, as the verifier takes them.
+    pub fn to_values(&self) -> Vec<M31> {
+        std::iter::once(M31::one())
+            .chain(self.0.iter().copied())
+            .collect()
+    }
+}
+
+// Reads an R1CS generated for another prime field, such as BN254, to be lowered to M31 through
+// field emulation. Without a witness, the circuit can only be generated in `Mode::INDEX`.
+pub fn load_emulated_r1cs<F: PrimeField>(r1cs_data: impl Read + Seek) -> IoResult<EmulatedR1CS<F>> {
+    let r1cs: R1CS<F> = read_r1cs_file::<F, _>(r1cs_data)?.into();
+
+    let swap = |terms: Vec<(usize, F)>| {
+        terms
+            .into_iter()
+            .map(|(idx, coeff)| (coeff, idx))
+            .collect::<Vec<_>>()
+    };
+    Ok(EmulatedR1CS {
+        num_instance_variables: r1cs.num_inputs,
+        num_variables: r1cs.num_variables,
+        constraints: r1cs
+            .constraints
+            .into_iter()
+            .map(|(a, b, c)| [swap(a), swap(b), swap(c)])
+            .collect(),
+        assignment: None,
     })
 }
 
+pub fn load_emulated_r1cs_and_witness<F: PrimeField>(
+    r1cs_data: impl Read + Seek,
+    witness_data: impl Read,
+) -> IoResult<EmulatedR1CS<F>> {
+    let mut r1cs = load_emulated_r1cs(r1cs_data)?;
+    let witness = witness_read_field::<F, _>(witness_data)?;
+    if witness.len() != r1cs.num_variables {
+        return Err(IoError(Error::new(
+            ErrorKind::InvalidData,
+            "The witness does not match the number of wires",
+        )));
+    }
+    r1cs.assignment = Some(witness);
+    Ok(r1cs)
+}
+
 #[cfg(test)]
 mod test {
     use crate::circuit::Mode;
-    use crate::from_r1cs::circom::load_r1cs_and_witness;
-    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::field::{to_m31, ReductionPolicy, FM31};
+    use crate::from_r1cs::circom::{
+        load_r1cs, load_r1cs_and_witness, load_r1cs_and_witness_with_outputs, load_r1cs_with,
+        sym_read, witness_read, witness_read_field, witness_read_with, witness_write,
+        witness_write_with, CircomStatement, PublicSignals,
+    };
+    use crate::from_r1cs::r1cs_constraint_processor::{
+        generate_circuit, generate_circuit_with_names,
+    };
+    use ark_circom::{CircomCircuit, R1CS};
+    use ark_ff::{BigInteger, PrimeField};
     use ark_std::io::Cursor;
-    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::{M31, P};
 
     #[test]
     fn test_multiplier2() {
@@ -121,11 +554,241 @@ mod test {
         let circom_circuit =
             load_r1cs_and_witness(Cursor::new(r1cs), Cursor::new(witness)).unwrap();
 
-        let circuit = generate_circuit(circom_circuit.clone(), Mode::PROVE).unwrap();
+        let circuit = generate_circuit(circom_circuit.clone(), Mode::PROVE)
+            .unwrap()
+            .circuit;
         assert!(circuit.is_constraint_satisfied());
         assert_eq!(circuit.num_rows, 11);
 
-        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
-        assert!(circuit.is_logup_satisfied(&mut prng, &circuit.input_maps));
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_multiplier2_statement() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+
+        let (circom_circuit, num_pub_out) =
+            load_r1cs_and_witness_with_outputs(Cursor::new(r1cs), Cursor::new(witness)).unwrap();
+        assert_eq!(num_pub_out, 1);
+
+        let result = generate_circuit(circom_circuit, Mode::PROVE).unwrap();
+        let statement = CircomStatement::new(&result, num_pub_out);
+        assert_eq!(statement.outputs.len(), 1);
+        assert!(statement.inputs.is_empty());
+
+        let values = witness_read(Cursor::new(witness)).unwrap();
+        assert_eq!(
+            statement.output_values(&result.circuit),
+            vec![to_m31(&values[1])]
+        );
+
+        let (circom_circuit, _) = load_r1cs(Cursor::new(r1cs)).unwrap();
+        let index = generate_circuit(circom_circuit, Mode::INDEX).unwrap();
+        assert_eq!(index.circuit.index(), result.circuit.index());
+    }
+
+    // The file with the first coefficient of A increased by the modulus, which R1CSFile would read
+    // as the same element.
+    pub(crate) fn with_non_canonical_coefficient(r1cs: &[u8]) -> Vec<u8> {
+        let word = |at: usize| u32::from_le_bytes(r1cs[at..at + 4].try_into().unwrap()) as usize;
+        let mut at = 12;
+        let (mut n8, mut constraints) = (0, 0);
+        for _ in 0..word(8) {
+            let size = u64::from_le_bytes(r1cs[at + 4..at + 12].try_into().unwrap()) as usize;
+            match word(at) {
+                1 => n8 = word(at + 12),
+                2 => constraints = at + 12,
+                _ => {}
+            }
+            at += 12 + size;
+        }
+        // the number of terms and the wire come before the coefficient
+        let coeff = constraints + 8;
+        let mut data = r1cs.to_vec();
+        let value = u64::from_le_bytes(r1cs[coeff..coeff + 8].try_into().unwrap()) + P as u64;
+        data[coeff..coeff + 8].copy_from_slice(&value.to_le_bytes());
+        assert_eq!(n8, 8);
+        data
+    }
+
+    #[test]
+    fn test_load_r1cs_non_canonical() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let data = with_non_canonical_coefficient(r1cs);
+
+        let err = load_r1cs(Cursor::new(&data)).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("Coefficient 0 of A in constraint 0 is not an M31 element"));
+        let (reduced, _) = load_r1cs_with(Cursor::new(&data), ReductionPolicy::Reduce).unwrap();
+        let (expected, _) = load_r1cs(Cursor::new(r1cs)).unwrap();
+        assert_eq!(reduced.r1cs.constraints, expected.r1cs.constraints);
+    }
+
+    #[test]
+    fn test_public_signals() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+
+        let (circom_circuit, num_pub_out) =
+            load_r1cs_and_witness_with_outputs(Cursor::new(r1cs), Cursor::new(witness)).unwrap();
+        let result = generate_circuit(circom_circuit, Mode::PROVE).unwrap();
+        let statement = CircomStatement::new(&result, num_pub_out);
+        let signals = statement.public_signals(&result.circuit);
+        let values = result.circuit.input_maps.values();
+        assert_eq!(signals.to_values(), values);
+        assert_eq!(PublicSignals::from_values(&values), Some(signals.clone()));
+        assert_eq!(PublicSignals::from_values(&values[1..]), None);
+
+        let json = serde_json::to_string(&signals).unwrap();
+        assert_eq!(json, format!(r#"["{}"]"#, values[1].0));
+        let decoded: PublicSignals = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, signals);
+        // as snarkjs writes it
+        let decoded: PublicSignals = serde_json::from_str("[\n \"33\",\n \"3\"\n]").unwrap();
+        assert_eq!(decoded.0, vec![M31::from(33), M31::from(3)]);
+        assert!(serde_json::from_str::<PublicSignals>(r#"["2147483647"]"#).is_err());
+        // the largest element of BN254, as snarkjs writes `-1` for circuits over it
+        let bn254 =
+            r#"["21888242871839275222246405745257275088548364400416034343698204186575808495616"]"#;
+        let err = serde_json::from_str::<PublicSignals>(bn254).unwrap_err();
+        assert!(err.to_string().contains("rather than BN254"));
+    }
+
+    // circom puts the outputs before the public inputs
+    #[test]
+    fn test_public_signals_order() {
+        let fm31 = |v: u32| FM31::from(v);
+        // out = x * y, with `out` an output, `x` a public input and `y` private
+        let r1cs = R1CS {
+            num_inputs: 3,
+            num_aux: 1,
+            num_variables: 4,
+            constraints: vec![(vec![(2, fm31(1))], vec![(3, fm31(1))], vec![(1, fm31(1))])],
+            wire_mapping: None,
+        };
+        let witness = [1, 21, 3, 7].map(fm31).to_vec();
+        let circom_circuit = CircomCircuit {
+            r1cs,
+            witness: Some(witness),
+        };
+        let result = generate_circuit(circom_circuit, Mode::PROVE).unwrap();
+        let statement = CircomStatement::new(&result, 1);
+        assert_eq!(statement.output_values(&result.circuit), [M31::from(21)]);
+
+        let signals = statement.public_signals(&result.circuit);
+        assert_eq!(signals.0, [M31::from(21), M31::from(3)]);
+        assert_eq!(signals.to_values(), result.circuit.input_maps.values());
+        assert_eq!(serde_json::to_string(&signals).unwrap(), r#"["21","3"]"#);
+    }
+
+    #[test]
+    fn test_multiplier2_sym() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+        let sym = include_bytes!("./multiplier2.sym");
+
+        let names = sym_read(Cursor::new(sym)).unwrap();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[&1], "main.c");
+
+        let circom_circuit =
+            load_r1cs_and_witness(Cursor::new(r1cs), Cursor::new(witness)).unwrap();
+        let circuit = generate_circuit_with_names(circom_circuit, Mode::PROVE, names)
+            .unwrap()
+            .circuit;
+        assert!(circuit.is_constraint_satisfied());
+
+        if cfg!(feature = "debug") {
+            let labels = (0..circuit.num_rows)
+                .filter_map(|row| circuit.get_label(row))
+                .collect::<Vec<_>>();
+            for name in ["main.a", "main.b", "main.c"] {
+                assert!(labels.iter().any(|label| label == name), "{}", name);
+            }
+        }
+
+        assert!(sym_read(Cursor::new("1,1,0")).is_err());
+        assert!(sym_read(Cursor::new("1,x,0,main.c")).is_err());
+        assert!(sym_read(Cursor::new("1,-1,0,main.c\n")).unwrap().is_empty());
+    }
+
+    fn encode_wtns(n8: usize, prime: u64, values: &[u64]) -> Vec<u8> {
+        let element = |v: u64| {
+            let mut bytes = v.to_le_bytes().to_vec();
+            bytes.resize(n8, 0);
+            bytes
+        };
+
+        let mut data = b"wtns".to_vec();
+        for v in [2u32, 2, 1] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&(8 + n8 as u64).to_le_bytes());
+        data.extend_from_slice(&(n8 as u32).to_le_bytes());
+        data.extend(element(prime));
+        data.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&((n8 * values.len()) as u64).to_le_bytes());
+        for &v in values {
+            data.extend(element(v));
+        }
+        data
+    }
+
+    #[test]
+    fn test_witness_write() {
+        let data = include_bytes!("./output.wtns");
+        let values = witness_read(Cursor::new(data)).unwrap();
+
+        let mut written = vec![];
+        witness_write(&mut written, &values).unwrap();
+        assert_eq!(written, data.to_vec());
+
+        for n8 in [4, 32] {
+            let mut written = vec![];
+            witness_write_with(&mut written, &values, n8).unwrap();
+            assert_eq!(witness_read(Cursor::new(written)).unwrap(), values);
+        }
+        assert!(witness_write_with(vec![], &values, 16).is_err());
+    }
+
+    #[test]
+    fn test_witness_encodings() {
+        let expected = witness_read(Cursor::new(include_bytes!("./output.wtns"))).unwrap();
+        let values = [1, 33, 3, 11];
+        for n8 in [4, 8, 32] {
+            let data = encode_wtns(n8, P as u64, &values);
+            assert_eq!(witness_read(Cursor::new(data)).unwrap(), expected);
+        }
+        assert!(witness_read(Cursor::new(encode_wtns(16, P as u64, &values))).is_err());
+
+        // out of range values, and a different modulus
+        let data = encode_wtns(32, P as u64, &[1, P as u64 + 5]);
+        let err = witness_read(Cursor::new(data.clone())).unwrap_err();
+        assert!(err.to_string().contains("Witness 1 is not an M31 element"));
+        assert_eq!(
+            witness_read_with(Cursor::new(data), ReductionPolicy::Reduce).unwrap(),
+            vec![FM31::from(1u32), FM31::from(5u32)]
+        );
+        let data = encode_wtns(8, (1 << 61) - 1, &[1, 2]);
+        assert!(witness_read(Cursor::new(data.clone())).is_err());
+        assert!(witness_read_with(Cursor::new(data), ReductionPolicy::Reduce).is_ok());
+    }
+
+    #[test]
+    fn test_witness_read_field() {
+        let mut data = encode_wtns(32, P as u64, &[1, 5]);
+        data[28..60].copy_from_slice(&ark_bn254::Fr::MODULUS.to_bytes_le());
+
+        assert_eq!(
+            witness_read_field::<ark_bn254::Fr, _>(Cursor::new(&data)).unwrap(),
+            vec![ark_bn254::Fr::from(1u64), ark_bn254::Fr::from(5u64)]
+        );
+        assert!(witness_read(Cursor::new(&data)).is_err());
+
+        let data = encode_wtns(32, P as u64, &[1, 5]);
+        assert!(witness_read_field::<ark_bn254::Fr, _>(Cursor::new(&data)).is_err());
     }
 }
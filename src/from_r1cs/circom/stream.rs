@@ -0,0 +1,603 @@
+use crate::circuit::{Circuit, FastHashMap};
+use crate::field::{from_m31, IntoM31Iter, M31Reducer, ReductionPolicy, FM31};
+use crate::from_r1cs::circom::custom::{CustomGate, CustomGateApplication, CustomGateMap};
+use crate::from_r1cs::circom::mapped::MappedWitness;
+use crate::from_r1cs::circom::{m31_modulus, read_element, IoResult};
+use crate::from_r1cs::r1cs_constraint_processor::{
+    process_r1cs_constraint, Assignments, ConversionResult, OnDemandAllocator,
+};
+use ark_ff::Zero;
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt};
+use stwo_prover::core::fields::m31::M31;
+
+// Reads the constraints of a `.r1cs` file one at a time instead of loading the whole file like
+// `R1CSFile::new`, so that converting a multi-gigabyte file only holds the circuit being built.
+
+pub type LinearCombination = Vec<(FM31, usize)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct R1CSHeader {
+    pub n8: u32,
+    pub num_wires: u32,
+    pub num_pub_out: u32,
+    pub num_pub_in: u32,
+    pub num_prv_in: u32,
+    pub num_labels: u64,
+    pub num_constraints: u32,
+}
+
+impl R1CSHeader {
+    // The constant one followed by the public outputs and inputs.
+    pub fn num_instance_variables(&self) -> usize {
+        1 + self.num_pub_out as usize + self.num_pub_in as usize
+    }
+}
+
+fn invalid_data(msg: &str) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::InvalidData, msg))
+}
+
+pub struct ConstraintReader<R> {
+    reader: R,
+    header: R1CSHeader,
+    custom_gates: Vec<CustomGate>,
+    custom_gate_applications: Vec<CustomGateApplication>,
+    wire_labels: Vec<u64>,
+    reducer: M31Reducer,
+    remaining: u32,
+}
+
+impl<R: Read + Seek> ConstraintReader<R> {
+    // Reads the header and leaves the reader at the first constraint. The sections can come in
+    // any order, so the file is first scanned for where they start.
+    pub fn new(reader: R) -> IoResult<Self> {
+        Self::new_with(reader, ReductionPolicy::Strict)
+    }
+
+    // Under `ReductionPolicy::Strict`, the file must be generated for M31 and a coefficient that
+    // is not a canonical M31 element is an error naming its constraint and term. Under
+    // `ReductionPolicy::Reduce`, the coefficients are reduced and counted by `reducer`.
+    pub fn new_with(mut reader: R, policy: ReductionPolicy) -> IoResult<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *b"r1cs" {
+            return Err(invalid_data("Invalid magic number"));
+        }
+
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != 1 {
+            return Err(invalid_data("Unsupported version"));
+        }
+
+        let num_sections = reader.read_u32::<LittleEndian>()?;
+        let mut header_start = None;
+        let mut constraints_start = None;
+        let mut labels_start = None;
+        let mut custom_gates_start = None;
+        let mut applications_start = None;
+        for _ in 0..num_sections {
+            let section_type = reader.read_u32::<LittleEndian>()?;
+            let section_size = reader.read_u64::<LittleEndian>()?;
+            let start = reader.stream_position()?;
+            match section_type {
+                1 => header_start = Some(start),
+                2 => constraints_start = Some(start),
+                3 => labels_start = Some(start),
+                4 => custom_gates_start = Some(start),
+                5 => applications_start = Some(start),
+                _ => {}
+            }
+            let end = start
+                .checked_add(section_size)
+                .ok_or_else(|| invalid_data("Invalid section size"))?;
+            reader.seek(SeekFrom::Start(end))?;
+        }
+
+        let header_start = header_start.ok_or_else(|| invalid_data("Missing header section"))?;
+        reader.seek(SeekFrom::Start(header_start))?;
+
+        let n8 = reader.read_u32::<LittleEndian>()?;
+        if ![4, 8, 32].contains(&n8) {
+            return Err(invalid_data("Unexpected n8"));
+        }
+        let mut prime = vec![0u8; n8 as usize];
+        reader.read_exact(&mut prime)?;
+        if policy == ReductionPolicy::Strict && prime != m31_modulus(n8) {
+            return Err(invalid_data("R1CS is not generated for M31"));
+        }
+
+        let header = R1CSHeader {
+            n8,
+            num_wires: reader.read_u32::<LittleEndian>()?,
+            num_pub_out: reader.read_u32::<LittleEndian>()?,
+            num_pub_in: reader.read_u32::<LittleEndian>()?,
+            num_prv_in: reader.read_u32::<LittleEndian>()?,
+            num_labels: reader.read_u64::<LittleEndian>()?,
+            num_constraints: reader.read_u32::<LittleEndian>()?,
+        };
+        if header.num_instance_variables() > header.num_wires as usize {
+            return Err(invalid_data("Too many public signals"));
+        }
+
+        let wire_labels = match labels_start {
+            Some(start) => {
+                reader.seek(SeekFrom::Start(start))?;
+                let mut labels = Vec::with_capacity(header.num_wires.min(1 << 20) as usize);
+                for _ in 0..header.num_wires {
+                    labels.push(reader.read_u64::<LittleEndian>()?);
+                }
+                labels
+            }
+            None => vec![],
+        };
+        let custom_gates = match custom_gates_start {
+            Some(start) => {
+                reader.seek(SeekFrom::Start(start))?;
+                read_custom_gates(&mut reader, n8)?
+            }
+            None => vec![],
+        };
+        let custom_gate_applications = match applications_start {
+            Some(start) => {
+                reader.seek(SeekFrom::Start(start))?;
+                read_custom_gate_applications(&mut reader, &header, custom_gates.len())?
+            }
+            None => vec![],
+        };
+
+        let constraints_start =
+            constraints_start.ok_or_else(|| invalid_data("Missing constraints section"))?;
+        reader.seek(SeekFrom::Start(constraints_start))?;
+
+        Ok(Self {
+            reader,
+            header,
+            custom_gates,
+            custom_gate_applications,
+            wire_labels,
+            reducer: M31Reducer::new(policy),
+            remaining: header.num_constraints,
+        })
+    }
+
+    pub fn custom_gates(&self) -> &[CustomGate] {
+        &self.custom_gates
+    }
+
+    pub fn custom_gate_applications(&self) -> &[CustomGateApplication] {
+        &self.custom_gate_applications
+    }
+
+    // The label of every wire, from the wire-to-label section, or nothing if there is none.
+    pub fn wire_labels(&self) -> &[u64] {
+        &self.wire_labels
+    }
+
+    pub fn header(&self) -> &R1CSHeader {
+        &self.header
+    }
+
+    // How many of the coefficients read so far were reduced.
+    pub fn reducer(&self) -> &M31Reducer {
+        &self.reducer
+    }
+
+    // Terms are sorted by wire, as in the R1CS matrices of arkworks.
+    fn read_linear_combination(
+        &mut self,
+        constraint: u32,
+        matrix: char,
+    ) -> IoResult<LinearCombination> {
+        let num_terms = self.reader.read_u32::<LittleEndian>()?;
+        if num_terms > self.header.num_wires {
+            return Err(invalid_data("Too many terms"));
+        }
+
+        let mut bytes = vec![0u8; self.header.n8 as usize];
+        let mut terms = Vec::with_capacity(num_terms as usize);
+        for term in 0..num_terms {
+            let wire = self.reader.read_u32::<LittleEndian>()?;
+            if wire >= self.header.num_wires {
+                return Err(invalid_data("Wire out of range"));
+            }
+            self.reader.read_exact(&mut bytes)?;
+            let coeff = self.reducer.convert_le_bytes(&bytes).ok_or_else(|| {
+                invalid_data(&format!(
+                    "Coefficient {} of {} in constraint {} is not an M31 element",
+                    term, matrix, constraint
+                ))
+            })?;
+            terms.push((from_m31(coeff), wire as usize));
+        }
+        terms.sort_unstable_by_key(|&(_, wire)| wire);
+        Ok(terms)
+    }
+
+    fn read_constraint(&mut self, constraint: u32) -> IoResult<[LinearCombination; 3]> {
+        Ok([
+            self.read_linear_combination(constraint, 'A')?,
+            self.read_linear_combination(constraint, 'B')?,
+            self.read_linear_combination(constraint, 'C')?,
+        ])
+    }
+}
+
+// The name of every custom gate as a null-terminated string, followed by its parameters as
+// field elements.
+fn read_custom_gates<R: Read>(reader: &mut R, n8: u32) -> IoResult<Vec<CustomGate>> {
+    let num_gates = reader.read_u32::<LittleEndian>()?;
+    let mut gates = Vec::with_capacity(num_gates.min(1 << 10) as usize);
+    for _ in 0..num_gates {
+        let mut name = vec![];
+        loop {
+            match reader.read_u8()? {
+                0 => break,
+                b => name.push(b),
+            }
+        }
+        let name = String::from_utf8(name).map_err(|_| invalid_data("Invalid custom gate name"))?;
+
+        let num_parameters = reader.read_u32::<LittleEndian>()?;
+        let mut parameters = Vec::with_capacity(num_parameters.min(1 << 10) as usize);
+        for _ in 0..num_parameters {
+            let (parameter, fits) = read_element(reader, n8)?;
+            if !fits {
+                return Err(invalid_data("Parameter is not an M31 element"));
+            }
+            parameters.push(M31::from(parameter));
+        }
+        gates.push(CustomGate { name, parameters });
+    }
+    Ok(gates)
+}
+
+// The index of the gate and the wires of its signals, which circom writes as u64s.
+fn read_custom_gate_applications<R: Read>(
+    reader: &mut R,
+    header: &R1CSHeader,
+    num_gates: usize,
+) -> IoResult<Vec<CustomGateApplication>> {
+    let num_applications = reader.read_u32::<LittleEndian>()?;
+    let mut applications = Vec::with_capacity(num_applications.min(1 << 20) as usize);
+    for _ in 0..num_applications {
+        let gate = reader.read_u32::<LittleEndian>()? as usize;
+        if gate >= num_gates {
+            return Err(invalid_data("Custom gate out of range"));
+        }
+
+        let num_signals = reader.read_u32::<LittleEndian>()?;
+        if num_signals > header.num_wires {
+            return Err(invalid_data("Too many signals"));
+        }
+        let mut signals = Vec::with_capacity(num_signals as usize);
+        for _ in 0..num_signals {
+            let wire = reader.read_u64::<LittleEndian>()?;
+            if wire >= header.num_wires as u64 {
+                return Err(invalid_data("Wire out of range"));
+            }
+            signals.push(wire as usize);
+        }
+        applications.push(CustomGateApplication { gate, signals });
+    }
+    Ok(applications)
+}
+
+impl<R: Read + Seek> Iterator for ConstraintReader<R> {
+    type Item = IoResult<[LinearCombination; 3]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let constraint = self.read_constraint(self.header.num_constraints - self.remaining - 1);
+        if constraint.is_err() {
+            self.remaining = 0;
+        }
+        Some(constraint)
+    }
+}
+
+// Converts the constraints as they are read, like `generate_circuit` on a `CircomCircuit`. The
+// witness is indexed by wire, as read from a `.wtns` file; without one, the circuit is generated
+// as in `Mode::INDEX`.
+pub fn generate_circuit_streaming<R: Read + Seek>(
+    r1cs_data: R,
+    witness: Option<&[FM31]>,
+) -> IoResult<Circuit> {
+    generate_circuit_streaming_from(r1cs_data, witness.map(|w| w.iter().into_m31()))
+}
+
+// Like `generate_circuit_streaming`, with the witness values converted as they are collected.
+pub fn generate_circuit_streaming_from<R, I>(r1cs_data: R, witness: Option<I>) -> IoResult<Circuit>
+where
+    R: Read + Seek,
+    I: ExactSizeIterator<Item = M31>,
+{
+    convert(r1cs_data, collect(witness), &CustomGateMap::new(), None).map(|result| result.circuit)
+}
+
+// Like `generate_circuit_streaming`, with the witness values read from the mapped file as the
+// wires are allocated, so that the witness is never copied out of the mapping.
+pub fn generate_circuit_streaming_mapped<R: Read + Seek>(
+    r1cs_data: R,
+    witness: MappedWitness,
+) -> IoResult<Circuit> {
+    convert(r1cs_data, Some(witness.into()), &CustomGateMap::new(), None)
+        .map(|result| result.circuit)
+}
+
+// Like `generate_circuit_streaming`, labeling the wires with the names of their signals, which
+// `symbols` gives by label as read by `sym_read_labels`.
+pub fn generate_circuit_streaming_with_labels<R: Read + Seek>(
+    r1cs_data: R,
+    witness: Option<&[FM31]>,
+    symbols: &FastHashMap<u64, String>,
+) -> IoResult<Circuit> {
+    let witness = witness.map(|w| w.iter().into_m31());
+    convert(
+        r1cs_data,
+        collect(witness),
+        &CustomGateMap::new(),
+        Some(symbols),
+    )
+    .map(|result| result.circuit)
+}
+
+// Like `generate_circuit_streaming_from`, for files with custom gates, each of which must be in
+// `custom_gates`.
+pub fn generate_circuit_streaming_with_custom_gates<R, I>(
+    r1cs_data: R,
+    witness: Option<I>,
+    custom_gates: &CustomGateMap,
+) -> IoResult<Circuit>
+where
+    R: Read + Seek,
+    I: ExactSizeIterator<Item = M31>,
+{
+    convert(r1cs_data, collect(witness), custom_gates, None).map(|result| result.circuit)
+}
+
+// Like `generate_circuit_streaming` without a witness, keeping where the variables ended up,
+// which places the values of later witnesses in the index.
+pub fn generate_index_streaming<R: Read + Seek>(r1cs_data: R) -> IoResult<ConversionResult> {
+    convert(r1cs_data, None, &CustomGateMap::new(), None)
+}
+
+fn collect<I: ExactSizeIterator<Item = M31>>(witness: Option<I>) -> Option<Assignments> {
+    witness.map(|witness| witness.collect::<Vec<_>>().into())
+}
+
+fn convert<R: Read + Seek>(
+    r1cs_data: R,
+    witness: Option<Assignments>,
+    custom_gates: &CustomGateMap,
+    symbols: Option<&FastHashMap<u64, String>>,
+) -> IoResult<ConversionResult> {
+    let constraints = ConstraintReader::new(r1cs_data)?;
+    let header = *constraints.header();
+
+    let num_wires = header.num_wires as usize;
+    let assignments = match witness {
+        Some(witness) => {
+            if witness.len() != num_wires {
+                return Err(invalid_data(
+                    "The witness does not match the number of wires",
+                ));
+            }
+            witness
+        }
+        None => vec![M31::zero(); num_wires].into(),
+    };
+
+    let num_instance = header.num_instance_variables();
+    let mut allocator = OnDemandAllocator::new(assignments, num_instance);
+    if let Some(symbols) = symbols {
+        allocator = allocator.with_names(wire_names(constraints.wire_labels(), symbols));
+    }
+
+    let mut output = Circuit::new();
+    for i in 0..num_instance {
+        allocator.get(&mut output, i);
+    }
+    custom_gates.apply(
+        &mut output,
+        &mut allocator,
+        constraints.custom_gates(),
+        constraints.custom_gate_applications(),
+    )?;
+
+    for constraint in constraints {
+        let [a, b, c] = constraint?;
+        process_r1cs_constraint(&mut output, &mut allocator, &a, &b, &c);
+    }
+
+    Ok(ConversionResult::new(output, allocator))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::gadget::Gadget;
+    use crate::circuit::Mode;
+    use crate::from_r1cs::circom::custom::{CustomGate, CustomGateApplication, CustomGateMap};
+    use crate::from_r1cs::circom::stream::{
+        generate_circuit_streaming, generate_circuit_streaming_with_custom_gates,
+        generate_circuit_streaming_with_labels, ConstraintReader,
+    };
+    use crate::from_r1cs::circom::{
+        load_r1cs, load_r1cs_and_witness, m31_modulus, sym_read_labels, wire_names, witness_read,
+    };
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use ark_std::io::Cursor;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_streaming_multiplier2() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+
+        let reader = ConstraintReader::new(Cursor::new(r1cs)).unwrap();
+        assert_eq!(reader.header().num_wires, 4);
+        assert_eq!(reader.header().num_instance_variables(), 2);
+        assert_eq!(reader.count(), 1);
+
+        let values = witness_read(Cursor::new(witness)).unwrap();
+        let circuit =
+            generate_circuit_streaming(Cursor::new(r1cs), Some(values.as_slice())).unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        let circom_circuit =
+            load_r1cs_and_witness(Cursor::new(r1cs), Cursor::new(witness)).unwrap();
+        let expected = generate_circuit(circom_circuit, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert_eq!(circuit.index(), expected.index());
+        assert_eq!(circuit.witness(), expected.witness());
+
+        let index = generate_circuit_streaming(Cursor::new(r1cs), None).unwrap();
+        assert_eq!(index.index(), expected.index());
+
+        assert!(generate_circuit_streaming(Cursor::new(r1cs), Some(&values[1..])).is_err());
+        assert!(ConstraintReader::new(Cursor::new(&r1cs[..40])).is_err());
+    }
+
+    #[test]
+    fn test_streaming_labels() {
+        let r1cs = include_bytes!("./multiplier2.r1cs");
+        let witness = include_bytes!("./output.wtns");
+        let sym = include_bytes!("./multiplier2.sym");
+
+        let reader = ConstraintReader::new(Cursor::new(r1cs)).unwrap();
+        assert_eq!(reader.wire_labels(), &[0, 1, 2, 3]);
+
+        let symbols = sym_read_labels(Cursor::new(sym)).unwrap();
+        let names = wire_names(reader.wire_labels(), &symbols);
+        assert_eq!(names[&0], "label 0");
+        assert_eq!(names[&2], "main.a");
+
+        let values = witness_read(Cursor::new(witness)).unwrap();
+        let circuit = generate_circuit_streaming_with_labels(
+            Cursor::new(r1cs),
+            Some(values.as_slice()),
+            &symbols,
+        )
+        .unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        if cfg!(feature = "debug") {
+            let labels = (0..circuit.num_rows)
+                .filter_map(|row| circuit.get_label(row))
+                .collect::<Vec<_>>();
+            for name in ["main.a", "main.b", "main.c"] {
+                assert!(labels.iter().any(|label| label == name), "{}", name);
+            }
+        }
+
+        assert!(sym_read_labels(Cursor::new("x,1,0,main.c")).is_err());
+        assert_eq!(
+            sym_read_labels(Cursor::new("4,-1,0,main.d\n")).unwrap()[&4],
+            "main.d"
+        );
+    }
+
+    // (y + 1) * 1 = z with x public, and a custom gate `MulConst(2)` for y = 2 * x * x, with
+    // elements of 4 bytes
+    fn custom_gate_r1cs() -> Vec<u8> {
+        let mut data = b"r1cs".to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        let mut section = |section_type: u32, body: Vec<u8>| {
+            data.extend_from_slice(&section_type.to_le_bytes());
+            data.extend_from_slice(&(body.len() as u64).to_le_bytes());
+            data.extend_from_slice(&body);
+        };
+        let words = |words: &[u32]| {
+            words
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+
+        let mut header = words(&[4]);
+        header.extend_from_slice(&m31_modulus(4));
+        header.extend_from_slice(&words(&[4, 0, 1, 0, 4, 0, 1]));
+        section(1, header);
+        section(2, words(&[2, 2, 1, 0, 1, 1, 0, 1, 1, 3, 1]));
+
+        let mut gates = words(&[1]);
+        gates.extend_from_slice(b"MulConst\0");
+        gates.extend_from_slice(&words(&[1, 2]));
+        section(4, gates);
+        section(5, words(&[1, 0, 2, 1, 0, 2, 0]));
+        data
+    }
+
+    #[test]
+    fn test_custom_gates() {
+        let r1cs = custom_gate_r1cs();
+        let reader = ConstraintReader::new(Cursor::new(&r1cs)).unwrap();
+        assert_eq!(
+            reader.custom_gates(),
+            &[CustomGate {
+                name: "MulConst".to_string(),
+                parameters: vec![M31::from(2u32)],
+            }]
+        );
+        assert_eq!(
+            reader.custom_gate_applications(),
+            &[CustomGateApplication {
+                gate: 0,
+                signals: vec![1, 2],
+            }]
+        );
+        assert_eq!(reader.count(), 1);
+
+        let custom_gates = CustomGateMap::new().register("MulConst", |parameters| {
+            let k = parameters[0];
+            Gadget::new(&[M31::from(0u32)], |circuit, inputs| {
+                let square = circuit.mul(inputs[0], inputs[0]);
+                vec![circuit.mul_by_constant(square, k)]
+            })
+        });
+        let witness = |values: [u32; 4]| Some(values.map(M31::from).into_iter());
+
+        let circuit = generate_circuit_streaming_with_custom_gates(
+            Cursor::new(&r1cs),
+            witness([1, 3, 18, 19]),
+            &custom_gates,
+        )
+        .unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        let index = generate_circuit_streaming_with_custom_gates(
+            Cursor::new(&r1cs),
+            None::<std::vec::IntoIter<M31>>,
+            &custom_gates,
+        )
+        .unwrap();
+        assert_eq!(index.index(), circuit.index());
+
+        // the R1CS alone holds with y = 17, but not the custom gate
+        let circuit = generate_circuit_streaming_with_custom_gates(
+            Cursor::new(&r1cs),
+            witness([1, 3, 17, 18]),
+            &custom_gates,
+        )
+        .unwrap();
+        assert!(!circuit.is_constraint_satisfied());
+
+        assert!(generate_circuit_streaming(Cursor::new(&r1cs), None).is_err());
+        // the loaders that go through `R1CSFile` would drop the gates
+        assert!(load_r1cs(Cursor::new(&r1cs)).is_err());
+        let other = CustomGateMap::new().register("Other", |_| Gadget::new(&[], |_, _| vec![]));
+        assert!(generate_circuit_streaming_with_custom_gates(
+            Cursor::new(&r1cs),
+            witness([1, 3, 18, 19]),
+            &other,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::field::{to_m31, ReductionPolicy, FM31};
+use crate::from_r1cs::circom::{load_r1cs_with, IoResult};
+use ark_circom::CircomCircuit;
+use ark_serialize::SerializationError::IoError;
+use ark_std::io::{Error, ErrorKind, Read, Seek};
+use serde_json::Value;
+use stwo_prover::core::fields::m31::P;
+use wasmtime::{Engine, Instance, Linker, Module, Store, TypedFunc};
+
+// Runs the `.wasm` witness calculator that circom generates with `--wasm`, so that no separate
+// snarkjs step is needed to produce the `.wtns` file. The circuit must be compiled for M31.
+//
+// This follows the protocol of circom's `witness_calculator.js`: every input signal is addressed
+// by the 64-bit FNV-1a hash of its name, and field elements go through a shared memory of
+// `getFieldNumLen32` words.
+pub struct WitnessCalculator {
+    store: Store<()>,
+    instance: Instance,
+    n32: u32,
+}
+
+fn runtime_error<E: ToString>(err: E) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::Other, err.to_string()))
+}
+
+fn invalid_data(msg: &str) -> ark_serialize::SerializationError {
+    IoError(Error::new(ErrorKind::InvalidData, msg))
+}
+
+pub(crate) fn fnv_hash(name: &str) -> (i32, i32) {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in name.as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    ((hash >> 32) as i32, hash as i32)
+}
+
+// Signal values in the input map are numbers or decimal strings, possibly nested in arrays for
+// array signals, and are flattened in row-major order.
+pub(crate) fn flatten_input(value: &Value, values: &mut Vec<FM31>) -> IoResult<()> {
+    let value = match value {
+        Value::Array(array) => {
+            for value in array.iter() {
+                flatten_input(value, values)?;
+            }
+            return Ok(());
+        }
+        Value::Number(number) => number.as_i64(),
+        Value::String(string) => string.trim().parse::<i64>().ok(),
+        _ => None,
+    };
+    match value {
+        Some(value) if value.unsigned_abs() < P as u64 => {
+            values.push(FM31::from(value));
+            Ok(())
+        }
+        _ => Err(invalid_data("Input value is not an M31 element")),
+    }
+}
+
+impl WitnessCalculator {
+    pub fn new(wasm: &[u8]) -> IoResult<Self> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm).map_err(runtime_error)?;
+        let mut store = Store::new(&engine, ());
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("runtime", "exceptionHandler", |code: i32| {
+                Err::<(), _>(wasmtime::Error::msg(format!(
+                    "The witness calculator failed with code {}",
+                    code
+                )))
+            })
+            .map_err(runtime_error)?;
+        for name in [
+            "printErrorMessage",
+            "writeBufferMessage",
+            "showSharedRWMemory",
+        ] {
+            linker
+                .func_wrap("runtime", name, || {})
+                .map_err(runtime_error)?;
+        }
+        linker
+            .define_unknown_imports_as_traps(&module)
+            .map_err(runtime_error)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(runtime_error)?;
+
+        let mut calculator = Self {
+            store,
+            instance,
+            n32: 0,
+        };
+
+        let version = calculator.call::<(), i32>("getVersion", ())?;
+        if version != 2 {
+            return Err(invalid_data("Unsupported witness calculator version"));
+        }
+        calculator.n32 = calculator.call::<(), i32>("getFieldNumLen32", ())? as u32;
+        calculator.call::<(), ()>("getRawPrime", ())?;
+        if calculator.read_shared_memory()? != Some(P as u64) {
+            return Err(invalid_data("Witness calculator is not compiled for M31"));
+        }
+
+        Ok(calculator)
+    }
+
+    fn call<Params, Results>(&mut self, name: &str, params: Params) -> IoResult<Results>
+    where
+        Params: wasmtime::WasmParams,
+        Results: wasmtime::WasmResults,
+    {
+        let func: TypedFunc<Params, Results> = self
+            .instance
+            .get_typed_func(&mut self.store, name)
+            .map_err(runtime_error)?;
+        func.call(&mut self.store, params).map_err(runtime_error)
+    }
+
+    // The value in the shared memory, or `None` if it does not fit in 64 bits.
+    fn read_shared_memory(&mut self) -> IoResult<Option<u64>> {
+        let mut value = 0u64;
+        let mut fits = true;
+        for j in 0..self.n32 {
+            let limb = self.call::<i32, i32>("readSharedRWMemory", j as i32)? as u32 as u64;
+            match j {
+                0 => value = limb,
+                1 => value |= limb << 32,
+                _ => fits &= limb == 0,
+            }
+        }
+        Ok(fits.then_some(value))
+    }
+
+    fn write_shared_memory(&mut self, value: FM31) -> IoResult<()> {
+        let value = to_m31(&value).0;
+        for j in 0..self.n32 {
+            let limb = if j == 0 { value } else { 0 };
+            self.call::<(i32, i32), ()>("writeSharedRWMemory", (j as i32, limb as i32))?;
+        }
+        Ok(())
+    }
+
+    // Computes the full assignment, in the order of the R1CS wires, from a JSON object mapping
+    // input signal names to their values.
+    pub fn calculate(&mut self, inputs: &str) -> IoResult<Vec<FM31>> {
+        let inputs: Value =
+            serde_json::from_str(inputs).map_err(|_| invalid_data("Invalid JSON inputs"))?;
+        let Value::Object(inputs) = inputs else {
+            return Err(invalid_data("The inputs are not a JSON object"));
+        };
+
+        self.call::<i32, ()>("init", 1)?;
+
+        for (name, value) in inputs.iter() {
+            let (msb, lsb) = fnv_hash(name);
+            let size = self.call::<(i32, i32), i32>("getInputSignalSize", (msb, lsb))?;
+            if size < 0 {
+                return Err(invalid_data(&format!("Unknown input signal {}", name)));
+            }
+
+            let mut values = vec![];
+            flatten_input(value, &mut values)?;
+            if values.len() != size as usize {
+                return Err(invalid_data(&format!(
+                    "Input signal {} expects {} values",
+                    name, size
+                )));
+            }
+
+            for (i, value) in values.into_iter().enumerate() {
+                self.write_shared_memory(value)?;
+                self.call::<(i32, i32, i32), ()>("setInputSignal", (msb, lsb, i as i32))?;
+            }
+        }
+
+        let num_witnesses = self.call::<(), i32>("getWitnessSize", ())?;
+        let mut witnesses = Vec::with_capacity(num_witnesses as usize);
+        for i in 0..num_witnesses {
+            self.call::<i32, ()>("getWitness", i)?;
+            match self.read_shared_memory()? {
+                Some(value) if value < P as u64 => witnesses.push(FM31::from(value as u32)),
+                _ => return Err(invalid_data("Witness is not an M31 element")),
+            }
+        }
+        Ok(witnesses)
+    }
+}
+
+pub fn load_r1cs_and_wasm_witness(
+    r1cs_data: impl Read + Seek,
+    wasm: &[u8],
+    inputs: &str,
+) -> IoResult<CircomCircuit<FM31>> {
+    let (circuit, _) = load_r1cs_with(r1cs_data, ReductionPolicy::Strict)?;
+
+    let witness = WitnessCalculator::new(wasm)?.calculate(inputs)?;
+    Ok(CircomCircuit::<FM31> {
+        witness: Some(witness),
+        ..circuit
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::FM31;
+    use crate::from_r1cs::circom::test::with_non_canonical_coefficient;
+    use crate::from_r1cs::circom::wasm::{flatten_input, fnv_hash, load_r1cs_and_wasm_witness};
+    use ark_std::io::Cursor;
+
+    #[test]
+    fn test_inputs() {
+        // the hash of "a" as computed by circom's `witness_calculator.js`
+        let (msb, lsb) = fnv_hash("a");
+        assert_eq!(
+            ((msb as u32 as u64) << 32) | lsb as u32 as u64,
+            0xaf63dc4c8601ec8c
+        );
+
+        let value = serde_json::from_str(r#"[["1", 2], [-1, "2147483646"]]"#).unwrap();
+        let mut values = vec![];
+        flatten_input(&value, &mut values).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                FM31::from(1u32),
+                FM31::from(2u32),
+                FM31::from(2147483646u32),
+                FM31::from(2147483646u32)
+            ]
+        );
+
+        let value = serde_json::from_str(r#""2147483647""#).unwrap();
+        assert!(flatten_input(&value, &mut values).is_err());
+        let value = serde_json::from_str(r#"{"a": 1}"#).unwrap();
+        assert!(flatten_input(&value, &mut values).is_err());
+    }
+
+    #[test]
+    fn test_load_r1cs_non_canonical() {
+        // the constraints are read before the witness generator, which is never reached
+        let r1cs = with_non_canonical_coefficient(include_bytes!("./multiplier2.r1cs"));
+        let err = load_r1cs_and_wasm_witness(Cursor::new(r1cs), &[], "{}")
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("Coefficient 0 of A in constraint 0 is not an M31 element"));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::circuit::{Circuit, FastHashMap, Mode, PublicWire};
+use crate::field::{to_m31, try_to_m31, FM31};
+use crate::from_r1cs::r1cs_constraint_processor::{
+    check_constraint, process_r1cs_constraint, reduce_coefs, sort_linear_combinations,
+    ConversionError, ConversionResult, NonCanonicalValue, OnDemandAllocator, ValueLocation,
+};
+use ark_ff::{One, Zero};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, LinearCombination, SynthesisError, SynthesisMode,
+    Variable,
+};
+use stwo_prover::core::fields::m31::M31;
+
+// A constraint system with the interface of the arkworks one, which lowers every constraint to
+// gates as soon as it is enforced, instead of keeping the linear combinations around until the
+// matrices are built. The memory then only grows with the circuit itself.
+//
+// The variables are numbered in the order they are created, the constant one being 0, and the
+// inputs are placed right away. A linear combination from `new_lc` is a variable of its own, which
+// is lowered once, when a constraint first uses it, and whose wire is reused afterwards.
+//
+// As `ConstraintSynthesizer` only takes the concrete arkworks constraint system, circuits that must
+// never hold their matrices are written against this type, through the same calls. Existing
+// `ConstraintSynthesizer` and r1cs-std circuits go through `synthesize`. The arkworks constraint
+// system has no hook on `enforce_constraint` and keeps its constraints private, so their matrices
+// are the only way to read them back. `synthesize` frees everything else of arkworks first, and
+// then each row once it is lowered.
+pub struct DirectConstraintSystem {
+    mode: Mode,
+    circuit: Circuit,
+    allocator: OnDemandAllocator,
+    public_wires: Vec<PublicWire>,
+    // the linear combinations from `new_lc` that no constraint has used yet
+    pending: FastHashMap<usize, Vec<(FM31, usize)>>,
+    num_constraints: usize,
+}
+
+impl DirectConstraintSystem {
+    pub fn new(mode: Mode) -> Self {
+        let mut circuit = Circuit::new();
+        // the inputs are placed as they are created, so the allocator only creates witnesses
+        let mut allocator = OnDemandAllocator::new(vec![M31::one()], 0);
+        let one = circuit.new_input(M31::one());
+        allocator.set_allocated(&mut circuit, 0, one.wire());
+
+        Self {
+            mode,
+            circuit,
+            allocator,
+            public_wires: vec![one],
+            pending: FastHashMap::default(),
+            num_constraints: 0,
+        }
+    }
+
+    pub fn with_outline_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.allocator = self.allocator.with_outline_threshold(threshold);
+        self
+    }
+
+    pub fn with_boolean_rows(mut self, boolean_rows: bool) -> Self {
+        self.allocator = self.allocator.with_boolean_rows(boolean_rows);
+        self
+    }
+
+    fn value<F>(&self, f: F) -> Result<M31, SynthesisError>
+    where
+        F: FnOnce() -> Result<FM31, SynthesisError>,
+    {
+        if self.mode.has_witness() {
+            // the value is of the variable about to be created
+            let location = ValueLocation::Variable(self.allocator.assignments.len());
+            try_to_m31(&f()?).map_err(|error| {
+                ConversionError::from(NonCanonicalValue { location, error }).into()
+            })
+        } else {
+            Ok(M31::zero())
+        }
+    }
+
+    fn new_variable(&mut self, value: M31) -> usize {
+        self.allocator.assignments.push(value);
+        self.allocator.assignments.len() - 1
+    }
+
+    pub fn new_input_variable<F>(&mut self, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<FM31, SynthesisError>,
+    {
+        let value = self.value(f)?;
+        let idx = self.new_variable(value);
+        let input = self.circuit.new_input(value);
+        self.allocator
+            .set_allocated(&mut self.circuit, idx, input.wire());
+        self.public_wires.push(input);
+        Ok(Variable::Instance(idx))
+    }
+
+    pub fn new_witness_variable<F>(&mut self, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<FM31, SynthesisError>,
+    {
+        let value = self.value(f)?;
+        Ok(Variable::Witness(self.new_variable(value)))
+    }
+
+    fn terms(&self, lc: &LinearCombination<FM31>) -> Result<Vec<(FM31, usize)>, SynthesisError> {
+        let mut terms = Vec::with_capacity(lc.0.len());
+        for &(coeff, var) in lc.0.iter() {
+            let idx = match var {
+                Variable::Zero => continue,
+                Variable::One => 0,
+                Variable::Instance(idx) | Variable::Witness(idx) => idx,
+                // only the arkworks constraint system hands these out
+                Variable::SymbolicLc(_) => return Err(SynthesisError::AssignmentMissing),
+            };
+            if idx >= self.allocator.assignments.len() {
+                return Err(SynthesisError::AssignmentMissing);
+            }
+            terms.push((coeff, idx));
+        }
+        Ok(terms)
+    }
+
+    pub fn new_lc(&mut self, lc: LinearCombination<FM31>) -> Result<Variable, SynthesisError> {
+        let terms = self.terms(&lc)?;
+        let mut value = M31::zero();
+        for &(coeff, idx) in terms.iter() {
+            value += to_m31(&coeff) * self.allocator.assignments.get(idx);
+        }
+        let idx = self.new_variable(value);
+        self.pending.insert(idx, terms);
+        Ok(Variable::Witness(idx))
+    }
+
+    fn lower_pending(&mut self, terms: &[(FM31, usize)]) {
+        for &(_, idx) in terms.iter() {
+            if let Some(lc) = self.pending.remove(&idx) {
+                self.lower_pending(&lc);
+                let lc = sort_linear_combinations(&lc);
+                let wire = reduce_coefs(&mut self.circuit, &mut self.allocator, &lc);
+                self.allocator.set_allocated(&mut self.circuit, idx, wire);
+            }
+        }
+    }
+
+    pub fn enforce_constraint(
+        &mut self,
+        a: LinearCombination<FM31>,
+        b: LinearCombination<FM31>,
+        c: LinearCombination<FM31>,
+    ) -> Result<(), SynthesisError> {
+        let a = self.terms(&a)?;
+        let b = self.terms(&b)?;
+        let c = self.terms(&c)?;
+        check_constraint(self.num_constraints, &a, &b, &c).map_err(ConversionError::from)?;
+        for terms in [&a, &b, &c] {
+            self.lower_pending(terms);
+        }
+
+        process_r1cs_constraint(&mut self.circuit, &mut self.allocator, &a, &b, &c);
+        self.num_constraints += 1;
+        Ok(())
+    }
+
+    // Synthesizes a circuit written for the arkworks constraint system, and lowers its constraints
+    // in order. The variables keep their index in the arkworks matrices, the instance variables
+    // coming first.
+    pub fn synthesize<C: ConstraintSynthesizer<FM31>>(
+        circuit: C,
+        mode: Mode,
+    ) -> Result<Self, SynthesisError> {
+        let cs = ConstraintSystem::new_ref();
+        if mode == Mode::INDEX {
+            cs.set_mode(SynthesisMode::Setup);
+        } else {
+            cs.set_mode(SynthesisMode::Prove {
+                construct_matrices: true,
+            });
+        }
+        circuit.generate_constraints(cs.clone())?;
+        cs.finalize();
+
+        let num_instance = cs.num_instance_variables();
+        let matrices = cs.to_matrices().ok_or(SynthesisError::MissingCS)?;
+        // the linear combinations of arkworks are freed before any constraint is lowered, so that
+        // only the rows not lowered yet are held next to the circuit
+        let mut system = match cs.borrow_mut() {
+            Some(mut system) => std::mem::take(&mut *system),
+            None => return Err(SynthesisError::MissingCS),
+        };
+        let instance = std::mem::take(&mut system.instance_assignment);
+        let witness = std::mem::take(&mut system.witness_assignment);
+        drop(system);
+        let value = |values: &[FM31], i: usize| {
+            values
+                .get(i)
+                .copied()
+                .ok_or(SynthesisError::AssignmentMissing)
+        };
+
+        let mut direct = Self::new(mode);
+        for i in 1..num_instance {
+            direct.new_input_variable(|| value(&instance, i))?;
+        }
+        for i in 0..matrices.num_witness_variables {
+            direct.new_witness_variable(|| value(&witness, i))?;
+        }
+
+        let variable = |(coeff, idx): (FM31, usize)| {
+            let var = match idx {
+                0 => Variable::One,
+                idx if idx < num_instance => Variable::Instance(idx),
+                idx => Variable::Witness(idx),
+            };
+            (coeff, var)
+        };
+        let lc = |terms: Vec<(FM31, usize)>| {
+            LinearCombination(terms.into_iter().map(variable).collect())
+        };
+        for ((a, b), c) in matrices.a.into_iter().zip(matrices.b).zip(matrices.c) {
+            direct.enforce_constraint(lc(a), lc(b), lc(c))?;
+        }
+        Ok(direct)
+    }
+
+    pub fn num_instance_variables(&self) -> usize {
+        self.public_wires.len()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.num_constraints
+    }
+
+    pub fn finalize(self) -> ConversionResult {
+        ConversionResult {
+            circuit: self.circuit,
+            variable_map: self.allocator.mapping,
+            public_wires: self.public_wires,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::field::FM31;
+    use crate::from_r1cs::direct::DirectConstraintSystem;
+    use crate::from_r1cs::r1cs_constraint_processor::ConversionResult;
+    use crate::from_r1cs::TestCircuit;
+    use ark_relations::lc;
+    use ark_relations::r1cs::SynthesisError;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    // x * y = z and (x + y) * (x + y) = w through a linear combination, with x and w public
+    fn synthesize(
+        mode: Mode,
+        x: Option<u32>,
+        y: Option<u32>,
+        w: Option<u32>,
+    ) -> Result<ConversionResult, SynthesisError> {
+        let value = |v: Option<u32>| v.map(FM31::from).ok_or(SynthesisError::AssignmentMissing);
+        let z_value = x.zip(y).map(|(x, y)| x * y);
+
+        let mut cs = DirectConstraintSystem::new(mode);
+        let x = cs.new_input_variable(|| value(x))?;
+        let y = cs.new_witness_variable(|| value(y))?;
+        let z = cs.new_witness_variable(|| value(z_value))?;
+        let w = cs.new_input_variable(|| value(w))?;
+        cs.enforce_constraint(lc!() + x, lc!() + y, lc!() + z)?;
+
+        let sum = cs.new_lc(lc!() + x + y)?;
+        cs.enforce_constraint(lc!() + sum, lc!() + sum, lc!() + w)?;
+        assert_eq!(cs.num_instance_variables(), 3);
+        assert_eq!(cs.num_constraints(), 2);
+        Ok(cs.finalize())
+    }
+
+    #[test]
+    fn test_direct_constraint_system() {
+        let result = synthesize(Mode::PROVE, Some(3), Some(7), Some(100)).unwrap();
+        let circuit = &result.circuit;
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+        let x = result.public_wires[1].wire();
+        assert_eq!(circuit.get_output_wire(x), M31::from(3u32));
+
+        let index = synthesize(Mode::INDEX, None, None, None).unwrap();
+        assert_eq!(index.circuit.index(), circuit.index());
+
+        let wrong = synthesize(Mode::PROVE, Some(3), Some(7), Some(99)).unwrap();
+        assert!(!wrong.circuit.is_constraint_satisfied());
+
+        assert!(synthesize(Mode::PROVE, Some(3), None, Some(100)).is_err());
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let result = DirectConstraintSystem::synthesize(test_circuit.clone(), Mode::PROVE)
+            .unwrap()
+            .finalize();
+        let circuit = &result.circuit;
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        let index = DirectConstraintSystem::synthesize(test_circuit, Mode::INDEX)
+            .unwrap()
+            .finalize();
+        assert_eq!(index.circuit.index(), circuit.index());
+    }
+}
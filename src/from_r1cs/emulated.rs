@@ -0,0 +1,250 @@
+use crate::circuit::Mode;
+use crate::field::FM31;
+use crate::from_r1cs::r1cs_constraint_processor::{
+    generate_circuit, ConversionError, ConversionResult,
+};
+use ark_ff::{One, PrimeField};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::emulated_fp::EmulatedFpVar;
+use ark_r1cs_std::fields::FieldVar;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, OptimizationGoal, SynthesisError,
+    SynthesisMode,
+};
+
+pub type EmulatedLinearCombination<F> = Vec<(F, usize)>;
+
+// An R1CS over another prime field, such as BN254, which is lowered to M31 by allocating every
+// variable as an `EmulatedFpVar` and enforcing every constraint in emulated arithmetic, as
+// `TestCircuit` does by hand.
+//
+// Variable 0 is the constant one, followed by the public variables and then the private ones.
+#[derive(Clone, Debug)]
+pub struct EmulatedR1CS<F: PrimeField> {
+    pub num_instance_variables: usize,
+    pub num_variables: usize,
+    pub constraints: Vec<[EmulatedLinearCombination<F>; 3]>,
+    // the values of all the variables, including the constant one, or `None` in `Mode::INDEX`
+    pub assignment: Option<Vec<F>>,
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<FM31> for EmulatedR1CS<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<FM31>) -> Result<(), SynthesisError> {
+        if let Some(assignment) = &self.assignment {
+            if assignment.len() != self.num_variables {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        }
+        let value = |i: usize| {
+            self.assignment
+                .as_ref()
+                .map(|assignment| assignment[i])
+                .ok_or(SynthesisError::AssignmentMissing)
+        };
+
+        let mut vars = Vec::with_capacity(self.num_variables);
+        vars.push(EmulatedFpVar::<F, FM31>::one());
+        for i in 1..self.num_variables {
+            let var = if i < self.num_instance_variables {
+                EmulatedFpVar::new_input(cs.clone(), || value(i))?
+            } else {
+                EmulatedFpVar::new_witness(cs.clone(), || value(i))?
+            };
+            vars.push(var);
+        }
+
+        let linear_combination = |terms: &EmulatedLinearCombination<F>| {
+            let mut sum = EmulatedFpVar::<F, FM31>::zero();
+            for &(coeff, idx) in terms.iter() {
+                let var = vars.get(idx).ok_or(SynthesisError::AssignmentMissing)?;
+                if idx == 0 {
+                    sum += coeff;
+                } else if coeff.is_one() {
+                    sum += var;
+                } else {
+                    sum += var * coeff;
+                }
+            }
+            Ok::<_, SynthesisError>(sum)
+        };
+
+        for [a, b, c] in self.constraints.iter() {
+            let a = linear_combination(a)?;
+            let b = linear_combination(b)?;
+            let c = linear_combination(c)?;
+            (a * b).enforce_equal(&c)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> EmulatedR1CS<F> {
+    // Synthesizes a circuit written over `F` natively (for example, with `FpVar<F>`) and takes
+    // its matrices, and its assignment in `Mode::PROVE`.
+    pub fn synthesize<C: ConstraintSynthesizer<F>>(
+        circuit: C,
+        mode: Mode,
+    ) -> Result<Self, SynthesisError> {
+        let cs = ConstraintSystem::<F>::new_ref();
+        cs.set_optimization_goal(OptimizationGoal::Weight);
+        if mode == Mode::INDEX {
+            cs.set_mode(SynthesisMode::Setup);
+        } else {
+            cs.set_mode(SynthesisMode::Prove {
+                construct_matrices: true,
+            });
+        }
+        circuit.generate_constraints(cs.clone())?;
+        cs.finalize();
+
+        let matrices = cs.to_matrices().ok_or(SynthesisError::MissingCS)?;
+        let constraints = matrices
+            .a
+            .into_iter()
+            .zip(matrices.b)
+            .zip(matrices.c)
+            .map(|((a, b), c)| [a, b, c])
+            .collect();
+
+        let assignment = if mode.has_witness() {
+            let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+            let mut assignment = cs.instance_assignment.clone();
+            assignment.extend_from_slice(&cs.witness_assignment);
+            Some(assignment)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            num_instance_variables: cs.num_instance_variables(),
+            num_variables: cs.num_instance_variables() + cs.num_witness_variables(),
+            constraints,
+            assignment,
+        })
+    }
+}
+
+// Like `generate_circuit`, for a circuit over any prime field `F`, whose elements are then split
+// into M31 limbs by `EmulatedFpVar` instead of by hand in the circuit.
+pub fn generate_circuit_emulated<F, C>(
+    circuit: C,
+    mode: Mode,
+) -> Result<ConversionResult, ConversionError>
+where
+    F: PrimeField,
+    C: ConstraintSynthesizer<F>,
+{
+    generate_circuit(EmulatedR1CS::synthesize(circuit, mode)?, mode)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::emulated::{generate_circuit_emulated, EmulatedR1CS};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use ark_ff::One;
+    use ark_r1cs_std::alloc::AllocVar;
+    use ark_r1cs_std::eq::EqGadget;
+    use ark_r1cs_std::fields::fp::FpVar;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+
+    // a * b + 5 = c over BN254, with c public
+    struct NativeCircuit {
+        a: Option<ark_bn254::Fr>,
+        b: Option<ark_bn254::Fr>,
+        c: Option<ark_bn254::Fr>,
+    }
+
+    impl ConstraintSynthesizer<ark_bn254::Fr> for NativeCircuit {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<ark_bn254::Fr>,
+        ) -> Result<(), SynthesisError> {
+            let missing = || SynthesisError::AssignmentMissing;
+            let c = FpVar::new_input(cs.clone(), || self.c.ok_or_else(missing))?;
+            let a = FpVar::new_witness(cs.clone(), || self.a.ok_or_else(missing))?;
+            let b = FpVar::new_witness(cs, || self.b.ok_or_else(missing))?;
+            (a * b + ark_bn254::Fr::from(5u64)).enforce_equal(&c)
+        }
+    }
+
+    #[test]
+    fn test_emulated_r1cs() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let a = ark_bn254::Fr::rand(&mut prng);
+        let b = ark_bn254::Fr::rand(&mut prng);
+        let one = ark_bn254::Fr::one();
+
+        // (one, c, a, b) with c = a * b + 5
+        let five = ark_bn254::Fr::from(5u64);
+        let r1cs = EmulatedR1CS {
+            num_instance_variables: 2,
+            num_variables: 4,
+            constraints: vec![[vec![(one, 2)], vec![(one, 3)], vec![(one, 1), (-five, 0)]]],
+            assignment: Some(vec![one, a * b + five, a, b]),
+        };
+
+        let circuit = generate_circuit(r1cs.clone(), Mode::PROVE).unwrap().circuit;
+        assert!(circuit.is_constraint_satisfied());
+
+        let index = generate_circuit(
+            EmulatedR1CS {
+                assignment: None,
+                ..r1cs.clone()
+            },
+            Mode::INDEX,
+        )
+        .unwrap()
+        .circuit;
+        assert_eq!(index.index(), circuit.index());
+
+        let wrong = EmulatedR1CS {
+            assignment: Some(vec![one, a * b, a, b]),
+            ..r1cs
+        };
+        let circuit = generate_circuit(wrong, Mode::PROVE).unwrap().circuit;
+        assert!(!circuit.is_constraint_satisfied());
+    }
+
+    #[test]
+    fn test_generate_circuit_emulated() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let a = ark_bn254::Fr::rand(&mut prng);
+        let b = ark_bn254::Fr::rand(&mut prng);
+        let c = a * b + ark_bn254::Fr::from(5u64);
+
+        let native = NativeCircuit {
+            a: Some(a),
+            b: Some(b),
+            c: Some(c),
+        };
+        let circuit = generate_circuit_emulated(native, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(circuit.is_constraint_satisfied());
+
+        let setup = NativeCircuit {
+            a: None,
+            b: None,
+            c: None,
+        };
+        let index = generate_circuit_emulated(setup, Mode::INDEX)
+            .unwrap()
+            .circuit;
+        assert_eq!(index.index(), circuit.index());
+
+        let wrong = NativeCircuit {
+            a: Some(a),
+            b: Some(b),
+            c: Some(a * b),
+        };
+        let circuit = generate_circuit_emulated(wrong, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(!circuit.is_constraint_satisfied());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::circuit::{Circuit, Mode};
+use crate::field::{from_m31, FM31};
+use crate::from_r1cs::r1cs_constraint_processor::{process_r1cs_constraint, OnDemandAllocator};
+use ark_ff::{One, Zero};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{Error, ErrorKind, Read, Result};
+use stwo_prover::core::fields::m31::{M31, P};
+
+// The lowering of constraint systems shaped like those of gnark over a 31-bit field. gnark stores
+// every coefficient once in a table and refers to it from the terms, and numbers the wires as the
+// constant one, the public inputs, the secret inputs and then the internal wires.
+//
+// There is no parser for the serialized constraint systems of gnark, whose CBOR layout depends on
+// the gnark version: the caller fills a `GnarkR1CS` with the constraints, for example exported
+// from Go. The witness files written by `witness.MarshalBinary` are read directly, but they only
+// hold the public and secret values, so the values of the internal wires, which gnark's solver
+// computes, are given next to them (see `GnarkWitness::solution`).
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GnarkTerm {
+    pub coeff_id: u32,
+    pub wire_id: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GnarkR1CS {
+    pub num_public: usize,
+    pub num_secret: usize,
+    pub num_internal: usize,
+    pub coefficients: Vec<M31>,
+    pub constraints: Vec<[Vec<GnarkTerm>; 3]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GnarkWitness {
+    pub public: Vec<M31>,
+    pub secret: Vec<M31>,
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+// The binary witness format: the numbers of public and secret values and of all the values, as
+// big-endian u32s, followed by the public and then the secret values as big-endian field
+// elements of 4 bytes. The public values do not include the constant one.
+pub fn gnark_witness_read<R: Read>(mut reader: R) -> Result<GnarkWitness> {
+    let num_public = reader.read_u32::<BigEndian>()? as usize;
+    let num_secret = reader.read_u32::<BigEndian>()? as usize;
+    let num_values = reader.read_u32::<BigEndian>()? as usize;
+    if num_public.checked_add(num_secret) != Some(num_values) {
+        return Err(invalid_data("Unexpected number of values"));
+    }
+
+    let mut read_values = |n: usize| {
+        let mut values = Vec::with_capacity(n.min(1 << 20));
+        for _ in 0..n {
+            let value = reader.read_u32::<BigEndian>()?;
+            if value >= P {
+                return Err(invalid_data("Witness is not an M31 element"));
+            }
+            values.push(M31::from(value));
+        }
+        Ok(values)
+    };
+    let public = read_values(num_public)?;
+    let secret = read_values(num_secret)?;
+    Ok(GnarkWitness { public, secret })
+}
+
+impl GnarkWitness {
+    // The values of all the wires, in the order of `GnarkR1CS::num_wires`, from the witness and the
+    // values of the internal wires.
+    pub fn solution(&self, internal: &[M31]) -> Vec<M31> {
+        let mut solution =
+            Vec::with_capacity(1 + self.public.len() + self.secret.len() + internal.len());
+        solution.push(M31::one());
+        solution.extend_from_slice(&self.public);
+        solution.extend_from_slice(&self.secret);
+        solution.extend_from_slice(internal);
+        solution
+    }
+}
+
+impl GnarkR1CS {
+    pub fn num_wires(&self) -> usize {
+        1 + self.num_public + self.num_secret + self.num_internal
+    }
+
+    fn terms(&self, terms: &[GnarkTerm]) -> Result<Vec<(FM31, usize)>> {
+        let mut terms = terms
+            .iter()
+            .map(|term| {
+                let coeff = self
+                    .coefficients
+                    .get(term.coeff_id as usize)
+                    .ok_or_else(|| invalid_data("Coefficient out of range"))?;
+                if term.wire_id as usize >= self.num_wires() {
+                    return Err(invalid_data("Wire out of range"));
+                }
+                Ok((from_m31(*coeff), term.wire_id as usize))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        terms.sort_unstable_by_key(|&(_, wire)| wire);
+        Ok(terms)
+    }
+
+    // Lowers the constraints the same way as the circom path. gnark computes the internal wires
+    // in its solver, so in `Mode::PROVE` the values of all the wires (as in `num_wires`) are
+    // needed, not only the witness.
+    pub fn generate_circuit(&self, mode: Mode, solution: Option<&[M31]>) -> Result<Circuit> {
+        let assignments = match (mode, solution) {
+            (Mode::PROVE | Mode::CHECK, Some(solution)) => {
+                if solution.len() != self.num_wires() {
+                    return Err(invalid_data(
+                        "The solution does not match the number of wires",
+                    ));
+                }
+                solution.to_vec()
+            }
+            (Mode::PROVE | Mode::CHECK, None) => return Err(invalid_data("Missing solution")),
+            (Mode::INDEX, _) => vec![M31::zero(); self.num_wires()],
+        };
+
+        let num_instance = 1 + self.num_public;
+        let mut allocator = OnDemandAllocator::new(assignments, num_instance);
+        let mut output = Circuit::new();
+        for i in 0..num_instance {
+            allocator.get(&mut output, i);
+        }
+
+        for [a, b, c] in self.constraints.iter() {
+            let (a, b, c) = (self.terms(a)?, self.terms(b)?, self.terms(c)?);
+            process_r1cs_constraint(&mut output, &mut allocator, &a, &b, &c);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::gnark::{gnark_witness_read, GnarkR1CS, GnarkTerm};
+    use ark_std::io::Cursor;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_gnark() {
+        // x * y = z with x public, y secret and z internal, and z + 5 = 2 * w with w internal
+        let term = |coeff_id, wire_id| GnarkTerm { coeff_id, wire_id };
+        let r1cs = GnarkR1CS {
+            num_public: 1,
+            num_secret: 1,
+            num_internal: 2,
+            coefficients: vec![0u32, 1, 2, 5].into_iter().map(M31::from).collect(),
+            constraints: vec![
+                [vec![term(1, 1)], vec![term(1, 2)], vec![term(1, 3)]],
+                [
+                    vec![term(1, 3), term(3, 0)],
+                    vec![term(1, 0)],
+                    vec![term(2, 4)],
+                ],
+            ],
+        };
+
+        let mut data = vec![];
+        for v in [1u32, 1, 2, 3, 7] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        let witness = gnark_witness_read(Cursor::new(&data)).unwrap();
+        assert_eq!(witness.public, vec![M31::from(3)]);
+        assert_eq!(witness.secret, vec![M31::from(7)]);
+
+        // z = 21, w = 13
+        let solution = witness.solution(&[M31::from(21), M31::from(13)]);
+        assert_eq!(solution, [1u32, 3, 7, 21, 13].map(M31::from));
+        let circuit = r1cs
+            .generate_circuit(Mode::PROVE, Some(&solution[..]))
+            .unwrap();
+        assert!(circuit.is_constraint_satisfied());
+
+        let index = r1cs.generate_circuit(Mode::INDEX, None).unwrap();
+        assert_eq!(index.index(), circuit.index());
+
+        assert!(r1cs.generate_circuit(Mode::PROVE, None).is_err());
+        assert!(r1cs
+            .generate_circuit(Mode::PROVE, Some(&solution[1..]))
+            .is_err());
+
+        data[11] = 3;
+        assert!(gnark_witness_read(Cursor::new(&data)).is_err());
+    }
+}
@@ -8,8 +8,17 @@ use ark_std::UniformRand;
 
 pub mod r1cs_constraint_processor;
 
+#[cfg(feature = "bellpepper")]
+pub mod bellpepper;
+
 pub mod circom;
 
+pub mod direct;
+
+pub mod emulated;
+
+pub mod gnark;
+
 #[cfg(test)]
 mod test;
 
@@ -16,40 +16,137 @@
     You should have received a copy of the GNU General Public License along with
     snarkjs. If not, see <https://www.gnu.org/licenses/>.
 */
-use crate::circuit::{Circuit, Mode};
-use crate::field::{to_m31, FM31};
+use crate::circuit::cache::IndexCache;
+use crate::circuit::gadget::Gadget;
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::{BudgetError, Circuit, FastHashMap, Mode, PublicWire, UnsatisfiedRow, Wire};
+use crate::field::{to_m31, try_to_m31, try_to_m31_slice_into, NonCanonicalError, FM31};
+use crate::from_r1cs::circom::mapped::MappedWitness;
 use ark_ff::{Field, One, Zero};
 use ark_relations::r1cs::{
-    ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisMode,
+    ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisError,
+    SynthesisMode,
 };
-use std::collections::HashMap;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
 use stwo_prover::core::fields::m31::M31;
 
+// The values of the R1CS variables by index, either held in memory or read from a mapped `.wtns`
+// file as the variables are allocated, so that the witness is not held twice during conversion.
+// Variables added during conversion, past the end of a mapped witness, are kept in `appended`.
+pub enum Assignments {
+    Values(Vec<M31>),
+    Mapped {
+        witness: MappedWitness,
+        appended: Vec<M31>,
+    },
+}
+
+impl Assignments {
+    pub fn len(&self) -> usize {
+        match self {
+            Assignments::Values(values) => values.len(),
+            Assignments::Mapped { witness, appended } => witness.len() + appended.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: usize) -> M31 {
+        match self {
+            Assignments::Values(values) => values[idx],
+            Assignments::Mapped { witness, appended } => match idx.checked_sub(witness.len()) {
+                Some(i) => appended[i],
+                None => witness.get(idx).unwrap(),
+            },
+        }
+    }
+
+    pub fn push(&mut self, value: M31) {
+        match self {
+            Assignments::Values(values) => values.push(value),
+            Assignments::Mapped { appended, .. } => appended.push(value),
+        }
+    }
+}
+
+impl From<Vec<M31>> for Assignments {
+    fn from(values: Vec<M31>) -> Self {
+        Assignments::Values(values)
+    }
+}
+
+impl From<MappedWitness> for Assignments {
+    fn from(witness: MappedWitness) -> Self {
+        Assignments::Mapped {
+            witness,
+            appended: vec![],
+        }
+    }
+}
+
 pub struct OnDemandAllocator {
-    pub assignments: Vec<M31>,
-    pub mapping: HashMap<usize, usize>,
+    pub assignments: Assignments,
+    pub mapping: FastHashMap<usize, Wire>,
     pub num_input: usize,
+    // names of the R1CS variables, attached as labels to the wires they end up in
+    pub names: FastHashMap<usize, String>,
+    // linear combinations with at least this many variables are only lowered once, up to a
+    // factor, and `outlined` keeps their wires by their terms scaled to a first coefficient of one
+    pub outline_threshold: Option<usize>,
+    pub outlined: FastHashMap<(Vec<(usize, FM31)>, FM31), Wire>,
+    // variables constrained to be zero or one become boolean rows when first allocated
+    pub boolean_rows: bool,
 }
 
 impl OnDemandAllocator {
-    pub fn new(assignments: Vec<M31>, num_input: usize) -> Self {
+    pub fn new(assignments: impl Into<Assignments>, num_input: usize) -> Self {
         Self {
-            assignments,
-            mapping: HashMap::new(),
+            assignments: assignments.into(),
+            mapping: FastHashMap::default(),
             num_input,
+            names: FastHashMap::default(),
+            outline_threshold: None,
+            outlined: FastHashMap::default(),
+            boolean_rows: false,
+        }
+    }
+
+    pub fn with_names(mut self, names: FastHashMap<usize, String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    pub fn with_outline_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.outline_threshold = threshold;
+        self
+    }
+
+    pub fn with_boolean_rows(mut self, boolean_rows: bool) -> Self {
+        self.boolean_rows = boolean_rows;
+        self
+    }
+
+    fn label(&self, circuit: &mut Circuit, idx: usize, wire: Wire) {
+        if let Some(name) = self.names.get(&idx) {
+            circuit.set_label(wire, name);
         }
     }
 
-    pub fn get(&mut self, circuit: &mut Circuit, idx: usize) -> usize {
+    pub fn get(&mut self, circuit: &mut Circuit, idx: usize) -> Wire {
         if let Some(&v) = self.mapping.get(&idx) {
             v
         } else {
             let v = if idx < self.num_input {
-                circuit.new_input(self.assignments[idx])
+                circuit.new_input(self.assignments.get(idx)).wire()
             } else {
-                circuit.new_witness(self.assignments[idx])
+                circuit.new_witness(self.assignments.get(idx))
             };
             self.mapping.insert(idx, v);
+            self.label(circuit, idx, v);
             v
         }
     }
@@ -58,18 +155,623 @@ impl OnDemandAllocator {
         self.mapping.contains_key(&idx)
     }
 
-    pub fn set_allocated(&mut self, idx: usize, allocated: usize) {
+    pub fn set_allocated(&mut self, circuit: &mut Circuit, idx: usize, allocated: Wire) {
         assert!(!self.is_allocated(idx));
         self.mapping.insert(idx, allocated);
+        self.label(circuit, idx, allocated);
+    }
+}
+
+// The circuit together with where the R1CS variables ended up, by their index in the matrices:
+// the wire of every variable that the constraints use, and the wires of the instance variables in
+// order, starting with the constant one, which are the public inputs of the circuit.
+pub struct ConversionResult {
+    pub circuit: Circuit,
+    pub variable_map: FastHashMap<usize, Wire>,
+    pub public_wires: Vec<PublicWire>,
+}
+
+impl ConversionResult {
+    pub fn new(circuit: Circuit, allocator: OnDemandAllocator) -> Self {
+        let public_wires = (0..allocator.num_input)
+            .map(|idx| PublicWire(allocator.mapping[&idx]))
+            .collect();
+        Self {
+            circuit,
+            variable_map: allocator.mapping,
+            public_wires,
+        }
+    }
+}
+
+// How the constraints are synthesized and lowered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConversionOptions {
+    // `OptimizationGoal::Weight` outlines the symbolic linear combinations that are used more
+    // than once into new variables when arkworks finalizes the constraint system, and the other
+    // goals inline them into every constraint.
+    pub optimization_goal: OptimizationGoal,
+    // A linear combination with at least this many variables is lowered once, and its wire is
+    // reused wherever it appears again, like a new variable in the R1CS, and scaled by one row
+    // wherever a multiple of it appears. This saves the rows of linear combinations repeated by
+    // inlining, such as the limb recombinations of emulated fields, at the cost of remembering
+    // them.
+    pub new_variable_threshold: Option<usize>,
+    // Fails with `ConversionError::Budget` as soon as a constraint takes the circuit over
+    // `2^max_log_size` rows, which the converted circuit then keeps as its
+    // `Circuit::with_max_log_size`.
+    pub max_log_size: Option<u32>,
+    // Lowers the constraints that restrict a variable to zero or one, such as `x * (x - 1) = 0`,
+    // into a single boolean row for the variable where it is first used. Off by default, as it
+    // changes the index of circuits converted before.
+    pub boolean_rows: bool,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            optimization_goal: OptimizationGoal::Weight,
+            new_variable_threshold: None,
+            max_log_size: None,
+            boolean_rows: false,
+        }
     }
 }
 
 pub fn generate_circuit<C: ConstraintSynthesizer<FM31>>(
     circuit: C,
     mode: Mode,
-) -> ark_relations::r1cs::Result<Circuit> {
+) -> Result<ConversionResult, ConversionError> {
+    generate_circuit_with_names(circuit, mode, FastHashMap::default())
+}
+
+// Like `generate_circuit`, with the synthesis and the lowering tuned by `options`.
+pub fn generate_circuit_with_options<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    mode: Mode,
+    options: &ConversionOptions,
+) -> Result<ConversionResult, ConversionError> {
+    let (mut output, mut allocator, matrices) =
+        synthesize(circuit, mode, FastHashMap::default(), options)?;
+
+    for ((a, b), c) in matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter())
+    {
+        process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+        if let Some(log_size) = options.max_log_size {
+            output.check_log_size(log_size)?;
+        }
+    }
+    output.max_log_size = options.max_log_size;
+
+    Ok(ConversionResult::new(output, allocator))
+}
+
+// Like `generate_circuit`, with names of the R1CS variables (by their index in the matrices, as
+// read from a circom `.sym` file) attached as labels to their wires.
+pub fn generate_circuit_with_names<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    mode: Mode,
+    names: FastHashMap<usize, String>,
+) -> Result<ConversionResult, ConversionError> {
+    let (mut output, mut allocator, matrices) =
+        synthesize(circuit, mode, names, &ConversionOptions::default())?;
+
+    // witness values layout
+    // - zero_var
+    // - one_var
+    // - instance_vars
+    // - witness_vars
+
+    for ((a, b), c) in matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter())
+    {
+        process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+    }
+
+    Ok(ConversionResult::new(output, allocator))
+}
+
+// Like `generate_circuit`, with the constraints lowered by `process_r1cs_constraints_parallel`,
+// which gives the same circuit.
+pub fn generate_circuit_parallel<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    mode: Mode,
+) -> Result<ConversionResult, ConversionError> {
+    let (mut output, mut allocator, matrices) = synthesize(
+        circuit,
+        mode,
+        FastHashMap::default(),
+        &ConversionOptions::default(),
+    )?;
+    process_r1cs_constraints_parallel(
+        &mut output,
+        &mut allocator,
+        &matrices.a,
+        &matrices.b,
+        &matrices.c,
+    );
+    Ok(ConversionResult::new(output, allocator))
+}
+
+// Like `generate_circuit` in `Mode::INDEX`, looking the index up in `cache` by the hash of the
+// constraint matrices first, so that a circuit converted before is only synthesized again.
+pub fn generate_index_cached<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    cache: &IndexCache,
+) -> Result<CircuitIndex, ConversionError> {
+    let (mut output, mut allocator, matrices) = synthesize(
+        circuit,
+        Mode::INDEX,
+        FastHashMap::default(),
+        &ConversionOptions::default(),
+    )?;
+
+    cache.get_or_insert_with(&matrices_cache_key(&matrices), || {
+        for ((a, b), c) in matrices
+            .a
+            .iter()
+            .zip(matrices.b.iter())
+            .zip(matrices.c.iter())
+        {
+            process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+        }
+        Ok(output)
+    })
+}
+
+fn matrices_cache_key(matrices: &ConstraintMatrices<FM31>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"circle-plonk r1cs matrices v1");
+    hasher.update((matrices.num_instance_variables as u64).to_le_bytes());
+    hasher.update((matrices.num_witness_variables as u64).to_le_bytes());
+    hasher.update((matrices.num_constraints as u64).to_le_bytes());
+    for matrix in [&matrices.a, &matrices.b, &matrices.c] {
+        for row in matrix.iter() {
+            hasher.update((row.len() as u64).to_le_bytes());
+            for (coeff, idx) in row.iter() {
+                hasher.update(to_m31(coeff).0.to_le_bytes());
+                hasher.update((*idx as u64).to_le_bytes());
+            }
+        }
+    }
+    hasher.finalize().into()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsatisfiedConstraint {
+    pub constraint: usize,
+    // A * B - C over the assignments
+    pub value: M31,
+}
+
+impl Display for UnsatisfiedConstraint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "R1CS constraint {} unsatisfied: A * B - C = {}",
+            self.constraint, self.value
+        )
+    }
+}
+
+impl std::error::Error for UnsatisfiedConstraint {}
+
+// Where a value that is not a canonical M31 element was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueLocation {
+    // an instance or witness variable, by its index in the matrices
+    Variable(usize),
+    // a term of the linear combination `A`, `B` or `C` of a constraint
+    Coefficient {
+        constraint: usize,
+        matrix: char,
+        term: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonCanonicalValue {
+    pub location: ValueLocation,
+    pub error: NonCanonicalError,
+}
+
+impl Display for NonCanonicalValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            ValueLocation::Variable(idx) => write!(f, "R1CS variable {}: {}", idx, self.error),
+            ValueLocation::Coefficient {
+                constraint,
+                matrix,
+                term,
+            } => write!(
+                f,
+                "R1CS constraint {}, term {} of {}: {}",
+                constraint, term, matrix, self.error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NonCanonicalValue {}
+
+// Whether every coefficient of a constraint is canonical, or the first that is not.
+pub fn check_constraint(
+    constraint: usize,
+    a: &[(FM31, usize)],
+    b: &[(FM31, usize)],
+    c: &[(FM31, usize)],
+) -> Result<(), NonCanonicalValue> {
+    for (matrix, lc) in [('A', a), ('B', b), ('C', c)] {
+        for (term, (coeff, _)) in lc.iter().enumerate() {
+            try_to_m31(coeff).map_err(|error| NonCanonicalValue {
+                location: ValueLocation::Coefficient {
+                    constraint,
+                    matrix,
+                    term,
+                },
+                error,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check_coefficients(matrices: &ConstraintMatrices<FM31>) -> Result<(), NonCanonicalValue> {
+    let rows = matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter());
+    for (constraint, ((a, b), c)) in rows.enumerate() {
+        check_constraint(constraint, a, b, c)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ConversionError {
+    Synthesis(SynthesisError),
+    Unsatisfied(UnsatisfiedConstraint),
+    NonCanonical(NonCanonicalValue),
+    Budget(BudgetError),
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::Synthesis(err) => write!(f, "{}", err),
+            ConversionError::Unsatisfied(err) => write!(f, "{}", err),
+            ConversionError::NonCanonical(err) => write!(f, "{}", err),
+            ConversionError::Budget(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<SynthesisError> for ConversionError {
+    fn from(err: SynthesisError) -> Self {
+        ConversionError::Synthesis(err)
+    }
+}
+
+impl From<UnsatisfiedConstraint> for ConversionError {
+    fn from(err: UnsatisfiedConstraint) -> Self {
+        ConversionError::Unsatisfied(err)
+    }
+}
+
+impl From<NonCanonicalValue> for ConversionError {
+    fn from(err: NonCanonicalValue) -> Self {
+        ConversionError::NonCanonical(err)
+    }
+}
+
+impl From<BudgetError> for ConversionError {
+    fn from(err: BudgetError) -> Self {
+        ConversionError::Budget(err)
+    }
+}
+
+// For a conversion nested in a `ConstraintSynthesizer`, whose `SynthesisError` has no room for
+// where a value was found, so it is logged.
+impl From<ConversionError> for SynthesisError {
+    fn from(err: ConversionError) -> Self {
+        match err {
+            ConversionError::Synthesis(err) => err,
+            err => {
+                tracing::error!("{}", err);
+                SynthesisError::Unsatisfiable
+            }
+        }
+    }
+}
+
+// Evaluates A * B - C over the assignments, which is zero when the constraint is satisfied.
+pub fn evaluate_r1cs_constraint(
+    assignments: &Assignments,
+    a: &[(FM31, usize)],
+    b: &[(FM31, usize)],
+    c: &[(FM31, usize)],
+) -> M31 {
+    let evaluate = |lc: &[(FM31, usize)]| {
+        lc.iter()
+            .map(|(coeff, idx)| to_m31(coeff) * assignments.get(*idx))
+            .fold(M31::zero(), |acc, v| acc + v)
+    };
+    evaluate(a) * evaluate(b) - evaluate(c)
+}
+
+// Like `generate_circuit` in `Mode::PROVE`, with every constraint evaluated over the assignments
+// before it is lowered, so that a witness that does not satisfy the R1CS is reported at the first
+// constraint that fails instead of giving a circuit that is only found unsatisfiable later.
+pub fn generate_circuit_validated<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+) -> Result<ConversionResult, ConversionError> {
+    let (mut output, mut allocator, matrices) = synthesize(
+        circuit,
+        Mode::PROVE,
+        FastHashMap::default(),
+        &ConversionOptions::default(),
+    )?;
+
+    for (constraint, ((a, b), c)) in matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter())
+        .enumerate()
+    {
+        let value = evaluate_r1cs_constraint(&allocator.assignments, a, b, c);
+        if !value.is_zero() {
+            return Err(UnsatisfiedConstraint { constraint, value }.into());
+        }
+        process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+    }
+
+    Ok(ConversionResult::new(output, allocator))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConstraintClass {
+    // A or B is zero, so only C is lowered
+    Addition,
+    // A or B is a constant
+    Equal,
+    // both A and B are constants
+    ConstantFold,
+    Multiplication,
+}
+
+pub fn classify_r1cs_constraint(a: &[(FM31, usize)], b: &[(FM31, usize)]) -> ConstraintClass {
+    let lct_a = get_linear_combination_type(&sort_linear_combinations(a));
+    let lct_b = get_linear_combination_type(&sort_linear_combinations(b));
+    match (lct_a, lct_b) {
+        (LinearCombinationType::NULLABLE, _) | (_, LinearCombinationType::NULLABLE) => {
+            ConstraintClass::Addition
+        }
+        (LinearCombinationType::CONSTANT(_), LinearCombinationType::CONSTANT(_)) => {
+            ConstraintClass::ConstantFold
+        }
+        (LinearCombinationType::CONSTANT(_), _) | (_, LinearCombinationType::CONSTANT(_)) => {
+            ConstraintClass::Equal
+        }
+        _ => ConstraintClass::Multiplication,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClassExpansion {
+    pub constraints: usize,
+    pub rows: usize,
+}
+
+// How many rows each constraint expanded into. The rows of a witness variable count towards the
+// constraint that first uses it, and `initial_rows` are the zero row and the instance variables.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpansionReport {
+    pub initial_rows: usize,
+    pub addition: ClassExpansion,
+    pub equal: ClassExpansion,
+    pub constant_fold: ClassExpansion,
+    pub multiplication: ClassExpansion,
+    pub rows_per_constraint: Vec<u32>,
+}
+
+impl ExpansionReport {
+    pub fn class(&self, class: ConstraintClass) -> &ClassExpansion {
+        match class {
+            ConstraintClass::Addition => &self.addition,
+            ConstraintClass::Equal => &self.equal,
+            ConstraintClass::ConstantFold => &self.constant_fold,
+            ConstraintClass::Multiplication => &self.multiplication,
+        }
+    }
+
+    fn class_mut(&mut self, class: ConstraintClass) -> &mut ClassExpansion {
+        match class {
+            ConstraintClass::Addition => &mut self.addition,
+            ConstraintClass::Equal => &mut self.equal,
+            ConstraintClass::ConstantFold => &mut self.constant_fold,
+            ConstraintClass::Multiplication => &mut self.multiplication,
+        }
+    }
+
+    // The `n` constraints that expanded into the most rows, largest first.
+    pub fn largest(&self, n: usize) -> Vec<(usize, u32)> {
+        let mut constraints = self
+            .rows_per_constraint
+            .iter()
+            .copied()
+            .enumerate()
+            .collect::<Vec<_>>();
+        constraints.sort_by_key(|&(constraint, rows)| (std::cmp::Reverse(rows), constraint));
+        constraints.truncate(n);
+        constraints
+    }
+}
+
+impl Display for ExpansionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "initial rows: {}", self.initial_rows)?;
+        for (name, class) in [
+            ("addition", &self.addition),
+            ("equal", &self.equal),
+            ("constant fold", &self.constant_fold),
+            ("multiplication", &self.multiplication),
+        ] {
+            writeln!(
+                f,
+                "{}: {} constraints, {} rows",
+                name, class.constraints, class.rows
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// What `check_circuit` found, from the R1CS to the lowered circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckReport {
+    pub num_constraints: usize,
+    pub num_rows: usize,
+    pub unsatisfied_constraints: Vec<UnsatisfiedConstraint>,
+    pub unsatisfied_row: Option<UnsatisfiedRow>,
+    pub logup_satisfied: bool,
+}
+
+impl CheckReport {
+    pub fn is_satisfied(&self) -> bool {
+        self.unsatisfied_constraints.is_empty()
+            && self.unsatisfied_row.is_none()
+            && self.logup_satisfied
+    }
+}
+
+impl Display for CheckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} R1CS constraints lowered to {} rows",
+            self.num_constraints, self.num_rows
+        )?;
+        for err in self.unsatisfied_constraints.iter() {
+            writeln!(f, "{}", err)?;
+        }
+        if let Some(err) = &self.unsatisfied_row {
+            writeln!(f, "{}", err)?;
+        }
+        if !self.logup_satisfied {
+            writeln!(f, "logup sum is not zero")?;
+        }
+        write!(
+            f,
+            "{}",
+            if self.is_satisfied() {
+                "satisfied"
+            } else {
+                "unsatisfied"
+            }
+        )
+    }
+}
+
+// A preflight for a witness without paying for a proof: synthesizes the constraints in
+// `Mode::CHECK`, evaluates every R1CS constraint, lowers them and checks the rows and the logup
+// sum of the circuit, with the challenges drawn from `Circuit::logup_channel`.
+//
+// The lowering takes the values of the variables it defines from the gates, so a circuit can be
+// satisfied even though the R1CS witness is not.
+pub fn check_circuit<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+) -> Result<(ConversionResult, CheckReport), ConversionError> {
+    let (mut output, mut allocator, matrices) = synthesize(
+        circuit,
+        Mode::CHECK,
+        FastHashMap::default(),
+        &ConversionOptions::default(),
+    )?;
+
+    let mut unsatisfied_constraints = vec![];
+    for (constraint, ((a, b), c)) in matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter())
+        .enumerate()
+    {
+        let value = evaluate_r1cs_constraint(&allocator.assignments, a, b, c);
+        if !value.is_zero() {
+            unsatisfied_constraints.push(UnsatisfiedConstraint { constraint, value });
+        }
+        process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+    }
+    output.mode = Mode::CHECK;
+
+    let mut channel = output.logup_channel(&output.input_maps);
+    let report = CheckReport {
+        num_constraints: matrices.a.len(),
+        num_rows: output.num_rows,
+        unsatisfied_constraints,
+        unsatisfied_row: output.check_satisfied().err(),
+        logup_satisfied: output.is_logup_satisfied_with_channel(&mut channel, &output.input_maps),
+    };
+    Ok((ConversionResult::new(output, allocator), report))
+}
+
+// Like `generate_circuit`, also reporting how many rows each class of constraints expanded into.
+pub fn generate_circuit_with_report<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    mode: Mode,
+) -> Result<(ConversionResult, ExpansionReport), ConversionError> {
+    let (mut output, mut allocator, matrices) = synthesize(
+        circuit,
+        mode,
+        FastHashMap::default(),
+        &ConversionOptions::default(),
+    )?;
+
+    let mut report = ExpansionReport {
+        initial_rows: output.num_rows,
+        rows_per_constraint: Vec::with_capacity(matrices.a.len()),
+        ..Default::default()
+    };
+    for ((a, b), c) in matrices
+        .a
+        .iter()
+        .zip(matrices.b.iter())
+        .zip(matrices.c.iter())
+    {
+        let num_rows = output.num_rows;
+        process_r1cs_constraint(&mut output, &mut allocator, a, b, c);
+        let rows = output.num_rows - num_rows;
+
+        let class = report.class_mut(classify_r1cs_constraint(a, b));
+        class.constraints += 1;
+        class.rows += rows;
+        report.rows_per_constraint.push(rows as u32);
+    }
+
+    Ok((ConversionResult::new(output, allocator), report))
+}
+
+// Synthesizes the constraints and starts the circuit with the instance variables. The values of
+// the variables and the coefficients must be canonical.
+fn synthesize<C: ConstraintSynthesizer<FM31>>(
+    circuit: C,
+    mode: Mode,
+    names: FastHashMap<usize, String>,
+    options: &ConversionOptions,
+) -> Result<(Circuit, OnDemandAllocator, ConstraintMatrices<FM31>), ConversionError> {
     let cs = ConstraintSystem::new_ref();
-    cs.set_optimization_goal(OptimizationGoal::Weight);
+    cs.set_optimization_goal(options.optimization_goal);
     if mode == Mode::INDEX {
         cs.set_mode(SynthesisMode::Setup);
     } else {
@@ -83,59 +785,258 @@ pub fn generate_circuit<C: ConstraintSynthesizer<FM31>>(
     // copy-and-paste the values
     let num_variables = cs.num_instance_variables() + cs.num_witness_variables();
 
-    let mut assignments = Vec::<M31>::with_capacity(num_variables);
-    if mode == Mode::PROVE {
-        for elem in cs.borrow().unwrap().instance_assignment.iter() {
-            assignments.push(to_m31(elem));
-        }
-        for elem in cs.borrow().unwrap().witness_assignment.iter() {
-            assignments.push(to_m31(elem));
-        }
-    } else {
-        assignments.resize(num_variables, M31::zero());
+    let mut assignments = vec![M31::zero(); num_variables];
+    if mode.has_witness() {
+        let system = cs.borrow().unwrap();
+        let num_instance = system.instance_assignment.len();
+        let (instance, witness) = assignments.split_at_mut(num_instance);
+        let non_canonical = |idx, error| NonCanonicalValue {
+            location: ValueLocation::Variable(idx),
+            error,
+        };
+        try_to_m31_slice_into(&system.instance_assignment, instance)
+            .map_err(|(i, error)| non_canonical(i, error))?;
+        try_to_m31_slice_into(&system.witness_assignment, witness)
+            .map_err(|(i, error)| non_canonical(num_instance + i, error))?;
     }
 
-    let mut allocator = OnDemandAllocator::new(assignments, cs.num_instance_variables());
+    let mut allocator = OnDemandAllocator::new(assignments, cs.num_instance_variables())
+        .with_names(names)
+        .with_outline_threshold(options.new_variable_threshold)
+        .with_boolean_rows(options.boolean_rows);
 
+    // the budget is checked by the caller as the constraints are lowered, rather than by
+    // `next_row`, which would panic
     let mut output = Circuit::new();
-
     for i in 0..cs.num_instance_variables() {
         allocator.get(&mut output, i);
     }
+    if let Some(log_size) = options.max_log_size {
+        output.check_log_size(log_size)?;
+    }
 
     let matrices = cs.to_matrices().unwrap();
+    check_coefficients(&matrices)?;
 
-    // witness values layout
-    // - zero_var
-    // - one_var
-    // - instance_vars
-    // - witness_vars
-
-    for ((a, b), c) in matrices
+    // every constant row comes from a term of the one variable, so their count bounds the number
+    // of distinct constants
+    let num_constant_terms = matrices
         .a
         .iter()
-        .zip(matrices.b.iter())
-        .zip(matrices.c.iter())
-    {
-        let lct_a = get_linear_combination_type(a);
-        let lct_b = get_linear_combination_type(b);
-
-        if lct_a == LinearCombinationType::NULLABLE || lct_b == LinearCombinationType::NULLABLE {
-            let c = sort_linear_combinations(c);
-            process_r1cs_addition_constraint(&mut output, &mut allocator, &c);
-        } else if let LinearCombinationType::CONSTANT(a_constant) = lct_a {
-            process_r1cs_equal_constraint(&mut output, &mut allocator, b, a_constant, c);
-        } else if let LinearCombinationType::CONSTANT(b_constant) = lct_b {
-            process_r1cs_equal_constraint(&mut output, &mut allocator, a, b_constant, c);
-        } else {
-            let a = sort_linear_combinations(a);
-            let b = sort_linear_combinations(b);
-            let c = sort_linear_combinations(c);
-            process_r1cs_multiplication_constraint(&mut output, &mut allocator, &a, &b, &c);
+        .chain(matrices.b.iter())
+        .chain(matrices.c.iter())
+        .flatten()
+        .filter(|(_, idx)| *idx == 0)
+        .count();
+    output.reserve_constants(num_constant_terms);
+
+    Ok((output, allocator, matrices))
+}
+
+pub fn process_r1cs_constraint(
+    circuit: &mut Circuit,
+    allocator: &mut OnDemandAllocator,
+    a: &[(FM31, usize)],
+    b: &[(FM31, usize)],
+    c: &[(FM31, usize)],
+) {
+    let a = sort_linear_combinations(a);
+    let b = sort_linear_combinations(b);
+    let c = sort_linear_combinations(c);
+
+    if allocator.boolean_rows {
+        if let Some(idx) = boolean_variable(&a, &b, &c) {
+            if !allocator.is_allocated(idx) {
+                let wire = circuit.new_boolean(allocator.assignments.get(idx)).wire();
+                allocator.set_allocated(circuit, idx, wire);
+                return;
+            }
+        }
+    }
+
+    let lct_a = get_linear_combination_type(&a);
+    let lct_b = get_linear_combination_type(&b);
+
+    if lct_a == LinearCombinationType::NULLABLE || lct_b == LinearCombinationType::NULLABLE {
+        process_r1cs_addition_constraint(circuit, allocator, &c);
+    } else if let LinearCombinationType::CONSTANT(a_constant) = lct_a {
+        process_r1cs_equal_constraint(circuit, allocator, &b, a_constant, &c);
+    } else if let LinearCombinationType::CONSTANT(b_constant) = lct_b {
+        process_r1cs_equal_constraint(circuit, allocator, &a, b_constant, &c);
+    } else {
+        process_r1cs_multiplication_constraint(circuit, allocator, &a, &b, &c);
+    }
+}
+
+// The variable that a sorted constraint restricts to zero or one, if it is of the form
+// `(k * x) * (l * x - l) = 0` in either order, or `(k * x) * (l * x) = k * l * x`.
+fn boolean_variable(
+    a: &[(FM31, usize)],
+    b: &[(FM31, usize)],
+    c: &[(FM31, usize)],
+) -> Option<usize> {
+    let single = |lc: &[(FM31, usize)]| match lc {
+        &[(coeff, idx)] if idx != 0 => Some((coeff, idx)),
+        _ => None,
+    };
+    let shifted = |lc: &[(FM31, usize)]| match lc {
+        &[(k, 0), (coeff, idx)] if idx != 0 && k == -coeff => Some(idx),
+        _ => None,
+    };
+
+    if c.is_empty() {
+        match (single(a), single(b)) {
+            (Some((_, x)), None) if shifted(b) == Some(x) => Some(x),
+            (None, Some((_, x))) if shifted(a) == Some(x) => Some(x),
+            _ => None,
         }
+    } else {
+        let ((k, x), (l, y), (m, z)) = (single(a)?, single(b)?, single(c)?);
+        (x == y && y == z && m == k * l).then_some(x)
     }
+}
+
+// The variables that processing the constraint allocates if they are not allocated yet: those left
+// after merging in the linear combinations that `reduce_coefs` goes through, which include the one
+// that may be passed to `set_allocated`.
+fn touched_variables(a: &[(FM31, usize)], b: &[(FM31, usize)], c: &[(FM31, usize)]) -> Vec<usize> {
+    let a = sort_linear_combinations(a);
+    let b = sort_linear_combinations(b);
+    let c = sort_linear_combinations(c);
+    let nullable = get_linear_combination_type(&a) == LinearCombinationType::NULLABLE
+        || get_linear_combination_type(&b) == LinearCombinationType::NULLABLE;
+    let (a, b) = if nullable {
+        (&[][..], &[][..])
+    } else {
+        (&a[..], &b[..])
+    };
+    a.iter()
+        .chain(b)
+        .chain(c.iter())
+        .filter(|(_, idx)| *idx != 0)
+        .map(|&(_, idx)| idx)
+        .collect()
+}
+
+// Processes the constraints like `process_r1cs_constraint` on each of them in turn, and gives the
+// same circuit, but builds partitions of them on separate threads.
+//
+// A first pass finds the constraint that first allocates each variable. Every partition is then
+// built as a `Gadget` whose inputs are the variables allocated before it, with the variables
+// renumbered in the same order so that the lowering makes the same choices, and the gadgets are
+// instantiated in order. The instance variables must already be allocated.
+pub fn process_r1cs_constraints_parallel(
+    circuit: &mut Circuit,
+    allocator: &mut OnDemandAllocator,
+    a: &[Vec<(FM31, usize)>],
+    b: &[Vec<(FM31, usize)>],
+    c: &[Vec<(FM31, usize)>],
+) {
+    assert!(
+        (0..allocator.num_input).all(|idx| allocator.is_allocated(idx)),
+        "the instance variables must be allocated first"
+    );
+    assert!(a.len() == b.len() && b.len() == c.len());
+    let constraints = a
+        .iter()
+        .zip(b)
+        .zip(c)
+        .map(|((a, b), c)| [a, b, c])
+        .collect::<Vec<_>>();
+    let num_constraints = constraints.len();
+
+    let mut first_use = vec![usize::MAX; allocator.assignments.len()];
+    for (i, [a, b, c]) in constraints.iter().enumerate() {
+        for idx in touched_variables(a, b, c) {
+            first_use[idx] = first_use[idx].min(i);
+        }
+    }
+
+    let partition_size = num_constraints
+        .div_ceil(4 * rayon::current_num_threads())
+        .max(1);
+    let allocator_ref = &*allocator;
+    let segments = (0..num_constraints)
+        .step_by(partition_size)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|start| {
+            let end = (start + partition_size).min(num_constraints);
+
+            let partition = &constraints[start..end];
+
+            let mut variables = vec![0];
+            for [a, b, c] in partition.iter() {
+                variables.extend(
+                    a.iter()
+                        .chain(b.iter())
+                        .chain(c.iter())
+                        .map(|&(_, idx)| idx),
+                );
+            }
+            variables.sort_unstable();
+            variables.dedup();
+            let local = |idx: usize| variables.binary_search(&idx).unwrap();
+
+            let allocated_before =
+                |idx: usize| allocator_ref.is_allocated(idx) || first_use[idx] < start;
+            let inputs = variables[1..]
+                .iter()
+                .copied()
+                .filter(|&idx| allocated_before(idx))
+                .collect::<Vec<_>>();
+            let outputs = variables[1..]
+                .iter()
+                .copied()
+                .filter(|&idx| !allocated_before(idx) && first_use[idx] < end)
+                .collect::<Vec<_>>();
 
-    Ok(output)
+            let input_values = inputs
+                .iter()
+                .map(|&idx| allocator_ref.assignments.get(idx))
+                .collect::<Vec<_>>();
+            let gadget = Gadget::new(&input_values, |segment, input_wires| {
+                let assignments = variables
+                    .iter()
+                    .map(|&idx| allocator_ref.assignments.get(idx))
+                    .collect::<Vec<_>>();
+                let mut local_allocator = OnDemandAllocator::new(assignments, 0)
+                    .with_boolean_rows(allocator_ref.boolean_rows);
+                // the one variable is only ever checked by `is_allocated`
+                local_allocator.mapping.insert(0, Wire::ONE);
+                for (&idx, &wire) in inputs.iter().zip(input_wires.iter()) {
+                    local_allocator.mapping.insert(local(idx), wire);
+                }
+
+                let remap = |lc: &[(FM31, usize)]| {
+                    lc.iter()
+                        .map(|&(coeff, idx)| (coeff, local(idx)))
+                        .collect::<Vec<_>>()
+                };
+                for [a, b, c] in partition.iter() {
+                    let (a, b, c) = (remap(a), remap(b), remap(c));
+                    process_r1cs_constraint(segment, &mut local_allocator, &a, &b, &c);
+                }
+
+                outputs
+                    .iter()
+                    .map(|&idx| local_allocator.mapping[&local(idx)])
+                    .collect()
+            });
+            (inputs, outputs, gadget)
+        })
+        .collect::<Vec<_>>();
+
+    for (inputs, outputs, gadget) in segments {
+        let input_wires = inputs
+            .iter()
+            .map(|&idx| allocator.get(circuit, idx))
+            .collect::<Vec<_>>();
+        let output_wires = circuit.instantiate(&gadget, &input_wires);
+        for (idx, wire) in outputs.into_iter().zip(output_wires) {
+            allocator.set_allocated(circuit, idx, wire);
+        }
+    }
 }
 
 pub fn process_r1cs_equal_constraint(
@@ -162,7 +1063,7 @@ pub fn process_r1cs_equal_constraint(
         if !c[0].0.is_one() {
             v = circuit.mul_by_constant(v, to_m31(&c[0].0.inverse().unwrap()));
         }
-        allocator.set_allocated(c[0].1, v);
+        allocator.set_allocated(circuit, c[0].1, v);
     } else {
         let c = reduce_coefs(circuit, allocator, c);
         let c_neg = circuit.neg(c);
@@ -171,30 +1072,76 @@ pub fn process_r1cs_equal_constraint(
     }
 }
 
+// Sorts the terms by variable and merges the terms of the same variable, which some R1CS outputs
+// repeat, dropping those whose coefficients cancel out.
 pub fn sort_linear_combinations(lin_com: &[(FM31, usize)]) -> Vec<(FM31, usize)> {
     let mut lin_com = lin_com.to_vec();
     lin_com.sort_unstable_by(|&(_, a_idx), &(_, b_idx)| a_idx.cmp(&b_idx));
-    lin_com
+
+    let mut merged: Vec<(FM31, usize)> = Vec::with_capacity(lin_com.len());
+    for (coeff, idx) in lin_com {
+        match merged.last_mut() {
+            Some(last) if last.1 == idx => last.0 += coeff,
+            _ => merged.push((coeff, idx)),
+        }
+    }
+    merged.retain(|(coeff, _)| !coeff.is_zero());
+    merged
 }
 
 pub fn reduce_coefs(
     circuit: &mut Circuit,
     allocator: &mut OnDemandAllocator,
     c: &[(FM31, usize)],
-) -> usize {
+) -> Wire {
     let mut k = FM31::zero();
     let mut cs = vec![];
 
+    // repeated variables are merged when they are next to each other, as after
+    // `sort_linear_combinations`
     for &(coeff, idx) in c.iter() {
         if idx == 0 {
             k += coeff;
-        } else if !coeff.is_zero() {
+        } else if let Some(last) = cs.last_mut().filter(|(last_idx, _)| *last_idx == idx) {
+            last.1 += coeff;
+        } else {
             cs.push((idx, coeff));
         }
     }
+    cs.retain(|(_, coeff)| !coeff.is_zero());
 
     if cs.len() == 0 {
-        return 0;
+        return if k.is_zero() {
+            Wire::ZERO
+        } else {
+            circuit.new_constant(to_m31(&k))
+        };
+    }
+
+    let outline = allocator
+        .outline_threshold
+        .is_some_and(|threshold| cs.len() >= threshold);
+    // outlined linear combinations are lowered with their first coefficient scaled to one, so
+    // that their multiples, such as their negation, share the wire and only add a row to scale it
+    let scale = if outline { cs[0].1 } else { FM31::one() };
+    if !scale.is_one() {
+        let scale_inv = scale.inverse().unwrap();
+        for (_, coeff) in cs.iter_mut() {
+            *coeff *= scale_inv;
+        }
+        k *= scale_inv;
+    }
+    let scaled = |circuit: &mut Circuit, wire: Wire| {
+        if scale.is_one() {
+            wire
+        } else {
+            circuit.mul_by_constant(wire, to_m31(&scale))
+        }
+    };
+    if outline {
+        if let Some(&wire) = allocator.outlined.get(&(cs.clone(), k)) {
+            return scaled(circuit, wire);
+        }
     }
 
     let mut sum = allocator.get(circuit, cs[0].0);
@@ -215,7 +1162,10 @@ pub fn reduce_coefs(
         sum = circuit.add(sum, constant);
     }
 
-    sum
+    if outline {
+        allocator.outlined.insert((cs, k), sum);
+    }
+    scaled(circuit, sum)
 }
 
 pub fn process_r1cs_addition_constraint(
@@ -242,7 +1192,7 @@ pub fn process_r1cs_multiplication_constraint(
         if !c[0].0.is_one() {
             v = circuit.mul_by_constant(v, to_m31(&c[0].0.inverse().unwrap()));
         }
-        allocator.set_allocated(c[0].1, v);
+        allocator.set_allocated(circuit, c[0].1, v);
     } else {
         let c = reduce_coefs(circuit, allocator, c);
 
@@ -1,9 +1,21 @@
-use crate::circuit::Mode;
-use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use crate::circuit::cache::IndexCache;
+use crate::circuit::{Circuit, Mode, Wire};
+use crate::field::{to_m31, FM31};
+use crate::from_r1cs::r1cs_constraint_processor::{
+    check_circuit, generate_circuit, generate_circuit_parallel, generate_circuit_validated,
+    generate_circuit_with_options, generate_circuit_with_report, generate_index_cached,
+    process_r1cs_constraint, reduce_coefs, sort_linear_combinations, ConversionError,
+    ConversionOptions, OnDemandAllocator, UnsatisfiedConstraint, ValueLocation,
+};
 use crate::from_r1cs::TestCircuit;
-use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, OptimizationGoal};
+use ark_ff::BigInt;
+use ark_relations::lc;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, OptimizationGoal, SynthesisError,
+};
 use ark_std::rand::SeedableRng;
 use ark_std::UniformRand;
+use stwo_prover::core::fields::m31::{M31, P};
 
 #[test]
 fn test_groth16_weight() {
@@ -45,12 +57,425 @@ fn test_conversion() {
     let test_circuit = TestCircuit::rand(&mut prng);
 
     for _ in 0..10 {
-        let circuit = generate_circuit(test_circuit.clone(), Mode::PROVE).unwrap();
+        let circuit = generate_circuit(test_circuit.clone(), Mode::PROVE)
+            .unwrap()
+            .circuit;
         assert!(circuit.is_constraint_satisfied());
-        assert!(circuit.is_logup_satisfied(&mut prng, &circuit.input_maps));
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
         assert_eq!(circuit.num_rows, 29265);
     }
 
-    let circuit = generate_circuit(test_circuit, Mode::INDEX).unwrap();
+    let circuit = generate_circuit(test_circuit, Mode::INDEX).unwrap().circuit;
     assert_eq!(circuit.num_rows, 29265);
 }
+
+#[test]
+fn test_conversion_parallel() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+
+    for mode in [Mode::PROVE, Mode::INDEX] {
+        let serial = generate_circuit(test_circuit.clone(), mode)
+            .unwrap()
+            .circuit;
+        let parallel = generate_circuit_parallel(test_circuit.clone(), mode)
+            .unwrap()
+            .circuit;
+        assert_eq!(parallel.num_rows, serial.num_rows);
+        assert_eq!(parallel.index(), serial.index());
+        assert_eq!(parallel.witness(), serial.witness());
+        assert_eq!(parallel.mult, serial.mult);
+        assert_eq!(parallel.input_maps, serial.input_maps);
+    }
+}
+
+#[test]
+fn test_conversion_result() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+
+    let result = generate_circuit(test_circuit, Mode::PROVE).unwrap();
+    assert_eq!(result.public_wires.len(), 33);
+    assert_eq!(result.public_wires[0].wire(), Wire(1));
+    assert_eq!(result.variable_map[&0], Wire(1));
+
+    let inputs = result
+        .public_wires
+        .iter()
+        .map(|w| (w.wire().index(), result.circuit.get_output_wire(w.wire())))
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, result.circuit.input_maps);
+    assert!(result
+        .variable_map
+        .values()
+        .all(|wire| wire.index() < result.circuit.num_rows));
+}
+
+// a * b = c and b * b = d, with a public
+struct MulCircuit {
+    a: u32,
+    b: u32,
+    c: u32,
+    d: u32,
+}
+
+impl ConstraintSynthesizer<FM31> for MulCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<FM31>) -> Result<(), SynthesisError> {
+        let a = cs.new_input_variable(|| Ok(FM31::from(self.a)))?;
+        let b = cs.new_witness_variable(|| Ok(FM31::from(self.b)))?;
+        let c = cs.new_witness_variable(|| Ok(FM31::from(self.c)))?;
+        let d = cs.new_witness_variable(|| Ok(FM31::from(self.d)))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)?;
+        cs.enforce_constraint(lc!() + b, lc!() + b, lc!() + d)
+    }
+}
+
+fn unsatisfied(circuit: MulCircuit) -> UnsatisfiedConstraint {
+    match generate_circuit_validated(circuit).err().unwrap() {
+        ConversionError::Unsatisfied(err) => err,
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_conversion_validated() {
+    let valid = MulCircuit {
+        a: 3,
+        b: 5,
+        c: 15,
+        d: 25,
+    };
+    let result = generate_circuit_validated(valid).unwrap();
+    assert!(result.circuit.is_constraint_satisfied());
+
+    let wrong_c = MulCircuit {
+        a: 3,
+        b: 5,
+        c: 16,
+        d: 25,
+    };
+    assert_eq!(
+        unsatisfied(wrong_c),
+        UnsatisfiedConstraint {
+            constraint: 0,
+            value: -M31::from(1u32),
+        }
+    );
+
+    let wrong_d = MulCircuit {
+        a: 3,
+        b: 5,
+        c: 15,
+        d: 24,
+    };
+    let err = unsatisfied(wrong_d);
+    assert_eq!(err.constraint, 1);
+    assert_eq!(err.value, M31::from(1u32));
+    assert_eq!(
+        err.to_string(),
+        "R1CS constraint 1 unsatisfied: A * B - C = 1"
+    );
+}
+
+// A witness value built unchecked, whose Montgomery limb is that of 5 plus the modulus.
+struct NonCanonicalCircuit;
+
+impl ConstraintSynthesizer<FM31> for NonCanonicalCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<FM31>) -> Result<(), SynthesisError> {
+        let a = cs.new_input_variable(|| Ok(FM31::from(3u32)))?;
+        let limb = FM31::from(5u32).0 .0[0] + P as u64;
+        let b = cs.new_witness_variable(|| Ok(FM31::new_unchecked(BigInt([limb]))))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + b)
+    }
+}
+
+#[test]
+fn test_conversion_non_canonical() {
+    let err = match generate_circuit_validated(NonCanonicalCircuit)
+        .err()
+        .unwrap()
+    {
+        ConversionError::NonCanonical(err) => err,
+        err => panic!("unexpected error: {}", err),
+    };
+    assert_eq!(err.location, ValueLocation::Variable(2));
+    assert!(err.to_string().starts_with("R1CS variable 2: "));
+
+    match generate_circuit(NonCanonicalCircuit, Mode::PROVE).err() {
+        Some(ConversionError::NonCanonical(err)) => {
+            assert_eq!(err.location, ValueLocation::Variable(2))
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+    // without values, there is nothing to reject
+    assert!(generate_circuit(NonCanonicalCircuit, Mode::INDEX).is_ok());
+}
+
+#[test]
+fn test_duplicate_variables() {
+    let f = |v: i64| FM31::from(v);
+    assert_eq!(
+        sort_linear_combinations(&[(f(2), 2), (f(1), 0), (f(3), 2), (f(1), 1), (f(-1), 1)]),
+        vec![(f(1), 0), (f(5), 2)]
+    );
+
+    // (one, x, y) with 2 * x + 3 * x = y, lowered like 5 * x = y
+    let lower = |a: &[(FM31, usize)]| {
+        let values = [1u32, 3, 15].map(M31::from).to_vec();
+        let mut allocator = OnDemandAllocator::new(values, 1);
+        let mut circuit = Circuit::new();
+        allocator.get(&mut circuit, 0);
+        process_r1cs_constraint(&mut circuit, &mut allocator, a, &[(f(1), 0)], &[(f(1), 2)]);
+        circuit
+    };
+    let merged = lower(&[(f(5), 1)]);
+    let repeated = lower(&[(f(2), 1), (f(3), 1)]);
+    assert!(repeated.is_constraint_satisfied());
+    assert_eq!(repeated.num_rows, merged.num_rows);
+    assert_eq!(repeated.index(), merged.index());
+}
+
+#[test]
+fn test_expansion_report() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+
+    let (result, report) = generate_circuit_with_report(test_circuit, Mode::PROVE).unwrap();
+    assert_eq!(result.circuit.num_rows, 29265);
+    assert_eq!(report.rows_per_constraint.len(), 3802);
+
+    let classes = [
+        report.addition,
+        report.equal,
+        report.constant_fold,
+        report.multiplication,
+    ];
+    assert_eq!(classes.iter().map(|c| c.constraints).sum::<usize>(), 3802);
+    assert_eq!(
+        report.initial_rows + classes.iter().map(|c| c.rows).sum::<usize>(),
+        result.circuit.num_rows
+    );
+
+    let largest = report.largest(3);
+    assert_eq!(largest.len(), 3);
+    assert!(largest[0].1 >= largest[1].1 && largest[1].1 >= largest[2].1);
+    assert_eq!(
+        largest[0].1,
+        report.rows_per_constraint.iter().copied().max().unwrap()
+    );
+}
+
+#[test]
+fn test_generate_index_cached() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+    let expected = generate_circuit(test_circuit.clone(), Mode::INDEX)
+        .unwrap()
+        .circuit
+        .index();
+
+    let dir = std::env::temp_dir().join(format!("circle-plonk-index-{}", std::process::id()));
+    let cache = IndexCache::new(&dir);
+    let index = generate_index_cached(test_circuit.clone(), &cache).unwrap();
+    assert_eq!(index, expected);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let cached = generate_index_cached(test_circuit, &cache).unwrap();
+    assert_eq!(cached, expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_conversion_options() {
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+
+    let expected = generate_circuit(test_circuit.clone(), Mode::PROVE)
+        .unwrap()
+        .circuit;
+    let circuit = generate_circuit_with_options(
+        test_circuit.clone(),
+        Mode::PROVE,
+        &ConversionOptions::default(),
+    )
+    .unwrap()
+    .circuit;
+    assert_eq!(circuit.index(), expected.index());
+
+    let inlined_options = ConversionOptions {
+        optimization_goal: OptimizationGoal::Constraints,
+        ..Default::default()
+    };
+    let inlined =
+        generate_circuit_with_options(test_circuit.clone(), Mode::PROVE, &inlined_options)
+            .unwrap()
+            .circuit;
+    assert!(inlined.is_constraint_satisfied());
+
+    // the linear combinations repeated by inlining are only lowered once
+    let outlined_options = ConversionOptions {
+        new_variable_threshold: Some(2),
+        ..inlined_options
+    };
+    let outlined =
+        generate_circuit_with_options(test_circuit.clone(), Mode::PROVE, &outlined_options)
+            .unwrap()
+            .circuit;
+    assert!(outlined.is_constraint_satisfied());
+    assert!(outlined.num_rows <= inlined.num_rows);
+
+    let index = generate_circuit_with_options(test_circuit.clone(), Mode::INDEX, &outlined_options)
+        .unwrap()
+        .circuit;
+    assert_eq!(index.index(), outlined.index());
+
+    // a budget too small is an error, and a large enough one is kept by the circuit
+    let log_size = expected.num_rows.next_power_of_two().ilog2();
+    let options = |max_log_size| ConversionOptions {
+        max_log_size: Some(max_log_size),
+        ..Default::default()
+    };
+    let result = generate_circuit_with_options(test_circuit.clone(), Mode::PROVE, &options(2));
+    assert!(matches!(result, Err(ConversionError::Budget(err)) if err.log_size == 2));
+    let circuit = generate_circuit_with_options(test_circuit, Mode::PROVE, &options(log_size))
+        .unwrap()
+        .circuit;
+    assert_eq!(circuit.index(), expected.index());
+    assert_eq!(circuit.max_log_size, Some(log_size));
+}
+
+#[test]
+fn test_outlined_multiples() {
+    let f = |v: i64| FM31::from(v);
+
+    // (one, x, y) with x + 2 * y + 1, its negation and three times it
+    let values = [1u32, 2, 3].map(M31::from).to_vec();
+    let mut allocator = OnDemandAllocator::new(values, 1).with_outline_threshold(Some(2));
+    let mut circuit = Circuit::new();
+    allocator.get(&mut circuit, 0);
+
+    let lc = [(f(1), 0), (f(1), 1), (f(2), 2)];
+    let wire = reduce_coefs(&mut circuit, &mut allocator, &lc);
+    assert_eq!(circuit.get_output_wire(wire), M31::from(9));
+    let num_rows = circuit.num_rows;
+    assert_eq!(reduce_coefs(&mut circuit, &mut allocator, &lc), wire);
+    assert_eq!(circuit.num_rows, num_rows);
+
+    for factor in [-1, 3] {
+        let multiple = lc.map(|(coeff, idx)| (coeff * f(factor), idx));
+        let num_rows = circuit.num_rows;
+        let wire = reduce_coefs(&mut circuit, &mut allocator, &multiple);
+        assert_eq!(circuit.num_rows, num_rows + 1);
+        assert_eq!(
+            circuit.get_output_wire(wire),
+            M31::from(9) * to_m31(&f(factor))
+        );
+    }
+    assert_eq!(allocator.outlined.len(), 1);
+    assert!(circuit.is_constraint_satisfied());
+}
+
+#[test]
+fn test_boolean_rows() {
+    let f = |v: i64| FM31::from(v);
+
+    // (one, x, y) with x * (x - 1) = 0 and y * y = y
+    let lower = |values: [u32; 3], boolean_rows: bool| {
+        let values = values.map(M31::from).to_vec();
+        let mut allocator = OnDemandAllocator::new(values, 1).with_boolean_rows(boolean_rows);
+        let mut circuit = Circuit::new();
+        circuit.mode = Mode::PROVE;
+        allocator.get(&mut circuit, 0);
+        let x_minus_one = [(f(1), 1), (f(-1), 0)];
+        process_r1cs_constraint(
+            &mut circuit,
+            &mut allocator,
+            &[(f(1), 1)],
+            &x_minus_one,
+            &[],
+        );
+        process_r1cs_constraint(
+            &mut circuit,
+            &mut allocator,
+            &[(f(2), 2)],
+            &[(f(3), 2)],
+            &[(f(6), 2)],
+        );
+        // x is allocated by now, so this one is lowered as usual
+        process_r1cs_constraint(
+            &mut circuit,
+            &mut allocator,
+            &x_minus_one,
+            &[(f(1), 1)],
+            &[],
+        );
+        circuit
+    };
+
+    let circuit = lower([1, 1, 0], true);
+    assert!(circuit.is_constraint_satisfied());
+    assert_eq!(circuit.stats().num_booleans, 2);
+    assert_eq!(circuit.validate(), Ok(()));
+    let generic = lower([1, 1, 0], false);
+    assert!(generic.is_constraint_satisfied());
+    assert_eq!(generic.stats().num_booleans, 0);
+    assert!(circuit.num_rows < generic.num_rows);
+
+    assert!(!lower([1, 2, 0], true).is_constraint_satisfied());
+    assert!(!lower([1, 1, 5], true).is_constraint_satisfied());
+
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let test_circuit = TestCircuit::rand(&mut prng);
+    let expected = generate_circuit(test_circuit.clone(), Mode::PROVE)
+        .unwrap()
+        .circuit;
+    let options = ConversionOptions {
+        boolean_rows: true,
+        ..Default::default()
+    };
+    let circuit = generate_circuit_with_options(test_circuit, Mode::PROVE, &options)
+        .unwrap()
+        .circuit;
+    assert!(circuit.is_constraint_satisfied());
+    assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    assert!(circuit.num_rows < expected.num_rows);
+}
+
+#[test]
+fn test_check_circuit() {
+    let valid = MulCircuit {
+        a: 3,
+        b: 5,
+        c: 15,
+        d: 25,
+    };
+    let (result, report) = check_circuit(valid).unwrap();
+    assert!(report.is_satisfied());
+    assert_eq!(report.num_constraints, 2);
+    assert_eq!(report.num_rows, result.circuit.num_rows);
+    assert!(result.circuit.mode == Mode::CHECK);
+
+    // c and d are defined by the gates, so only the R1CS check notices
+    let wrong = MulCircuit {
+        a: 3,
+        b: 5,
+        c: 16,
+        d: 24,
+    };
+    let (result, report) = check_circuit(wrong).unwrap();
+    assert!(result.circuit.is_constraint_satisfied());
+    assert!(!report.is_satisfied());
+    assert_eq!(
+        report
+            .unsatisfied_constraints
+            .iter()
+            .map(|err| err.constraint)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert!(report.unsatisfied_row.is_none());
+    assert!(report.logup_satisfied);
+    assert!(report
+        .to_string()
+        .contains("R1CS constraint 1 unsatisfied: A * B - C = 1"));
+}
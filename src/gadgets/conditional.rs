@@ -0,0 +1,60 @@
+use crate::circuit::{BoolWire, Circuit, Wire};
+
+// Enforces `flag * constraint == 0`, i.e., `constraint` must be zero whenever `flag` is set.
+pub fn enforce_if(circuit: &mut Circuit, flag: BoolWire, constraint: Wire) {
+    let v = circuit.mul(flag.into(), constraint);
+    circuit.zero_test(v);
+}
+
+pub fn enforce_equal_if(circuit: &mut Circuit, flag: BoolWire, a: Wire, b: Wire) {
+    let b_neg = circuit.neg(b);
+    let diff = circuit.add(a, b_neg);
+    enforce_if(circuit, flag, diff);
+}
+
+pub fn enforce_equal_vec_if(circuit: &mut Circuit, flag: BoolWire, a: &[Wire], b: &[Wire]) {
+    assert_eq!(a.len(), b.len());
+    for (&a, &b) in a.iter().zip(b.iter()) {
+        enforce_equal_if(circuit, flag, a, b);
+    }
+}
+
+// Enforces `flag * flag - flag == 0`.
+pub fn enforce_bool(circuit: &mut Circuit, flag: Wire) -> BoolWire {
+    let flag_squared = circuit.mul(flag, flag);
+    let flag_neg = circuit.neg(flag);
+    let diff = circuit.add(flag_squared, flag_neg);
+    circuit.zero_test(diff);
+    BoolWire(flag)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::gadgets::conditional::{enforce_bool, enforce_equal_vec_if};
+    use stwo_prover::core::fields::m31::M31;
+
+    fn build(flag: u32) -> Circuit {
+        let mut circuit = Circuit::new();
+        let flag = circuit.new_input(M31::from(flag)).wire();
+        let a = [circuit.new_witness(M31::from(3)), circuit.new_witness(M31::from(5))];
+        let b = [circuit.new_witness(M31::from(3)), circuit.new_witness(M31::from(7))];
+
+        let flag = enforce_bool(&mut circuit, flag);
+        enforce_equal_vec_if(&mut circuit, flag, &a, &b);
+        circuit
+    }
+
+    #[test]
+    fn test_enforce_if() {
+        let circuit = build(0);
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        let circuit = build(1);
+        assert!(!circuit.is_constraint_satisfied());
+
+        let circuit = build(2);
+        assert!(!circuit.is_constraint_satisfied());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::circuit::{Circuit, Wire};
+use crate::gadgets::qm31::{
+    qm31_add, qm31_constant, qm31_div, qm31_inverse, qm31_mul_by_m31, qm31_sub, qm31_witness,
+    QM31Wire,
+};
+use ark_ff::Zero;
+use std::ops::Range;
+use stwo_prover::constraint_framework::logup::LookupElements;
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+// The wiring logup sum of `PlonkComponent` over wires, so that a verifier inside a circuit can
+// recompute the sum of the rows it is given and check it against the claimed sum. Each row adds
+// `1 / d(a) + 1 / d(b) - mult / d(c)`, with `d(x) = x_wire + alpha * x_val - z` as in
+// `LookupElements::combine`, and over the whole trace the sum is the one of the inputs, as
+// `claimed_sum_from_inputs` computes it. The sums of fragments of a trace add up to the sum of the
+// trace, so the rows can be checked a fragment at a time.
+//
+// The lookup elements are wires, as a recursive verifier draws them from its channel. That the
+// rows are those of the committed trace is for the caller to check, against the decommitment of
+// the trace tree.
+
+// The columns of a row that the sum reads. `op` is not one of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogupRow {
+    pub mult: Wire,
+    pub a_wire: Wire,
+    pub b_wire: Wire,
+    pub c_wire: Wire,
+    pub a_val: Wire,
+    pub b_val: Wire,
+    pub c_val: Wire,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LookupElementsWire {
+    pub z: QM31Wire,
+    pub alpha: QM31Wire,
+}
+
+pub fn lookup_elements_witness(
+    circuit: &mut Circuit,
+    elements: &LookupElements<2>,
+) -> LookupElementsWire {
+    LookupElementsWire {
+        z: qm31_witness(circuit, elements.z),
+        alpha: qm31_witness(circuit, elements.alpha),
+    }
+}
+
+// Witnesses of the rows of a trace, for a fragment of it that was opened to the verifier.
+pub fn logup_row_witnesses(
+    circuit: &mut Circuit,
+    trace: &PlonkCircuitTrace,
+    rows: Range<usize>,
+) -> Vec<LogupRow> {
+    let [mult, a_wire, b_wire, c_wire, a_val, b_val, c_val] = [
+        &trace.mult,
+        &trace.a_wire,
+        &trace.b_wire,
+        &trace.c_wire,
+        &trace.a_val,
+        &trace.b_val,
+        &trace.c_val,
+    ]
+    .map(|column| column.to_cpu()[rows.clone()].to_vec());
+    (0..rows.len())
+        .map(|i| LogupRow {
+            mult: circuit.new_witness(mult[i]),
+            a_wire: circuit.new_witness(a_wire[i]),
+            b_wire: circuit.new_witness(b_wire[i]),
+            c_wire: circuit.new_witness(c_wire[i]),
+            a_val: circuit.new_witness(a_val[i]),
+            b_val: circuit.new_witness(b_val[i]),
+            c_val: circuit.new_witness(c_val[i]),
+        })
+        .collect()
+}
+
+// `wire + alpha * value - z`
+fn denominator(
+    circuit: &mut Circuit,
+    elements: &LookupElementsWire,
+    wire: Wire,
+    value: Wire,
+) -> QM31Wire {
+    let mut combined = qm31_mul_by_m31(circuit, elements.alpha, value);
+    combined.0[0] = circuit.add(combined.0[0], wire);
+    qm31_sub(circuit, combined, elements.z)
+}
+
+// The sum of the rows of a fragment.
+pub fn logup_sum(
+    circuit: &mut Circuit,
+    elements: &LookupElementsWire,
+    rows: &[LogupRow],
+) -> QM31Wire {
+    let mut sum = qm31_constant(circuit, QM31::zero());
+    for row in rows {
+        let a = denominator(circuit, elements, row.a_wire, row.a_val);
+        let b = denominator(circuit, elements, row.b_wire, row.b_val);
+        let c = denominator(circuit, elements, row.c_wire, row.c_val);
+
+        let a_inv = qm31_inverse(circuit, a);
+        let b_inv = qm31_inverse(circuit, b);
+        let mut mult = QM31Wire([Wire::ZERO; 4]);
+        mult.0[0] = row.mult;
+        let c_term = qm31_div(circuit, mult, c);
+
+        sum = qm31_add(circuit, sum, a_inv);
+        sum = qm31_add(circuit, sum, b_inv);
+        sum = qm31_sub(circuit, sum, c_term);
+    }
+    sum
+}
+
+// `claimed_sum_from_inputs` over wires, the sum that the rows of the whole trace add up to.
+pub fn logup_inputs_sum(
+    circuit: &mut Circuit,
+    elements: &LookupElementsWire,
+    inputs: &[(Wire, Wire)],
+) -> QM31Wire {
+    let mut sum = qm31_constant(circuit, QM31::zero());
+    for &(idx, value) in inputs {
+        let d = denominator(circuit, elements, idx, value);
+        let inv = qm31_inverse(circuit, d);
+        sum = qm31_add(circuit, sum, inv);
+    }
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::gadgets::logup::{
+        logup_inputs_sum, logup_row_witnesses, logup_sum, lookup_elements_witness,
+    };
+    use crate::gadgets::qm31::{qm31_add, qm31_value};
+    use crate::proof_system::{claimed_sum_from_inputs, HasherConfig, Sha256Config};
+    use ark_ff::One;
+    use stwo_prover::constraint_framework::logup::LookupElements;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+    #[test]
+    fn test_logup_sum() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        let d = circuit.add(c, a);
+        circuit.expose(d);
+        circuit.pad_minimal(LOG_N_LANES);
+        let trace = PlonkCircuitTrace::try_from(&circuit).unwrap();
+        let elements = LookupElements::<2>::draw(&mut Sha256Config::channel());
+        let expected = claimed_sum_from_inputs(&elements, &circuit.input_maps);
+
+        let mut verifier = Circuit::new();
+        verifier.new_input(M31::one());
+        let lookup_elements = lookup_elements_witness(&mut verifier, &elements);
+
+        // the fragments add up to the sum of the inputs
+        let half = circuit.num_rows / 2;
+        let first = logup_row_witnesses(&mut verifier, &trace, 0..half);
+        let second = logup_row_witnesses(&mut verifier, &trace, half..circuit.num_rows);
+        let first = logup_sum(&mut verifier, &lookup_elements, &first);
+        let second = logup_sum(&mut verifier, &lookup_elements, &second);
+        let sum = qm31_add(&mut verifier, first, second);
+        assert_eq!(qm31_value(&verifier, sum), expected);
+
+        let inputs = circuit
+            .input_maps
+            .iter()
+            .map(|&(idx, v)| {
+                (
+                    verifier.new_witness(M31::from(idx)),
+                    verifier.new_witness(v),
+                )
+            })
+            .collect::<Vec<_>>();
+        let inputs_sum = logup_inputs_sum(&mut verifier, &lookup_elements, &inputs);
+        assert_eq!(qm31_value(&verifier, inputs_sum), expected);
+        assert!(verifier.is_constraint_satisfied());
+    }
+}
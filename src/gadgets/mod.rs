@@ -0,0 +1,12 @@
+pub mod conditional;
+
+pub mod logup;
+
+pub mod qm31;
+
+pub mod smt;
+
+pub mod std;
+
+#[cfg(feature = "u256")]
+pub mod u256;
@@ -0,0 +1,175 @@
+use crate::circuit::{Circuit, Wire};
+use crate::gadgets::std::{constant, enforce_equal, hash_to_field, sub};
+use ark_ff::{One, Zero};
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::fields::FieldExpOps;
+
+// Arithmetic over `QM31 = CM31[u] / (u^2 - (2 + i))`, with `CM31 = M31[i] / (i^2 + 1)`, the field
+// of the challenges and of the out-of-domain samples of the verifier, as a first piece of a
+// verifier inside a circuit. An element is four wires, in the order of `QM31::to_m31_array`.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QM31Wire(pub [Wire; 4]);
+
+pub fn qm31_constant(circuit: &mut Circuit, value: QM31) -> QM31Wire {
+    QM31Wire(value.to_m31_array().map(|v| constant(circuit, v)))
+}
+
+pub fn qm31_witness(circuit: &mut Circuit, value: QM31) -> QM31Wire {
+    QM31Wire(value.to_m31_array().map(|v| circuit.new_witness(v)))
+}
+
+pub fn qm31_value(circuit: &Circuit, a: QM31Wire) -> QM31 {
+    QM31::from_m31_array(a.0.map(|wire| circuit.get_output_wire(wire)))
+}
+
+pub fn qm31_add(circuit: &mut Circuit, a: QM31Wire, b: QM31Wire) -> QM31Wire {
+    QM31Wire([0, 1, 2, 3].map(|i| circuit.add(a.0[i], b.0[i])))
+}
+
+pub fn qm31_sub(circuit: &mut Circuit, a: QM31Wire, b: QM31Wire) -> QM31Wire {
+    QM31Wire([0, 1, 2, 3].map(|i| sub(circuit, a.0[i], b.0[i])))
+}
+
+// `(a0 + a1 i)(b0 + b1 i) = (a0 b0 - a1 b1) + (a0 b1 + a1 b0) i`
+fn cm31_mul(circuit: &mut Circuit, a: [Wire; 2], b: [Wire; 2]) -> [Wire; 2] {
+    let a0b0 = circuit.mul(a[0], b[0]);
+    let a1b1 = circuit.mul(a[1], b[1]);
+    let a0b1 = circuit.mul(a[0], b[1]);
+    let a1b0 = circuit.mul(a[1], b[0]);
+    [sub(circuit, a0b0, a1b1), circuit.add(a0b1, a1b0)]
+}
+
+// `(2 + i)(c0 + c1 i) = (2 c0 - c1) + (c0 + 2 c1) i`
+fn mul_by_non_residue(circuit: &mut Circuit, c: [Wire; 2]) -> [Wire; 2] {
+    let two_c0 = circuit.mul_by_constant(c[0], M31::from(2));
+    let two_c1 = circuit.mul_by_constant(c[1], M31::from(2));
+    [sub(circuit, two_c0, c[1]), circuit.add(c[0], two_c1)]
+}
+
+// `(a0 + a1 u)(b0 + b1 u) = (a0 b0 + (2 + i) a1 b1) + (a0 b1 + a1 b0) u`, over CM31
+pub fn qm31_mul(circuit: &mut Circuit, a: QM31Wire, b: QM31Wire) -> QM31Wire {
+    let [a0, a1] = [[a.0[0], a.0[1]], [a.0[2], a.0[3]]];
+    let [b0, b1] = [[b.0[0], b.0[1]], [b.0[2], b.0[3]]];
+
+    let a0b0 = cm31_mul(circuit, a0, b0);
+    let a1b1 = cm31_mul(circuit, a1, b1);
+    let a1b1 = mul_by_non_residue(circuit, a1b1);
+    let a0b1 = cm31_mul(circuit, a0, b1);
+    let a1b0 = cm31_mul(circuit, a1, b0);
+    QM31Wire([
+        circuit.add(a0b0[0], a1b1[0]),
+        circuit.add(a0b0[1], a1b1[1]),
+        circuit.add(a0b1[0], a1b0[0]),
+        circuit.add(a0b1[1], a1b0[1]),
+    ])
+}
+
+pub fn qm31_mul_by_m31(circuit: &mut Circuit, a: QM31Wire, b: Wire) -> QM31Wire {
+    QM31Wire(a.0.map(|v| circuit.mul(v, b)))
+}
+
+// The inverse of `a`, which must be nonzero. Each limb of the inverse is a hint, and their
+// product with `a` is checked to be one.
+pub fn qm31_inverse(circuit: &mut Circuit, a: QM31Wire) -> QM31Wire {
+    let inv = QM31Wire([0, 1, 2, 3].map(|i| {
+        circuit.hint(&a.0, move |v| {
+            let a = QM31::from_m31_array(v.try_into().unwrap());
+            if a.is_zero() {
+                M31::zero()
+            } else {
+                a.inverse().to_m31_array()[i]
+            }
+        })
+    }));
+    let product = qm31_mul(circuit, a, inv);
+    let one = qm31_constant(circuit, QM31::one());
+    for (v, one) in product.0.into_iter().zip(one.0) {
+        enforce_equal(circuit, v, one);
+    }
+    inv
+}
+
+// `a / b`, for a nonzero `b`.
+pub fn qm31_div(circuit: &mut Circuit, a: QM31Wire, b: QM31Wire) -> QM31Wire {
+    let b_inv = qm31_inverse(circuit, b);
+    qm31_mul(circuit, a, b_inv)
+}
+
+// `poseidon::hash_to_qm31` over wires, for the challenges of a verifier inside a circuit.
+pub fn hash_to_qm31(
+    circuit: &mut Circuit,
+    domain: M31,
+    input: &[Wire],
+    num_outputs: usize,
+) -> Vec<QM31Wire> {
+    hash_to_field(circuit, domain, input, 4 * num_outputs)
+        .chunks(4)
+        .map(|limbs| QM31Wire(limbs.try_into().unwrap()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::gadgets::qm31::{
+        hash_to_qm31, qm31_add, qm31_constant, qm31_div, qm31_inverse, qm31_mul, qm31_mul_by_m31,
+        qm31_sub, qm31_value, qm31_witness,
+    };
+    use crate::poseidon;
+    use crate::testing::random_m31;
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    #[test]
+    fn test_qm31_arithmetic() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut random = || QM31::from_m31_array([(); 4].map(|_| random_m31(&mut prng)));
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        for _ in 0..10 {
+            let (x, y, z) = (random(), random(), random());
+            let a = qm31_witness(&mut circuit, x);
+            let b = qm31_witness(&mut circuit, y);
+            let c = qm31_constant(&mut circuit, z);
+
+            let product = qm31_mul(&mut circuit, a, b);
+            assert_eq!(qm31_value(&circuit, product), x * y);
+            let sum = qm31_add(&mut circuit, product, c);
+            assert_eq!(qm31_value(&circuit, sum), x * y + z);
+            let diff = qm31_sub(&mut circuit, a, c);
+            assert_eq!(qm31_value(&circuit, diff), x - z);
+
+            let inv = qm31_inverse(&mut circuit, a);
+            assert_eq!(qm31_value(&circuit, inv), x.inverse());
+            let quotient = qm31_div(&mut circuit, b, a);
+            assert_eq!(qm31_value(&circuit, quotient), y * x.inverse());
+            let scaled = qm31_mul_by_m31(&mut circuit, a, b.0[0]);
+            assert_eq!(qm31_value(&circuit, scaled), y.to_m31_array()[0] * x);
+        }
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_hash_to_qm31() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let input = (0..5).map(M31::from).collect::<Vec<_>>();
+        let wires = input
+            .iter()
+            .map(|&v| circuit.new_witness(v))
+            .collect::<Vec<_>>();
+        let output = hash_to_qm31(&mut circuit, M31::from(3), &wires, 3)
+            .into_iter()
+            .map(|a| qm31_value(&circuit, a))
+            .collect::<Vec<_>>();
+        assert_eq!(output, poseidon::hash_to_qm31(M31::from(3), &input, 3));
+        assert!(circuit.is_constraint_satisfied());
+    }
+}
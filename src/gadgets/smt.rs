@@ -0,0 +1,276 @@
+use crate::circuit::{BoolWire, Circuit, FastHashMap, Wire};
+use crate::gadgets::std::{constant, enforce_equal, poseidon_sponge, select, sub, to_bits};
+use crate::poseidon;
+use std::array;
+use std::marker::PhantomData;
+use stwo_prover::core::fields::m31::M31;
+
+// A sparse Merkle tree over `2^depth` leaves, for state whose keys are spread over a large space,
+// such as an account tree or a UTXO set. A leaf is a digest of a value, such as the hash of an
+// account, and the zero digest is an empty leaf, so that reading the zero digest at a key shows
+// that the key is absent. A subtree without leaves hashes to the default digest of its level, and
+// the native tree only keeps the nodes that differ from it.
+//
+// Keys are below `2^depth`, with a depth of at most 30, so that a key is a wire and `to_bits`
+// splits it. Bit `i` of a key, from the least significant, is whether its node at level `i`, with
+// the leaves at level 0, is a right child.
+//
+// The tree is generic over the hash of its nodes, which a circuit computes with the same function
+// as the native tree.
+
+pub const DIGEST_LEN: usize = 8;
+pub const MAX_DEPTH: usize = 30;
+
+pub type Digest = [M31; DIGEST_LEN];
+pub type DigestWire = [Wire; DIGEST_LEN];
+
+// The parent of two nodes, natively and over wires.
+pub trait MerkleHash {
+    fn hash(left: &Digest, right: &Digest) -> Digest;
+
+    fn hash_wires(circuit: &mut Circuit, left: &DigestWire, right: &DigestWire) -> DigestWire;
+}
+
+// the domain of the nodes in the Poseidon sponge, "SMT"
+const POSEIDON_DOMAIN: u32 = 0x534d54;
+
+// A node is the Poseidon sponge of its two children, eight elements (248 bits) each.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoseidonMerkleHash;
+
+impl MerkleHash for PoseidonMerkleHash {
+    fn hash(left: &Digest, right: &Digest) -> Digest {
+        let input = [*left, *right].concat();
+        poseidon::poseidon_sponge(M31::from(POSEIDON_DOMAIN), &input, DIGEST_LEN)
+            .try_into()
+            .unwrap()
+    }
+
+    fn hash_wires(circuit: &mut Circuit, left: &DigestWire, right: &DigestWire) -> DigestWire {
+        let input = [*left, *right].concat();
+        poseidon_sponge(circuit, M31::from(POSEIDON_DOMAIN), &input, DIGEST_LEN)
+            .try_into()
+            .unwrap()
+    }
+}
+
+pub struct SparseMerkleTree<H: MerkleHash> {
+    depth: usize,
+    // the nodes that are not the defaults of their level, by level and then index in the level
+    nodes: Vec<FastHashMap<u32, Digest>>,
+    defaults: Vec<Digest>,
+    _hash: PhantomData<H>,
+}
+
+impl<H: MerkleHash> SparseMerkleTree<H> {
+    pub fn new(depth: usize) -> Self {
+        assert!(depth <= MAX_DEPTH, "the depth is above {}", MAX_DEPTH);
+        let mut defaults = vec![[M31::from(0); DIGEST_LEN]];
+        for level in 0..depth {
+            defaults.push(H::hash(&defaults[level], &defaults[level]));
+        }
+        Self {
+            depth,
+            nodes: vec![FastHashMap::default(); depth + 1],
+            defaults,
+            _hash: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn node(&self, level: usize, index: u32) -> Digest {
+        self.nodes[level]
+            .get(&index)
+            .copied()
+            .unwrap_or(self.defaults[level])
+    }
+
+    fn set_node(&mut self, level: usize, index: u32, value: Digest) {
+        if value == self.defaults[level] {
+            self.nodes[level].remove(&index);
+        } else {
+            self.nodes[level].insert(index, value);
+        }
+    }
+
+    pub fn root(&self) -> Digest {
+        self.node(self.depth, 0)
+    }
+
+    // The zero digest if the key is absent.
+    pub fn get(&self, key: u32) -> Digest {
+        self.node(0, key)
+    }
+
+    // The siblings of the nodes from the leaf of a key up to the root.
+    pub fn path(&self, key: u32) -> Vec<Digest> {
+        (0..self.depth)
+            .map(|level| self.node(level, (key >> level) ^ 1))
+            .collect()
+    }
+
+    // Sets the leaf of a key, where the zero digest removes the key.
+    pub fn insert(&mut self, key: u32, value: Digest) {
+        assert!((key as u64) < 1 << self.depth, "the key is out of the tree");
+        self.set_node(0, key, value);
+        for level in 1..=self.depth {
+            let index = key >> level;
+            let left = self.node(level - 1, 2 * index);
+            let right = self.node(level - 1, 2 * index + 1);
+            self.set_node(level, index, H::hash(&left, &right));
+        }
+    }
+}
+
+pub fn digest_constant(circuit: &mut Circuit, value: &Digest) -> DigestWire {
+    value.map(|v| constant(circuit, v))
+}
+
+pub fn digest_witness(circuit: &mut Circuit, value: &Digest) -> DigestWire {
+    value.map(|v| circuit.new_witness(v))
+}
+
+pub fn digest_value(circuit: &Circuit, a: &DigestWire) -> Digest {
+    a.map(|wire| circuit.get_output_wire(wire))
+}
+
+pub fn enforce_digest_equal(circuit: &mut Circuit, a: &DigestWire, b: &DigestWire) {
+    for (&a, &b) in a.iter().zip(b.iter()) {
+        enforce_equal(circuit, a, b);
+    }
+}
+
+// The root of the tree with `leaf` at the key of `key_bits`, given the siblings of its path. The
+// left child is selected as `node + bit * (sibling - node)`, and the right child is the other one.
+pub fn smt_root<H: MerkleHash>(
+    circuit: &mut Circuit,
+    key_bits: &[BoolWire],
+    leaf: &DigestWire,
+    path: &[DigestWire],
+) -> DigestWire {
+    assert_eq!(key_bits.len(), path.len());
+    let mut node = *leaf;
+    for (&bit, sibling) in key_bits.iter().zip(path.iter()) {
+        let left: DigestWire = array::from_fn(|i| select(circuit, bit, sibling[i], node[i]));
+        let right = array::from_fn(|i| {
+            let pair = circuit.add(node[i], sibling[i]);
+            sub(circuit, pair, left[i])
+        });
+        node = H::hash_wires(circuit, &left, &right);
+    }
+    node
+}
+
+// Checks that `value` is at `key` in the tree of `root`, where the zero digest shows that the key
+// is absent. The depth of the tree is the length of the path.
+pub fn smt_read<H: MerkleHash>(
+    circuit: &mut Circuit,
+    root: &DigestWire,
+    key: Wire,
+    value: &DigestWire,
+    path: &[DigestWire],
+) {
+    let bits = to_bits(circuit, key, path.len());
+    let computed = smt_root::<H>(circuit, &bits, value, path);
+    enforce_digest_equal(circuit, &computed, root);
+}
+
+// Checks that setting `key` from `old_value` to `new_value` turns the tree of `old_root` into the
+// tree of `new_root`: both roots are computed over the same path, which the update does not change.
+pub fn smt_update<H: MerkleHash>(
+    circuit: &mut Circuit,
+    old_root: &DigestWire,
+    key: Wire,
+    old_value: &DigestWire,
+    new_value: &DigestWire,
+    new_root: &DigestWire,
+    path: &[DigestWire],
+) {
+    let bits = to_bits(circuit, key, path.len());
+    let computed = smt_root::<H>(circuit, &bits, old_value, path);
+    enforce_digest_equal(circuit, &computed, old_root);
+    let computed = smt_root::<H>(circuit, &bits, new_value, path);
+    enforce_digest_equal(circuit, &computed, new_root);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::gadgets::smt::{
+        digest_witness, smt_read, smt_update, Digest, DigestWire, PoseidonMerkleHash,
+        SparseMerkleTree,
+    };
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    type Tree = SparseMerkleTree<PoseidonMerkleHash>;
+
+    fn digest(v: u32) -> Digest {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| M31::from(v * 8 + i))
+    }
+
+    fn path_witness(circuit: &mut Circuit, path: &[Digest]) -> Vec<DigestWire> {
+        path.iter().map(|d| digest_witness(circuit, d)).collect()
+    }
+
+    #[test]
+    fn test_sparse_merkle_tree() {
+        let mut tree = Tree::new(8);
+        let empty_root = tree.root();
+        tree.insert(3, digest(1));
+        tree.insert(200, digest(2));
+        assert_eq!(tree.get(3), digest(1));
+        assert_ne!(tree.root(), empty_root);
+        // removing a key restores the tree without it
+        let expected = tree.root();
+        tree.insert(77, digest(3));
+        tree.insert(77, [M31::from(0); 8]);
+        assert_eq!(tree.root(), expected);
+        assert_eq!(tree.nodes[0].len(), 2);
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let old_root = digest_witness(&mut circuit, &tree.root());
+
+        // a read of a key and of an absent one
+        let key = circuit.new_witness(M31::from(200));
+        let value = digest_witness(&mut circuit, &digest(2));
+        let path = path_witness(&mut circuit, &tree.path(200));
+        smt_read::<PoseidonMerkleHash>(&mut circuit, &old_root, key, &value, &path);
+        let key = circuit.new_witness(M31::from(77));
+        let zero = digest_witness(&mut circuit, &[M31::from(0); 8]);
+        let path = path_witness(&mut circuit, &tree.path(77));
+        smt_read::<PoseidonMerkleHash>(&mut circuit, &old_root, key, &zero, &path);
+
+        // an update of a key
+        let path = path_witness(&mut circuit, &tree.path(3));
+        tree.insert(3, digest(4));
+        let key = circuit.new_witness(M31::from(3));
+        let old_value = digest_witness(&mut circuit, &digest(1));
+        let new_value = digest_witness(&mut circuit, &digest(4));
+        let new_root = digest_witness(&mut circuit, &tree.root());
+        smt_update::<PoseidonMerkleHash>(
+            &mut circuit,
+            &old_root,
+            key,
+            &old_value,
+            &new_value,
+            &new_root,
+            &path,
+        );
+        assert!(circuit.is_constraint_satisfied());
+
+        // a wrong old value
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let root = digest_witness(&mut circuit, &tree.root());
+        let key = circuit.new_witness(M31::from(3));
+        let value = digest_witness(&mut circuit, &digest(1));
+        let path = path_witness(&mut circuit, &tree.path(3));
+        smt_read::<PoseidonMerkleHash>(&mut circuit, &root, key, &value, &path);
+        assert!(!circuit.is_constraint_satisfied());
+    }
+}
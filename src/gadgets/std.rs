@@ -0,0 +1,454 @@
+use crate::circuit::{BoolWire, Circuit, Wire};
+use crate::poseidon::{sponge_blocks, PoseidonParams, SPONGE_RATE, SPONGE_WIDTH};
+use ark_ff::{One, Zero};
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::FieldExpOps;
+
+pub use crate::gadgets::conditional::{
+    enforce_bool, enforce_equal_if, enforce_equal_vec_if, enforce_if,
+};
+
+// The gadgets that applications build on, in one place: arithmetic, boolean logic, comparisons
+// and hashing. Each takes the circuit first and returns the wires it creates, and every value
+// that is computed by a hint is also checked by gates, so that the gadgets are sound for any
+// witness.
+//
+// As with `Circuit::new_constant`, the circuit must have the constant one at `Wire::ONE`, which is
+// the case for circuits built by the builder or converted from R1CS.
+
+// Arithmetic.
+
+pub fn constant(circuit: &mut Circuit, value: M31) -> Wire {
+    if value.is_zero() {
+        Wire::ZERO
+    } else {
+        circuit.new_constant(value)
+    }
+}
+
+pub fn add_constant(circuit: &mut Circuit, a: Wire, value: M31) -> Wire {
+    if value.is_zero() {
+        return a;
+    }
+    let c = circuit.new_constant(value);
+    circuit.add(a, c)
+}
+
+pub fn sub(circuit: &mut Circuit, a: Wire, b: Wire) -> Wire {
+    let b_neg = circuit.neg(b);
+    circuit.add(a, b_neg)
+}
+
+pub fn sum(circuit: &mut Circuit, values: &[Wire]) -> Wire {
+    values
+        .iter()
+        .copied()
+        .reduce(|acc, v| circuit.add(acc, v))
+        .unwrap_or(Wire::ZERO)
+}
+
+pub fn enforce_equal(circuit: &mut Circuit, a: Wire, b: Wire) {
+    let diff = sub(circuit, a, b);
+    circuit.zero_test(diff);
+}
+
+fn inverse_or_zero(v: &[M31]) -> M31 {
+    if v[0].is_zero() {
+        M31::zero()
+    } else {
+        v[0].inverse()
+    }
+}
+
+// The inverse of `a`, which must be nonzero.
+pub fn inverse(circuit: &mut Circuit, a: Wire) -> Wire {
+    let inv = circuit.hint(&[a], inverse_or_zero);
+    let product = circuit.mul(a, inv);
+    let one = circuit.new_constant(M31::one());
+    enforce_equal(circuit, product, one);
+    inv
+}
+
+// `a / b`, for a nonzero `b`.
+pub fn div(circuit: &mut Circuit, a: Wire, b: Wire) -> Wire {
+    let b_inv = inverse(circuit, b);
+    circuit.mul(a, b_inv)
+}
+
+// Boolean logic.
+
+pub fn not(circuit: &mut Circuit, a: BoolWire) -> BoolWire {
+    let one = circuit.new_constant(M31::one());
+    BoolWire(sub(circuit, one, a.into()))
+}
+
+pub fn and(circuit: &mut Circuit, a: BoolWire, b: BoolWire) -> BoolWire {
+    BoolWire(circuit.mul(a.into(), b.into()))
+}
+
+// `a + b - ab`
+pub fn or(circuit: &mut Circuit, a: BoolWire, b: BoolWire) -> BoolWire {
+    let ab = circuit.mul(a.into(), b.into());
+    let a_plus_b = circuit.add(a.into(), b.into());
+    BoolWire(sub(circuit, a_plus_b, ab))
+}
+
+// `a + b - 2ab`
+pub fn xor(circuit: &mut Circuit, a: BoolWire, b: BoolWire) -> BoolWire {
+    let ab = circuit.mul(a.into(), b.into());
+    let two_ab = circuit.mul_by_constant(ab, M31::from(2));
+    let a_plus_b = circuit.add(a.into(), b.into());
+    BoolWire(sub(circuit, a_plus_b, two_ab))
+}
+
+// `a` if `flag` is set and `b` otherwise, as `b + flag * (a - b)`.
+pub fn select(circuit: &mut Circuit, flag: BoolWire, a: Wire, b: Wire) -> Wire {
+    let diff = sub(circuit, a, b);
+    let masked = circuit.mul(flag.into(), diff);
+    circuit.add(b, masked)
+}
+
+// Comparisons.
+
+// Whether `a` is zero: with `inv` the inverse of `a` (or zero), `1 - a * inv` is one exactly
+// when `a` is zero, and `a * (1 - a * inv) == 0` rules out any other `inv`.
+pub fn is_zero(circuit: &mut Circuit, a: Wire) -> BoolWire {
+    let inv = circuit.hint(&[a], inverse_or_zero);
+    let product = circuit.mul(a, inv);
+    let one = circuit.new_constant(M31::one());
+    let result = sub(circuit, one, product);
+    let check = circuit.mul(a, result);
+    circuit.zero_test(check);
+    BoolWire(result)
+}
+
+pub fn is_equal(circuit: &mut Circuit, a: Wire, b: Wire) -> BoolWire {
+    let diff = sub(circuit, a, b);
+    is_zero(circuit, diff)
+}
+
+/// The `num_bits` least significant bits of `a`, which is thereby range checked to be below
+/// `2^num_bits`. Sums of at most 30 bits stay below the modulus, so the decomposition is unique.
+///
+/// ```
+/// use circle_plonk::circuit::Circuit;
+/// use circle_plonk::gadgets::std::{from_bits, to_bits};
+/// use stwo_prover::core::fields::m31::M31;
+///
+/// let mut circuit = Circuit::new();
+/// circuit.new_input(M31::from(1));
+/// let a = circuit.new_witness(M31::from(0b1011));
+/// let bits = to_bits(&mut circuit, a, 4);
+/// let values = bits.iter().map(|&bit| circuit.get_output_wire(bit.into()).0);
+/// assert_eq!(values.collect::<Vec<_>>(), [1, 1, 0, 1]);
+/// let b = from_bits(&mut circuit, &bits);
+/// assert_eq!(circuit.get_output_wire(b), M31::from(0b1011));
+/// assert!(circuit.is_constraint_satisfied());
+/// ```
+pub fn to_bits(circuit: &mut Circuit, a: Wire, num_bits: usize) -> Vec<BoolWire> {
+    assert!(num_bits <= 30);
+    let bits = (0..num_bits)
+        .map(|i| {
+            let bit = circuit.hint(&[a], move |v| M31::from((v[0].0 >> i) & 1));
+            enforce_bool(circuit, bit)
+        })
+        .collect::<Vec<_>>();
+    let recomposed = from_bits(circuit, &bits);
+    enforce_equal(circuit, recomposed, a);
+    bits
+}
+
+// The little-endian value of `bits`.
+pub fn from_bits(circuit: &mut Circuit, bits: &[BoolWire]) -> Wire {
+    assert!(bits.len() <= 30);
+    let terms = bits
+        .iter()
+        .enumerate()
+        .map(|(i, &bit)| circuit.mul_by_constant(bit.into(), M31::from(1u32 << i)))
+        .collect::<Vec<_>>();
+    sum(circuit, &terms)
+}
+
+/// Whether `a < b`, for `a` and `b` already known to be below `2^num_bits`: `a - b + 2^num_bits`
+/// is then below `2^(num_bits + 1)`, and its top bit is set exactly when `a >= b`.
+///
+/// ```
+/// use circle_plonk::circuit::Circuit;
+/// use circle_plonk::gadgets::std::{less_than, to_bits};
+/// use stwo_prover::core::fields::m31::M31;
+///
+/// let mut circuit = Circuit::new();
+/// circuit.new_input(M31::from(1));
+/// let a = circuit.new_witness(M31::from(3));
+/// let b = circuit.new_witness(M31::from(200));
+/// // the operands must be range checked first
+/// to_bits(&mut circuit, a, 8);
+/// to_bits(&mut circuit, b, 8);
+/// let lt = less_than(&mut circuit, a, b, 8);
+/// assert_eq!(circuit.get_output_wire(lt.into()), M31::from(1));
+/// assert!(circuit.is_constraint_satisfied());
+/// ```
+pub fn less_than(circuit: &mut Circuit, a: Wire, b: Wire, num_bits: usize) -> BoolWire {
+    assert!(num_bits < 30);
+    let shifted = add_constant(circuit, a, M31::from(1u32 << num_bits));
+    let diff = sub(circuit, shifted, b);
+    let bits = to_bits(circuit, diff, num_bits + 1);
+    not(circuit, bits[num_bits])
+}
+
+// Hashing.
+
+// The Poseidon permutation over a state of one of `PoseidonParams::supported_widths`, with the
+// parameters of the native permutation.
+pub fn poseidon_permute(circuit: &mut Circuit, state: &[Wire]) -> Vec<Wire> {
+    let params = PoseidonParams::get(state.len());
+    let half = params.full_rounds / 2;
+    let sbox = |circuit: &mut Circuit, x: Wire| {
+        let x2 = circuit.mul(x, x);
+        let x4 = circuit.mul(x2, x2);
+        circuit.mul(x4, x)
+    };
+
+    let mut state = state.to_vec();
+    for (round, constants) in params.round_constants.iter().enumerate() {
+        for (v, &c) in state.iter_mut().zip(constants.iter()) {
+            *v = add_constant(circuit, *v, c);
+        }
+        if round < half || round >= half + params.partial_rounds {
+            for v in state.iter_mut() {
+                *v = sbox(circuit, *v);
+            }
+        } else {
+            state[0] = sbox(circuit, state[0]);
+        }
+
+        state = params
+            .mds
+            .iter()
+            .map(|row| {
+                let terms = row
+                    .iter()
+                    .zip(state.iter())
+                    .map(|(&m, &v)| circuit.mul_by_constant(v, m))
+                    .collect::<Vec<_>>();
+                sum(circuit, &terms)
+            })
+            .collect();
+    }
+    state
+}
+
+/// `poseidon::poseidon_sponge` over wires, with the domain as a constant.
+///
+/// ```
+/// use circle_plonk::circuit::Circuit;
+/// use circle_plonk::gadgets::std::poseidon_sponge;
+/// use circle_plonk::poseidon;
+/// use stwo_prover::core::fields::m31::M31;
+///
+/// let mut circuit = Circuit::new();
+/// circuit.new_input(M31::from(1));
+/// let values = [M31::from(5), M31::from(7)];
+/// let input = values.map(|v| circuit.new_witness(v));
+/// let output = poseidon_sponge(&mut circuit, M31::from(1), &input, 2);
+/// let output = output.iter().map(|&wire| circuit.get_output_wire(wire));
+/// assert_eq!(
+///     output.collect::<Vec<_>>(),
+///     poseidon::poseidon_sponge(M31::from(1), &values, 2)
+/// );
+/// assert!(circuit.is_constraint_satisfied());
+/// ```
+pub fn poseidon_sponge(
+    circuit: &mut Circuit,
+    domain: M31,
+    input: &[Wire],
+    num_outputs: usize,
+) -> Vec<Wire> {
+    let mut state = vec![Wire::ZERO; SPONGE_WIDTH];
+    state[SPONGE_RATE] = constant(circuit, domain);
+    let one = constant(circuit, M31::one());
+    for block in sponge_blocks(input, one, Wire::ZERO) {
+        for (v, x) in state.iter_mut().zip(block) {
+            // the first block is absorbed into zeros
+            *v = if *v == Wire::ZERO {
+                x
+            } else {
+                circuit.add(*v, x)
+            };
+        }
+        state = poseidon_permute(circuit, &state);
+    }
+
+    let mut output = state[..SPONGE_RATE].to_vec();
+    while output.len() < num_outputs {
+        state = poseidon_permute(circuit, &state);
+        output.extend_from_slice(&state[..SPONGE_RATE]);
+    }
+    output.truncate(num_outputs);
+    output
+}
+
+// `poseidon::hash_to_field` over wires.
+pub fn hash_to_field(
+    circuit: &mut Circuit,
+    domain: M31,
+    input: &[Wire],
+    num_outputs: usize,
+) -> Vec<Wire> {
+    poseidon_sponge(circuit, domain, input, num_outputs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{BoolWire, Circuit, Wire};
+    use crate::gadgets::std::{
+        and, div, enforce_bool, from_bits, hash_to_field, is_equal, is_zero, less_than, not, or,
+        poseidon_permute, poseidon_sponge, select, to_bits, xor,
+    };
+    use crate::poseidon::{self, PoseidonParams, PERMUTATION_KATS, SPONGE_RATE};
+    use ark_ff::{One, Zero};
+    use stwo_prover::core::fields::m31::M31;
+
+    fn new_circuit() -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        circuit
+    }
+
+    fn value(circuit: &Circuit, wire: impl Into<Wire>) -> u32 {
+        circuit.get_output_wire(wire.into()).0
+    }
+
+    #[test]
+    fn test_arithmetic_and_logic() {
+        let mut circuit = new_circuit();
+        let a = circuit.new_witness(M31::from(12));
+        let b = circuit.new_witness(M31::from(4));
+        let q = div(&mut circuit, a, b);
+        assert_eq!(value(&circuit, q), 3);
+
+        let bits = [0, 1].map(|v| {
+            let wire = circuit.new_witness(M31::from(v));
+            enforce_bool(&mut circuit, wire)
+        });
+        let results = [
+            and(&mut circuit, bits[0], bits[1]),
+            or(&mut circuit, bits[0], bits[1]),
+            xor(&mut circuit, bits[1], bits[1]),
+            not(&mut circuit, bits[0]),
+        ];
+        let results = results.map(|wire| value(&circuit, wire));
+        assert_eq!(results, [0, 1, 0, 1]);
+        let selected = select(&mut circuit, bits[1], a, b);
+        assert_eq!(value(&circuit, selected), 12);
+
+        let zero = circuit.new_witness(M31::zero());
+        let results = [
+            is_zero(&mut circuit, zero),
+            is_zero(&mut circuit, a),
+            is_equal(&mut circuit, q, q),
+            is_equal(&mut circuit, a, b),
+        ];
+        let results = results.map(|wire| value(&circuit, wire));
+        assert_eq!(results, [1, 0, 1, 0]);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+
+        // a zero divisor has no inverse to check against
+        let mut circuit = new_circuit();
+        let a = circuit.new_witness(M31::from(12));
+        div(&mut circuit, a, Wire::ZERO);
+        assert!(!circuit.is_constraint_satisfied());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut circuit = new_circuit();
+        let a = circuit.new_witness(M31::from(100));
+        let b = circuit.new_witness(M31::from(1000));
+        let bits = to_bits(&mut circuit, a, 8);
+        let recomposed = from_bits(&mut circuit, &bits);
+        assert_eq!(value(&circuit, recomposed), 100);
+
+        let results = [
+            less_than(&mut circuit, a, b, 10),
+            less_than(&mut circuit, b, a, 10),
+            less_than(&mut circuit, a, a, 10),
+        ];
+        let results = results.map(|wire: BoolWire| value(&circuit, wire));
+        assert_eq!(results, [1, 0, 0]);
+        assert!(circuit.is_constraint_satisfied());
+
+        // 1000 does not fit in 8 bits
+        let mut circuit = new_circuit();
+        let b = circuit.new_witness(M31::from(1000));
+        to_bits(&mut circuit, b, 8);
+        assert!(!circuit.is_constraint_satisfied());
+    }
+
+    #[test]
+    fn test_poseidon_permute() {
+        let params = PoseidonParams::get(16);
+        let mut expected = (0..16).map(M31::from).collect::<Vec<_>>();
+        let mut circuit = new_circuit();
+        let state = expected
+            .iter()
+            .map(|&v| circuit.new_witness(v))
+            .collect::<Vec<_>>();
+
+        let output = poseidon_permute(&mut circuit, &state);
+        params.permute(&mut expected);
+        let output = output
+            .iter()
+            .map(|&wire| circuit.get_output_wire(wire))
+            .collect::<Vec<_>>();
+        assert_eq!(output, expected);
+        assert!(circuit.is_constraint_satisfied());
+
+        for (width, expected) in PERMUTATION_KATS {
+            let mut circuit = new_circuit();
+            let state = (0..width as u32)
+                .map(|v| circuit.new_witness(M31::from(v)))
+                .collect::<Vec<_>>();
+            let output = poseidon_permute(&mut circuit, &state);
+            let output = output
+                .iter()
+                .map(|&wire| value(&circuit, wire))
+                .collect::<Vec<_>>();
+            assert_eq!(output, expected);
+            assert!(circuit.is_constraint_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_poseidon_sponge() {
+        let domain = M31::from(7);
+        for len in [0, 1, SPONGE_RATE - 1, SPONGE_RATE, 2 * SPONGE_RATE + 1] {
+            let input = (0..len as u32).map(M31::from).collect::<Vec<_>>();
+            let mut circuit = new_circuit();
+            let wires = input
+                .iter()
+                .map(|&v| circuit.new_witness(v))
+                .collect::<Vec<_>>();
+
+            let output = poseidon_sponge(&mut circuit, domain, &wires, SPONGE_RATE + 1);
+            let output = output
+                .iter()
+                .map(|&wire| circuit.get_output_wire(wire))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                output,
+                poseidon::poseidon_sponge(domain, &input, SPONGE_RATE + 1)
+            );
+
+            let output = hash_to_field(&mut circuit, domain, &wires, 2);
+            let output = output
+                .iter()
+                .map(|&wire| circuit.get_output_wire(wire))
+                .collect::<Vec<_>>();
+            assert_eq!(output, poseidon::hash_to_field(domain, &input, 2));
+            assert!(circuit.is_constraint_satisfied());
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::circuit::{BoolWire, Circuit, Wire};
+use crate::gadgets::std::{constant, enforce_equal, is_zero, not, sub, sum, to_bits};
+use ark_ff::One;
+use num_bigint::BigUint;
+use stwo_prover::core::fields::m31::M31;
+
+// Unsigned 256-bit integers as 32 little-endian bytes, the layout of txids, storage keys and hash
+// outputs. With bytes, a column of the schoolbook product, a sum of up to 32 products of two
+// bytes, stays below 2^21, so that the carries of a sum of columns can be split off exactly.
+//
+// Every limb of a `U256Wire` is range checked when it is created, so the gadgets below can rely
+// on it being a byte.
+
+pub const NUM_LIMBS: usize = 32;
+pub const LIMB_BITS: usize = 8;
+
+// the carries out of a column below 2^22 are below 2^14
+const CARRY_BITS: usize = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct U256Wire(pub [Wire; NUM_LIMBS]);
+
+fn to_biguint(limbs: &[M31]) -> BigUint {
+    BigUint::from_bytes_le(&limbs.iter().map(|v| v.0 as u8).collect::<Vec<_>>())
+}
+
+fn byte_of(value: &BigUint, i: usize) -> M31 {
+    M31::from(value.to_bytes_le().get(i).copied().unwrap_or(0) as u32)
+}
+
+pub fn u256_constant(circuit: &mut Circuit, value: [u8; 32]) -> U256Wire {
+    U256Wire(value.map(|byte| constant(circuit, M31::from(byte as u32))))
+}
+
+pub fn u256_witness(circuit: &mut Circuit, value: [u8; 32]) -> U256Wire {
+    U256Wire(value.map(|byte| {
+        let limb = circuit.new_witness(M31::from(byte as u32));
+        to_bits(circuit, limb, LIMB_BITS);
+        limb
+    }))
+}
+
+pub fn u256_value(circuit: &Circuit, a: U256Wire) -> [u8; 32] {
+    a.0.map(|limb| circuit.get_output_wire(limb).0 as u8)
+}
+
+// The bytes of the integer `sum(columns[k] * 2^(8k))`, for columns below 2^21, and then the carry
+// out of the last column, which the caller bounds. Each carry is hinted and range checked, and
+// `t - 256 * carry` is range checked to a byte, which pins the carry down as the sums involved
+// are far below the modulus.
+fn normalize(circuit: &mut Circuit, columns: &[Wire]) -> Vec<Wire> {
+    let mut limbs = Vec::with_capacity(columns.len() + 1);
+    let mut carry = Wire::ZERO;
+    for &column in columns {
+        let t = circuit.add(column, carry);
+        carry = circuit.hint(&[t], |v| M31::from(v[0].0 >> LIMB_BITS));
+        to_bits(circuit, carry, CARRY_BITS);
+        let shifted = circuit.mul_by_constant(carry, M31::from(1 << LIMB_BITS));
+        let limb = sub(circuit, t, shifted);
+        to_bits(circuit, limb, LIMB_BITS);
+        limbs.push(limb);
+    }
+    limbs.push(carry);
+    limbs
+}
+
+fn limbs(limbs: &[Wire]) -> U256Wire {
+    U256Wire(limbs.try_into().unwrap())
+}
+
+// `a + b` modulo 2^256, and the carry out.
+pub fn u256_add(circuit: &mut Circuit, a: U256Wire, b: U256Wire) -> (U256Wire, BoolWire) {
+    let columns = (0..NUM_LIMBS)
+        .map(|i| circuit.add(a.0[i], b.0[i]))
+        .collect::<Vec<_>>();
+    let result = normalize(circuit, &columns);
+    (limbs(&result[..NUM_LIMBS]), BoolWire(result[NUM_LIMBS]))
+}
+
+// `a - b` modulo 2^256, and the borrow, computed as `a + !b + 1`, whose carry out is set exactly
+// when `a >= b`.
+pub fn u256_sub(circuit: &mut Circuit, a: U256Wire, b: U256Wire) -> (U256Wire, BoolWire) {
+    let max = constant(circuit, M31::from(0xff));
+    let columns = (0..NUM_LIMBS)
+        .map(|i| {
+            let not_b = sub(circuit, max, b.0[i]);
+            let column = circuit.add(a.0[i], not_b);
+            if i == 0 {
+                let one = constant(circuit, M31::one());
+                circuit.add(column, one)
+            } else {
+                column
+            }
+        })
+        .collect::<Vec<_>>();
+    let result = normalize(circuit, &columns);
+    let borrow = not(circuit, BoolWire(result[NUM_LIMBS]));
+    (limbs(&result[..NUM_LIMBS]), borrow)
+}
+
+// The low and the high 256 bits of `a * b`.
+pub fn u256_mul(circuit: &mut Circuit, a: U256Wire, b: U256Wire) -> (U256Wire, U256Wire) {
+    let mut columns = vec![vec![]; 2 * NUM_LIMBS - 1];
+    for (i, &a) in a.0.iter().enumerate() {
+        for (j, &b) in b.0.iter().enumerate() {
+            let product = circuit.mul(a, b);
+            columns[i + j].push(product);
+        }
+    }
+    let columns = columns
+        .iter()
+        .map(|terms| sum(circuit, terms))
+        .collect::<Vec<_>>();
+    // the product is below 2^512, so the last carry is a byte
+    let result = normalize(circuit, &columns);
+    (limbs(&result[..NUM_LIMBS]), limbs(&result[NUM_LIMBS..]))
+}
+
+pub fn u256_less_than(circuit: &mut Circuit, a: U256Wire, b: U256Wire) -> BoolWire {
+    u256_sub(circuit, a, b).1
+}
+
+// The squares of the differences of the limbs are below 2^16, so their sum is zero exactly when
+// every difference is.
+pub fn u256_is_equal(circuit: &mut Circuit, a: U256Wire, b: U256Wire) -> BoolWire {
+    let squares = (0..NUM_LIMBS)
+        .map(|i| {
+            let diff = sub(circuit, a.0[i], b.0[i]);
+            circuit.mul(diff, diff)
+        })
+        .collect::<Vec<_>>();
+    let total = sum(circuit, &squares);
+    is_zero(circuit, total)
+}
+
+// `x mod modulus`, for `x` given as any number of byte limbs, such as the 64 of the two halves
+// of a product, and a nonzero constant modulus. The quotient and the remainder are hinted, and
+// `quotient * modulus + remainder` is checked to be `x` limb by limb, with `remainder < modulus`.
+pub fn u256_mod_constant(circuit: &mut Circuit, x: &[Wire], modulus: [u8; 32]) -> U256Wire {
+    let m = BigUint::from_bytes_le(&modulus);
+    assert!(m.bits() > 0, "the modulus is zero");
+
+    let hint_limbs = |circuit: &mut Circuit, count: usize, remainder: bool| {
+        (0..count)
+            .map(|i| {
+                let m = m.clone();
+                let limb = circuit.hint(x, move |v| {
+                    let x = to_biguint(v);
+                    let value = if remainder { &x % &m } else { &x / &m };
+                    byte_of(&value, i)
+                });
+                to_bits(circuit, limb, LIMB_BITS);
+                limb
+            })
+            .collect::<Vec<_>>()
+    };
+    let quotient = hint_limbs(circuit, x.len(), false);
+    let remainder = limbs(&hint_limbs(circuit, NUM_LIMBS, true));
+
+    let mut columns = vec![vec![]; x.len() + NUM_LIMBS - 1];
+    for (i, &q) in quotient.iter().enumerate() {
+        for (j, &byte) in modulus.iter().enumerate() {
+            if byte != 0 {
+                columns[i + j].push(circuit.mul_by_constant(q, M31::from(byte as u32)));
+            }
+        }
+    }
+    for (column, &r) in columns.iter_mut().zip(remainder.0.iter()) {
+        column.push(r);
+    }
+    let columns = columns
+        .iter()
+        .map(|terms| sum(circuit, terms))
+        .collect::<Vec<_>>();
+    let result = normalize(circuit, &columns);
+    for (k, &limb) in result.iter().enumerate() {
+        enforce_equal(circuit, limb, x.get(k).copied().unwrap_or(Wire::ZERO));
+    }
+
+    let modulus = u256_constant(circuit, modulus);
+    let in_range = u256_less_than(circuit, remainder, modulus);
+    let one = constant(circuit, M31::one());
+    enforce_equal(circuit, in_range.into(), one);
+    remainder
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::gadgets::u256::{
+        u256_add, u256_is_equal, u256_less_than, u256_mod_constant, u256_mul, u256_sub, u256_value,
+        u256_witness,
+    };
+    use ark_ff::One;
+    use ark_std::rand::{Rng, SeedableRng};
+    use num_bigint::BigUint;
+    use std::sync::Arc;
+    use stwo_prover::core::fields::m31::M31;
+
+    fn to_bytes(value: &BigUint) -> [u8; 32] {
+        let mut bytes = value.to_bytes_le();
+        bytes.resize(32, 0);
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_u256_arithmetic() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let two_256 = BigUint::one() << 256;
+        let x = BigUint::from_bytes_le(&prng.gen::<[u8; 32]>());
+        let y = BigUint::from_bytes_le(&prng.gen::<[u8; 32]>());
+        // the order of secp256k1
+        let n = BigUint::parse_bytes(
+            b"fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        )
+        .unwrap();
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = u256_witness(&mut circuit, to_bytes(&x));
+        let b = u256_witness(&mut circuit, to_bytes(&y));
+        let value = |circuit: &Circuit, a| BigUint::from_bytes_le(&u256_value(circuit, a));
+
+        let (s, carry) = u256_add(&mut circuit, a, b);
+        assert_eq!(value(&circuit, s), (&x + &y) % &two_256);
+        assert_eq!(
+            circuit.get_output_wire(carry.into()).0 == 1,
+            &x + &y >= two_256
+        );
+
+        let (d, borrow) = u256_sub(&mut circuit, a, b);
+        assert_eq!(value(&circuit, d), (&two_256 + &x - &y) % &two_256);
+        assert_eq!(circuit.get_output_wire(borrow.into()).0 == 1, x < y);
+        let lt = u256_less_than(&mut circuit, b, a);
+        assert_eq!(circuit.get_output_wire(lt.into()).0 == 1, y < x);
+        let eq = u256_is_equal(&mut circuit, a, b);
+        assert_eq!(circuit.get_output_wire(eq.into()).0, 0);
+        let eq = u256_is_equal(&mut circuit, a, a);
+        assert_eq!(circuit.get_output_wire(eq.into()).0, 1);
+
+        let (lo, hi) = u256_mul(&mut circuit, a, b);
+        let product = &x * &y;
+        assert_eq!(value(&circuit, lo), &product % &two_256);
+        assert_eq!(value(&circuit, hi), &product >> 256);
+
+        let wide = [lo.0, hi.0].concat();
+        let r = u256_mod_constant(&mut circuit, &wide, to_bytes(&n));
+        assert_eq!(value(&circuit, r), &product % &n);
+        let r = u256_mod_constant(&mut circuit, &a.0, to_bytes(&n));
+        assert_eq!(value(&circuit, r), &x % &n);
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_u256_tampered_carry() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = u256_witness(&mut circuit, [0xff; 32]);
+        let mut one = [0; 32];
+        one[0] = 1;
+        let b = u256_witness(&mut circuit, one);
+        let first = circuit.hints.len();
+        u256_add(&mut circuit, a, b);
+        assert!(circuit.is_constraint_satisfied());
+
+        // a carry one too large out of the first column leaves `t - 256 * carry` at `-256`,
+        // which is not a byte
+        let (inputs, witnesses) = circuit.assignment();
+        let mut index = circuit.index();
+        let carry = &mut index.hints[first];
+        assert_eq!(carry.evaluate(&circuit.output_wires), M31::one());
+        carry.compute = Arc::new(|v| M31::from((v[0].0 >> 8) + 1));
+        let witness = index.evaluate(&inputs, &witnesses).unwrap();
+        assert!(!Circuit::from_parts(index, witness).is_constraint_satisfied());
+    }
+}
@@ -1,9 +1,56 @@
 #![feature(iter_array_chunks)]
+#![feature(avx512_target_feature)]
 
 pub mod field;
 
+#[cfg(feature = "bench-utils")]
+pub mod bench_utils;
+
 pub mod circuit;
 
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+pub mod from_acir;
+
+pub mod from_plonkish;
+
 pub mod from_r1cs;
 
+pub mod gadgets;
+
+pub mod metrics;
+
+#[cfg(feature = "mobile")]
+pub mod mobile;
+
+#[cfg(feature = "mobile")]
+uniffi::setup_scaffolding!();
+
+pub mod optimizer;
+
+pub mod poseidon;
+
 pub mod proof_system;
+
+#[cfg(feature = "proptest-support")]
+pub mod proptest_support;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(feature = "secure")]
+pub mod secure;
+
+#[cfg(feature = "service")]
+pub mod service;
+
+pub mod simd;
+
+pub mod testing;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
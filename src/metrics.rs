@@ -0,0 +1,413 @@
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+// The target of the events below, for subscribers to pick them out of the other logs, for example
+// with `RUST_LOG=circle_plonk::metrics=info`.
+pub const METRICS_TARGET: &str = "circle_plonk::metrics";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Twiddles,
+    Trace,
+    Interaction,
+    Constant,
+    Prove,
+    Verify,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Twiddles,
+        Phase::Trace,
+        Phase::Interaction,
+        Phase::Constant,
+        Phase::Prove,
+        Phase::Verify,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Twiddles => "twiddles",
+            Phase::Trace => "trace",
+            Phase::Interaction => "interaction",
+            Phase::Constant => "constant",
+            Phase::Prove => "prove",
+            Phase::Verify => "verify",
+        }
+    }
+
+    pub fn start(self, rows: usize) -> PhaseTimer {
+        PhaseTimer {
+            phase: self,
+            rows,
+            heap_start: heap().map(|heap| {
+                heap.reset_peak();
+                heap.allocated()
+            }),
+            rss_reset: heap().is_some() && reset_peak_rss(),
+            // there is no clock on `wasm32-unknown-unknown`, where `Instant::now` panics
+            start: (!cfg!(all(target_arch = "wasm32", target_os = "unknown"))).then(Instant::now),
+        }
+    }
+}
+
+// One phase of the prover or the verifier, over a trace of `rows` rows. `bytes` is what the phase
+// committed to (the extended evaluations of its columns), if anything, and `memory` is only
+// measured with `TrackingAllocator`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PhaseEvent {
+    pub phase: Phase,
+    pub duration_us: u64,
+    pub rows: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryUsage>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MemoryUsage {
+    // the bytes allocated during the phase, whether or not they were freed since
+    pub allocated_bytes: u64,
+    // the most bytes in use on the heap at once during the phase
+    pub peak_heap_bytes: u64,
+    // the peak resident set size of the process during the phase, where it can be reset at the
+    // start of the phase, as on Linux
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_rss_bytes: Option<u64>,
+}
+
+pub struct PhaseTimer {
+    phase: Phase,
+    rows: usize,
+    heap_start: Option<u64>,
+    rss_reset: bool,
+    start: Option<Instant>,
+}
+
+impl PhaseTimer {
+    // Adds the phase to the global counters and emits it as a tracing event at `METRICS_TARGET`,
+    // whose fields are those of `PhaseEvent`.
+    pub fn finish(self, bytes: Option<u64>) -> PhaseEvent {
+        let event = PhaseEvent {
+            phase: self.phase,
+            duration_us: self
+                .start
+                .map_or(0, |start| start.elapsed().as_micros() as u64),
+            rows: self.rows,
+            bytes,
+            memory: heap()
+                .zip(self.heap_start)
+                .map(|(heap, start)| MemoryUsage {
+                    allocated_bytes: heap.allocated() - start,
+                    peak_heap_bytes: heap.peak(),
+                    peak_rss_bytes: self.rss_reset.then(peak_rss_bytes).flatten(),
+                }),
+        };
+        Metrics::global().record(&event);
+        tracing::info!(
+            target: METRICS_TARGET,
+            phase = event.phase.as_str(),
+            duration_us = event.duration_us,
+            rows = event.rows,
+            bytes = event.bytes,
+            allocated_bytes = event.memory.map(|m| m.allocated_bytes),
+            peak_heap_bytes = event.memory.map(|m| m.peak_heap_bytes),
+            peak_rss_bytes = event.memory.and_then(|m| m.peak_rss_bytes),
+        );
+        event
+    }
+}
+
+#[derive(Default)]
+struct PhaseCounters {
+    count: AtomicU64,
+    duration_us: AtomicU64,
+    rows: AtomicU64,
+    bytes: AtomicU64,
+    allocated_bytes: AtomicU64,
+}
+
+// Counters accumulated over every phase that ran in the process, for a proving service to export
+// in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    phases: [PhaseCounters; Phase::ALL.len()],
+}
+
+impl Metrics {
+    pub fn global() -> &'static Metrics {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        METRICS.get_or_init(Metrics::default)
+    }
+
+    pub fn record(&self, event: &PhaseEvent) {
+        let counters = &self.phases[event.phase as usize];
+        counters.count.fetch_add(1, Ordering::Relaxed);
+        counters
+            .duration_us
+            .fetch_add(event.duration_us, Ordering::Relaxed);
+        counters
+            .rows
+            .fetch_add(event.rows as u64, Ordering::Relaxed);
+        if let Some(bytes) = event.bytes {
+            counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+        if let Some(memory) = event.memory {
+            counters
+                .allocated_bytes
+                .fetch_add(memory.allocated_bytes, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count(&self, phase: Phase) -> u64 {
+        self.phases[phase as usize].count.load(Ordering::Relaxed)
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let families: [(&str, &str, fn(&PhaseCounters) -> String); 5] = [
+            (
+                "circle_plonk_phase_total",
+                "Number of runs of each phase",
+                |c| c.count.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "circle_plonk_phase_seconds_total",
+                "Time spent in each phase",
+                |c| (c.duration_us.load(Ordering::Relaxed) as f64 / 1e6).to_string(),
+            ),
+            (
+                "circle_plonk_phase_rows_total",
+                "Trace rows processed by each phase",
+                |c| c.rows.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "circle_plonk_phase_bytes_total",
+                "Bytes committed by each phase",
+                |c| c.bytes.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "circle_plonk_phase_allocated_bytes_total",
+                "Bytes allocated by each phase",
+                |c| c.allocated_bytes.load(Ordering::Relaxed).to_string(),
+            ),
+        ];
+
+        let mut out = String::new();
+        for (name, help, value) in families {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} counter", name).unwrap();
+            for phase in Phase::ALL {
+                let counters = &self.phases[phase as usize];
+                writeln!(
+                    out,
+                    "{}{{phase=\"{}\"}} {}",
+                    name,
+                    phase.as_str(),
+                    value(counters)
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+}
+
+// The heap in use, in bytes. The global counters are only updated by `TrackingAllocator`, which a
+// program opts into, as it costs a few atomic operations on every allocation:
+//
+//     #[global_allocator]
+//     static ALLOCATOR: circle_plonk::metrics::TrackingAllocator = TrackingAllocator;
+//
+// There is one peak for the whole process, which each phase resets as it starts, so that phases
+// that run at the same time, as in a proving service, report the peak of all of them.
+#[derive(Default)]
+pub struct MemoryCounters {
+    current: AtomicU64,
+    peak: AtomicU64,
+    allocated: AtomicU64,
+}
+
+impl MemoryCounters {
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+            allocated: AtomicU64::new(0),
+        }
+    }
+
+    pub fn on_alloc(&self, size: u64) {
+        self.allocated.fetch_add(size, Ordering::Relaxed);
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    pub fn on_dealloc(&self, size: u64) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    pub fn current(&self) -> u64 {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    // the bytes allocated so far, including those freed since
+    pub fn allocated(&self) -> u64 {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_peak(&self) {
+        self.peak.store(self.current(), Ordering::Relaxed);
+    }
+}
+
+static HEAP: MemoryCounters = MemoryCounters::new();
+
+// The counters of `TrackingAllocator`, if it is the global allocator.
+pub fn heap() -> Option<&'static MemoryCounters> {
+    (HEAP.allocated() > 0).then_some(&HEAP)
+}
+
+// The system allocator, counting the bytes in `heap()`.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            HEAP.on_alloc(layout.size() as u64);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            HEAP.on_alloc(layout.size() as u64);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        HEAP.on_dealloc(layout.size() as u64);
+    }
+
+    // counted as a new allocation before the old one is freed, as a move holds both
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            HEAP.on_alloc(new_size as u64);
+            HEAP.on_dealloc(layout.size() as u64);
+        }
+        new_ptr
+    }
+}
+
+// Resets the peak resident set size of the process to the current one, which Linux supports
+// since 4.0. Like the peak of the heap, it is shared by the phases that run at the same time.
+pub fn reset_peak_rss() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// The peak resident set size of the process since it started or since `reset_peak_rss`, which
+// only Linux reports.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::{
+        peak_rss_bytes, reset_peak_rss, MemoryCounters, MemoryUsage, Metrics, Phase, PhaseEvent,
+    };
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::default();
+        for bytes in [Some(100), None] {
+            metrics.record(&PhaseEvent {
+                phase: Phase::Trace,
+                duration_us: 1_500_000,
+                rows: 1 << 10,
+                bytes,
+                memory: None,
+            });
+        }
+        assert_eq!(metrics.count(Phase::Trace), 2);
+        assert_eq!(metrics.count(Phase::Verify), 0);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("circle_plonk_phase_total{phase=\"trace\"} 2\n"));
+        assert!(text.contains("circle_plonk_phase_seconds_total{phase=\"trace\"} 3\n"));
+        assert!(text.contains("circle_plonk_phase_rows_total{phase=\"trace\"} 2048\n"));
+        assert!(text.contains("circle_plonk_phase_bytes_total{phase=\"trace\"} 100\n"));
+        assert!(text.contains("circle_plonk_phase_total{phase=\"verify\"} 0\n"));
+
+        let event = Phase::Verify.start(16).finish(None);
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with("{\"phase\":\"verify\",\"duration_us\":"));
+        assert!(json.ends_with(",\"rows\":16}"));
+    }
+
+    #[test]
+    fn test_memory() {
+        let heap = MemoryCounters::new();
+        heap.on_alloc(100);
+        heap.on_alloc(50);
+        heap.on_dealloc(100);
+        assert_eq!(
+            (heap.current(), heap.peak(), heap.allocated()),
+            (50, 150, 150)
+        );
+        heap.reset_peak();
+        heap.on_alloc(20);
+        assert_eq!(
+            (heap.current(), heap.peak(), heap.allocated()),
+            (70, 70, 170)
+        );
+
+        if cfg!(target_os = "linux") {
+            assert!(peak_rss_bytes().unwrap() > 0);
+        }
+        if reset_peak_rss() {
+            let start = peak_rss_bytes().unwrap();
+            let touched = vec![1u8; 64 << 20];
+            assert!(peak_rss_bytes().unwrap() >= start + (32 << 20));
+            drop(touched);
+        }
+
+        let metrics = Metrics::default();
+        metrics.record(&PhaseEvent {
+            phase: Phase::Prove,
+            duration_us: 0,
+            rows: 16,
+            bytes: None,
+            memory: Some(MemoryUsage {
+                allocated_bytes: 1000,
+                peak_heap_bytes: 600,
+                peak_rss_bytes: None,
+            }),
+        });
+        let text = metrics.to_prometheus();
+        assert!(text.contains("circle_plonk_phase_allocated_bytes_total{phase=\"prove\"} 1000\n"));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::circuit::compressed::CompressedIndex;
+use crate::circuit::index::Witness;
+use crate::proof_system::{estimated_prover_memory, verify_with_key, PlonkProof, VerificationKey};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use stwo_prover::core::fields::m31::{M31, P};
+
+// The interface for Kotlin and Swift, generated with UniFFI from the library:
+//
+//     uniffi-bindgen generate --library target/release/libcircle_plonk.so --language kotlin
+//
+// Phones run the verifier, and a prover for small circuits only: the trace is capped at
+// `MAX_LOG_N_ROWS` and the estimated memory of the prover at what the caller allows, and inputs
+// are decoded with a bound on their size, so that a bad file fails instead of getting the app
+// killed.
+
+pub const MAX_LOG_N_ROWS: u32 = 18;
+
+// the largest key, index, witness or proof that is decoded
+const MAX_INPUT_BYTES: u64 = 256 << 20;
+
+#[derive(Debug, PartialEq, Eq, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum MobileError {
+    InvalidIndex,
+    // malformed, or not for the index
+    InvalidWitness,
+    UnsatisfiedWitness,
+    InvalidKey,
+    InvalidProof,
+    TooLarge { log_n_rows: u32, memory_mib: u64 },
+}
+
+impl Display for MobileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MobileError::InvalidIndex => write!(f, "the index is malformed"),
+            MobileError::InvalidWitness => write!(f, "the witness is not for the index"),
+            MobileError::UnsatisfiedWitness => {
+                write!(f, "the witness does not satisfy the constraints")
+            }
+            MobileError::InvalidKey => write!(f, "the verification key is malformed"),
+            MobileError::InvalidProof => write!(f, "the proof is malformed"),
+            MobileError::TooLarge {
+                log_n_rows,
+                memory_mib,
+            } => write!(
+                f,
+                "proving 2^{} rows takes about {} MiB, over the limits for this device",
+                log_n_rows, memory_mib
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MobileError {}
+
+// The same encoding as `bincode::deserialize`, with a bound on the size of the input.
+fn decode<T: DeserializeOwned>(bytes: &[u8], err: MobileError) -> Result<T, MobileError> {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_INPUT_BYTES)
+        .deserialize(bytes)
+        .map_err(|_| err)
+}
+
+// Proves a witness of a circuit given as the compressed index written by `circle-plonk setup`,
+// provided that the prover fits in `max_memory_mib`.
+#[uniffi::export]
+pub fn prove(
+    index: Vec<u8>,
+    witness: Vec<u8>,
+    max_memory_mib: u64,
+) -> Result<Vec<u8>, MobileError> {
+    let index: CompressedIndex = decode(&index, MobileError::InvalidIndex)?;
+    if index.validate().is_err() || !index.num_rows.is_power_of_two() {
+        return Err(MobileError::InvalidIndex);
+    }
+    let log_n_rows = index.num_rows.ilog2();
+    let memory_mib = estimated_prover_memory(log_n_rows).div_ceil(1 << 20) as u64;
+    if log_n_rows > MAX_LOG_N_ROWS || memory_mib > max_memory_mib {
+        return Err(MobileError::TooLarge {
+            log_n_rows,
+            memory_mib,
+        });
+    }
+
+    let witness: Witness = decode(&witness, MobileError::InvalidWitness)?;
+    if !index.is_compatible(&witness) {
+        return Err(MobileError::InvalidWitness);
+    }
+    // fails on a witness that does not satisfy the gates
+    let proof = PlonkProof::prove_compressed(&index, &witness)
+        .map_err(|_| MobileError::UnsatisfiedWitness)?;
+    Ok(bincode::serialize(&proof).unwrap())
+}
+
+// Returns whether the proof is valid for the key and the values of the inputs, in the order of
+// the key.
+#[uniffi::export]
+pub fn verify(key: Vec<u8>, proof: Vec<u8>, values: Vec<u32>) -> Result<bool, MobileError> {
+    let key: VerificationKey = decode(&key, MobileError::InvalidKey)?;
+    let proof: PlonkProof = decode(&proof, MobileError::InvalidProof)?;
+    if values.iter().any(|&v| v >= P)
+        || proof.log_n_rows != key.log_n_rows
+        || !proof.is_num_rows_consistent()
+    {
+        return Ok(false);
+    }
+    let values = values.into_iter().map(M31::from).collect::<Vec<_>>();
+    Ok(verify_with_key(&key, &values, proof.proof).is_ok())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::mobile::{prove, verify, MobileError};
+    use crate::proof_system::VerificationKey;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_mobile() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (index, witness) = circuit.split();
+        let key = bincode::serialize(&VerificationKey::new(&index)).unwrap();
+        let values = witness
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v.0)
+            .collect::<Vec<_>>();
+        // the index as `circle-plonk setup` writes it
+        let index = bincode::serialize(&index.compress()).unwrap();
+        let mut unsatisfied = witness.clone();
+        *unsatisfied.output_wires.last_mut().unwrap() += M31::from(1);
+        let unsatisfied = bincode::serialize(&unsatisfied).unwrap();
+        let witness = bincode::serialize(&witness).unwrap();
+
+        let proof = prove(index.clone(), witness.clone(), 1 << 10).unwrap();
+        assert_eq!(verify(key.clone(), proof.clone(), values.clone()), Ok(true));
+        let mut wrong = values.clone();
+        wrong[0] ^= 1;
+        assert_eq!(verify(key.clone(), proof.clone(), wrong), Ok(false));
+        assert_eq!(
+            verify(key[..key.len() / 2].to_vec(), proof.clone(), values),
+            Err(MobileError::InvalidKey)
+        );
+
+        assert!(matches!(
+            prove(index.clone(), witness.clone(), 0),
+            Err(MobileError::TooLarge { .. })
+        ));
+        assert_eq!(
+            prove(index.clone(), unsatisfied, 1 << 10),
+            Err(MobileError::UnsatisfiedWitness)
+        );
+        assert_eq!(
+            prove(index.clone(), index, 1 << 10),
+            Err(MobileError::InvalidWitness)
+        );
+    }
+}
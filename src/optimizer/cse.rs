@@ -0,0 +1,94 @@
+use crate::circuit::{Circuit, FastHashMap, FastHashSet, RowKind};
+use crate::optimizer::rebuild;
+use stwo_prover::core::fields::m31::M31;
+
+// Merges gate rows computing the same `(op, idx_a, idx_b)` (the gate is symmetric in its two
+// inputs) as well as repeated zero tests of the same wire. Returns the number of removed rows.
+pub fn eliminate_common_subexpressions(circuit: &mut Circuit) -> usize {
+    let kinds = circuit.row_kinds();
+
+    let mut remap = (0..circuit.num_rows).collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(circuit.num_rows);
+
+    let mut gates = FastHashMap::<(M31, usize, usize), usize>::default();
+    let mut zero_tests = FastHashSet::<usize>::default();
+
+    for row in 0..circuit.num_rows {
+        let a = remap[circuit.idx_a[row]];
+        let b = remap[circuit.idx_b[row]];
+
+        match kinds[row] {
+            RowKind::Gate => {
+                let key = (circuit.op[row], a.min(b), a.max(b));
+                if let Some(&existing) = gates.get(&key) {
+                    remap[row] = existing;
+                    continue;
+                }
+                gates.insert(key, row);
+            }
+            RowKind::ZeroTest => {
+                if !zero_tests.insert(a) {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        order.push(row);
+    }
+
+    let removed = circuit.num_rows - order.len();
+    if removed > 0 {
+        *circuit = rebuild(circuit, &order, &remap);
+    }
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::optimizer::cse::eliminate_common_subexpressions;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_cse() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+
+        let x1 = circuit.mul(a, b);
+        let x2 = circuit.mul(b, a);
+        let y1 = circuit.add(x1, a);
+        let y2 = circuit.add(x2, a);
+        let y2_neg = circuit.neg(y2);
+        let diff = circuit.add(y1, y2_neg);
+        circuit.zero_test(diff);
+        circuit.zero_test(diff);
+
+        let num_rows = circuit.num_rows;
+        assert_eq!(eliminate_common_subexpressions(&mut circuit), 3);
+        assert_eq!(circuit.num_rows, num_rows - 3);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_cse_on_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let mut circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        let num_rows = circuit.num_rows;
+
+        let removed = eliminate_common_subexpressions(&mut circuit);
+        assert!(removed > 0);
+        assert_eq!(circuit.num_rows, num_rows - removed);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::circuit::{Circuit, RowKind};
+use crate::optimizer::rebuild;
+
+// Removes gate and witness rows whose outputs are never referenced by another row and are not
+// part of the statement, cascading to rows that only fed removed rows. Inputs and zero tests are
+// always kept. Returns the number of removed rows.
+pub fn eliminate_dead_gates(circuit: &mut Circuit) -> usize {
+    let kinds = circuit.row_kinds();
+
+    let mut uses = vec![0usize; circuit.num_rows];
+    for (row, (&idx_a, &idx_b)) in circuit.idx_a.iter().zip(circuit.idx_b.iter()).enumerate() {
+        if idx_a != row {
+            uses[idx_a] += 1;
+        }
+        if idx_b != row {
+            uses[idx_b] += 1;
+        }
+    }
+    for &(idx, _) in circuit.input_maps.iter() {
+        uses[idx] += 1;
+    }
+
+    let mut order = vec![];
+    for row in (0..circuit.num_rows).rev() {
+        let removable = matches!(kinds[row], RowKind::Gate | RowKind::Witness);
+        if removable && uses[row] == 0 {
+            let (idx_a, idx_b) = (circuit.idx_a[row], circuit.idx_b[row]);
+            if idx_a != row {
+                uses[idx_a] -= 1;
+            }
+            uses[idx_b] -= 1;
+        } else {
+            order.push(row);
+        }
+    }
+    order.reverse();
+
+    let removed = circuit.num_rows - order.len();
+    if removed > 0 {
+        let remap = (0..circuit.num_rows).collect::<Vec<_>>();
+        *circuit = rebuild(circuit, &order, &remap);
+    }
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::optimizer::dead_gate::eliminate_dead_gates;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_dead_gates() {
+        let mut circuit = Circuit::new();
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let unused = circuit.new_witness(M31::from(7));
+
+        let x = circuit.mul(a, b);
+        let dead = circuit.add(x, unused);
+        circuit.neg(dead);
+
+        let x_neg = circuit.neg(x);
+        let expected = circuit.new_witness(M31::from(15));
+        let diff = circuit.add(expected, x_neg);
+        circuit.zero_test(diff);
+
+        assert_eq!(eliminate_dead_gates(&mut circuit), 3);
+        assert_eq!(circuit.num_rows, 8);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_dead_gates_on_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let mut circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        let num_rows = circuit.num_rows;
+
+        let removed = eliminate_dead_gates(&mut circuit);
+        assert_eq!(circuit.num_rows, num_rows - removed);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+}
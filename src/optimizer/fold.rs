@@ -0,0 +1,156 @@
+use crate::circuit::{Circuit, FastHashSet, RowKind, Wire};
+use crate::optimizer::rebuild;
+use ark_ff::{One, Zero};
+use stwo_prover::core::fields::m31::M31;
+
+// Replaces gate rows whose inputs are all constants by the constant they compute, with the same
+// rules as `Circuit::new_row`, which already does so for rows built one at a time. This catches
+// the rows of circuits assembled otherwise, such as spliced gadgets and deserialized circuits,
+// and those that only become constant once earlier rows are folded. A folded row is redirected
+// to the zero row or to an earlier row of its constant, or else becomes that constant's row, as
+// a row may only read the rows before it.
+// Rows that read a row of the statement are kept, as they may be the use that its entry in
+// `input_maps` stands in for. Returns the number of removed rows.
+pub fn fold_constants(circuit: &mut Circuit) -> usize {
+    let kinds = circuit.row_kinds();
+    let public = circuit
+        .input_maps
+        .iter()
+        .map(|&(idx, _)| idx)
+        .collect::<FastHashSet<_>>();
+
+    let mut remap = (0..circuit.num_rows).collect::<Vec<_>>();
+    let mut values = (0..circuit.num_rows)
+        .map(|row| circuit.get_constant(Wire(row)))
+        .collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(circuit.num_rows);
+
+    for row in 0..circuit.num_rows {
+        let reads_public =
+            public.contains(&circuit.idx_a[row]) || public.contains(&circuit.idx_b[row]);
+        if kinds[row] != RowKind::Gate || values[row].is_some() || reads_public {
+            order.push(row);
+            continue;
+        }
+
+        let a = remap[circuit.idx_a[row]];
+        let b = remap[circuit.idx_b[row]];
+        let folded = match (values[a], values[b]) {
+            (Some(x), Some(y)) => Some((x, y)),
+            (Some(x), None) if b == 0 => Some((x, M31::zero())),
+            (None, Some(y)) if a == 0 => Some((M31::zero(), y)),
+            _ => None,
+        };
+        let Some((x, y)) = folded else {
+            order.push(row);
+            continue;
+        };
+
+        let op = circuit.op[row];
+        let value = op * (x + y) + (M31::one() - op) * x * y;
+        if value.is_zero() {
+            remap[row] = 0;
+        } else if let Some(&wire) = circuit
+            .constant_maps
+            .get(&value)
+            .filter(|wire| wire.0 < row)
+        {
+            remap[row] = wire.0;
+        } else {
+            circuit.op[row] = value;
+            circuit.idx_a[row] = Wire::ONE.0;
+            circuit.idx_b[row] = Wire::ZERO.0;
+            circuit.constant_maps.insert(value, Wire(row));
+            values[row] = Some(value);
+            order.push(row);
+        }
+    }
+
+    let removed = circuit.num_rows - order.len();
+    *circuit = rebuild(circuit, &order, &remap);
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode, Wire};
+    use crate::optimizer::fold::fold_constants;
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_fold_constants() {
+        let mut circuit = Circuit::new();
+        circuit.mode = Mode::PROVE;
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(3));
+        let three = circuit.new_constant(M31::from(3));
+        let four = circuit.new_constant(M31::from(4));
+
+        // gates over constants, as splicing or deserializing may leave them
+        let gate = |circuit: &mut Circuit, op: u32, x: Wire, y: Wire, value: u32| {
+            let row = circuit.mul(a, a);
+            circuit.op[row.0] = M31::from(op);
+            circuit.idx_a[row.0] = x.0;
+            circuit.idx_b[row.0] = y.0;
+            circuit.output_wires[row.0] = M31::from(value);
+            row
+        };
+        let twelve = gate(&mut circuit, 0, three, four, 12);
+        let seven = gate(&mut circuit, 1, three, four, 7);
+        let also_twelve = gate(&mut circuit, 0, four, three, 12);
+        let nineteen = gate(&mut circuit, 1, also_twelve, seven, 19);
+        let result = circuit.mul(nineteen, a);
+        circuit.expose(result);
+        circuit.expose(twelve);
+        circuit.recompute_mult();
+        assert!(circuit.is_constraint_satisfied());
+
+        let num_rows = circuit.num_rows;
+        assert_eq!(fold_constants(&mut circuit), 1);
+        assert_eq!(circuit.num_rows, num_rows - 1);
+        // the rows before the removed one keep their indices
+        for (value, row) in [(12, twelve), (7, seven)] {
+            assert_eq!(circuit.get_constant(row), Some(M31::from(value)));
+        }
+        assert!(circuit.constant_maps.contains_key(&M31::from(19)));
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+        assert_eq!(circuit.input_maps[1].1, M31::from(57));
+        assert_eq!(circuit.validate(), Ok(()));
+
+        assert_eq!(fold_constants(&mut circuit), 0);
+    }
+
+    #[test]
+    fn test_fold_before_constant_row() {
+        let mut circuit = Circuit::new();
+        circuit.mode = Mode::PROVE;
+        circuit.new_input(M31::one());
+        let a = circuit.new_witness(M31::from(3));
+        let three = circuit.new_constant(M31::from(3));
+        let four = circuit.new_constant(M31::from(4));
+
+        // a gate computing 12 over constants, before the row of the constant 12
+        let twelve = circuit.mul(a, a);
+        circuit.op[twelve.0] = M31::from(0);
+        circuit.idx_a[twelve.0] = three.0;
+        circuit.idx_b[twelve.0] = four.0;
+        circuit.output_wires[twelve.0] = M31::from(12);
+        let result = circuit.mul(twelve, a);
+        let constant = circuit.new_constant(M31::from(12));
+        assert!(constant.0 > twelve.0);
+        let other = circuit.mul(constant, a);
+        circuit.expose(result);
+        circuit.expose(other);
+        circuit.recompute_mult();
+        assert!(circuit.is_constraint_satisfied());
+
+        // the gate becomes the row of 12 rather than reading the later one
+        assert_eq!(fold_constants(&mut circuit), 0);
+        assert_eq!(circuit.get_constant(twelve), Some(M31::from(12)));
+        assert_eq!(circuit.validate(), Ok(()));
+        assert!(circuit.is_constraint_satisfied());
+        assert_eq!(circuit.input_maps[1].1, M31::from(36));
+    }
+}
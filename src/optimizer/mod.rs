@@ -0,0 +1,282 @@
+use crate::circuit::hint::Hint;
+use crate::circuit::public_inputs::PublicInput;
+use crate::circuit::{Circuit, Wire};
+use crate::optimizer::cse::eliminate_common_subexpressions;
+use crate::optimizer::dead_gate::eliminate_dead_gates;
+use crate::optimizer::fold::fold_constants;
+use crate::optimizer::schedule::{reference_distance, schedule_for_locality};
+use std::fmt::{Display, Formatter};
+
+pub mod cse;
+
+pub mod dead_gate;
+
+pub mod fold;
+
+pub mod schedule;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    FoldConstants,
+    EliminateCommonSubexpressions,
+    EliminateDeadGates,
+    ScheduleForLocality,
+}
+
+impl Pass {
+    // Folding first turns constant gates into shared constant rows, which CSE then merges and
+    // dead-gate elimination drops once unused; scheduling only reorders, so it comes last.
+    pub const ALL: [Pass; 4] = [
+        Pass::FoldConstants,
+        Pass::EliminateCommonSubexpressions,
+        Pass::EliminateDeadGates,
+        Pass::ScheduleForLocality,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pass::FoldConstants => "fold_constants",
+            Pass::EliminateCommonSubexpressions => "cse",
+            Pass::EliminateDeadGates => "dead_gates",
+            Pass::ScheduleForLocality => "schedule",
+        }
+    }
+
+    pub fn run(&self, circuit: &mut Circuit) {
+        match self {
+            Pass::FoldConstants => {
+                fold_constants(circuit);
+            }
+            Pass::EliminateCommonSubexpressions => {
+                eliminate_common_subexpressions(circuit);
+            }
+            Pass::EliminateDeadGates => {
+                eliminate_dead_gates(circuit);
+            }
+            Pass::ScheduleForLocality => schedule_for_locality(circuit),
+        }
+    }
+}
+
+// The effect of one pass, as the rows and the reference distance (see `reference_distance`)
+// before and after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassReport {
+    pub pass: Pass,
+    pub rows_before: usize,
+    pub rows_after: usize,
+    pub distance_before: usize,
+    pub distance_after: usize,
+}
+
+impl PassReport {
+    pub fn rows_removed(&self) -> usize {
+        self.rows_before.saturating_sub(self.rows_after)
+    }
+
+    pub fn rows_added(&self) -> usize {
+        self.rows_after.saturating_sub(self.rows_before)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub passes: Vec<PassReport>,
+}
+
+impl OptimizationReport {
+    pub fn rows_removed(&self) -> usize {
+        self.passes.iter().map(PassReport::rows_removed).sum()
+    }
+
+    pub fn rows_added(&self) -> usize {
+        self.passes.iter().map(PassReport::rows_added).sum()
+    }
+}
+
+impl Display for OptimizationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>10} {:>10} {:>8} {:>8} {:>12}",
+            "pass", "before", "after", "removed", "added", "distance"
+        )?;
+        for report in self.passes.iter() {
+            writeln!(
+                f,
+                "{:<16} {:>10} {:>10} {:>8} {:>8} {:>12}",
+                report.pass.as_str(),
+                report.rows_before,
+                report.rows_after,
+                report.rows_removed(),
+                report.rows_added(),
+                report.distance_after
+            )?;
+        }
+        write!(
+            f,
+            "total: {} rows removed, {} rows added",
+            self.rows_removed(),
+            self.rows_added()
+        )
+    }
+}
+
+// Runs a sequence of passes over a circuit. The default runs all of them, in the order of
+// `Pass::ALL`; the passes are also exposed on their own, for callers that only want one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Optimizer {
+    pub passes: Vec<Pass>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self {
+            passes: Pass::ALL.to_vec(),
+        }
+    }
+}
+
+impl Optimizer {
+    pub fn with_passes(mut self, passes: &[Pass]) -> Self {
+        self.passes = passes.to_vec();
+        self
+    }
+
+    pub fn run(&self, circuit: &mut Circuit) -> OptimizationReport {
+        let mut report = OptimizationReport::default();
+        for &pass in self.passes.iter() {
+            let rows_before = circuit.num_rows;
+            let distance_before = reference_distance(circuit);
+            pass.run(circuit);
+            report.passes.push(PassReport {
+                pass,
+                rows_before,
+                rows_after: circuit.num_rows,
+                distance_before,
+                distance_after: reference_distance(circuit),
+            });
+        }
+        report
+    }
+}
+
+// Builds a new circuit out of the rows in `order` (old indices, in their new order). References
+// to an old row `r` are redirected to `remap[r]`, which must be one of the kept rows. The caller
+// is responsible for `order` starting with the zero row and keeping definitions before uses.
+pub(crate) fn rebuild(circuit: &Circuit, order: &[usize], remap: &[usize]) -> Circuit {
+    assert_eq!(order.first(), Some(&0));
+
+    let mut position = vec![usize::MAX; circuit.num_rows];
+    for (new, &old) in order.iter().enumerate() {
+        position[old] = new;
+    }
+    let new_index = |old: usize| {
+        let new = position[remap[old]];
+        assert_ne!(new, usize::MAX, "row {} is referenced but removed", old);
+        new
+    };
+
+    let mut output = Circuit {
+        num_rows: order.len(),
+        mode: circuit.mode,
+        max_log_size: circuit.max_log_size,
+        ..Default::default()
+    };
+
+    for &old in order.iter() {
+        output.output_wires.push(circuit.output_wires[old]);
+        output.op.push(circuit.op[old]);
+        output.idx_a.push(new_index(circuit.idx_a[old]));
+        output.idx_b.push(new_index(circuit.idx_b[old]));
+    }
+
+    output.input_maps = circuit
+        .input_maps
+        .entries()
+        .map(|entry| PublicInput {
+            wire: new_index(entry.wire),
+            ..entry
+        })
+        .collect();
+    output.constant_maps = circuit
+        .constant_maps
+        .iter()
+        .filter_map(|(constant, wire)| {
+            let new = position[remap[wire.0]];
+            (new != usize::MAX).then_some((constant, Wire(new)))
+        })
+        .collect();
+    // a hint is dropped with its row or with any row it reads, leaving a plain witness row
+    output.hints = circuit
+        .hints
+        .iter()
+        .filter_map(|hint| {
+            let row = position[remap[hint.row]];
+            let inputs = hint
+                .inputs
+                .iter()
+                .map(|&idx| position[remap[idx]])
+                .collect::<Vec<_>>();
+            (row != usize::MAX && inputs.iter().all(|&idx| idx != usize::MAX)).then(|| Hint {
+                row,
+                inputs,
+                compute: hint.compute.clone(),
+            })
+        })
+        .collect();
+    output.debug_info = circuit.debug_info.remap(|old| {
+        let new = position[old];
+        (new != usize::MAX).then_some(new)
+    });
+
+    output.recompute_mult();
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::optimizer::{Optimizer, Pass};
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+
+    #[test]
+    fn test_optimizer() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let mut circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        let num_rows = circuit.num_rows;
+        let report = Optimizer::default().run(&mut circuit);
+        assert_eq!(
+            report.passes.iter().map(|r| r.pass).collect::<Vec<_>>(),
+            Pass::ALL.to_vec()
+        );
+        for pair in report.passes.windows(2) {
+            assert_eq!(pair[0].rows_after, pair[1].rows_before);
+            assert_eq!(pair[0].distance_after, pair[1].distance_before);
+        }
+        assert_eq!(
+            circuit.num_rows,
+            num_rows - report.rows_removed() + report.rows_added()
+        );
+        assert_eq!(report.passes[3].rows_removed(), 0);
+        assert!(report.to_string().ends_with(&format!(
+            "total: {} rows removed, 0 rows added",
+            report.rows_removed()
+        )));
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+        assert_eq!(circuit.validate(), Ok(()));
+
+        let report = Optimizer::default()
+            .with_passes(&[Pass::EliminateDeadGates])
+            .run(&mut circuit);
+        assert_eq!(report.passes.len(), 1);
+        assert_eq!(report.rows_removed(), 0);
+    }
+}
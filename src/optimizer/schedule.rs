@@ -0,0 +1,148 @@
+use crate::circuit::{Circuit, RowKind};
+use crate::optimizer::rebuild;
+use std::collections::HashMap;
+
+// Reorders the rows so that each row is placed right after the rows it depends on, by walking
+// the dependencies of every sink (a row that no other row refers to) in their original order.
+// This makes most `idx_a`/`idx_b` references point to nearby rows. The zero row and the input
+// rows keep their relative order at the front of the circuit, so the constant one stays at
+// `Wire::ONE`. The rows read by a hint count as dependencies of the hinted row.
+pub fn schedule_for_locality(circuit: &mut Circuit) {
+    let kinds = circuit.row_kinds();
+    let hint_inputs = circuit
+        .hints
+        .iter()
+        .map(|hint| (hint.row, hint.inputs.as_slice()))
+        .collect::<HashMap<_, _>>();
+
+    let mut has_consumer = vec![false; circuit.num_rows];
+    for (row, (&idx_a, &idx_b)) in circuit.idx_a.iter().zip(circuit.idx_b.iter()).enumerate() {
+        if idx_a != row {
+            has_consumer[idx_a] = true;
+        }
+        if idx_b != row {
+            has_consumer[idx_b] = true;
+        }
+    }
+
+    let mut scheduled = vec![false; circuit.num_rows];
+    let mut expanding = vec![false; circuit.num_rows];
+    let mut order = Vec::with_capacity(circuit.num_rows);
+
+    for row in 0..circuit.num_rows {
+        if matches!(kinds[row], RowKind::Zero | RowKind::Input) {
+            scheduled[row] = true;
+            order.push(row);
+        }
+    }
+
+    let mut stack = vec![];
+    for root in 0..circuit.num_rows {
+        if scheduled[root] || has_consumer[root] {
+            continue;
+        }
+
+        stack.push((root, false));
+        while let Some((row, expanded)) = stack.pop() {
+            if scheduled[row] {
+                continue;
+            }
+            if expanded {
+                scheduled[row] = true;
+                order.push(row);
+                continue;
+            }
+
+            if expanding[row] {
+                // only a hint reading its own consumers can get here, and it cannot be ordered
+                continue;
+            }
+            expanding[row] = true;
+
+            stack.push((row, true));
+            let hint_deps = hint_inputs.get(&row).copied().unwrap_or_default();
+            for &dep in hint_deps.iter().rev() {
+                if !scheduled[dep] {
+                    stack.push((dep, false));
+                }
+            }
+            for dep in [circuit.idx_b[row], circuit.idx_a[row]] {
+                if dep != row && !scheduled[dep] {
+                    stack.push((dep, false));
+                }
+            }
+        }
+    }
+
+    let remap = (0..circuit.num_rows).collect::<Vec<_>>();
+    *circuit = rebuild(circuit, &order, &remap);
+}
+
+// The total distance between gate rows and the rows they refer to, as a measure of locality.
+pub fn reference_distance(circuit: &Circuit) -> usize {
+    let mut distance = 0;
+    for (row, (&idx_a, &idx_b)) in circuit.idx_a.iter().zip(circuit.idx_b.iter()).enumerate() {
+        if idx_a != 0 {
+            distance += row.abs_diff(idx_a);
+        }
+        if idx_b != 0 {
+            distance += row.abs_diff(idx_b);
+        }
+    }
+    distance
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode, Wire};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::optimizer::schedule::{reference_distance, schedule_for_locality};
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_schedule() {
+        let mut circuit = Circuit::new();
+        let x = circuit.new_input(M31::from(2)).wire();
+
+        let witnesses = (0..8)
+            .map(|i| circuit.new_witness(M31::from(i)))
+            .collect::<Vec<_>>();
+        let mut products = vec![];
+        for &w in witnesses.iter() {
+            products.push(circuit.mul(w, x));
+        }
+        let mut acc = Wire::ZERO;
+        for &p in products.iter().rev() {
+            acc = circuit.add(acc, p);
+        }
+
+        let distance = reference_distance(&circuit);
+        let num_rows = circuit.num_rows;
+        schedule_for_locality(&mut circuit);
+        assert!(reference_distance(&circuit) < distance);
+        assert_eq!(circuit.num_rows, num_rows);
+        assert_eq!(circuit.input_maps[0].0, 1);
+        assert_eq!(circuit.output_wires[num_rows - 1], M31::from(56));
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+
+    #[test]
+    fn test_schedule_on_conversion() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+
+        let mut circuit = generate_circuit(test_circuit, Mode::PROVE).unwrap().circuit;
+        let num_rows = circuit.num_rows;
+
+        schedule_for_locality(&mut circuit);
+        assert_eq!(circuit.num_rows, num_rows);
+
+        assert!(circuit.is_constraint_satisfied());
+        assert!(circuit.is_logup_satisfied(&circuit.input_maps));
+    }
+}
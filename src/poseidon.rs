@@ -0,0 +1,458 @@
+use ark_ff::{One, Zero};
+use std::sync::OnceLock;
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::fields::FieldExpOps;
+
+// The S-box is x^5, which is a permutation of M31 since gcd(5, p - 1) = 1.
+pub const ALPHA: u32 = 5;
+
+// The widths with parameters, as (width, full rounds, partial rounds), with the round numbers for
+// 128-bit security over a 31-bit field with x^5.
+const ROUND_NUMBERS: [(usize, usize, usize); 2] = [(16, 8, 14), (24, 8, 22)];
+
+// The parameters of the Poseidon permutation over M31 at a given width, generated as by the
+// reference script of the Poseidon paper (`generate_parameters_grain.sage`): the round constants
+// and then the Cauchy MDS matrix are sampled from the Grain LFSR seeded with the field, the S-box,
+// the width and the round numbers, so that anyone can regenerate and check them.
+//
+// The native permutation below, the channel and the in-circuit gadget all take their parameters
+// from `PoseidonParams::get`, so that they cannot disagree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonParams {
+    pub width: usize,
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    // one row of `width` constants per round, the full rounds being split evenly around the
+    // partial ones
+    pub round_constants: Vec<Vec<M31>>,
+    pub mds: Vec<Vec<M31>>,
+}
+
+struct GrainLfsr {
+    state: [bool; 80],
+}
+
+impl GrainLfsr {
+    fn new(field_bits: usize, width: usize, full_rounds: usize, partial_rounds: usize) -> Self {
+        let mut state = [true; 80];
+        let mut pos = 0;
+        // the field is a prime field (1) and the S-box is x^alpha (0)
+        for (value, len) in [
+            (1, 2),
+            (0, 4),
+            (field_bits, 12),
+            (width, 12),
+            (full_rounds, 10),
+            (partial_rounds, 10),
+        ] {
+            for i in (0..len).rev() {
+                state[pos] = (value >> i) & 1 == 1;
+                pos += 1;
+            }
+        }
+        // the remaining 30 bits are ones
+
+        let mut lfsr = Self { state };
+        for _ in 0..160 {
+            lfsr.step();
+        }
+        lfsr
+    }
+
+    fn step(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.copy_within(1.., 0);
+        self.state[79] = bit;
+        bit
+    }
+
+    // The bits are taken in pairs, and the second one is kept only if the first one is set.
+    fn next_bit(&mut self) -> bool {
+        loop {
+            let keep = self.step();
+            let bit = self.step();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    // The most significant bit comes first.
+    fn next_bits(&mut self, num_bits: usize) -> u32 {
+        (0..num_bits).fold(0, |acc, _| (acc << 1) | self.next_bit() as u32)
+    }
+
+    fn next_element(&mut self) -> M31 {
+        loop {
+            let v = self.next_bits(31);
+            if v < P {
+                return M31::from(v);
+            }
+        }
+    }
+}
+
+// Whether a subspace of states keeps the S-box of the partial rounds inactive in every round, which
+// is an infinitely long subspace trail (Grassi, Rechberger and Schofnegger, "Proving Resistance
+// Against Infinitely Long Subspace Trails", ToSC 2021). The S-box reads `(M^i x)[0]` after `i`
+// rounds, so the largest such subspace is the kernel of the rows `e_0 M^i`, and by Cayley-Hamilton
+// the first `width` of them span all the others. A trail that is only invariant under a power of
+// `M` lies in that kernel too, so none exists when the rows have full rank.
+fn has_infinite_subspace_trail(mds: &[Vec<M31>]) -> bool {
+    let width = mds.len();
+    let mut row = (0..width)
+        .map(|j| if j == 0 { M31::one() } else { M31::zero() })
+        .collect::<Vec<_>>();
+    let mut rows = Vec::with_capacity(width);
+    for _ in 0..width {
+        let next = (0..width)
+            .map(|j| (0..width).fold(M31::zero(), |acc, k| acc + row[k] * mds[k][j]))
+            .collect();
+        rows.push(std::mem::replace(&mut row, next));
+    }
+    rank(rows) < width
+}
+
+fn rank(mut rows: Vec<Vec<M31>>) -> usize {
+    let mut rank = 0;
+    for col in 0..rows.first().map_or(0, |row| row.len()) {
+        let Some(pivot) = (rank..rows.len()).find(|&i| !rows[i][col].is_zero()) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+        let inv = rows[rank][col].inverse();
+        for i in rank + 1..rows.len() {
+            let factor = rows[i][col] * inv;
+            for k in col..rows[i].len() {
+                let v = rows[rank][k];
+                rows[i][k] -= factor * v;
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+impl PoseidonParams {
+    pub fn supported_widths() -> impl Iterator<Item = usize> {
+        ROUND_NUMBERS.iter().map(|&(width, _, _)| width)
+    }
+
+    // Panics if the width is not one of `supported_widths`.
+    pub fn generate(width: usize) -> Self {
+        let &(_, full_rounds, partial_rounds) = ROUND_NUMBERS
+            .iter()
+            .find(|&&(w, _, _)| w == width)
+            .unwrap_or_else(|| panic!("No Poseidon parameters for width {}", width));
+
+        let mut lfsr = GrainLfsr::new(31, width, full_rounds, partial_rounds);
+        let round_constants = (0..full_rounds + partial_rounds)
+            .map(|_| (0..width).map(|_| lfsr.next_element()).collect())
+            .collect();
+
+        // M[i][j] = 1 / (x_i + y_j) for 2 * width distinct values, which is MDS as long as no
+        // x_i + y_j is zero. Unlike the round constants, these are reduced rather than rejected.
+        // As in the reference script, a matrix that lets an infinitely long subspace trail through
+        // the partial rounds is rejected as well.
+        let mds = loop {
+            let values = (0..2 * width)
+                .map(|_| M31::from(lfsr.next_bits(31)))
+                .collect::<Vec<_>>();
+            let distinct = (0..values.len()).all(|i| !values[..i].contains(&values[i]));
+            if !distinct {
+                continue;
+            }
+
+            let (xs, ys) = values.split_at(width);
+            let sums = xs
+                .iter()
+                .map(|&x| ys.iter().map(|&y| x + y).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            if sums.iter().flatten().any(|v| v.is_zero()) {
+                continue;
+            }
+            let mds = sums
+                .into_iter()
+                .map(|row| row.into_iter().map(|v| v.inverse()).collect())
+                .collect::<Vec<_>>();
+            if !has_infinite_subspace_trail(&mds) {
+                break mds;
+            }
+        };
+
+        Self {
+            width,
+            full_rounds,
+            partial_rounds,
+            round_constants,
+            mds,
+        }
+    }
+
+    // The parameters are generated once per process and then shared.
+    pub fn get(width: usize) -> &'static Self {
+        static PARAMS: OnceLock<Vec<PoseidonParams>> = OnceLock::new();
+        PARAMS
+            .get_or_init(|| Self::supported_widths().map(Self::generate).collect())
+            .iter()
+            .find(|params| params.width == width)
+            .unwrap_or_else(|| panic!("No Poseidon parameters for width {}", width))
+    }
+
+    fn sbox(v: M31) -> M31 {
+        let v2 = v * v;
+        v2 * v2 * v
+    }
+
+    pub fn permute(&self, state: &mut [M31]) {
+        assert_eq!(state.len(), self.width);
+        let half = self.full_rounds / 2;
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (v, &c) in state.iter_mut().zip(constants.iter()) {
+                *v += c;
+            }
+            if round < half || round >= half + self.partial_rounds {
+                for v in state.iter_mut() {
+                    *v = Self::sbox(*v);
+                }
+            } else {
+                state[0] = Self::sbox(state[0]);
+            }
+
+            let product = self
+                .mds
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(state.iter())
+                        .fold(M31::zero(), |acc, (&m, &v)| acc + m * v)
+                })
+                .collect::<Vec<_>>();
+            state.copy_from_slice(&product);
+        }
+    }
+}
+
+// A sponge over the permutation of width 16, whose capacity of eight elements (248 bits) gives
+// 124 bits of security. The domain, which separates the uses of the sponge (a commitment, a
+// nullifier, a challenge), is the first element of the capacity. The input is padded with a one
+// and then zeros up to a multiple of the rate, so that inputs of different lengths, such as `[x]`
+// and `[x, 0]`, are absorbed differently. The gadgets `poseidon_sponge` and `hash_to_field`
+// compute the same function in a circuit.
+pub const SPONGE_WIDTH: usize = 16;
+pub const SPONGE_RATE: usize = 8;
+
+// The blocks of the padded input.
+pub fn sponge_blocks<T: Copy>(input: &[T], one: T, zero: T) -> Vec<[T; SPONGE_RATE]> {
+    let mut padded = input.to_vec();
+    padded.push(one);
+    padded.resize(padded.len().next_multiple_of(SPONGE_RATE), zero);
+    padded
+        .chunks(SPONGE_RATE)
+        .map(|block| block.try_into().unwrap())
+        .collect()
+}
+
+// Absorbs the input and squeezes `num_outputs` elements, a rate at a time.
+pub fn poseidon_sponge(domain: M31, input: &[M31], num_outputs: usize) -> Vec<M31> {
+    let params = PoseidonParams::get(SPONGE_WIDTH);
+    let mut state = [M31::zero(); SPONGE_WIDTH];
+    state[SPONGE_RATE] = domain;
+    for block in sponge_blocks(input, M31::one(), M31::zero()) {
+        for (v, x) in state.iter_mut().zip(block) {
+            *v += x;
+        }
+        params.permute(&mut state);
+    }
+
+    let mut output = state[..SPONGE_RATE].to_vec();
+    while output.len() < num_outputs {
+        params.permute(&mut state);
+        output.extend_from_slice(&state[..SPONGE_RATE]);
+    }
+    output.truncate(num_outputs);
+    output
+}
+
+// Elements of M31 derived from the input. Each is an element of the state of the permutation, so
+// unlike a reduction of hash bytes, it is uniform over the field.
+pub fn hash_to_field(domain: M31, input: &[M31], num_outputs: usize) -> Vec<M31> {
+    poseidon_sponge(domain, input, num_outputs)
+}
+
+// Elements of QM31 derived from the input, each from four elements of M31 in the order of
+// `QM31::from_m31_array`.
+pub fn hash_to_qm31(domain: M31, input: &[M31], num_outputs: usize) -> Vec<QM31> {
+    poseidon_sponge(domain, input, 4 * num_outputs)
+        .chunks(4)
+        .map(|limbs| QM31::from_m31_array(limbs.try_into().unwrap()))
+        .collect()
+}
+
+// Known answers for the permutation of `[0, 1, ..., width - 1]` at every supported width, from a
+// separate implementation of the reference script. The native permutation and the gadget are both
+// checked against them, as a recursive verifier depends on all of them computing the same
+// function.
+#[cfg(test)]
+pub(crate) const PERMUTATION_KATS: [(usize, &[u32]); 2] = [
+    (
+        16,
+        &[
+            1182793737, 1050618593, 535552758, 1388303390, 587104080, 164009065, 544365430,
+            220249815, 204143655, 622546178, 1194423514, 556563026, 239219151, 682066528,
+            1593407198, 477702140,
+        ],
+    ),
+    (
+        24,
+        &[
+            1665581592, 1659810388, 1820639640, 671298388, 32383121, 615981033, 988069772,
+            1632788739, 1550779326, 1227049567, 1723511987, 1308552361, 1555434706, 73777309,
+            1122384362, 495343211, 139209827, 43998251, 1462718534, 1696342152, 726423021,
+            975076681, 1757436190, 1923222716,
+        ],
+    ),
+];
+
+#[cfg(test)]
+mod test {
+    use crate::poseidon::{
+        has_infinite_subspace_trail, hash_to_field, hash_to_qm31, poseidon_sponge, GrainLfsr,
+        PoseidonParams, PERMUTATION_KATS, SPONGE_RATE,
+    };
+    use ark_ff::Zero;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    #[test]
+    fn test_grain_lfsr() {
+        // the first round constant of Poseidon over BN254 with width 3, as in circomlib
+        let mut lfsr = GrainLfsr::new(254, 3, 8, 57);
+        let bits = (0..256)
+            .map(|i| i >= 2 && lfsr.next_bit())
+            .collect::<Vec<_>>();
+        let hex = bits
+            .chunks(4)
+            .map(|nibble| {
+                let v = nibble.iter().fold(0, |acc, &bit| (acc << 1) | bit as u32);
+                char::from_digit(v, 16).unwrap()
+            })
+            .collect::<String>();
+        assert_eq!(
+            hex,
+            "0ee9a592ba9a9518d05986d656f40c2114c4993c11bb29938d21d47304cd8e6e"
+        );
+    }
+
+    #[test]
+    fn test_poseidon_params() {
+        for width in PoseidonParams::supported_widths() {
+            let params = PoseidonParams::get(width);
+            assert_eq!(*params, PoseidonParams::generate(width));
+            assert_eq!(
+                params.round_constants.len(),
+                params.full_rounds + params.partial_rounds
+            );
+            assert!(params.round_constants.iter().all(|row| row.len() == width));
+
+            // the MDS matrix is invertible
+            let mut m = params.mds.clone();
+            for col in 0..width {
+                let pivot = (col..width).find(|&row| !m[row][col].is_zero());
+                let pivot = pivot.expect("the MDS matrix is singular");
+                m.swap(col, pivot);
+                let inv = m[col][col].inverse();
+                for row in col + 1..width {
+                    let factor = m[row][col] * inv;
+                    for k in col..width {
+                        let v = m[col][k];
+                        m[row][k] -= factor * v;
+                    }
+                }
+            }
+
+            let mut a = (0..width as u32).map(M31::from).collect::<Vec<_>>();
+            let mut b = a.clone();
+            b[width - 1] += M31::from(1);
+            params.permute(&mut a);
+            params.permute(&mut b);
+            assert!(a.iter().zip(b.iter()).all(|(a, b)| a != b));
+        }
+
+        // the first round constant and the first MDS entry, as the reference script gives them
+        assert_eq!(
+            PoseidonParams::get(16).round_constants[0][0],
+            M31::from(1988864850)
+        );
+        assert_eq!(PoseidonParams::get(16).mds[0][0], M31::from(840314848));
+        assert_eq!(
+            PoseidonParams::get(24).round_constants[0][0],
+            M31::from(535476833)
+        );
+        assert_eq!(PoseidonParams::get(24).mds[0][0], M31::from(458338370));
+
+        for (width, expected) in PERMUTATION_KATS {
+            let mut state = (0..width as u32).map(M31::from).collect::<Vec<_>>();
+            PoseidonParams::get(width).permute(&mut state);
+            assert_eq!(
+                state,
+                expected.iter().copied().map(M31::from).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_subspace_trail() {
+        for width in PoseidonParams::supported_widths() {
+            let mds = &PoseidonParams::get(width).mds;
+            assert!(!has_infinite_subspace_trail(mds));
+
+            // with the first two cells mixed only among themselves, the states that are zero on
+            // them never reach the S-box
+            let mut block = mds.clone();
+            for (i, row) in block.iter_mut().enumerate() {
+                for (j, v) in row.iter_mut().enumerate() {
+                    if (i < 2) != (j < 2) {
+                        *v = M31::zero();
+                    }
+                }
+            }
+            assert!(has_infinite_subspace_trail(&block));
+        }
+    }
+
+    #[test]
+    fn test_poseidon_sponge() {
+        let domain = M31::from(7);
+        let inputs = (0..=2 * SPONGE_RATE)
+            .map(|len| (0..len as u32).map(M31::from).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let hashes = inputs
+            .iter()
+            .map(|input| poseidon_sponge(domain, input, 1)[0])
+            .collect::<Vec<_>>();
+        // in particular `[]` and `[0]`, or a full block and the same block with a zero after it
+        for i in 0..hashes.len() {
+            assert!(!hashes[..i].contains(&hashes[i]));
+        }
+        let zeros = [M31::zero(); SPONGE_RATE];
+        assert_ne!(
+            poseidon_sponge(domain, &zeros, 1),
+            poseidon_sponge(domain, &zeros[..SPONGE_RATE - 1], 1)
+        );
+        assert_ne!(
+            poseidon_sponge(domain, &inputs[3], 1),
+            poseidon_sponge(M31::from(8), &inputs[3], 1)
+        );
+
+        // squeezing more is an extension of squeezing less
+        let long = poseidon_sponge(domain, &inputs[3], 2 * SPONGE_RATE + 1);
+        assert_eq!(long.len(), 2 * SPONGE_RATE + 1);
+        assert_eq!(long[..3], poseidon_sponge(domain, &inputs[3], 3));
+        assert_eq!(hash_to_field(domain, &inputs[3], 3), long[..3]);
+        let challenges = hash_to_qm31(domain, &inputs[3], 3);
+        assert_eq!(challenges[2].to_m31_array(), long[8..12]);
+    }
+}
@@ -0,0 +1,919 @@
+use crate::circuit::public_inputs::PublicInputs;
+use crate::proof_system::{
+    check_commitments, claimed_sum_from_inputs, column_log_sizes, max_degree, max_degrees,
+    verify_plonk_on, HasherConfig, KeyVerificationError, PlonkProof, Sha256Config, VerificationKey,
+};
+use ark_ff::{One, Zero};
+use itertools::{zip_eq, Itertools};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use stwo_prover::constraint_framework::logup::LookupElements;
+use stwo_prover::core::air::Component;
+use stwo_prover::core::channel::{BWSSha256Channel, Channel};
+use stwo_prover::core::circle::{CirclePoint, Coset};
+use stwo_prover::core::fields::m31::{BaseField, M31};
+use stwo_prover::core::fields::qm31::SecureField;
+use stwo_prover::core::fields::secure_column::SECURE_EXTENSION_DEGREE;
+use stwo_prover::core::fields::{FieldExpOps, IntoSlice};
+use stwo_prover::core::fri::{get_opening_positions, SparseCircleEvaluation};
+use stwo_prover::core::pcs::quotients::{fri_answers, PointSample};
+use stwo_prover::core::pcs::CommitmentSchemeVerifier;
+use stwo_prover::core::poly::line::LineDomain;
+use stwo_prover::core::prover::{StarkProof, VerificationError, LOG_BLOWUP_FACTOR, N_QUERIES};
+use stwo_prover::core::queries::Queries;
+use stwo_prover::core::utils::bit_reverse_index;
+use stwo_prover::core::vcs::bws_sha256_hash::{BWSSha256Hash, BWSSha256Hasher};
+use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
+use stwo_prover::core::vcs::prover::MerkleDecommitment;
+use stwo_prover::examples::plonk::PlonkComponent;
+
+// A proof laid out as the stack items of a Bitcoin script verifier, split over the inputs of a
+// chain of covenant transactions, one tapleaf per input.
+//
+// The items are, in order:
+// - the statement: `log_n_rows` and the values of the inputs;
+// - the trace, interaction and constant commitments;
+// - the Fiat-Shamir hints for the first round, which the script checks rather than recomputes:
+//   the lookup elements `z` and `alpha` and the claimed logup sum, each as four M31;
+// - the sampled values, by tree and then column;
+// - the Merkle decommitments of the trees;
+// - the queried values, by tree and then column;
+// - the nonce of the proof of work, as 8 little-endian bytes;
+// - the FRI proof: every inner layer, as its evaluations, its decommitment and its commitment,
+//   and then the coefficients of the last layer.
+//
+// A list whose length depends on the queries, such as the values of a column or the hashes of a
+// decommitment, is preceded by its length, and a decommitment is its hashes and then its column
+// values. M31 elements are script numbers, elements of the secure field are their four M31
+// coordinates, and hashes are 32-byte pushes, so that no item is above the 520-byte limit of a
+// push.
+//
+// Each chunk is bound to the next one by a running hash of the items, `state = sha256(state ||
+// item)` from 32 zero bytes, which a tapleaf recomputes with OP_CAT and OP_SHA256 and commits to
+// in the covenant of its output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BitcoinArtifacts {
+    pub chunks: Vec<TapleafChunk>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TapleafChunk {
+    pub state_in: [u8; 32],
+    pub items: Vec<Vec<u8>>,
+    pub state_out: [u8; 32],
+}
+
+// The tapscript limits that the chunks have to fit in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptLimits {
+    // the stack items of a single input, minus those the script needs for itself
+    pub max_items_per_chunk: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        // 1000 stack items, of which a few are left to the verifier
+        Self {
+            max_items_per_chunk: 900,
+        }
+    }
+}
+
+impl ScriptLimits {
+    pub fn with_max_items_per_chunk(mut self, max_items_per_chunk: usize) -> Self {
+        self.max_items_per_chunk = max_items_per_chunk;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum ArtifactsError {
+    // the limits leave no room for any item in an input
+    EmptyChunks,
+    Key(KeyVerificationError),
+}
+
+impl Display for ArtifactsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactsError::EmptyChunks => {
+                write!(f, "an input needs room for at least one item")
+            }
+            ArtifactsError::Key(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactsError {}
+
+impl From<KeyVerificationError> for ArtifactsError {
+    fn from(err: KeyVerificationError) -> Self {
+        ArtifactsError::Key(err)
+    }
+}
+
+// The minimal little-endian encoding of a nonnegative script number.
+pub fn script_num(v: u32) -> Vec<u8> {
+    let mut bytes = v.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    if bytes.last().is_some_and(|&b| b & 0x80 != 0) {
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn push_secure_field(items: &mut Vec<Vec<u8>>, v: SecureField) {
+    items.extend(v.to_m31_array().iter().map(|m| script_num(m.0)));
+}
+
+fn push_len(items: &mut Vec<Vec<u8>>, len: usize) {
+    items.push(script_num(len as u32));
+}
+
+fn push_m31s(items: &mut Vec<Vec<u8>>, values: &[BaseField]) {
+    push_len(items, values.len());
+    items.extend(values.iter().map(|v| script_num(v.0)));
+}
+
+fn push_secure_fields(items: &mut Vec<Vec<u8>>, values: &[SecureField]) {
+    push_len(items, values.len());
+    for &v in values.iter() {
+        push_secure_field(items, v);
+    }
+}
+
+fn push_decommitment(
+    items: &mut Vec<Vec<u8>>,
+    decommitment: &MerkleDecommitment<BWSSha256MerkleHasher>,
+) {
+    push_len(items, decommitment.hash_witness.len());
+    items.extend(
+        decommitment
+            .hash_witness
+            .iter()
+            .map(|hash| hash.as_ref().to_vec()),
+    );
+    push_m31s(items, &decommitment.column_witness);
+}
+
+// Everything of the STARK proof after the commitments.
+fn push_proof(items: &mut Vec<Vec<u8>>, proof: &StarkProof<BWSSha256MerkleHasher>) {
+    let scheme_proof = &proof.commitment_scheme_proof;
+    for tree in scheme_proof.sampled_values.iter() {
+        for column in tree.iter() {
+            push_secure_fields(items, column);
+        }
+    }
+    for decommitment in scheme_proof.decommitments.iter() {
+        push_decommitment(items, decommitment);
+    }
+    for tree in scheme_proof.queried_values.iter() {
+        for column in tree.iter() {
+            push_m31s(items, column);
+        }
+    }
+    items.push(scheme_proof.proof_of_work.nonce.to_le_bytes().to_vec());
+
+    let fri_proof = &scheme_proof.fri_proof;
+    push_len(items, fri_proof.inner_layers.len());
+    for layer in fri_proof.inner_layers.iter() {
+        push_secure_fields(items, &layer.evals_subset);
+        push_decommitment(items, &layer.decommitment);
+        items.push(layer.commitment.as_ref().to_vec());
+    }
+    push_secure_fields(items, &fri_proof.last_layer_poly);
+}
+
+// The lookup elements drawn after the trace commitment, by replaying the transcript of
+// `verify_plonk` up to that point.
+fn draw_lookup_elements(
+    log_n_rows: u32,
+    proof: &StarkProof<BWSSha256MerkleHasher>,
+) -> Result<LookupElements<2>, VerificationError> {
+    check_commitments(proof)?;
+    let channel = &mut BWSSha256Channel::new(BWSSha256Hasher::hash(BaseField::into_slice(&[])));
+    let commitment_scheme = &mut CommitmentSchemeVerifier::<BWSSha256MerkleHasher>::new();
+    let sizes = column_log_sizes(log_n_rows);
+    commitment_scheme.commit(proof.commitments[0], &max_degrees(&sizes[0]), channel);
+    Ok(LookupElements::draw(channel))
+}
+
+impl BitcoinArtifacts {
+    pub fn new(
+        key: &VerificationKey,
+        values: &[M31],
+        proof: &PlonkProof,
+        limits: ScriptLimits,
+    ) -> Result<Self, ArtifactsError> {
+        if limits.max_items_per_chunk == 0 {
+            return Err(ArtifactsError::EmptyChunks);
+        }
+        if values.len() != key.input_wires.len() {
+            return Err(KeyVerificationError::WrongNumberOfInputs {
+                expected: key.input_wires.len(),
+                actual: values.len(),
+            }
+            .into());
+        }
+        if proof.log_n_rows != key.log_n_rows {
+            return Err(KeyVerificationError::WrongNumberOfRows {
+                expected: key.log_n_rows,
+                actual: proof.log_n_rows,
+            }
+            .into());
+        }
+
+        let mut items = vec![script_num(key.log_n_rows)];
+        items.extend(values.iter().map(|v| script_num(v.0)));
+        for commitment in proof.proof.commitments.iter() {
+            items.push(commitment.as_ref().to_vec());
+        }
+
+        let lookup_elements = draw_lookup_elements(key.log_n_rows, &proof.proof)
+            .map_err(KeyVerificationError::Stark)?;
+        let inputs = key
+            .input_wires
+            .iter()
+            .copied()
+            .zip(values.iter().copied())
+            .collect::<Vec<_>>();
+        let claimed_sum = claimed_sum_from_inputs(&lookup_elements, &inputs);
+        push_secure_field(&mut items, lookup_elements.z);
+        push_secure_field(&mut items, lookup_elements.alpha);
+        push_secure_field(&mut items, claimed_sum);
+
+        push_proof(&mut items, &proof.proof);
+
+        let mut state = [0u8; 32];
+        let chunks = items
+            .chunks(limits.max_items_per_chunk)
+            .map(|items| {
+                let state_in = state;
+                for item in items.iter() {
+                    state = Sha256::new()
+                        .chain_update(state)
+                        .chain_update(item)
+                        .finalize()
+                        .into();
+                }
+                TapleafChunk {
+                    state_in,
+                    items: items.to_vec(),
+                    state_out: state,
+                }
+            })
+            .collect();
+        Ok(Self { chunks })
+    }
+}
+
+// One operation of the verifier on its Fiat-Shamir channel, with the digest of the channel after
+// it. Field elements are given by their four M31 coordinates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelOp {
+    MixDigest([u8; 32]),
+    MixFelts(Vec<[u32; 4]>),
+    MixNonce(u64),
+    DrawFelts(Vec<[u32; 4]>),
+    DrawBytes(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChannelStep {
+    pub op: ChannelOp,
+    pub digest: [u8; 32],
+}
+
+fn to_coordinates(felts: &[SecureField]) -> Vec<[u32; 4]> {
+    felts
+        .iter()
+        .map(|f| f.to_m31_array().map(|v| v.0))
+        .collect()
+}
+
+fn to_digest_bytes(digest: &BWSSha256Hash) -> [u8; 32] {
+    digest.as_ref().try_into().unwrap()
+}
+
+// The channel of the verifier, keeping every operation in `steps`.
+#[derive(Clone, Debug, Default)]
+struct RecordingChannel {
+    inner: BWSSha256Channel,
+    steps: Vec<ChannelStep>,
+}
+
+impl RecordingChannel {
+    fn record(&mut self, op: ChannelOp) {
+        let digest = to_digest_bytes(&self.inner.get_digest());
+        self.steps.push(ChannelStep { op, digest });
+    }
+}
+
+impl Channel for RecordingChannel {
+    type Digest = BWSSha256Hash;
+    const BYTES_PER_HASH: usize = BWSSha256Channel::BYTES_PER_HASH;
+
+    fn new(digest: Self::Digest) -> Self {
+        Self {
+            inner: BWSSha256Channel::new(digest),
+            steps: vec![],
+        }
+    }
+
+    fn get_digest(&self) -> Self::Digest {
+        self.inner.get_digest()
+    }
+
+    fn mix_digest(&mut self, digest: Self::Digest) {
+        let bytes = to_digest_bytes(&digest);
+        self.inner.mix_digest(digest);
+        self.record(ChannelOp::MixDigest(bytes));
+    }
+
+    fn mix_felts(&mut self, felts: &[SecureField]) {
+        self.inner.mix_felts(felts);
+        self.record(ChannelOp::MixFelts(to_coordinates(felts)));
+    }
+
+    fn mix_nonce(&mut self, nonce: u64) {
+        self.inner.mix_nonce(nonce);
+        self.record(ChannelOp::MixNonce(nonce));
+    }
+
+    fn draw_felt(&mut self) -> SecureField {
+        let felt = self.inner.draw_felt();
+        self.record(ChannelOp::DrawFelts(to_coordinates(&[felt])));
+        felt
+    }
+
+    fn draw_felts(&mut self, n_felts: usize) -> Vec<SecureField> {
+        let felts = self.inner.draw_felts(n_felts);
+        self.record(ChannelOp::DrawFelts(to_coordinates(&felts)));
+        felts
+    }
+
+    fn draw_random_bytes(&mut self) -> Vec<u8> {
+        let bytes = self.inner.draw_random_bytes();
+        self.record(ChannelOp::DrawBytes(bytes.clone()));
+        bytes
+    }
+}
+
+// What the verification gadgets of bitcoin-circle-stark take as hints besides the proof: the
+// transcript of the verifier, with the state of the channel after every operation and every
+// value it draws, so that the script checks each draw against a hash instead of computing it, and
+// the positions of the FRI queries.
+//
+// The queries are drawn after the proof of work as in `Queries::generate` of stwo: the 32-bit
+// little-endian words of the drawn bytes, reduced to the evaluation domain of the composition
+// polynomial, which is the first layer of FRI, and sorted without repetitions.
+//
+// The folded values of FRI are replayed natively, as `FriVerifier::decommit` of stwo computes
+// them: `fri_layers` has the values that every inner layer and then the last layer take at their
+// queries, the first inner layer holding the DEEP quotients folded from the circle to the line.
+// The script checks the values of a layer against its decommitment, with the siblings from
+// `evals_subset` of the proof, folds them with the alpha drawn after the commitment of the layer,
+// and compares the result with the values of the next layer, up to the last layer, whose values
+// are those of the polynomial of the proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VerifierHints {
+    pub transcript: Vec<ChannelStep>,
+    pub log_query_domain: u32,
+    pub query_positions: Vec<usize>,
+    pub fri_layers: Vec<FriLayerHints>,
+}
+
+// The queries of a layer of FRI, as positions in its bit-reversed evaluation domain, which are
+// those of the previous layer halved, and the values there.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FriLayerHints {
+    pub positions: Vec<usize>,
+    pub evals: Vec<[u32; 4]>,
+}
+
+impl FriLayerHints {
+    fn new(positions: &[usize], evals: &[SecureField]) -> Self {
+        Self {
+            positions: positions.to_vec(),
+            evals: to_coordinates(evals),
+        }
+    }
+}
+
+impl VerifierHints {
+    // Verifies the proof with the key and the values of the inputs, as `verify_with_key` does,
+    // and keeps the transcript.
+    pub fn new(
+        key: &VerificationKey,
+        values: &[M31],
+        proof: StarkProof<BWSSha256MerkleHasher>,
+    ) -> Result<Self, KeyVerificationError> {
+        if values.len() != key.input_wires.len() {
+            return Err(KeyVerificationError::WrongNumberOfInputs {
+                expected: key.input_wires.len(),
+                actual: values.len(),
+            });
+        }
+        check_commitments(&proof).map_err(KeyVerificationError::Stark)?;
+        if proof.commitments[2] != key.constant_commitment {
+            return Err(KeyVerificationError::ConstantCommitmentMismatch);
+        }
+
+        let inputs = PublicInputs::from_wires(&key.input_wires, values);
+        let replay =
+            FriReplay::new(key.log_n_rows, &inputs, &proof).map_err(KeyVerificationError::Stark)?;
+        let mut channel = RecordingChannel {
+            inner: Sha256Config::channel(),
+            steps: vec![],
+        };
+        verify_plonk_on(&mut channel, key.log_n_rows, &inputs, proof)
+            .map_err(KeyVerificationError::Stark)?;
+
+        // the queries are drawn in the evaluation domain of the largest column
+        let log_sizes = column_log_sizes(key.log_n_rows).concat();
+        let log_query_domain =
+            max_degrees(&log_sizes).into_iter().max().unwrap() + LOG_BLOWUP_FACTOR;
+        let query_positions = query_positions(&channel.steps, log_query_domain);
+        let fri_layers = replay
+            .fold(&channel.steps, &query_positions, log_query_domain)
+            .map_err(KeyVerificationError::Stark)?;
+        Ok(Self {
+            transcript: channel.steps,
+            log_query_domain,
+            query_positions,
+            fri_layers,
+        })
+    }
+
+    // The hints as stack items, in the conventions of `BitcoinArtifacts`: for every operation,
+    // the values it draws, if any, and then the digest after it, followed by the number of
+    // queries and their positions.
+    pub fn to_items(&self) -> Vec<Vec<u8>> {
+        let mut items = vec![];
+        for step in self.transcript.iter() {
+            match &step.op {
+                ChannelOp::DrawFelts(felts) => {
+                    items.extend(felts.iter().flatten().map(|&v| script_num(v)));
+                }
+                ChannelOp::DrawBytes(bytes) => items.push(bytes.clone()),
+                _ => {}
+            }
+            items.push(step.digest.to_vec());
+        }
+        items.push(script_num(self.query_positions.len() as u32));
+        items.extend(
+            self.query_positions
+                .iter()
+                .map(|&position| script_num(position as u32)),
+        );
+        for layer in self.fri_layers.iter() {
+            items.push(script_num(layer.evals.len() as u32));
+            items.extend(layer.evals.iter().flatten().map(|&v| script_num(v)));
+        }
+        items
+    }
+}
+
+fn from_coordinates(v: [u32; 4]) -> SecureField {
+    SecureField::from_m31_array(v.map(M31::from))
+}
+
+// What FRI is replayed from: the parts of the proof that the folds read, kept aside as the stwo
+// verifier consumes the proof, and the points at which the columns are sampled.
+struct FriReplay {
+    log_n_rows: u32,
+    component: PlonkComponent,
+    composition_commitment: [u8; 32],
+    sampled_values: Vec<Vec<Vec<SecureField>>>,
+    queried_values: Vec<Vec<BaseField>>,
+    evals_subsets: Vec<Vec<SecureField>>,
+}
+
+impl FriReplay {
+    fn new(
+        log_n_rows: u32,
+        inputs: &PublicInputs,
+        proof: &StarkProof<BWSSha256MerkleHasher>,
+    ) -> Result<Self, VerificationError> {
+        let lookup_elements = draw_lookup_elements(log_n_rows, proof)?;
+        let claimed_sum = claimed_sum_from_inputs(&lookup_elements, inputs);
+        let scheme_proof = &proof.commitment_scheme_proof;
+        Ok(Self {
+            log_n_rows,
+            component: PlonkComponent {
+                log_n_rows,
+                lookup_elements,
+                claimed_sum,
+            },
+            composition_commitment: to_digest_bytes(&proof.commitments[3]),
+            sampled_values: scheme_proof.sampled_values.iter().cloned().collect(),
+            queried_values: scheme_proof
+                .queried_values
+                .iter()
+                .flatten()
+                .cloned()
+                .collect(),
+            evals_subsets: scheme_proof
+                .fri_proof
+                .inner_layers
+                .iter()
+                .map(|layer| layer.evals_subset.clone())
+                .collect(),
+        })
+    }
+
+    // The values of every inner layer and of the last layer at their queries, given the
+    // transcript of a verification of the proof.
+    fn fold(
+        self,
+        transcript: &[ChannelStep],
+        query_positions: &[usize],
+        log_query_domain: u32,
+    ) -> Result<Vec<FriLayerHints>, VerificationError> {
+        // After the commitment of the composition polynomial, the verifier draws the OODS point,
+        // the coefficient of the quotients, the alpha that folds the circle into the line and
+        // then the alpha of every inner layer, after its commitment.
+        let start = transcript
+            .iter()
+            .position(|step| step.op == ChannelOp::MixDigest(self.composition_commitment))
+            .unwrap();
+        let mut draws = transcript[start..]
+            .iter()
+            .filter_map(|step| match &step.op {
+                ChannelOp::DrawFelts(felts) => Some(felts),
+                _ => None,
+            })
+            .flatten()
+            .map(|&v| from_coordinates(v));
+        let t = draws.next().unwrap();
+        let random_coeff = draws.next().unwrap();
+        let circle_alpha = draws.next().unwrap();
+        let layer_alphas = draws.take(self.evals_subsets.len()).collect::<Vec<_>>();
+
+        // `CirclePoint::get_random_point`
+        let t_square = t * t;
+        let denominator = (t_square + SecureField::one()).inverse();
+        let oods_point = CirclePoint {
+            x: (SecureField::one() - t_square) * denominator,
+            y: (t + t) * denominator,
+        };
+
+        let mut sample_points = self.component.mask_points(oods_point);
+        sample_points.push(vec![vec![oods_point]; SECURE_EXTENSION_DEGREE]);
+        let samples = sample_points
+            .iter()
+            .zip(self.sampled_values.iter())
+            .flat_map(|(points, values)| points.iter().zip(values.iter()))
+            .map(|(points, values)| {
+                zip_eq(points, values)
+                    .map(|(&point, &value)| PointSample { point, value })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // the degree bounds of the columns, with the composition polynomial as the last tree
+        let mut degree_bounds = max_degrees(&column_log_sizes(self.log_n_rows).concat());
+        degree_bounds.extend([max_degree(self.log_n_rows); SECURE_EXTENSION_DEGREE]);
+        let domain_log_sizes = degree_bounds
+            .iter()
+            .map(|&bound| bound + LOG_BLOWUP_FACTOR)
+            .sorted()
+            .dedup()
+            .collect::<Vec<_>>();
+        let queries = Queries {
+            positions: query_positions.to_vec(),
+            log_domain_size: log_query_domain,
+        };
+        let answers = fri_answers(
+            degree_bounds.clone(),
+            &samples,
+            random_coeff,
+            get_opening_positions(&queries, &domain_log_sizes),
+            &self.queried_values,
+        )?;
+
+        // The answers are by degree bound, from the largest, and the answers of a bound are
+        // folded into the line layer of the bound below it, as in `decommit_inner_layers`.
+        let column_bounds = degree_bounds.into_iter().sorted().rev().dedup();
+        let mut answers = zip_eq(answers, column_bounds).peekable();
+        let first_bound = answers.peek().unwrap().1 - 1;
+        let mut domain = LineDomain::new(Coset::half_odd(first_bound + LOG_BLOWUP_FACTOR));
+        let mut positions = query_positions
+            .iter()
+            .map(|&position| position >> 1)
+            .dedup()
+            .collect::<Vec<_>>();
+        let mut evals = vec![SecureField::zero(); positions.len()];
+        let mut layers = vec![];
+        for (i, (evals_subset, alpha)) in zip_eq(&self.evals_subsets, layer_alphas).enumerate() {
+            let layer_bound = first_bound - i as u32;
+            if let Some((answer, _)) = answers.next_if(|(_, bound)| bound - 1 == layer_bound) {
+                for (eval, folded) in zip_eq(&mut evals, fold_circle(answer, circle_alpha)) {
+                    *eval = *eval * circle_alpha * circle_alpha + folded;
+                }
+            }
+            layers.push(FriLayerHints::new(&positions, &evals));
+            (positions, evals) = fold_line(domain, evals_subset, &positions, &evals, alpha);
+            domain = domain.double();
+        }
+        layers.push(FriLayerHints::new(&positions, &evals));
+        Ok(layers)
+    }
+}
+
+// Folds the pairs `(f(p), f(-p))` of the DEEP quotients into the line, as
+// `fold_circle_into_line` does: `f0 + alpha * f1` with `f0 = f(p) + f(-p)` and
+// `f1 = (f(p) - f(-p)) / p.y`.
+fn fold_circle(mut answer: SparseCircleEvaluation, alpha: SecureField) -> Vec<SecureField> {
+    (&mut answer)
+        .into_iter()
+        .map(|evaluation| {
+            let p = evaluation.domain.at(0);
+            let (f_p, f_neg_p) = (evaluation.values[0], evaluation.values[1]);
+            (f_p + f_neg_p) + alpha * (f_p - f_neg_p) * p.y.inverse()
+        })
+        .collect()
+}
+
+// Folds a line layer at its queries, as `fold_line` does: `f0 + alpha * f1` with
+// `f0 = f(x) + f(-x)` and `f1 = (f(x) - f(-x)) / x`. The values at the positions that are not
+// queried, the siblings, are taken from the proof in order.
+fn fold_line(
+    domain: LineDomain,
+    evals_subset: &[SecureField],
+    positions: &[usize],
+    evals: &[SecureField],
+    alpha: SecureField,
+) -> (Vec<usize>, Vec<SecureField>) {
+    let mut siblings = evals_subset.iter().copied();
+    let mut known = zip_eq(positions.iter().copied(), evals.iter().copied()).peekable();
+    let mut folded_positions = vec![];
+    let mut folded_evals = vec![];
+    while let Some(&(position, _)) = known.peek() {
+        let start = position & !1;
+        let [f_x, f_neg_x] = [start, start + 1].map(|position| {
+            match known.next_if(|&(queried, _)| queried == position) {
+                Some((_, eval)) => eval,
+                // the proof was verified, so it has every sibling
+                None => siblings.next().unwrap(),
+            }
+        });
+        let x = domain.at(bit_reverse_index(start, domain.log_size()));
+        folded_positions.push(start >> 1);
+        folded_evals.push((f_x + f_neg_x) + alpha * (f_x - f_neg_x) * x.inverse());
+    }
+    (folded_positions, folded_evals)
+}
+
+fn query_positions(steps: &[ChannelStep], log_domain_size: u32) -> Vec<usize> {
+    let start = steps
+        .iter()
+        .rposition(|step| matches!(step.op, ChannelOp::MixNonce(_)))
+        .map_or(0, |i| i + 1);
+    let mask = (1u32 << log_domain_size) - 1;
+    let mut positions = steps[start..]
+        .iter()
+        .filter_map(|step| match &step.op {
+            ChannelOp::DrawBytes(bytes) => Some(bytes),
+            _ => None,
+        })
+        .flat_map(|bytes| bytes.chunks_exact(4))
+        .map(|word| (u32::from_le_bytes(word.try_into().unwrap()) & mask) as usize)
+        .take(N_QUERIES)
+        .collect::<Vec<_>>();
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::bitcoin::{
+        draw_lookup_elements, script_num, ArtifactsError, BitcoinArtifacts, ChannelOp,
+        ScriptLimits, VerifierHints,
+    };
+    use crate::proof_system::{prove_circuit, KeyVerificationError, PlonkProof, VerificationKey};
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use itertools::{zip_eq, Itertools};
+    use stwo_prover::core::circle::Coset;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::poly::line::LineDomain;
+    use stwo_prover::core::prover::{VerificationError, N_QUERIES};
+    use stwo_prover::core::utils::bit_reverse_index;
+
+    #[test]
+    fn test_script_num() {
+        assert_eq!(script_num(0), Vec::<u8>::new());
+        assert_eq!(script_num(1), vec![1]);
+        assert_eq!(script_num(0x80), vec![0x80, 0]);
+        assert_eq!(script_num(0x1234), vec![0x34, 0x12]);
+        assert_eq!(script_num((1 << 31) - 1), vec![0xff, 0xff, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn test_bitcoin_artifacts() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let proof = PlonkProof::prove(&mut circuit).unwrap();
+        let key = VerificationKey::new(&circuit.index());
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v)
+            .collect::<Vec<_>>();
+
+        let limits = ScriptLimits::default().with_max_items_per_chunk(16);
+        let artifacts = BitcoinArtifacts::new(&key, &values, &proof, limits).unwrap();
+        let chunks = &artifacts.chunks;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].state_in, [0u8; 32]);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].state_out, pair[1].state_in);
+        }
+        let items = chunks
+            .iter()
+            .flat_map(|c| c.items.iter())
+            .collect::<Vec<_>>();
+        assert!(chunks.iter().all(|c| c.items.len() <= 16));
+        assert!(items.iter().all(|item| item.len() <= 520));
+        assert_eq!(*items[0], script_num(key.log_n_rows));
+        assert_eq!(
+            items[1 + values.len() + 2].as_slice(),
+            key.constant_commitment.as_ref()
+        );
+        // the items after the hints start with the first column of sampled values
+        let sampled = &proof.proof.commitment_scheme_proof.sampled_values[0][0];
+        let start = 1 + values.len() + 3 + 12;
+        assert_eq!(*items[start], script_num(sampled.len() as u32));
+        assert_eq!(
+            *items[start + 1],
+            script_num(sampled[0].to_m31_array()[0].0)
+        );
+        // and end with the coefficients of the last layer of FRI
+        let last_layer = &proof
+            .proof
+            .commitment_scheme_proof
+            .fri_proof
+            .last_layer_poly;
+        let last = last_layer.last().unwrap().to_m31_array()[3];
+        assert_eq!(**items.last().unwrap(), script_num(last.0));
+
+        // the same proof gives the same artifacts
+        let again = BitcoinArtifacts::new(&key, &values, &proof, limits).unwrap();
+        assert_eq!(again, artifacts);
+
+        assert!(matches!(
+            BitcoinArtifacts::new(&key, &values[1..], &proof, limits),
+            Err(ArtifactsError::Key(
+                KeyVerificationError::WrongNumberOfInputs { .. }
+            ))
+        ));
+        let mut other = key.clone();
+        other.log_n_rows += 1;
+        assert!(matches!(
+            BitcoinArtifacts::new(&other, &values, &proof, limits),
+            Err(ArtifactsError::Key(
+                KeyVerificationError::WrongNumberOfRows { .. }
+            ))
+        ));
+        let empty = ScriptLimits::default().with_max_items_per_chunk(0);
+        assert!(matches!(
+            BitcoinArtifacts::new(&key, &values, &proof, empty),
+            Err(ArtifactsError::EmptyChunks)
+        ));
+    }
+
+    #[test]
+    fn test_verifier_hints() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let key = VerificationKey::new(&circuit.index());
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v)
+            .collect::<Vec<_>>();
+        let lookup_elements = draw_lookup_elements(key.log_n_rows, &proof).unwrap();
+        let trace_commitment = proof.commitments[0].as_ref().to_vec();
+        let fri_proof = &proof.commitment_scheme_proof.fri_proof;
+        let n_layers = fri_proof.inner_layers.len();
+        let last_layer_poly = fri_proof.last_layer_poly.clone();
+
+        let hints = VerifierHints::new(&key, &values, proof).unwrap();
+        let transcript = &hints.transcript;
+        assert_eq!(
+            transcript[0].op,
+            ChannelOp::MixDigest(trace_commitment.try_into().unwrap())
+        );
+        // the lookup elements are the first draws
+        let draws = transcript
+            .iter()
+            .filter_map(|step| match &step.op {
+                ChannelOp::DrawFelts(felts) => Some(felts.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(draws[0], lookup_elements.z.to_m31_array().map(|v| v.0));
+        assert_eq!(draws[1], lookup_elements.alpha.to_m31_array().map(|v| v.0));
+
+        assert!(transcript
+            .iter()
+            .any(|step| matches!(step.op, ChannelOp::MixNonce(_))));
+        let positions = &hints.query_positions;
+        assert!(!positions.is_empty() && positions.len() <= N_QUERIES);
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(positions.iter().all(|&p| p < 1 << hints.log_query_domain));
+
+        // the queries of FRI are halved from layer to layer, down to the last layer, which has
+        // the values of the polynomial of the proof
+        let layers = &hints.fri_layers;
+        assert_eq!(layers.len(), n_layers + 1);
+        assert_eq!(
+            layers[0].positions,
+            positions.iter().map(|p| p >> 1).dedup().collect::<Vec<_>>()
+        );
+        for pair in layers.windows(2) {
+            assert_eq!(
+                pair[1].positions,
+                pair[0]
+                    .positions
+                    .iter()
+                    .map(|p| p >> 1)
+                    .dedup()
+                    .collect::<Vec<_>>()
+            );
+        }
+        let last = layers.last().unwrap();
+        let domain = LineDomain::new(Coset::half_odd(
+            hints.log_query_domain - 1 - n_layers as u32,
+        ));
+        for (&position, &eval) in zip_eq(&last.positions, &last.evals) {
+            let x = domain.at(bit_reverse_index(position, domain.log_size()));
+            let expected = last_layer_poly.eval_at_point(x.into());
+            assert_eq!(eval, expected.to_m31_array().map(|v| v.0));
+        }
+
+        let items = hints.to_items();
+        let fri_items = layers
+            .iter()
+            .map(|layer| 1 + 4 * layer.evals.len())
+            .sum::<usize>();
+        assert_eq!(
+            items[items.len() - fri_items - positions.len() - 1],
+            script_num(positions.len() as u32)
+        );
+
+        // the same checks as `verify_with_key`
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let mut wrong = values.clone();
+        *wrong.last_mut().unwrap() += M31::one();
+        assert!(matches!(
+            VerifierHints::new(&key, &wrong, proof),
+            Err(KeyVerificationError::Stark(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_commitments() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        circuit.mul(a, a);
+        let mut proof = PlonkProof::prove(&mut circuit).unwrap();
+        let key = VerificationKey::new(&circuit.index());
+        let values = proof.inputs.values();
+        let limits = ScriptLimits::default();
+
+        for len in [3, 0] {
+            proof.proof.commitments.truncate(len);
+            assert!(matches!(
+                BitcoinArtifacts::new(&key, &values, &proof, limits),
+                Err(ArtifactsError::Key(KeyVerificationError::Stark(
+                    VerificationError::InvalidStructure(_)
+                )))
+            ));
+            let (_, mut stark_proof) = prove_circuit(&mut circuit).unwrap();
+            stark_proof.commitments.truncate(len);
+            assert!(matches!(
+                VerifierHints::new(&key, &values, stark_proof),
+                Err(KeyVerificationError::Stark(
+                    VerificationError::InvalidStructure(_)
+                ))
+            ));
+        }
+    }
+}
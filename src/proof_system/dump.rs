@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+use stwo_prover::constraint_framework::logup::LookupElements;
+use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+use stwo_prover::examples::plonk::{gen_interaction_trace, PlonkCircuitTrace};
+
+// Writes the columns of a trace to a file, one row per row of the trace, for looking at failing
+// constraints or at the layout of a circuit in a notebook or a spreadsheet. The values are the
+// canonical representatives of the M31 elements.
+//
+// The interaction columns are those that `prove_plonk` commits after drawing the lookup
+// elements, which its component holds. They are only defined for a padded trace.
+
+pub const TRACE_COLUMNS: [&str; 8] = [
+    "mult", "a_wire", "b_wire", "c_wire", "op", "a_val", "b_val", "c_val",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    Csv,
+    // one row group of `INT32` columns
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+pub trait TraceDump {
+    fn dump(&self, path: impl AsRef<Path>, format: DumpFormat) -> Result<()>;
+
+    fn dump_with_interaction(
+        &self,
+        path: impl AsRef<Path>,
+        format: DumpFormat,
+        lookup_elements: &LookupElements<2>,
+    ) -> Result<()>;
+}
+
+fn trace_columns(trace: &PlonkCircuitTrace) -> Vec<(String, Vec<u32>)> {
+    let columns = [
+        &trace.mult,
+        &trace.a_wire,
+        &trace.b_wire,
+        &trace.c_wire,
+        &trace.op,
+        &trace.a_val,
+        &trace.b_val,
+        &trace.c_val,
+    ];
+    TRACE_COLUMNS
+        .iter()
+        .zip(columns)
+        .map(|(name, column)| {
+            let values = column.to_cpu().iter().map(|v| v.0).collect();
+            (name.to_string(), values)
+        })
+        .collect()
+}
+
+impl TraceDump for PlonkCircuitTrace {
+    fn dump(&self, path: impl AsRef<Path>, format: DumpFormat) -> Result<()> {
+        write_columns(path.as_ref(), format, &trace_columns(self))
+    }
+
+    fn dump_with_interaction(
+        &self,
+        path: impl AsRef<Path>,
+        format: DumpFormat,
+        lookup_elements: &LookupElements<2>,
+    ) -> Result<()> {
+        let n_rows = self.a_wire.length;
+        if !n_rows.is_power_of_two() || n_rows < 1 << LOG_N_LANES {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the interaction columns are only defined for a padded trace",
+            ));
+        }
+
+        let mut columns = trace_columns(self);
+        let (interaction, _) = gen_interaction_trace(n_rows.ilog2(), self, lookup_elements);
+        for (i, column) in interaction.iter().enumerate() {
+            let values = column.values.to_cpu().iter().map(|v| v.0).collect();
+            columns.push((format!("interaction_{}", i), values));
+        }
+        write_columns(path.as_ref(), format, &columns)
+    }
+}
+
+fn write_columns(path: &Path, format: DumpFormat, columns: &[(String, Vec<u32>)]) -> Result<()> {
+    match format {
+        DumpFormat::Csv => write_csv(BufWriter::new(File::create(path)?), columns),
+        #[cfg(feature = "parquet")]
+        DumpFormat::Parquet => write_parquet(File::create(path)?, columns),
+    }
+}
+
+fn write_csv<W: Write>(mut writer: W, columns: &[(String, Vec<u32>)]) -> Result<()> {
+    let header = columns
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    writeln!(writer, "{}", header.join(","))?;
+
+    let n_rows = columns.first().map_or(0, |(_, values)| values.len());
+    for row in 0..n_rows {
+        let values = columns
+            .iter()
+            .map(|(_, values)| values[row].to_string())
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", values.join(","))?;
+    }
+    writer.flush()
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(file: File, columns: &[(String, Vec<u32>)]) -> Result<()> {
+    use parquet::data_type::Int32Type;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let to_io = |err: parquet::errors::ParquetError| Error::new(ErrorKind::Other, err);
+
+    let fields = columns
+        .iter()
+        .map(|(name, _)| format!("REQUIRED INT32 {};", name))
+        .collect::<Vec<_>>();
+    let schema =
+        parse_message_type(&format!("message trace {{ {} }}", fields.join(" "))).map_err(to_io)?;
+    let properties = WriterProperties::builder().build();
+    let mut writer =
+        SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties)).map_err(to_io)?;
+
+    // the values are below 2^31 - 1, so they fit in the signed columns
+    let mut row_group = writer.next_row_group().map_err(to_io)?;
+    for (_, values) in columns {
+        let values = values.iter().map(|&v| v as i32).collect::<Vec<_>>();
+        let mut column = row_group
+            .next_column()
+            .map_err(to_io)?
+            .expect("a column of the schema");
+        column
+            .typed::<Int32Type>()
+            .write_batch(&values, None, None)
+            .map_err(to_io)?;
+        column.close().map_err(to_io)?;
+    }
+    row_group.close().map_err(to_io)?;
+    writer.close().map_err(to_io)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::proof_system::dump::{DumpFormat, TraceDump, TRACE_COLUMNS};
+    use crate::proof_system::prove_circuit;
+    use ark_ff::One;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+    #[test]
+    fn test_dump_csv() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+        let (component, _) = prove_circuit(&mut circuit).unwrap();
+        let trace = PlonkCircuitTrace::try_from(&circuit).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("circle-plonk-dump-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("trace.csv");
+        trace.dump(&path, DumpFormat::Csv).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], TRACE_COLUMNS.join(","));
+        assert_eq!(lines.len(), circuit.num_rows + 1);
+        // the columns of the product after `mult`
+        assert!(lines[1 + c.0].ends_with(&format!(",{},{},{},0,3,5,15", a.0, b.0, c.0)));
+
+        trace
+            .dump_with_interaction(&path, DumpFormat::Csv, &component.lookup_elements)
+            .unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let header = text.lines().next().unwrap();
+        assert_eq!(header.split(',').count(), 16);
+        assert!(header.ends_with(",interaction_7"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_dump_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+        use std::fs::File;
+        use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+        circuit.pad_minimal(LOG_N_LANES);
+        let trace = PlonkCircuitTrace::try_from(&circuit).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("circle-plonk-parquet-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("trace.parquet");
+        trace.dump(&path, DumpFormat::Parquet).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let names = schema
+            .columns()
+            .iter()
+            .map(|column| column.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, TRACE_COLUMNS);
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), circuit.num_rows);
+        let product = (1..TRACE_COLUMNS.len())
+            .map(|i| rows[c.0].get_int(i).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(product, [a.0 as i32, b.0 as i32, c.0 as i32, 0, 3, 5, 15]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+use crate::circuit::Circuit;
+use crate::proof_system::{verify_plonk, PlonkProof};
+use crate::testing::{random_circuit, RandomCircuitConfig};
+use ark_ff::One;
+use ark_std::rand::SeedableRng;
+use std::path::PathBuf;
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::prover::LOG_BLOWUP_FACTOR;
+
+// Golden proofs: the proofs of fixed circuits, recorded under `fixtures/golden` in the encoding
+// that the FFI, the service and the bindings ship, and compared byte for byte on every run.
+// Proving is deterministic, so a difference is a change of the Fiat-Shamir transcript, of the
+// Merkle hashing or of the encoding, any of which breaks the proofs that verifiers already hold.
+// Such a change, once deliberate, is recorded with
+//
+//     CIRCLE_PLONK_UPDATE_GOLDEN=1 cargo test golden
+//
+// which also records the fixtures of new circuits: a missing fixture fails otherwise, so that a
+// checkout without them does not pass without checking anything. The blowup factor is part of the
+// name of a fixture, as the proofs differ between `tiny_blowup` and `small_blowup`.
+
+const UPDATE_VAR: &str = "CIRCLE_PLONK_UPDATE_GOLDEN";
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/golden")
+        .join(format!("{}-blowup{}.bin", name, LOG_BLOWUP_FACTOR))
+}
+
+fn golden_circuits() -> Vec<(&'static str, Circuit)> {
+    let mut exposed = Circuit::new();
+    exposed.new_input(M31::one());
+    let a = exposed.new_input(M31::from(3)).wire();
+    let b = exposed.new_witness(M31::from(5));
+    let c = exposed.mul(a, b);
+    let d = exposed.add(c, b);
+    exposed.expose(d);
+
+    let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let random = random_circuit(&mut prng, &RandomCircuitConfig::default());
+
+    vec![("exposed", exposed), ("random", random)]
+}
+
+// What a change between two proofs of the same circuit points to: the first two commitments are
+// those of the trace and of the interaction trace, which is the first to depend on the channel.
+fn describe_change(expected: &PlonkProof, actual: &PlonkProof, offset: usize) -> String {
+    let cause = if expected.inputs != actual.inputs || expected.log_n_rows != actual.log_n_rows {
+        "the statement"
+    } else if expected.proof.commitments[0] != actual.proof.commitments[0] {
+        "the trace or the Merkle hashing"
+    } else if expected.proof.commitments[1] != actual.proof.commitments[1] {
+        "the draws of the lookup elements from the channel"
+    } else if expected.proof.commitments[2] != actual.proof.commitments[2] {
+        "the constant columns"
+    } else {
+        "the rest of the transcript (the out-of-domain samples, FRI or the proof of work) or the \
+         encoding"
+    };
+    format!("the proof differs from byte {}, in {}", offset, cause)
+}
+
+fn check_golden(name: &str, circuit: &mut Circuit) {
+    let proof = PlonkProof::prove(circuit).unwrap();
+    let bytes = bincode::serialize(&proof).unwrap();
+    let path = fixture_path(name);
+
+    if std::env::var(UPDATE_VAR).is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &bytes).unwrap();
+    }
+    assert!(
+        path.exists(),
+        "{}: no golden proof at {}; run with {}=1 to record it",
+        name,
+        path.display(),
+        UPDATE_VAR
+    );
+
+    // the recorded proof still decodes, encodes to the same bytes and verifies
+    let recorded = std::fs::read(&path).unwrap();
+    let expected = bincode::deserialize::<PlonkProof>(&recorded)
+        .unwrap_or_else(|err| panic!("{}: the recorded proof no longer decodes: {}", name, err));
+    assert_eq!(
+        bincode::serialize(&expected).unwrap(),
+        recorded,
+        "{}: the recorded proof encodes differently",
+        name
+    );
+
+    if bytes != recorded {
+        let offset = bytes
+            .iter()
+            .zip(recorded.iter())
+            .position(|(a, b)| a != b)
+            .unwrap_or(bytes.len().min(recorded.len()));
+        panic!(
+            "{}: {}; run with {}=1 to record it if the change is deliberate",
+            name,
+            describe_change(&expected, &proof, offset),
+            UPDATE_VAR
+        );
+    }
+    verify_plonk(expected.log_n_rows, &expected.inputs, expected.proof).unwrap();
+}
+
+#[test]
+fn test_golden_proofs() {
+    for (name, mut circuit) in golden_circuits() {
+        check_golden(name, &mut circuit);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::proof_system::HasherConfig;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::fmt::{Debug, Display, Formatter};
+use stwo_prover::core::backend::simd::SimdBackend;
+use stwo_prover::core::backend::{Col, Column};
+use stwo_prover::core::channel::Channel;
+use stwo_prover::core::fields::m31::{BaseField, M31, P};
+use stwo_prover::core::fields::qm31::SecureField;
+use stwo_prover::core::vcs::ops::{MerkleHasher, MerkleOps};
+
+// Merkle trees and a Fiat-Shamir channel over keccak256, which the EVM computes natively, so that
+// an on-chain verifier does not have to emulate SHA-256. Field elements are hashed as
+// little-endian u32, as with BWS-SHA256.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Keccak256Hash(pub [u8; 32]);
+
+impl AsRef<[u8]> for Keccak256Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Display for Keccak256Hash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for b in self.0.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Keccak256Hash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+fn keccak256(chunks: &[&[u8]]) -> Keccak256Hash {
+    let mut hasher = Keccak256::new();
+    for chunk in chunks.iter() {
+        hasher.update(chunk);
+    }
+    Keccak256Hash(hasher.finalize().into())
+}
+
+// A node hashes its two children followed by the values of the columns at that layer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keccak256MerkleHasher;
+
+impl MerkleHasher for Keccak256MerkleHasher {
+    type Hash = Keccak256Hash;
+
+    fn hash_node(
+        children_hashes: Option<(Self::Hash, Self::Hash)>,
+        column_values: &[BaseField],
+    ) -> Self::Hash {
+        let values = column_values
+            .iter()
+            .flat_map(|v| v.0.to_le_bytes())
+            .collect::<Vec<_>>();
+        match children_hashes {
+            Some((left, right)) => keccak256(&[&left.0, &right.0, &values]),
+            None => keccak256(&[&values]),
+        }
+    }
+}
+
+impl MerkleOps<Keccak256MerkleHasher> for SimdBackend {
+    fn commit_on_layer(
+        log_size: u32,
+        prev_layer: Option<&Col<Self, Keccak256Hash>>,
+        columns: &[&Col<Self, BaseField>],
+    ) -> Col<Self, Keccak256Hash> {
+        (0..1usize << log_size)
+            .into_par_iter()
+            .map(|i| {
+                let children = prev_layer.map(|prev| (prev[2 * i], prev[2 * i + 1]));
+                let values = columns.iter().map(|c| c.at(i)).collect::<Vec<_>>();
+                Keccak256MerkleHasher::hash_node(children, &values)
+            })
+            .collect()
+    }
+}
+
+// The digest absorbs everything mixed into the channel, and each draw hashes the digest with a
+// counter that restarts whenever the digest changes.
+#[derive(Clone, Debug, Default)]
+pub struct Keccak256Channel {
+    digest: Keccak256Hash,
+    n_draws: u32,
+}
+
+impl Keccak256Channel {
+    fn mix_bytes(&mut self, bytes: &[u8]) {
+        self.digest = keccak256(&[&self.digest.0, bytes]);
+        self.n_draws = 0;
+    }
+}
+
+impl Channel for Keccak256Channel {
+    type Digest = Keccak256Hash;
+    const BYTES_PER_HASH: usize = 32;
+
+    fn new(digest: Self::Digest) -> Self {
+        Self { digest, n_draws: 0 }
+    }
+
+    fn get_digest(&self) -> Self::Digest {
+        self.digest
+    }
+
+    fn mix_digest(&mut self, digest: Self::Digest) {
+        self.mix_bytes(&digest.0);
+    }
+
+    fn mix_felts(&mut self, felts: &[SecureField]) {
+        let bytes = felts
+            .iter()
+            .flat_map(|f| f.to_m31_array())
+            .flat_map(|v| v.0.to_le_bytes())
+            .collect::<Vec<_>>();
+        self.mix_bytes(&bytes);
+    }
+
+    fn mix_nonce(&mut self, nonce: u64) {
+        self.mix_bytes(&nonce.to_le_bytes());
+    }
+
+    fn draw_felt(&mut self) -> SecureField {
+        // 31-bit limbs, of which P itself is rejected, so that the draw is uniform
+        let mut limbs = vec![];
+        while limbs.len() < 4 {
+            let bytes = self.draw_random_bytes();
+            limbs.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|c| u32::from_le_bytes(c.try_into().unwrap()) & P)
+                    .filter(|&v| v != P)
+                    .map(M31::from_u32_unchecked),
+            );
+        }
+        SecureField::from_m31_array(limbs[..4].try_into().unwrap())
+    }
+
+    fn draw_felts(&mut self, n_felts: usize) -> Vec<SecureField> {
+        (0..n_felts).map(|_| self.draw_felt()).collect()
+    }
+
+    fn draw_random_bytes(&mut self) -> Vec<u8> {
+        let bytes = keccak256(&[&self.digest.0, &self.n_draws.to_le_bytes()]);
+        self.n_draws += 1;
+        bytes.0.to_vec()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keccak256Config;
+
+impl HasherConfig for Keccak256Config {
+    type Hash = Keccak256Hash;
+    type MerkleHasher = Keccak256MerkleHasher;
+    type Channel = Keccak256Channel;
+    const NAME: &'static str = "keccak256";
+
+    fn channel() -> Self::Channel {
+        Keccak256Channel::new(keccak256(&[]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::keccak::{keccak256, Keccak256Config};
+    use crate::proof_system::{
+        prove_circuit, prove_plonk_with, verify_plonk_with, verify_with_key, VerificationKey,
+    };
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            keccak256(&[]).to_string(),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(keccak256(&[b"ab", b"c"]), keccak256(&[b"abc"]));
+    }
+
+    #[test]
+    fn test_keccak_plonk() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let (_, sha256_proof) = prove_circuit(&mut circuit).unwrap();
+        let log_n_rows = circuit.num_rows.ilog2();
+
+        let (_, proof) =
+            prove_plonk_with::<Keccak256Config>(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        verify_plonk_with::<Keccak256Config>(log_n_rows, &circuit.input_maps, proof).unwrap();
+
+        let key = VerificationKey::<Keccak256Config>::new_with_hasher(&circuit.index());
+        assert_ne!(
+            key.constant_commitment.as_ref(),
+            sha256_proof.commitments[2].as_ref()
+        );
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v)
+            .collect::<Vec<_>>();
+        let (_, proof) =
+            prove_plonk_with::<Keccak256Config>(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        assert_eq!(key.constant_commitment, proof.commitments[2]);
+        verify_with_key(&key, &values, proof).unwrap();
+
+        let mut wrong = values;
+        wrong[0] += M31::from(1);
+        let (_, proof) =
+            prove_plonk_with::<Keccak256Config>(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        assert!(verify_with_key(&key, &wrong, proof).is_err());
+    }
+}
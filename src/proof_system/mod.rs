@@ -1,78 +1,381 @@
-use crate::circuit::Circuit;
+use crate::circuit::chunked::{CHUNK_SIZE, LOG_CHUNK_SIZE};
+use crate::circuit::compressed::{CompressedColumn, CompressedIndex};
+use crate::circuit::index::{CircuitIndex, EvaluationError, Witness};
+use crate::circuit::packed::{
+    base_column_rows, fill_base_column, indices_into, mults_into, pad_base_column, row_indices_into,
+};
+use crate::circuit::public_inputs::PublicInputs;
+use crate::circuit::update::DependencyIndex;
+use crate::circuit::{check_size, Circuit, SizeError};
+use crate::metrics::Phase;
+use ark_ff::Zero;
 use itertools::{chain, Itertools};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::ops::Index;
 use stwo_prover::constraint_framework::logup::LookupElements;
 use stwo_prover::core::backend::simd::column::BaseColumn;
 use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
 use stwo_prover::core::backend::simd::SimdBackend;
 use stwo_prover::core::channel::{BWSSha256Channel, Channel};
 use stwo_prover::core::fields::m31::{BaseField, M31};
-use stwo_prover::core::fields::IntoSlice;
-use stwo_prover::core::pcs::CommitmentSchemeProver;
+use stwo_prover::core::fields::qm31::SecureField;
+use stwo_prover::core::fields::{FieldExpOps, IntoSlice};
+use stwo_prover::core::pcs::{CommitmentSchemeProver, CommitmentSchemeVerifier, TreeVec};
 use stwo_prover::core::poly::circle::{CanonicCoset, CircleEvaluation, PolyOps};
 use stwo_prover::core::poly::BitReversedOrder;
-use stwo_prover::core::prover::{prove, StarkProof, LOG_BLOWUP_FACTOR};
-use stwo_prover::core::vcs::bws_sha256_hash::BWSSha256Hasher;
+use stwo_prover::core::prover::{prove, verify, StarkProof, VerificationError, LOG_BLOWUP_FACTOR};
+use stwo_prover::core::vcs::bws_sha256_hash::{BWSSha256Hash, BWSSha256Hasher};
 use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
+use stwo_prover::core::vcs::ops::{MerkleHasher, MerkleOps};
 use stwo_prover::core::InteractionElements;
 use stwo_prover::examples::plonk::{
     gen_interaction_trace, gen_trace, PlonkCircuitTrace, PlonkComponent,
 };
 use tracing::{span, Level};
 
-impl From<&Circuit> for PlonkCircuitTrace {
-    fn from(circuit: &Circuit) -> Self {
-        assert!(circuit.num_rows.is_power_of_two());
-        let log_n_rows = circuit.num_rows.ilog2();
+#[cfg(feature = "bitcoin")]
+pub mod bitcoin;
+
+pub mod dump;
+
+#[cfg(test)]
+mod golden;
+
+#[cfg(feature = "keccak")]
+pub mod keccak;
+
+#[cfg(feature = "registry")]
+pub mod registry;
+
+#[cfg(feature = "solidity")]
+pub mod solidity;
+
+#[cfg(feature = "stwo-json")]
+pub mod stwo_json;
+
+// The hash of the Merkle trees and of the Fiat-Shamir channel of a proof. The prover and the
+// verifier default to BWS-SHA256, which is what a Bitcoin script can check; other hashers suit
+// other verifiers, such as keccak256 for the EVM.
+pub trait HasherConfig {
+    type Hash: AsRef<[u8]> + Serialize + DeserializeOwned;
+    type MerkleHasher: MerkleHasher<Hash = Self::Hash>;
+    type Channel: Channel<Digest = Self::Hash>;
+    // the name of the hasher in the generated verifiers
+    const NAME: &'static str;
+
+    // The channel at the start of a proof.
+    fn channel() -> Self::Channel;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha256Config;
+
+impl HasherConfig for Sha256Config {
+    type Hash = BWSSha256Hash;
+    type MerkleHasher = BWSSha256MerkleHasher;
+    type Channel = BWSSha256Channel;
+    const NAME: &'static str = "bws-sha256";
+
+    fn channel() -> Self::Channel {
+        BWSSha256Channel::new(BWSSha256Hasher::hash(BaseField::into_slice(&[])))
+    }
+}
+
+impl TryFrom<&Circuit> for PlonkCircuitTrace {
+    type Error = SizeError;
+
+    fn try_from(circuit: &Circuit) -> Result<Self, SizeError> {
+        // `op` and `output_wires` are already stored in the packed layout
+        gen_circuit_trace(
+            circuit.num_rows,
+            circuit.op.to_base_column(),
+            &circuit.idx_a.chunks().collect_vec(),
+            &circuit.idx_b.chunks().collect_vec(),
+            &circuit.mult.chunks().collect_vec(),
+            &circuit.output_wires,
+            circuit.output_wires.to_base_column(),
+        )
+    }
+}
+
+impl TryFrom<(&CircuitIndex, &Witness)> for PlonkCircuitTrace {
+    type Error = SizeError;
+
+    fn try_from((index, witness): (&CircuitIndex, &Witness)) -> Result<Self, SizeError> {
+        assert!(index.is_compatible(witness));
+        gen_circuit_trace(
+            index.num_rows,
+            copy_column(&index.op),
+            &index.idx_a.chunks(CHUNK_SIZE).collect_vec(),
+            &index.idx_b.chunks(CHUNK_SIZE).collect_vec(),
+            &index.mult.chunks(CHUNK_SIZE).collect_vec(),
+            &witness.output_wires,
+            copy_column(&witness.output_wires),
+        )
+    }
+}
+
+// The trace of a witness for a compressed index, decoded column by column straight into the
+// trace, so that proving from a compressed index never holds the expanded index.
+impl TryFrom<(&CompressedIndex, &Witness)> for PlonkCircuitTrace {
+    type Error = io::Error;
 
-        let mult = BaseColumn::from_iter(circuit.mult.iter().map(|&x| M31::from(x)));
-        let a_wire = BaseColumn::from_iter(circuit.idx_a.iter().map(|&x| M31::from(x)));
-        let b_wire = BaseColumn::from_iter(circuit.idx_b.iter().map(|&x| M31::from(x)));
-        let c_wire = (0..(1 << log_n_rows)).clone().map(|i| i.into()).collect();
-        let op = BaseColumn::from_iter(circuit.op.iter().copied());
-        let a_val = BaseColumn::from_iter(circuit.idx_a.iter().map(|&i| circuit.output_wires[i]));
-        let b_val = BaseColumn::from_iter(circuit.idx_b.iter().map(|&i| circuit.output_wires[i]));
-        let c_val = BaseColumn::from_iter(circuit.output_wires.iter().copied());
-
-        PlonkCircuitTrace {
-            mult,
-            a_wire,
-            b_wire,
-            c_wire,
-            op,
-            a_val,
-            b_val,
-            c_val,
+    fn try_from((index, witness): (&CompressedIndex, &Witness)) -> io::Result<Self> {
+        index.validate()?;
+        if !index.is_compatible(witness) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the witness does not match the index",
+            ));
         }
+        let wires = &witness.output_wires;
+        let column = |values: &CompressedColumn, f: &dyn Fn(usize) -> M31| {
+            let mut values = values.iter();
+            fill_base_column(index.num_rows, |_, out| {
+                for (o, v) in out.iter_mut().zip(&mut values) {
+                    *o = f(v);
+                }
+            })
+        };
+        let entry = |v: usize| M31::from(v as u32);
+        let value = |i: usize| wires[i];
+        Ok(PlonkCircuitTrace {
+            mult: column(&index.mult, &entry),
+            a_wire: column(&index.idx_a, &entry),
+            b_wire: column(&index.idx_b, &entry),
+            c_wire: fill_base_column(index.num_rows, row_indices_into),
+            op: column(&index.op, &entry),
+            a_val: column(&index.idx_a, &value),
+            b_val: column(&index.idx_b, &value),
+            c_val: copy_column(wires),
+        })
     }
 }
 
+fn copy_column(values: &[M31]) -> BaseColumn {
+    fill_base_column(values.len(), |first_row, out| {
+        out.copy_from_slice(&values[first_row..first_row + out.len()])
+    })
+}
+
+fn index_column(values: &[usize]) -> BaseColumn {
+    fill_base_column(values.len(), |first_row, out| {
+        indices_into(&values[first_row..first_row + out.len()], out)
+    })
+}
+
+// The index columns are passed as chunks of `CHUNK_SIZE` rows, as in the chunked circuit storage,
+// and converted a chunk at a time straight into the packed columns. A circuit over `MAX_NUM_ROWS`
+// rows or with a multiplicity over `MAX_MULT` is an error, as its indices and multiplicities would
+// otherwise be silently reduced modulo P.
+fn gen_circuit_trace<W>(
+    num_rows: usize,
+    op: BaseColumn,
+    idx_a: &[&[usize]],
+    idx_b: &[&[usize]],
+    mult: &[&[u32]],
+    output_wires: &W,
+    c_val: BaseColumn,
+) -> Result<PlonkCircuitTrace, SizeError>
+where
+    W: Index<usize, Output = M31> + ?Sized,
+{
+    assert_eq!(op.length, num_rows);
+    assert_eq!(c_val.length, num_rows);
+    check_size(num_rows, mult.iter().copied().flatten())?;
+
+    let convert = |chunks: &[&[usize]]| {
+        fill_base_column(num_rows, |first_row, out| {
+            indices_into(chunks[first_row >> LOG_CHUNK_SIZE], out)
+        })
+    };
+    let gather = |chunks: &[&[usize]]| {
+        fill_base_column(num_rows, |first_row, out| {
+            let chunk = chunks[first_row >> LOG_CHUNK_SIZE];
+            assert_eq!(chunk.len(), out.len());
+            for (o, &i) in out.iter_mut().zip(chunk) {
+                *o = output_wires[i];
+            }
+        })
+    };
+    let mult = fill_base_column(num_rows, |first_row, out| {
+        mults_into(mult[first_row >> LOG_CHUNK_SIZE], out)
+    });
+
+    Ok(PlonkCircuitTrace {
+        mult,
+        a_wire: convert(idx_a),
+        b_wire: convert(idx_b),
+        c_wire: fill_base_column(num_rows, row_indices_into),
+        op,
+        a_val: gather(idx_a),
+        b_val: gather(idx_b),
+        c_val,
+    })
+}
+
+// Pads a trace of any length to the smallest power of two that the prover supports. Each new row
+// multiplies the value of row 0 by itself, so that row 0 is looked up twice more and the logup
+// sum, which only depends on the inputs, stays the same.
+fn pad_trace(mut trace: PlonkCircuitTrace) -> PlonkCircuitTrace {
+    let n_rows = trace.a_wire.length;
+    assert!(n_rows > 0);
+    let padded = n_rows.next_power_of_two().max(1 << LOG_N_LANES);
+    if padded == n_rows {
+        return trace;
+    }
+
+    // the new rows are written into the packed words of the columns as they are
+    let value = base_column_rows(&mut trace.c_val)[0];
+    base_column_rows(&mut trace.mult)[0] += M31::from(2 * (padded - n_rows));
+    PlonkCircuitTrace {
+        mult: pad_base_column(trace.mult, padded, M31::zero()),
+        a_wire: pad_base_column(trace.a_wire, padded, M31::zero()),
+        b_wire: pad_base_column(trace.b_wire, padded, M31::zero()),
+        c_wire: fill_base_column(padded, row_indices_into),
+        op: pad_base_column(trace.op, padded, M31::zero()),
+        a_val: pad_base_column(trace.a_val, padded, value),
+        b_val: pad_base_column(trace.b_val, padded, value),
+        c_val: pad_base_column(trace.c_val, padded, value * value),
+    }
+}
+
+// The trace, interaction and constant columns, as committed by `prove_plonk`.
+const NUM_COLUMNS: usize = 4 + 8 + 4;
+
+// A rough lower bound on what the prover keeps in memory: the coefficients and the extended
+// evaluations of every column, and the three Merkle trees over the evaluations.
+pub fn estimated_prover_memory(log_n_rows: u32) -> usize {
+    let n_rows = 1usize << log_n_rows;
+    let n_evals = n_rows << LOG_BLOWUP_FACTOR;
+    NUM_COLUMNS * (n_rows + n_evals) * 4 + 3 * 2 * n_evals * 32
+}
+
+// Pads the circuit only up to the smallest size the prover supports and proves it.
+pub fn prove_circuit(
+    circuit: &mut Circuit,
+) -> Result<(PlonkComponent, StarkProof<BWSSha256MerkleHasher>), SizeError> {
+    circuit.pad_minimal(LOG_N_LANES);
+    Ok(prove_plonk(PlonkCircuitTrace::try_from(&*circuit)?))
+}
+
+// The constant columns `a_wire`, `b_wire`, `c_wire` and `op`, in the order in which they are
+// committed.
+fn constant_evals(
+    log_n_rows: u32,
+    columns: [BaseColumn; 4],
+) -> Vec<CircleEvaluation<SimdBackend, BaseField, BitReversedOrder>> {
+    chain!(columns.into_iter().map(|col| {
+        CircleEvaluation::<SimdBackend, _, BitReversedOrder>::new(
+            CanonicCoset::new(log_n_rows).circle_domain(),
+            col,
+        )
+    }))
+    .collect_vec()
+}
+
+// The log sizes of the columns of each tree, in the order in which they are committed: the
+// trace, the interaction trace and the constant columns. This is what the verifier knows of the
+// AIR. Each column is committed at its own size, so that a lookup table or a preprocessed column
+// much smaller than the trace is not extended to the size of the trace; the columns of
+// `PlonkComponent` all have one row per row of the circuit.
+pub fn column_log_sizes(log_n_rows: u32) -> TreeVec<Vec<u32>> {
+    TreeVec::new(vec![
+        vec![log_n_rows; 4],
+        vec![log_n_rows; 8],
+        vec![log_n_rows; 4],
+    ])
+}
+
+// The degree bound with which the commitment scheme takes a column of `2^log_size` rows: the
+// constraints are quadratic, so the quotients are evaluated on a domain twice as large.
+pub(crate) fn max_degree(log_size: u32) -> u32 {
+    log_size + 1
+}
+
+pub(crate) fn max_degrees(log_sizes: &[u32]) -> Vec<u32> {
+    log_sizes
+        .iter()
+        .map(|&log_size| max_degree(log_size))
+        .collect()
+}
+
+// Commits one tree, extending each run of columns of the same size to its own degree bound, as
+// the tree builder extends all the columns of a call alike. Returns the log sizes of the columns.
+fn commit_tree<H, Ch>(
+    commitment_scheme: &mut CommitmentSchemeProver<'_, SimdBackend, H>,
+    evals: Vec<CircleEvaluation<SimdBackend, BaseField, BitReversedOrder>>,
+    channel: &mut Ch,
+) -> Vec<u32>
+where
+    H: MerkleHasher,
+    Ch: Channel<Digest = H::Hash>,
+    SimdBackend: MerkleOps<H>,
+{
+    let log_sizes = evals
+        .iter()
+        .map(|eval| eval.domain.log_size())
+        .collect_vec();
+    let mut tree_builder = commitment_scheme.tree_builder();
+    let mut evals = evals.into_iter().peekable();
+    while let Some(first) = evals.next() {
+        let log_size = first.domain.log_size();
+        let mut run = vec![first];
+        while let Some(eval) = evals.next_if(|eval| eval.domain.log_size() == log_size) {
+            run.push(eval);
+        }
+        tree_builder.extend_evals(run, max_degree(log_size));
+    }
+    tree_builder.commit(channel);
+    log_sizes
+}
+
+// Proves a trace of any length, which is padded first if it is not a power of two of at least
+// `2^LOG_N_LANES` rows.
 pub fn prove_plonk(
     circuit: PlonkCircuitTrace,
 ) -> (PlonkComponent, StarkProof<BWSSha256MerkleHasher>) {
-    assert!(circuit.a_wire.length.is_power_of_two());
+    prove_plonk_with::<Sha256Config>(circuit)
+}
+
+pub fn prove_plonk_with<C: HasherConfig>(
+    circuit: PlonkCircuitTrace,
+) -> (PlonkComponent, StarkProof<C::MerkleHasher>)
+where
+    SimdBackend: MerkleOps<C::MerkleHasher>,
+{
+    let circuit = pad_trace(circuit);
     let log_n_rows = circuit.a_wire.length.ilog2();
-    assert!(log_n_rows >= LOG_N_LANES);
+
+    let n_rows = 1 << log_n_rows;
+    // the bytes of the extended evaluations of a number of columns
+    let committed_bytes = |n_columns: usize| (n_columns * (n_rows << LOG_BLOWUP_FACTOR) * 4) as u64;
 
     // Precompute twiddles.
     let span = span!(Level::INFO, "Precompute twiddles").entered();
+    let timer = Phase::Twiddles.start(n_rows);
     let twiddles = SimdBackend::precompute_twiddles(
         CanonicCoset::new(log_n_rows + LOG_BLOWUP_FACTOR + 1)
             .circle_domain()
             .half_coset,
     );
+    timer.finish(None);
     span.exit();
 
     // Setup protocol.
-    let channel = &mut BWSSha256Channel::new(BWSSha256Hasher::hash(BaseField::into_slice(&[])));
-    let commitment_scheme = &mut CommitmentSchemeProver::new(LOG_BLOWUP_FACTOR, &twiddles);
+    let channel = &mut C::channel();
+    let commitment_scheme =
+        &mut CommitmentSchemeProver::<_, C::MerkleHasher>::new(LOG_BLOWUP_FACTOR, &twiddles);
 
     // Trace.
     let span = span!(Level::INFO, "Trace").entered();
+    let timer = Phase::Trace.start(n_rows);
     let trace = gen_trace(log_n_rows, &circuit);
-    let max_degree = log_n_rows + 1;
-    let mut tree_builder = commitment_scheme.tree_builder();
-    tree_builder.extend_evals(trace, max_degree);
-    tree_builder.commit(channel);
+    let mut log_sizes = vec![commit_tree(commitment_scheme, trace, channel)];
+    timer.finish(Some(committed_bytes(4)));
     span.exit();
 
     // Draw lookup element.
@@ -80,29 +383,24 @@ pub fn prove_plonk(
 
     // Interaction trace.
     let span = span!(Level::INFO, "Interaction").entered();
+    let timer = Phase::Interaction.start(n_rows);
     let (trace, claimed_sum) = gen_interaction_trace(log_n_rows, &circuit, &lookup_elements);
-    let mut tree_builder = commitment_scheme.tree_builder();
-    tree_builder.extend_evals(trace, max_degree);
-    tree_builder.commit(channel);
+    log_sizes.push(commit_tree(commitment_scheme, trace, channel));
+    timer.finish(Some(committed_bytes(8)));
     span.exit();
 
     // Constant trace.
     let span = span!(Level::INFO, "Constant").entered();
-    let mut tree_builder = commitment_scheme.tree_builder();
-    tree_builder.extend_evals(
-        chain!([circuit.a_wire, circuit.b_wire, circuit.c_wire, circuit.op]
-            .into_iter()
-            .map(|col| {
-                CircleEvaluation::<SimdBackend, _, BitReversedOrder>::new(
-                    CanonicCoset::new(log_n_rows).circle_domain(),
-                    col,
-                )
-            }))
-        .collect_vec(),
-        max_degree,
+    let timer = Phase::Constant.start(n_rows);
+    let constants = constant_evals(
+        log_n_rows,
+        [circuit.a_wire, circuit.b_wire, circuit.c_wire, circuit.op],
     );
-    tree_builder.commit(channel);
+    log_sizes.push(commit_tree(commitment_scheme, constants, channel));
+    timer.finish(Some(committed_bytes(4)));
     span.exit();
+    // the verifier reads the sizes from the AIR rather than from the proof
+    debug_assert_eq!(log_sizes, *column_log_sizes(log_n_rows));
 
     // Prove constraints.
     let component = PlonkComponent {
@@ -111,6 +409,7 @@ pub fn prove_plonk(
         claimed_sum,
     };
 
+    let timer = Phase::Prove.start(n_rows);
     let proof = prove::<SimdBackend, _, _>(
         &[&component],
         channel,
@@ -118,27 +417,337 @@ pub fn prove_plonk(
         commitment_scheme,
     )
     .unwrap();
+    timer.finish(None);
 
     (component, proof)
 }
 
+// The logup sum over all rows equals the sum over the entries of `input_maps`, so the verifier
+// derives the claimed sum from the statement (the inputs and the exposed outputs) itself.
+pub fn claimed_sum_from_inputs(
+    lookup_elements: &LookupElements<2>,
+    inputs: &[(usize, M31)],
+) -> SecureField {
+    let mut sum = SecureField::zero();
+    for &(idx, value) in inputs.iter() {
+        let denominator: SecureField = lookup_elements.combine(&[M31::from(idx), value]);
+        sum += denominator.inverse();
+    }
+    sum
+}
+
+pub fn verify_plonk(
+    log_n_rows: u32,
+    inputs: &PublicInputs,
+    proof: StarkProof<BWSSha256MerkleHasher>,
+) -> Result<(), VerificationError> {
+    verify_plonk_with::<Sha256Config>(log_n_rows, inputs, proof)
+}
+
+pub fn verify_plonk_with<C: HasherConfig>(
+    log_n_rows: u32,
+    inputs: &PublicInputs,
+    proof: StarkProof<C::MerkleHasher>,
+) -> Result<(), VerificationError> {
+    let timer = Phase::Verify.start(1 << log_n_rows);
+    let result = verify_plonk_on(&mut C::channel(), log_n_rows, inputs, proof);
+    timer.finish(None);
+    result
+}
+
+// The trees that a proof commits to: the trace, the interaction trace, the constant columns and
+// the composition polynomial.
+pub(crate) const NUM_TREES: usize = 4;
+
+// A proof with another number of commitments is malformed, and is rejected before any of them is
+// read rather than panicking on it.
+pub(crate) fn check_commitments<H: MerkleHasher>(
+    proof: &StarkProof<H>,
+) -> Result<(), VerificationError> {
+    if proof.commitments.len() != NUM_TREES {
+        return Err(VerificationError::InvalidStructure(format!(
+            "expected {} commitments, got {}",
+            NUM_TREES,
+            proof.commitments.len()
+        )));
+    }
+    Ok(())
+}
+
+// The verifier over a channel of the caller, such as one that records the transcript.
+pub(crate) fn verify_plonk_on<H, Ch>(
+    channel: &mut Ch,
+    log_n_rows: u32,
+    inputs: &PublicInputs,
+    proof: StarkProof<H>,
+) -> Result<(), VerificationError>
+where
+    H: MerkleHasher,
+    Ch: Channel<Digest = H::Hash>,
+{
+    check_commitments(&proof)?;
+    let commitment_scheme = &mut CommitmentSchemeVerifier::<H>::new();
+
+    // Retrieve the expected column sizes in each commitment interaction, from the AIR.
+    let sizes = column_log_sizes(log_n_rows);
+
+    // Trace columns.
+    commitment_scheme.commit(proof.commitments[0], &max_degrees(&sizes[0]), channel);
+    // Draw lookup element.
+    let lookup_elements = LookupElements::<2>::draw(channel);
+    // Interaction columns.
+    commitment_scheme.commit(proof.commitments[1], &max_degrees(&sizes[1]), channel);
+    // Constant columns.
+    commitment_scheme.commit(proof.commitments[2], &max_degrees(&sizes[2]), channel);
+
+    let claimed_sum = claimed_sum_from_inputs(&lookup_elements, inputs);
+    let component = PlonkComponent {
+        log_n_rows,
+        lookup_elements,
+        claimed_sum,
+    };
+
+    verify(
+        &[&component],
+        channel,
+        &InteractionElements::default(),
+        commitment_scheme,
+        proof,
+    )
+}
+
+// A proof together with the statement it proves, as shipped from the prover to the verifier: the
+// size of the trace and the values of the inputs, including the exposed outputs.
+#[derive(Serialize, Deserialize)]
+pub struct PlonkProof {
+    // the rows of the circuit before padding, which the proof itself does not depend on
+    pub num_rows: usize,
+    pub log_n_rows: u32,
+    pub inputs: PublicInputs,
+    pub proof: StarkProof<BWSSha256MerkleHasher>,
+}
+
+impl PlonkProof {
+    // Proves a circuit with `prove_circuit`, keeping the size that it had before padding.
+    pub fn prove(circuit: &mut Circuit) -> Result<Self, SizeError> {
+        let num_rows = circuit.num_rows;
+        let (_, proof) = prove_circuit(circuit)?;
+        Ok(Self {
+            num_rows,
+            log_n_rows: circuit.num_rows.ilog2(),
+            inputs: circuit.input_maps.clone(),
+            proof,
+        })
+    }
+
+    // Proves a witness for a compressed index, such as a registered one, decoding the index into
+    // the trace rather than expanding it. The witness is checked against the gates first, as the
+    // prover would otherwise produce a proof that fails.
+    pub fn prove_compressed(index: &CompressedIndex, witness: &Witness) -> io::Result<Self> {
+        let trace = PlonkCircuitTrace::try_from((index, witness))?;
+        if let Some(row) = index.unsatisfied_row(witness) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the witness does not satisfy row {}", row),
+            ));
+        }
+        let (component, proof) = prove_plonk(trace);
+        Ok(Self {
+            num_rows: index.num_rows,
+            log_n_rows: component.log_n_rows,
+            inputs: witness.input_maps.clone(),
+            proof,
+        })
+    }
+
+    // Proves a circuit that was proven before with other inputs, with the values of `inputs` (see
+    // `Circuit::update_inputs`), for statements of the same shape proven over and over again.
+    // The circuit is not built again, and only the rows that the new inputs reach are evaluated.
+    pub fn reprove(
+        circuit: &mut Circuit,
+        index: &DependencyIndex,
+        inputs: &[M31],
+    ) -> Result<Self, ReproveError> {
+        circuit.update_inputs(index, inputs)?;
+        Ok(Self::prove(circuit)?)
+    }
+
+    pub fn is_num_rows_consistent(&self) -> bool {
+        self.num_rows <= 1 << self.log_n_rows
+    }
+}
+
+// What the verifier needs to know about a circuit: the size of the trace, the rows of the inputs,
+// in the order in which their values are given, and the commitment to the constant columns, which
+// pins down the gates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct VerificationKey<C: HasherConfig = Sha256Config> {
+    pub log_n_rows: u32,
+    pub input_wires: Vec<usize>,
+    pub constant_commitment: C::Hash,
+}
+
+impl VerificationKey {
+    pub fn new(index: &CircuitIndex) -> Self {
+        Self::new_with_hasher(index)
+    }
+}
+
+impl<C: HasherConfig> VerificationKey<C> {
+    // Commits to the constant columns of a padded index the same way the prover does, which
+    // costs as much as that step of a proof.
+    pub fn new_with_hasher(index: &CircuitIndex) -> Self
+    where
+        SimdBackend: MerkleOps<C::MerkleHasher>,
+    {
+        assert!(index.num_rows.is_power_of_two());
+        let log_n_rows = index.num_rows.ilog2();
+        assert!(log_n_rows >= LOG_N_LANES);
+
+        let twiddles = SimdBackend::precompute_twiddles(
+            CanonicCoset::new(log_n_rows + LOG_BLOWUP_FACTOR + 1)
+                .circle_domain()
+                .half_coset,
+        );
+        let channel = &mut C::channel();
+        let commitment_scheme =
+            &mut CommitmentSchemeProver::<_, C::MerkleHasher>::new(LOG_BLOWUP_FACTOR, &twiddles);
+
+        let columns = [
+            index_column(&index.idx_a),
+            index_column(&index.idx_b),
+            fill_base_column(index.num_rows, row_indices_into),
+            copy_column(&index.op),
+        ];
+        commit_tree(
+            commitment_scheme,
+            constant_evals(log_n_rows, columns),
+            channel,
+        );
+
+        Self {
+            log_n_rows,
+            input_wires: index.input_wires.clone(),
+            constant_commitment: commitment_scheme.roots()[0],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReproveError {
+    Evaluation(EvaluationError),
+    Size(SizeError),
+}
+
+impl Display for ReproveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReproveError::Evaluation(err) => write!(f, "{}", err),
+            ReproveError::Size(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReproveError {}
+
+impl From<EvaluationError> for ReproveError {
+    fn from(err: EvaluationError) -> Self {
+        ReproveError::Evaluation(err)
+    }
+}
+
+impl From<SizeError> for ReproveError {
+    fn from(err: SizeError) -> Self {
+        ReproveError::Size(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyVerificationError {
+    WrongNumberOfInputs { expected: usize, actual: usize },
+    // the proof is for a trace of another size
+    WrongNumberOfRows { expected: u32, actual: u32 },
+    // the proof is for another circuit
+    ConstantCommitmentMismatch,
+    Stark(VerificationError),
+}
+
+impl Display for KeyVerificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyVerificationError::WrongNumberOfInputs { expected, actual } => {
+                write!(f, "expected {} input values, got {}", expected, actual)
+            }
+            KeyVerificationError::WrongNumberOfRows { expected, actual } => {
+                write!(
+                    f,
+                    "expected a proof of 2^{} rows, got 2^{}",
+                    expected, actual
+                )
+            }
+            KeyVerificationError::ConstantCommitmentMismatch => {
+                write!(
+                    f,
+                    "the proof is not for the circuit of the verification key"
+                )
+            }
+            KeyVerificationError::Stark(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for KeyVerificationError {}
+
+// Verifies a proof of the circuit of `key` for the given input values, which `verify_plonk` alone
+// does not tie to any particular circuit.
+pub fn verify_with_key<C: HasherConfig>(
+    key: &VerificationKey<C>,
+    values: &[M31],
+    proof: StarkProof<C::MerkleHasher>,
+) -> Result<(), KeyVerificationError> {
+    if values.len() != key.input_wires.len() {
+        return Err(KeyVerificationError::WrongNumberOfInputs {
+            expected: key.input_wires.len(),
+            actual: values.len(),
+        });
+    }
+    check_commitments(&proof).map_err(KeyVerificationError::Stark)?;
+    if proof.commitments[2] != key.constant_commitment {
+        return Err(KeyVerificationError::ConstantCommitmentMismatch);
+    }
+
+    let inputs = PublicInputs::from_wires(&key.input_wires, values);
+    verify_plonk_with::<C>(key.log_n_rows, &inputs, proof).map_err(KeyVerificationError::Stark)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::prove_plonk;
-    use crate::circuit::Mode;
+    use super::{
+        claimed_sum_from_inputs, column_log_sizes, commit_tree, constant_evals, max_degrees,
+        pad_trace, prove_circuit, prove_plonk, verify_plonk, verify_with_key, HasherConfig,
+        KeyVerificationError, PlonkProof, Sha256Config, VerificationKey,
+    };
+    use crate::circuit::{Circuit, Mode, SizeError, MAX_MULT};
     use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
     use crate::from_r1cs::TestCircuit;
+    use ark_ff::One;
     use ark_std::rand::SeedableRng;
     use ark_std::UniformRand;
+    use std::iter::zip;
     use stwo_prover::constraint_framework::logup::LookupElements;
-    use stwo_prover::core::channel::{BWSSha256Channel, Channel};
-    use stwo_prover::core::fields::m31::BaseField;
-    use stwo_prover::core::fields::IntoSlice;
-    use stwo_prover::core::pcs::{CommitmentSchemeVerifier, TreeVec};
-    use stwo_prover::core::prover::{verify, LOG_BLOWUP_FACTOR};
-    use stwo_prover::core::vcs::bws_sha256_hash::BWSSha256Hasher;
+    use stwo_prover::core::backend::simd::column::BaseColumn;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::backend::simd::SimdBackend;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::pcs::{CommitmentSchemeProver, CommitmentSchemeVerifier};
+    use stwo_prover::core::poly::circle::{CanonicCoset, CircleEvaluation, PolyOps};
+    use stwo_prover::core::prover::{prove, verify, VerificationError, LOG_BLOWUP_FACTOR};
+    use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
     use stwo_prover::core::InteractionElements;
-    use stwo_prover::examples::plonk::PlonkCircuitTrace;
+    use stwo_prover::examples::plonk::{
+        gen_interaction_trace, gen_trace, PlonkCircuitTrace, PlonkComponent,
+    };
 
     // test instruction:
     // RUSTFLAGS="-C target-cpu=native" RUST_LOG_SPAN_EVENTS="enter,close" RUST_LOG="none,circle_plonk=info,stwo_prover=info" cargo test test_simd_plonk_prove --no-default-features --release -- --nocapture
@@ -151,45 +760,162 @@ mod tests {
 
         let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
         let test_circuit = TestCircuit::rand(&mut prng);
-        let mut circuit = generate_circuit(test_circuit.clone(), Mode::PROVE).unwrap();
+        let mut circuit = generate_circuit(test_circuit.clone(), Mode::PROVE)
+            .unwrap()
+            .circuit;
         circuit.pad_to_next_power_of_2();
 
-        let trace: PlonkCircuitTrace = PlonkCircuitTrace::from(&circuit);
+        let trace: PlonkCircuitTrace = PlonkCircuitTrace::try_from(&circuit).unwrap();
 
         // Get from environment variable:
         let log_n_instances = trace.a_wire.length.ilog2();
 
         // Prove.
         let (component, proof) = prove_plonk(trace);
+        assert_eq!(
+            claimed_sum_from_inputs(&component.lookup_elements, &circuit.input_maps),
+            component.claimed_sum
+        );
 
         // Verify.
-        // TODO: Create Air instance independently.
-        let channel = &mut BWSSha256Channel::new(BWSSha256Hasher::hash(BaseField::into_slice(&[])));
-        let commitment_scheme = &mut CommitmentSchemeVerifier::new();
-
-        // Decommit.
-        // Retrieve the expected column sizes in each commitment interaction, from the AIR.
-        let max_degree = log_n_instances + 1;
-
-        let sizes = TreeVec::new(vec![
-            vec![max_degree; 4],
-            vec![max_degree; 8],
-            vec![max_degree; 4],
-        ]);
-
-        // Trace columns.
-        commitment_scheme.commit(proof.commitments[0], &sizes[0], channel);
-        // Draw lookup element.
-        let lookup_elements = LookupElements::<2>::draw(channel);
-        assert_eq!(lookup_elements, component.lookup_elements);
-        // TODO(spapini): Check claimed sum against first and last instances.
-        // Interaction columns.
-        commitment_scheme.commit(proof.commitments[1], &sizes[1], channel);
-        // Constant columns.
-        commitment_scheme.commit(proof.commitments[2], &sizes[2], channel);
+        verify_plonk(log_n_instances, &circuit.input_maps, proof).unwrap();
+    }
 
+    #[test]
+    fn test_commit_mixed_sizes() {
+        assert!(column_log_sizes(5)
+            .iter()
+            .flatten()
+            .all(|&log_size| log_size == 5));
+
+        let twiddles = SimdBackend::precompute_twiddles(
+            CanonicCoset::new(5 + LOG_BLOWUP_FACTOR + 1)
+                .circle_domain()
+                .half_coset,
+        );
+        let commitment_scheme = &mut CommitmentSchemeProver::<_, BWSSha256MerkleHasher>::new(
+            LOG_BLOWUP_FACTOR,
+            &twiddles,
+        );
+
+        // a table of a quarter of the size of the trace, between columns of the trace
+        let log_sizes = vec![5, 3, 3, 5];
+        let evals = log_sizes
+            .iter()
+            .map(|&log_size: &u32| {
+                let values = (0..1u32 << log_size).map(M31::from);
+                CircleEvaluation::new(
+                    CanonicCoset::new(log_size).circle_domain(),
+                    BaseColumn::from_iter(values),
+                )
+            })
+            .collect();
+        let channel = &mut Sha256Config::channel();
+        assert_eq!(commit_tree(commitment_scheme, evals, channel), log_sizes);
+        let committed = commitment_scheme.polynomials()[0]
+            .iter()
+            .map(|poly| poly.log_size())
+            .collect::<Vec<_>>();
+        assert_eq!(committed, log_sizes);
+    }
+
+    // Two circuits of different sizes proven together, one component each, so that every tree
+    // holds the columns of the smaller circuit at their own size after those of the larger one.
+    #[test]
+    fn test_prove_mixed_sizes() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let large = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let mut small = Circuit::new();
+        small.new_input(M31::one());
+        let a = small.new_input(M31::from(3)).wire();
+        let b = small.new_witness(M31::from(5));
+        let c = small.mul(a, b);
+        small.expose(c);
+
+        let circuits = [&large, &small];
+        let traces = circuits
+            .iter()
+            .map(|&circuit| pad_trace(PlonkCircuitTrace::try_from(circuit).unwrap()))
+            .collect::<Vec<_>>();
+        let log_sizes = traces
+            .iter()
+            .map(|trace| trace.a_wire.length.ilog2())
+            .collect::<Vec<_>>();
+        assert!(log_sizes[1] < log_sizes[0]);
+        // the log sizes of the columns of each tree, over both components
+        let sizes = (0..3)
+            .map(|tree| {
+                log_sizes
+                    .iter()
+                    .flat_map(|&log_size| column_log_sizes(log_size)[tree].clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let twiddles = SimdBackend::precompute_twiddles(
+            CanonicCoset::new(log_sizes[0] + LOG_BLOWUP_FACTOR + 1)
+                .circle_domain()
+                .half_coset,
+        );
+        let channel = &mut Sha256Config::channel();
+        let commitment_scheme = &mut CommitmentSchemeProver::<_, BWSSha256MerkleHasher>::new(
+            LOG_BLOWUP_FACTOR,
+            &twiddles,
+        );
+        let trace = zip(&log_sizes, &traces)
+            .flat_map(|(&log_size, trace)| gen_trace(log_size, trace))
+            .collect();
+        assert_eq!(commit_tree(commitment_scheme, trace, channel), sizes[0]);
+        let lookup_elements = LookupElements::draw(channel);
+        let mut interaction = vec![];
+        let mut components = vec![];
+        for (&log_n_rows, trace) in zip(&log_sizes, &traces) {
+            let (trace, claimed_sum) = gen_interaction_trace(log_n_rows, trace, &lookup_elements);
+            interaction.extend(trace);
+            components.push(PlonkComponent {
+                log_n_rows,
+                lookup_elements: lookup_elements.clone(),
+                claimed_sum,
+            });
+        }
+        assert_eq!(
+            commit_tree(commitment_scheme, interaction, channel),
+            sizes[1]
+        );
+        let constants = zip(&log_sizes, traces)
+            .flat_map(|(&log_size, trace)| {
+                constant_evals(
+                    log_size,
+                    [trace.a_wire, trace.b_wire, trace.c_wire, trace.op],
+                )
+            })
+            .collect();
+        assert_eq!(commit_tree(commitment_scheme, constants, channel), sizes[2]);
+        let proof = prove::<SimdBackend, _, _>(
+            &[&components[0], &components[1]],
+            channel,
+            &InteractionElements::default(),
+            commitment_scheme,
+        )
+        .unwrap();
+
+        let channel = &mut Sha256Config::channel();
+        let commitment_scheme = &mut CommitmentSchemeVerifier::<BWSSha256MerkleHasher>::new();
+        commitment_scheme.commit(proof.commitments[0], &max_degrees(&sizes[0]), channel);
+        let lookup_elements = LookupElements::<2>::draw(channel);
+        commitment_scheme.commit(proof.commitments[1], &max_degrees(&sizes[1]), channel);
+        commitment_scheme.commit(proof.commitments[2], &max_degrees(&sizes[2]), channel);
+        let components = zip(&log_sizes, circuits)
+            .map(|(&log_n_rows, circuit)| PlonkComponent {
+                log_n_rows,
+                lookup_elements: lookup_elements.clone(),
+                claimed_sum: claimed_sum_from_inputs(&lookup_elements, &circuit.input_maps),
+            })
+            .collect::<Vec<_>>();
         verify(
-            &[&component],
+            &[&components[0], &components[1]],
             channel,
             &InteractionElements::default(),
             commitment_scheme,
@@ -197,4 +923,182 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_verify_exposed_output() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let mut d = circuit.mul(a, b);
+        for _ in 0..8 {
+            d = circuit.add(d, b);
+        }
+        circuit.expose(d);
+        assert_eq!(circuit.get_output_wire(d), M31::from(55));
+
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        assert_eq!(circuit.num_rows, 16);
+        let log_n_rows = circuit.num_rows.ilog2();
+
+        verify_plonk(log_n_rows, &circuit.input_maps, proof).unwrap();
+
+        // claiming a different output must fail
+        let mut inputs = circuit.input_maps.clone();
+        inputs.set_value(inputs.len() - 1, M31::from(21));
+        let (_, proof) = prove_plonk(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        assert!(verify_plonk(log_n_rows, &inputs, proof).is_err());
+    }
+
+    #[test]
+    fn test_prove_unpadded() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+        assert!(!circuit.num_rows.is_power_of_two());
+
+        let (component, proof) = prove_plonk(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        assert_eq!(component.log_n_rows, LOG_N_LANES);
+        verify_plonk(LOG_N_LANES, &circuit.input_maps, proof).unwrap();
+
+        let num_rows = circuit.num_rows;
+        let proof = PlonkProof::prove(&mut circuit).unwrap();
+        assert_eq!(proof.num_rows, num_rows);
+        assert_eq!(proof.log_n_rows, LOG_N_LANES);
+        assert!(proof.is_num_rows_consistent());
+    }
+
+    #[test]
+    fn test_prove_mult_overflow() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.mult[0] = MAX_MULT + 1;
+        let err = SizeError::MultOverflow {
+            row: 0,
+            mult: MAX_MULT + 1,
+        };
+        assert_eq!(PlonkCircuitTrace::try_from(&circuit).err(), Some(err));
+        assert_eq!(PlonkProof::prove(&mut circuit).err(), Some(err));
+    }
+
+    // The proof only depends on the circuit and the witness: the prover does not blind the trace,
+    // every challenge is drawn from the channel and sums over the field are exact in any order.
+    #[test]
+    fn test_prove_on_any_number_of_threads() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let test_circuit = TestCircuit::rand(&mut prng);
+        let prove_on = |threads: usize| {
+            let mut circuit = generate_circuit(test_circuit.clone(), Mode::PROVE)
+                .unwrap()
+                .circuit;
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| bincode::serialize(&PlonkProof::prove(&mut circuit).unwrap()).unwrap())
+        };
+        assert_eq!(prove_on(1), prove_on(4));
+    }
+
+    #[test]
+    fn test_reprove() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+        PlonkProof::prove(&mut circuit).unwrap();
+
+        let index = circuit.dependency_index();
+        let inputs = [M31::one(), M31::from(4)];
+        let proof = PlonkProof::reprove(&mut circuit, &index, &inputs).unwrap();
+        assert_eq!(
+            proof.inputs.values(),
+            vec![M31::one(), M31::from(4), M31::from(20)]
+        );
+        verify_plonk(proof.log_n_rows, &proof.inputs, proof.proof).unwrap();
+    }
+
+    #[test]
+    fn test_verify_with_key() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let key = VerificationKey::new(&circuit.index());
+        assert_eq!(key.log_n_rows, circuit.num_rows.ilog2());
+        assert_eq!(key.constant_commitment, proof.commitments[2]);
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v)
+            .collect::<Vec<_>>();
+        verify_with_key(&key, &values, proof).unwrap();
+
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let mut wrong = values.clone();
+        *wrong.last_mut().unwrap() += M31::one();
+        assert!(matches!(
+            verify_with_key(&key, &wrong, proof),
+            Err(KeyVerificationError::Stark(_))
+        ));
+
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        assert!(matches!(
+            verify_with_key(&key, &values[1..], proof),
+            Err(KeyVerificationError::WrongNumberOfInputs { .. })
+        ));
+
+        // a key for another circuit
+        let mut other = Circuit::new();
+        other.new_input(M31::one());
+        let a = other.new_input(M31::from(3)).wire();
+        other.mul(a, a);
+        let (_, other_proof) = prove_circuit(&mut other).unwrap();
+        let other_key = VerificationKey {
+            constant_commitment: other_proof.commitments[2],
+            ..key
+        };
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        assert!(matches!(
+            verify_with_key(&other_key, &values, proof),
+            Err(KeyVerificationError::ConstantCommitmentMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_verify_missing_commitments() {
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        circuit.mul(a, a);
+        prove_circuit(&mut circuit).unwrap();
+        let key = VerificationKey::new(&circuit.index());
+        let values = circuit.input_maps.values();
+
+        for len in [3, 0] {
+            let (_, mut proof) = prove_circuit(&mut circuit).unwrap();
+            proof.commitments.truncate(len);
+            assert!(matches!(
+                verify_plonk(key.log_n_rows, &circuit.input_maps, proof),
+                Err(VerificationError::InvalidStructure(_))
+            ));
+
+            let (_, mut proof) = prove_circuit(&mut circuit).unwrap();
+            proof.commitments.truncate(len);
+            assert!(matches!(
+                verify_with_key(&key, &values, proof),
+                Err(KeyVerificationError::Stark(
+                    VerificationError::InvalidStructure(_)
+                ))
+            ));
+        }
+    }
 }
@@ -0,0 +1,264 @@
+use crate::circuit::compressed::CompressedIndex;
+use crate::circuit::index::CircuitIndex;
+use crate::circuit::FastHashMap;
+use crate::proof_system::VerificationKey;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// The padded indexes of the circuits that were set up before, each with its verification key,
+// under the hex of the fingerprint of the index, so that a circuit seen before skips the setup:
+// neither the index is rebuilt nor the constant columns committed again. Unlike `IndexCache`,
+// which is keyed by what a circuit is generated from, the registry is keyed by the circuit
+// itself, which is also how the prover service names its indexes.
+//
+// The verification key holds the root of the constant tree. The tree itself is not kept, as
+// stwo builds the trees of a proof inside its commitment scheme and cannot be given one.
+//
+// Entries are bincode-encoded `RegistryEntry`s, with the index compressed, kept by a
+// `RegistryStore`: a directory, memory, or anything else that stores bytes by name.
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub index: CompressedIndex,
+    pub key: VerificationKey,
+}
+
+pub trait RegistryStore: Send + Sync {
+    // `Ok(None)` if there is nothing under `name`
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>>;
+
+    fn store(&self, name: &str, bytes: &[u8]) -> Result<()>;
+}
+
+// One file per entry, written under a temporary name and then renamed, as in `IndexCache`. Only
+// the names of fingerprints are taken, so that a name from a request cannot reach outside of the
+// directory.
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        if !is_fingerprint_id(name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The name is not the hex of a fingerprint",
+            ));
+        }
+        Ok(self.dir.join(format!("{}.cpreg", name)))
+    }
+}
+
+impl RegistryStore for DirStore {
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(name)?) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn store(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(bytes)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&tmp, &path)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<FastHashMap<String, Vec<u8>>>,
+}
+
+impl RegistryStore for MemoryStore {
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(name).cloned())
+    }
+
+    fn store(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+pub struct IndexRegistry {
+    store: Box<dyn RegistryStore>,
+}
+
+pub fn fingerprint_id(fingerprint: &[u8; 32]) -> String {
+    fingerprint.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Whether `id` is as `fingerprint_id` writes it: 64 lowercase hex digits.
+pub fn is_fingerprint_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl IndexRegistry {
+    pub fn new(store: impl RegistryStore + 'static) -> Self {
+        Self {
+            store: Box::new(store),
+        }
+    }
+
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self::new(DirStore::new(dir))
+    }
+
+    fn read(&self, id: &str) -> Result<Option<RegistryEntry>> {
+        let Some(bytes) = self.store.load(id)? else {
+            return Ok(None);
+        };
+        let entry: RegistryEntry =
+            bincode::deserialize(&bytes).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let index = entry.index.expand()?;
+        if fingerprint_id(&index.fingerprint()) != id {
+            return Err(Error::new(ErrorKind::InvalidData, "Fingerprint mismatch"));
+        }
+        if !index.num_rows.is_power_of_two() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The index is not padded",
+            ));
+        }
+        if entry.key.log_n_rows != index.num_rows.ilog2()
+            || entry.key.input_wires != index.input_wires
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The key is not for the index",
+            ));
+        }
+        Ok(Some(entry))
+    }
+
+    // The entry under the hex of a fingerprint. An unreadable entry is a miss.
+    pub fn get(&self, id: &str) -> Option<RegistryEntry> {
+        match self.read(id) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::debug!("ignoring registered index {}: {}", id, err);
+                None
+            }
+        }
+    }
+
+    fn write(&self, id: &str, entry: &RegistryEntry) -> Result<()> {
+        let bytes =
+            bincode::serialize(entry).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        self.store.store(id, &bytes)
+    }
+
+    // Sets up a padded index and registers it.
+    pub fn insert(&self, index: CircuitIndex) -> Result<RegistryEntry> {
+        let id = fingerprint_id(&index.fingerprint());
+        let entry = RegistryEntry {
+            key: VerificationKey::new(&index),
+            index: index.compress(),
+        };
+        self.write(&id, &entry)?;
+        Ok(entry)
+    }
+
+    // Returns the registered entry of a padded index, or sets it up and registers it. Failing to
+    // write the registry does not fail the setup.
+    pub fn get_or_insert(&self, index: CircuitIndex) -> RegistryEntry {
+        let id = fingerprint_id(&index.fingerprint());
+        if let Some(entry) = self.get(&id) {
+            return entry;
+        }
+
+        let entry = RegistryEntry {
+            key: VerificationKey::new(&index),
+            index: index.compress(),
+        };
+        if let Err(err) = self.write(&id, &entry) {
+            tracing::warn!("cannot register index {}: {}", id, err);
+        }
+        entry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::index::CircuitIndex;
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::registry::{
+        fingerprint_id, DirStore, IndexRegistry, MemoryStore, RegistryEntry, RegistryStore,
+    };
+    use crate::proof_system::VerificationKey;
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+
+    #[test]
+    fn test_index_registry() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::INDEX)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let index = circuit.index();
+        let id = fingerprint_id(&index.fingerprint());
+
+        let dir =
+            std::env::temp_dir().join(format!("circle-plonk-registry-{}", std::process::id()));
+        let registry = IndexRegistry::open(&dir);
+        assert!(registry.get(&id).is_none());
+        let entry = registry.get_or_insert(index.clone());
+        assert_eq!(entry.key, VerificationKey::new(&index));
+
+        // another process finds it
+        let registry = IndexRegistry::open(&dir);
+        assert_eq!(registry.get(&id), Some(entry.clone()));
+        assert_eq!(registry.get_or_insert(index.clone()), entry);
+
+        // an entry under the wrong name is ignored
+        let store = MemoryStore::default();
+        let bytes = bincode::serialize(&entry).unwrap();
+        store.store(&fingerprint_id(&[0; 32]), &bytes).unwrap();
+        let registry = IndexRegistry::new(store);
+        assert!(registry.get(&fingerprint_id(&[0; 32])).is_none());
+        assert_eq!(registry.insert(index).unwrap(), entry);
+        assert_eq!(registry.get(&id), Some(entry.clone()));
+
+        // an index without rows is a miss
+        let empty = CircuitIndex::default();
+        let empty_id = fingerprint_id(&empty.fingerprint());
+        let bytes = bincode::serialize(&RegistryEntry {
+            index: empty.compress(),
+            key: entry.key,
+        })
+        .unwrap();
+        let store = MemoryStore::default();
+        store.store(&empty_id, &bytes).unwrap();
+        assert!(IndexRegistry::new(store).get(&empty_id).is_none());
+
+        // a directory only takes the names of fingerprints
+        let store = DirStore::new(&dir);
+        assert!(store.load("../escape").is_err());
+        assert!(store.store(&id.to_uppercase(), &bytes).is_err());
+        assert!(IndexRegistry::open(&dir).get("../escape").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+use crate::proof_system::{HasherConfig, VerificationKey};
+use sha3::{Digest, Keccak256};
+use std::fmt::Write;
+use stwo_prover::core::fields::m31::{BaseField, M31, P};
+use stwo_prover::core::fields::qm31::SecureField;
+use stwo_prover::core::prover::{StarkProof, LOG_BLOWUP_FACTOR, N_QUERIES};
+use stwo_prover::core::vcs::ops::MerkleHasher;
+use stwo_prover::core::vcs::prover::MerkleDecommitment;
+
+// The generated contract binds a verification key to a shared on-chain verifier of Circle STARK
+// proofs, behind the `ICircleStarkVerifier` interface: it checks the input values and the
+// commitment to the constant columns, which is what ties a proof to the circuit, and forwards the
+// proof. The STARK verifier itself does not depend on the circuit and is not generated here.
+//
+// The proof is passed as the `StarkProof` struct of the contract, with the fields of the proof of
+// stwo in the same order, so that the calldata is plain ABI encoding that a verifier can read
+// field by field. M31 elements are `uint32`, and an element of the secure field takes four of
+// them, in the order of `to_m31_array`.
+//
+// The hasher of the key is named in the contract, and proofs meant for the EVM are best made with
+// `Keccak256Config`, for which hashing costs a single opcode.
+
+const VERIFY_SIGNATURE: &str = concat!(
+    "verify((bytes32[],uint32[][][],(bytes32[],uint32[])[],uint32[][][],uint64,",
+    "(uint32[],(bytes32[],uint32[]),bytes32)[],uint32[]),uint32[])"
+);
+
+const TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+// Generated by circle-plonk from a verification key, do not edit.
+pragma solidity ^0.8.20;
+
+// A Circle STARK proof over M31, with the fields of the proof of stwo. An element of the secure
+// field takes four M31 elements.
+struct MerkleDecommitment {
+    bytes32[] hashWitness;
+    uint32[] columnWitness;
+}
+
+struct FriLayerProof {
+    uint32[] evalsSubset;
+    MerkleDecommitment decommitment;
+    bytes32 commitment;
+}
+
+struct StarkProof {
+    // the trace, interaction, constant and composition commitments
+    bytes32[] commitments;
+    // by tree and then column
+    uint32[][][] sampledValues;
+    // by tree
+    MerkleDecommitment[] decommitments;
+    // by tree and then column
+    uint32[][][] queriedValues;
+    uint64 proofOfWork;
+    FriLayerProof[] friLayers;
+    uint32[] lastLayerPoly;
+}
+
+// A verifier of Circle STARK proofs with the {HASHER} Merkle hasher, shared by every circuit. The
+// logup sum of the trace is fixed by the inputs.
+interface ICircleStarkVerifier {
+    function verifyStark(
+        uint32 logNRows,
+        uint32[] calldata inputWires,
+        uint32[] calldata inputValues,
+        StarkProof calldata proof
+    ) external view returns (bool);
+}
+
+contract CirclePlonkVerifier {
+    uint32 public constant LOG_N_ROWS = {LOG_N_ROWS};
+    uint32 public constant LOG_BLOWUP_FACTOR = {LOG_BLOWUP_FACTOR};
+    uint32 public constant N_QUERIES = {N_QUERIES};
+    uint256 public constant N_INPUTS = {N_INPUTS};
+    bytes32 public constant CONSTANT_COMMITMENT = 0x{CONSTANT_COMMITMENT};
+    string public constant HASHER = "{HASHER}";
+    uint32 internal constant P = {P};
+
+    ICircleStarkVerifier public immutable starkVerifier;
+
+    constructor(ICircleStarkVerifier _starkVerifier) {
+        starkVerifier = _starkVerifier;
+    }
+
+    function inputWires() public pure returns (uint32[] memory wires) {
+        wires = new uint32[](N_INPUTS);
+{INPUT_WIRES}    }
+
+    function verify(StarkProof calldata proof, uint32[] calldata inputs)
+        external
+        view
+        returns (bool)
+    {
+        if (inputs.length != N_INPUTS || proof.commitments.length != 4) {
+            return false;
+        }
+        for (uint256 i = 0; i < inputs.length; i++) {
+            if (inputs[i] >= P) {
+                return false;
+            }
+        }
+        if (proof.commitments[2] != CONSTANT_COMMITMENT) {
+            return false;
+        }
+        return starkVerifier.verifyStark(LOG_N_ROWS, inputWires(), inputs, proof);
+    }
+}
+"#;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn solidity_verifier<C: HasherConfig>(key: &VerificationKey<C>) -> String {
+    let mut input_wires = String::new();
+    for (i, wire) in key.input_wires.iter().enumerate() {
+        writeln!(input_wires, "        wires[{}] = {};", i, wire).unwrap();
+    }
+
+    TEMPLATE
+        .replace("{HASHER}", C::NAME)
+        .replace("{LOG_N_ROWS}", &key.log_n_rows.to_string())
+        .replace("{LOG_BLOWUP_FACTOR}", &LOG_BLOWUP_FACTOR.to_string())
+        .replace("{N_QUERIES}", &N_QUERIES.to_string())
+        .replace("{N_INPUTS}", &key.input_wires.len().to_string())
+        .replace(
+            "{CONSTANT_COMMITMENT}",
+            &to_hex(key.constant_commitment.as_ref()),
+        )
+        .replace("{P}", &P.to_string())
+        .replace("{INPUT_WIRES}", &input_wires)
+}
+
+// A value to be ABI encoded: a word of a static type, a dynamic array, or a struct.
+enum AbiValue {
+    Word([u8; 32]),
+    Array(Vec<AbiValue>),
+    Tuple(Vec<AbiValue>),
+}
+
+impl AbiValue {
+    fn uint(value: u64) -> Self {
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&value.to_be_bytes());
+        AbiValue::Word(word)
+    }
+
+    // `bytes32` is aligned to the left.
+    fn bytes32(bytes: &[u8]) -> Self {
+        let mut word = [0u8; 32];
+        word[..bytes.len()].copy_from_slice(bytes);
+        AbiValue::Word(word)
+    }
+
+    fn m31s<'a>(values: impl IntoIterator<Item = &'a BaseField>) -> Self {
+        AbiValue::Array(values.into_iter().map(|v| Self::uint(v.0 as u64)).collect())
+    }
+
+    fn secure_fields<'a>(values: impl IntoIterator<Item = &'a SecureField>) -> Self {
+        let coordinates = values
+            .into_iter()
+            .flat_map(|v| v.to_m31_array())
+            .map(|v| Self::uint(v.0 as u64));
+        AbiValue::Array(coordinates.collect())
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            AbiValue::Word(_) => false,
+            AbiValue::Array(_) => true,
+            AbiValue::Tuple(values) => values.iter().any(AbiValue::is_dynamic),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            AbiValue::Word(word) => word.to_vec(),
+            AbiValue::Array(values) => {
+                let mut bytes = Self::uint(values.len() as u64).encode();
+                bytes.extend(encode_sequence(values));
+                bytes
+            }
+            AbiValue::Tuple(values) => encode_sequence(values),
+        }
+    }
+}
+
+// The heads of the values, where a dynamic value is the offset of its encoding from the start of
+// the heads, and then the encodings of the dynamic values.
+fn encode_sequence(values: &[AbiValue]) -> Vec<u8> {
+    let encodings = values.iter().map(AbiValue::encode).collect::<Vec<_>>();
+    let heads_len = values
+        .iter()
+        .zip(encodings.iter())
+        .map(|(value, encoding)| {
+            if value.is_dynamic() {
+                32
+            } else {
+                encoding.len()
+            }
+        })
+        .sum::<usize>();
+
+    let mut heads = vec![];
+    let mut tails = vec![];
+    for (value, encoding) in values.iter().zip(encodings) {
+        if value.is_dynamic() {
+            heads.extend(AbiValue::uint((heads_len + tails.len()) as u64).encode());
+            tails.extend(encoding);
+        } else {
+            heads.extend(encoding);
+        }
+    }
+    heads.extend(tails);
+    heads
+}
+
+fn decommitment_abi<H: MerkleHasher>(decommitment: &MerkleDecommitment<H>) -> AbiValue
+where
+    H::Hash: AsRef<[u8]>,
+{
+    AbiValue::Tuple(vec![
+        AbiValue::Array(
+            decommitment
+                .hash_witness
+                .iter()
+                .map(|hash| AbiValue::bytes32(hash.as_ref()))
+                .collect(),
+        ),
+        AbiValue::m31s(&decommitment.column_witness),
+    ])
+}
+
+// The `StarkProof` struct of the contract.
+fn proof_abi<H: MerkleHasher>(proof: &StarkProof<H>) -> AbiValue
+where
+    H::Hash: AsRef<[u8]>,
+{
+    let proof_commitments = proof
+        .commitments
+        .iter()
+        .map(|commitment| AbiValue::bytes32(commitment.as_ref()));
+    let scheme_proof = &proof.commitment_scheme_proof;
+    let sampled_values = scheme_proof
+        .sampled_values
+        .iter()
+        .map(|tree| AbiValue::Array(tree.iter().map(AbiValue::secure_fields).collect()));
+    let queried_values = scheme_proof
+        .queried_values
+        .iter()
+        .map(|tree| AbiValue::Array(tree.iter().map(AbiValue::m31s).collect()));
+    let fri_proof = &scheme_proof.fri_proof;
+    let fri_layers = fri_proof.inner_layers.iter().map(|layer| {
+        AbiValue::Tuple(vec![
+            AbiValue::secure_fields(&layer.evals_subset),
+            decommitment_abi(&layer.decommitment),
+            AbiValue::bytes32(layer.commitment.as_ref()),
+        ])
+    });
+
+    AbiValue::Tuple(vec![
+        AbiValue::Array(proof_commitments.collect()),
+        AbiValue::Array(sampled_values.collect()),
+        AbiValue::Array(
+            scheme_proof
+                .decommitments
+                .iter()
+                .map(decommitment_abi)
+                .collect(),
+        ),
+        AbiValue::Array(queried_values.collect()),
+        AbiValue::uint(scheme_proof.proof_of_work.nonce),
+        AbiValue::Array(fri_layers.collect()),
+        AbiValue::secure_fields(fri_proof.last_layer_poly.iter()),
+    ])
+}
+
+// The ABI encoding of a call to `verify` of the generated contract with the proof and the values
+// of the inputs, in the order of the verification key.
+pub fn encode_calldata<H: MerkleHasher>(proof: &StarkProof<H>, values: &[M31]) -> Vec<u8>
+where
+    H::Hash: AsRef<[u8]>,
+{
+    let mut calldata = Keccak256::digest(VERIFY_SIGNATURE.as_bytes())[..4].to_vec();
+    calldata.extend(encode_sequence(&[proof_abi(proof), AbiValue::m31s(values)]));
+    calldata
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::keccak::Keccak256Config;
+    use crate::proof_system::solidity::{
+        encode_calldata, encode_sequence, solidity_verifier, AbiValue, VERIFY_SIGNATURE,
+    };
+    use crate::proof_system::{prove_plonk_with, VerificationKey};
+    use ark_std::rand::SeedableRng;
+    use sha3::{Digest, Keccak256};
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::examples::plonk::PlonkCircuitTrace;
+
+    fn word(bytes: &[u8], i: usize) -> &[u8] {
+        &bytes[32 * i..32 * (i + 1)]
+    }
+
+    fn value(bytes: &[u8], i: usize) -> u64 {
+        u64::from_be_bytes(word(bytes, i)[24..].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_abi_encoding() {
+        // (uint64, uint32[], (bytes32[], uint32[]))
+        let encoded = encode_sequence(&[
+            AbiValue::uint(9),
+            AbiValue::m31s(&[M31::from(5), M31::from(7)]),
+            AbiValue::Tuple(vec![
+                AbiValue::Array(vec![AbiValue::bytes32(&[0xab; 32])]),
+                AbiValue::Array(vec![]),
+            ]),
+        ]);
+        let words = (0..encoded.len() / 32)
+            .map(|i| value(&encoded, i))
+            .collect::<Vec<_>>();
+        assert_eq!(encoded.len(), 32 * 11);
+        assert_eq!(words[..6], [9, 96, 192, 2, 5, 7]);
+        // the offsets in the struct are from its start
+        assert_eq!(words[6..8], [64, 128]);
+        assert_eq!(words[8], 1);
+        assert_eq!(word(&encoded, 9), [0xab; 32]);
+        assert_eq!(words[10], 0);
+        assert!(!AbiValue::Tuple(vec![AbiValue::uint(1)]).is_dynamic());
+    }
+
+    #[test]
+    fn test_encode_calldata() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (_, proof) =
+            prove_plonk_with::<Keccak256Config>(PlonkCircuitTrace::try_from(&circuit).unwrap());
+        let key = VerificationKey::<Keccak256Config>::new_with_hasher(&circuit.index());
+        let values = circuit.input_maps.values();
+
+        let calldata = encode_calldata(&proof, &values);
+        assert_eq!(
+            calldata[..4],
+            Keccak256::digest(VERIFY_SIGNATURE.as_bytes())[..4]
+        );
+        assert!(!VERIFY_SIGNATURE.contains(' '));
+        let args = &calldata[4..];
+        // the proof is at the start of the tail, and starts with the offset of its commitments
+        assert_eq!(value(args, 0), 64);
+        let proof_start = 64;
+        let commitments = proof_start + value(&args[proof_start..], 0) as usize;
+        // one per tree, the third of which is what the contract checks against its key
+        assert_eq!(value(&args[commitments..], 0), 4);
+        assert_eq!(
+            word(&args[commitments..], 3),
+            key.constant_commitment.as_ref()
+        );
+        // the values of the inputs close the calldata
+        let inputs = value(args, 1) as usize;
+        assert_eq!(value(&args[inputs..], 0), values.len() as u64);
+        assert_eq!(args.len(), inputs + 32 * (1 + values.len()));
+        assert_eq!(
+            value(args, args.len() / 32 - 1),
+            values.last().unwrap().0 as u64
+        );
+    }
+
+    #[test]
+    fn test_solidity_verifier() {
+        let key: VerificationKey = VerificationKey {
+            log_n_rows: 4,
+            input_wires: vec![1, 5],
+            constant_commitment: Default::default(),
+        };
+        let source = solidity_verifier(&key);
+        assert!(source.contains("string public constant HASHER = \"bws-sha256\";"));
+        assert!(source.contains("uint32 public constant LOG_N_ROWS = 4;"));
+        assert!(source.contains("uint256 public constant N_INPUTS = 2;"));
+        assert!(source.contains(&format!("CONSTANT_COMMITMENT = 0x{};", "00".repeat(32))));
+        assert!(source.contains("        wires[1] = 5;\n    }"));
+        // every placeholder is filled in
+        assert!(!source
+            .split('{')
+            .skip(1)
+            .any(|s| s.starts_with(char::is_uppercase)));
+
+        let key = VerificationKey::<Keccak256Config> {
+            log_n_rows: 4,
+            input_wires: vec![1, 5],
+            constant_commitment: Default::default(),
+        };
+        let source = solidity_verifier(&key);
+        assert!(source.contains("string public constant HASHER = \"keccak256\";"));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::circuit::public_inputs::PublicInputs;
+use crate::proof_system::{KeyVerificationError, PlonkProof, VerificationKey};
+use serde::de::Error;
+use serde_json::Result;
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::prover::StarkProof;
+use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
+
+// The JSON encoding that the stwo tooling reads and writes proofs in, which is the serde
+// encoding of `StarkProof` itself: field elements as numbers and hashes as arrays of bytes. It
+// carries the STARK proof only, so the statement (the size of the trace and the inputs) is passed
+// along separately, as the verification key and the values of the inputs.
+
+pub fn to_stwo_json(proof: &StarkProof<BWSSha256MerkleHasher>) -> Result<String> {
+    serde_json::to_string(proof)
+}
+
+pub fn from_stwo_json(json: &str) -> Result<StarkProof<BWSSha256MerkleHasher>> {
+    serde_json::from_str(json)
+}
+
+// The log size of the trace that a proof is for, read from its FRI layers: the composition
+// polynomial of a trace of `2^log_n_rows` rows has the degree bound `log_n_rows + 1`, so its
+// line has the bound `log_n_rows`, which each inner layer halves down to the last layer.
+pub fn proof_log_n_rows(proof: &StarkProof<BWSSha256MerkleHasher>) -> Option<u32> {
+    let fri_proof = &proof.commitment_scheme_proof.fri_proof;
+    let last_layer_len = fri_proof.last_layer_poly.len();
+    last_layer_len
+        .is_power_of_two()
+        .then(|| fri_proof.inner_layers.len() as u32 + last_layer_len.ilog2())
+}
+
+// Pairs a STARK proof in the stwo encoding with the statement of a proof of the circuit of `key`,
+// and fails rather than pairing it with a wrong number of input values or with a proof for a
+// trace of another size.
+pub fn plonk_proof_from_stwo_json(
+    key: &VerificationKey,
+    values: &[M31],
+    json: &str,
+) -> Result<PlonkProof> {
+    if values.len() != key.input_wires.len() {
+        return Err(serde_json::Error::custom(
+            KeyVerificationError::WrongNumberOfInputs {
+                expected: key.input_wires.len(),
+                actual: values.len(),
+            },
+        ));
+    }
+    let proof = from_stwo_json(json)?;
+    let log_n_rows = proof_log_n_rows(&proof).unwrap_or(0);
+    if log_n_rows != key.log_n_rows {
+        return Err(serde_json::Error::custom(
+            KeyVerificationError::WrongNumberOfRows {
+                expected: key.log_n_rows,
+                actual: log_n_rows,
+            },
+        ));
+    }
+    // the stwo encoding does not carry the rows of the circuit before padding, so they are taken
+    // to fill the trace
+    Ok(PlonkProof {
+        num_rows: 1 << log_n_rows,
+        log_n_rows,
+        inputs: PublicInputs::from_wires(&key.input_wires, values),
+        proof,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::stwo_json::{
+        from_stwo_json, plonk_proof_from_stwo_json, proof_log_n_rows, to_stwo_json,
+    };
+    use crate::proof_system::{prove_circuit, verify_plonk, verify_with_key, VerificationKey};
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_stwo_json() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let json = to_stwo_json(&proof).unwrap();
+        assert_eq!(to_stwo_json(&from_stwo_json(&json).unwrap()).unwrap(), json);
+
+        let key = VerificationKey::new(&circuit.index());
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            proof_log_n_rows(&from_stwo_json(&json).unwrap()),
+            Some(key.log_n_rows)
+        );
+        let proof = plonk_proof_from_stwo_json(&key, &values, &json).unwrap();
+        assert_eq!(proof.inputs, circuit.input_maps);
+        assert_eq!(proof.log_n_rows, key.log_n_rows);
+        verify_with_key(&key, &values, from_stwo_json(&json).unwrap()).unwrap();
+        verify_plonk(proof.log_n_rows, &proof.inputs, proof.proof).unwrap();
+
+        assert!(from_stwo_json(&json[..json.len() / 2]).is_err());
+        assert!(plonk_proof_from_stwo_json(&key, &values[1..], &json).is_err());
+
+        // a proof of a smaller circuit does not pass for one of the circuit of the key
+        let mut small = Circuit::new();
+        small.new_input(M31::one());
+        let a = small.new_witness(M31::from(3));
+        small.mul(a, a);
+        let (_, proof) = prove_circuit(&mut small).unwrap();
+        assert!(proof_log_n_rows(&proof).unwrap() < key.log_n_rows);
+        let json = to_stwo_json(&proof).unwrap();
+        assert!(plonk_proof_from_stwo_json(&key, &values, &json).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::circuit::Circuit;
+use crate::testing::{
+    apply_mutation, random_circuit, random_mutation, Mutation, RandomCircuitConfig, RandomR1cs,
+    RandomR1csConfig,
+};
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use proptest::prelude::*;
+use std::fmt::{Debug, Formatter};
+
+// Strategies over the generators of `testing`, for crates that build on this prover to property
+// test their gadgets against it. The sizes shrink towards small circuits, and each value is drawn
+// from a seed, which is what a failing case prints to be reproduced with `random_circuit` or
+// `RandomR1cs`.
+
+// A generated circuit with its witness, and the mutation that makes it unsatisfiable, if any.
+pub struct GeneratedCircuit {
+    pub circuit: Circuit,
+    pub config: RandomCircuitConfig,
+    pub seed: u64,
+    pub mutation: Option<Mutation>,
+}
+
+impl Debug for GeneratedCircuit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeneratedCircuit")
+            .field("config", &self.config)
+            .field("seed", &self.seed)
+            .field("mutation", &self.mutation)
+            .field("num_rows", &self.circuit.num_rows)
+            .finish()
+    }
+}
+
+fn generate(config: RandomCircuitConfig, seed: u64, perturb: bool) -> GeneratedCircuit {
+    let mut prng = StdRng::seed_from_u64(seed);
+    let mut circuit = random_circuit(&mut prng, &config);
+    let mutation = perturb.then(|| random_mutation(&mut prng, &circuit));
+    if let Some(mutation) = mutation {
+        apply_mutation(&mut circuit, mutation);
+    }
+    GeneratedCircuit {
+        circuit,
+        config,
+        seed,
+        mutation,
+    }
+}
+
+pub fn circuit_config() -> impl Strategy<Value = RandomCircuitConfig> {
+    (0..8usize, 0..16usize, 1..128usize, 0..8usize, 0..4usize).prop_map(
+        |(num_inputs, num_witnesses, num_gates, num_zero_tests, num_exposed)| RandomCircuitConfig {
+            num_inputs,
+            num_witnesses,
+            num_gates,
+            num_zero_tests,
+            num_exposed,
+        },
+    )
+}
+
+// Circuits whose witness satisfies both the row constraints and the logup sum.
+pub fn satisfiable_circuit(
+    config: impl Strategy<Value = RandomCircuitConfig>,
+) -> impl Strategy<Value = GeneratedCircuit> {
+    (config, any::<u64>()).prop_map(|(config, seed)| generate(config, seed, false))
+}
+
+// The circuits of `satisfiable_circuit` with one `Mutation` applied, which every check of the
+// witness must reject.
+pub fn unsatisfiable_circuit(
+    config: impl Strategy<Value = RandomCircuitConfig>,
+) -> impl Strategy<Value = GeneratedCircuit> {
+    (config, any::<u64>()).prop_map(|(config, seed)| generate(config, seed, true))
+}
+
+pub fn r1cs_config() -> impl Strategy<Value = RandomR1csConfig> {
+    (0..4usize, 0..8usize, 1..64usize, 1..5usize).prop_map(
+        |(num_inputs, num_witnesses, num_constraints, max_terms)| RandomR1csConfig {
+            num_inputs,
+            num_witnesses,
+            num_constraints,
+            max_terms,
+        },
+    )
+}
+
+// R1CS systems with a satisfying assignment, to be converted with `generate_circuit`.
+pub fn r1cs_system(
+    config: impl Strategy<Value = RandomR1csConfig>,
+) -> impl Strategy<Value = RandomR1cs> {
+    (config, any::<u64>()).prop_map(|(config, seed)| RandomR1cs { config, seed })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::proptest_support::{
+        circuit_config, r1cs_config, r1cs_system, satisfiable_circuit, unsatisfiable_circuit,
+    };
+    use crate::testing::is_satisfied;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_strategies(
+            satisfiable in satisfiable_circuit(circuit_config()),
+            unsatisfiable in unsatisfiable_circuit(circuit_config()),
+            r1cs in r1cs_system(r1cs_config()),
+        ) {
+            prop_assert!(is_satisfied(&satisfiable.circuit));
+            prop_assert!(!is_satisfied(&unsatisfiable.circuit));
+
+            let circuit = generate_circuit(r1cs, Mode::PROVE).unwrap().circuit;
+            prop_assert!(is_satisfied(&circuit));
+        }
+    }
+}
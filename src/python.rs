@@ -0,0 +1,251 @@
+use crate::circuit::{Circuit, Mode, Wire};
+use crate::from_r1cs::circom::{load_r1cs, load_r1cs_and_witness_with_outputs};
+use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use crate::proof_system::{verify_with_key, KeyVerificationError, PlonkProof, VerificationKey};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::fmt::Display;
+use std::io::Cursor;
+use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+use stwo_prover::core::fields::m31::{M31, P};
+
+// The Python module `circle_plonk`, built with `maturin build --features python`. Keys, indexes and
+// proofs are passed around as the bytes of the files of the `circle-plonk` tool, and field
+// elements as integers below the modulus.
+
+fn value_error<E: Display>(err: E) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+fn to_m31(value: u32) -> PyResult<M31> {
+    if value >= P {
+        return Err(value_error(format!("{} is not below the modulus", value)));
+    }
+    Ok(M31::from(value))
+}
+
+// Proves a witness-carrying circuit, returning the proof and the values of its inputs.
+fn prove_witness(mut circuit: Circuit) -> PyResult<(Vec<u8>, Vec<u32>)> {
+    // the prover does not check the witness, and would otherwise produce a proof that fails
+    if !circuit.is_constraint_satisfied() {
+        return Err(value_error("the witness does not satisfy the constraints"));
+    }
+    let proof = PlonkProof::prove(&mut circuit).map_err(value_error)?;
+    let values = proof.inputs.values().iter().map(|v| v.0).collect();
+    Ok((bincode::serialize(&proof).map_err(value_error)?, values))
+}
+
+// Proves a circom circuit from the contents of its `.r1cs` and `.wtns` files, and returns the
+// proof with the values of the inputs.
+#[pyfunction]
+fn prove<'py>(
+    py: Python<'py>,
+    r1cs: &[u8],
+    wtns: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Vec<u32>)> {
+    let (proof, values) = py.allow_threads(|| {
+        let (circom, _) = load_r1cs_and_witness_with_outputs(Cursor::new(r1cs), Cursor::new(wtns))
+            .map_err(value_error)?;
+        let circuit = generate_circuit(circom, Mode::PROVE)
+            .map_err(value_error)?
+            .circuit;
+        prove_witness(circuit)
+    })?;
+    Ok((PyBytes::new_bound(py, &proof), values))
+}
+
+// Returns the padded index and the verification key of a circom circuit, as `circle-plonk setup`.
+#[pyfunction]
+fn setup<'py>(
+    py: Python<'py>,
+    r1cs: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let (index, key) = py.allow_threads(|| {
+        let (circom, _) = load_r1cs(Cursor::new(r1cs)).map_err(value_error)?;
+        let mut circuit = generate_circuit(circom, Mode::INDEX)
+            .map_err(value_error)?
+            .circuit;
+        // the same padding as `prove_circuit`
+        circuit.pad_minimal(LOG_N_LANES);
+        let index = circuit.index();
+        let key = VerificationKey::new(&index);
+        Ok::<_, PyErr>((
+            bincode::serialize(&index.compress()).map_err(value_error)?,
+            bincode::serialize(&key).map_err(value_error)?,
+        ))
+    })?;
+    Ok((PyBytes::new_bound(py, &index), PyBytes::new_bound(py, &key)))
+}
+
+// Returns whether the proof is valid for the key and the values of the inputs. A malformed key or
+// proof, or a wrong number of values, raises `ValueError` instead.
+#[pyfunction]
+fn verify(py: Python<'_>, key: &[u8], proof: &[u8], values: Vec<u32>) -> PyResult<bool> {
+    let key: VerificationKey = bincode::deserialize(key).map_err(value_error)?;
+    let proof: PlonkProof = bincode::deserialize(proof).map_err(value_error)?;
+    let values = values
+        .into_iter()
+        .map(to_m31)
+        .collect::<PyResult<Vec<_>>>()?;
+    if proof.log_n_rows != key.log_n_rows || !proof.is_num_rows_consistent() {
+        return Ok(false);
+    }
+    match py.allow_threads(|| verify_with_key(&key, &values, proof.proof)) {
+        Ok(()) => Ok(true),
+        Err(err @ KeyVerificationError::WrongNumberOfInputs { .. }) => Err(value_error(err)),
+        Err(_) => Ok(false),
+    }
+}
+
+// A circuit built row by row from Python, with wires as row numbers.
+#[pyclass(name = "Circuit")]
+struct PyCircuit {
+    circuit: Circuit,
+}
+
+impl PyCircuit {
+    // `Circuit` is not `Clone`, but the index and the witness are all that proving needs.
+    fn copy(&self) -> Circuit {
+        Circuit::from_parts(self.circuit.index(), self.circuit.witness())
+    }
+
+    fn wire(&self, idx: usize) -> PyResult<Wire> {
+        if idx >= self.circuit.num_rows {
+            return Err(value_error(format!("no wire {}", idx)));
+        }
+        Ok(Wire(idx))
+    }
+}
+
+#[pymethods]
+impl PyCircuit {
+    #[new]
+    fn new() -> Self {
+        Self {
+            circuit: Circuit::new(),
+        }
+    }
+
+    #[getter]
+    fn num_rows(&self) -> usize {
+        self.circuit.num_rows
+    }
+
+    fn new_input(&mut self, value: u32) -> PyResult<usize> {
+        Ok(self.circuit.new_input(to_m31(value)?).wire().0)
+    }
+
+    fn new_witness(&mut self, value: u32) -> PyResult<usize> {
+        Ok(self.circuit.new_witness(to_m31(value)?).0)
+    }
+
+    fn new_constant(&mut self, value: u32) -> PyResult<usize> {
+        Ok(self.circuit.new_constant(to_m31(value)?).0)
+    }
+
+    fn add(&mut self, a: usize, b: usize) -> PyResult<usize> {
+        let (a, b) = (self.wire(a)?, self.wire(b)?);
+        Ok(self.circuit.add(a, b).0)
+    }
+
+    fn mul(&mut self, a: usize, b: usize) -> PyResult<usize> {
+        let (a, b) = (self.wire(a)?, self.wire(b)?);
+        Ok(self.circuit.mul(a, b).0)
+    }
+
+    fn neg(&mut self, a: usize) -> PyResult<usize> {
+        let a = self.wire(a)?;
+        Ok(self.circuit.neg(a).0)
+    }
+
+    fn mul_by_constant(&mut self, a: usize, constant: u32) -> PyResult<usize> {
+        let a = self.wire(a)?;
+        Ok(self.circuit.mul_by_constant(a, to_m31(constant)?).0)
+    }
+
+    fn zero_test(&mut self, a: usize) -> PyResult<()> {
+        let a = self.wire(a)?;
+        self.circuit.zero_test(a);
+        Ok(())
+    }
+
+    // Makes the value of a wire part of the statement, as a new input.
+    fn expose(&mut self, a: usize) -> PyResult<usize> {
+        let a = self.wire(a)?;
+        Ok(self.circuit.expose(a).wire().0)
+    }
+
+    fn value(&self, a: usize) -> PyResult<u32> {
+        Ok(self.circuit.get_output_wire(self.wire(a)?).0)
+    }
+
+    fn is_satisfied(&self) -> bool {
+        self.circuit.is_constraint_satisfied()
+    }
+
+    // The padded index and the verification key, as `setup` returns them.
+    fn setup<'py>(&self, py: Python<'py>) -> (Bound<'py, PyBytes>, Bound<'py, PyBytes>) {
+        let mut circuit = self.copy();
+        circuit.pad_minimal(LOG_N_LANES);
+        let index = circuit.index();
+        let key = VerificationKey::new(&index);
+        (
+            PyBytes::new_bound(py, &bincode::serialize(&index.compress()).unwrap()),
+            PyBytes::new_bound(py, &bincode::serialize(&key).unwrap()),
+        )
+    }
+
+    // Proves a copy of the circuit, which can still be extended afterwards.
+    fn prove<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyBytes>, Vec<u32>)> {
+        let circuit = self.copy();
+        let (proof, values) = py.allow_threads(|| prove_witness(circuit))?;
+        Ok((PyBytes::new_bound(py, &proof), values))
+    }
+}
+
+#[pymodule]
+fn circle_plonk(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(prove, m)?)?;
+    m.add_function(wrap_pyfunction!(setup, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    m.add_class::<PyCircuit>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::python::{prove, setup, verify, PyCircuit};
+    use pyo3::prelude::*;
+
+    #[test]
+    fn test_prove_and_verify() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let r1cs = include_bytes!("./from_r1cs/circom/multiplier2.r1cs");
+            let wtns = include_bytes!("./from_r1cs/circom/output.wtns");
+
+            let (_, key) = setup(py, r1cs).unwrap();
+            let (proof, values) = prove(py, r1cs, wtns).unwrap();
+            let (key, proof) = (key.as_bytes(), proof.as_bytes());
+            assert!(verify(py, key, proof, values.clone()).unwrap());
+
+            let mut wrong = values.clone();
+            wrong[1] += 1;
+            assert!(!verify(py, key, proof, wrong).unwrap());
+            assert!(verify(py, key, proof, values[1..].to_vec()).is_err());
+            assert!(verify(py, &key[..key.len() - 1], proof, values).is_err());
+
+            let mut circuit = PyCircuit::new();
+            circuit.new_input(1).unwrap();
+            let x = circuit.new_input(3).unwrap();
+            let y = circuit.new_witness(7).unwrap();
+            let z = circuit.mul(x, y).unwrap();
+            circuit.expose(z).unwrap();
+            let (_, key) = circuit.setup(py);
+            let (proof, values) = circuit.prove(py).unwrap();
+            assert_eq!(values, vec![1, 3, 21]);
+            assert!(verify(py, key.as_bytes(), proof.as_bytes(), values).unwrap());
+        });
+    }
+}
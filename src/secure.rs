@@ -0,0 +1,147 @@
+use crate::circuit::index::Witness;
+use crate::circuit::packed::PackedM31Vec;
+use crate::circuit::Circuit;
+use crate::field::FM31;
+use crate::from_r1cs::r1cs_constraint_processor::Assignments;
+use stwo_prover::core::fields::m31::M31;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+// Witness values are secrets. The comparisons here take a time that only depends on the number
+// of values, unlike `==`, which stops at the first difference, and `Zeroize` overwrites them in
+// a way that the compiler cannot elide. The containers are not wiped when they are dropped, as
+// their fields are moved out in places; `zeroize::Zeroizing` does so for those that should be.
+//
+// Both `M31` and `FM31` always hold their canonical representative, so that equal values have
+// equal words.
+
+pub fn ct_eq_m31(a: &M31, b: &M31) -> Choice {
+    a.0.ct_eq(&b.0)
+}
+
+pub fn ct_eq_fm31(a: &FM31, b: &FM31) -> Choice {
+    a.0 .0.ct_eq(&b.0 .0)
+}
+
+// The lengths are not secret, and sequences of different lengths are unequal.
+pub fn ct_eq_m31_iter<'a>(
+    a: impl ExactSizeIterator<Item = &'a M31>,
+    b: impl ExactSizeIterator<Item = &'a M31>,
+) -> Choice {
+    if a.len() != b.len() {
+        return Choice::from(0);
+    }
+    a.zip(b)
+        .fold(Choice::from(1), |acc, (a, b)| acc & ct_eq_m31(a, b))
+}
+
+pub fn zeroize_fm31(values: &mut [FM31]) {
+    for v in values.iter_mut() {
+        v.0 .0.zeroize();
+    }
+}
+
+fn zeroize_m31(values: &mut [M31]) {
+    for v in values.iter_mut() {
+        v.0.zeroize();
+    }
+}
+
+impl ConstantTimeEq for PackedM31Vec {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        if self.len() != other.len() {
+            return Choice::from(0);
+        }
+        self.iter()
+            .zip(other.iter())
+            .fold(Choice::from(1), |acc, (a, b)| acc & ct_eq_m31(a, b))
+    }
+}
+
+impl Zeroize for PackedM31Vec {
+    fn zeroize(&mut self) {
+        for i in 0..self.len() {
+            self[i].0.zeroize();
+        }
+    }
+}
+
+fn input_values(witness: &Witness) -> impl ExactSizeIterator<Item = &M31> + '_ {
+    witness.input_maps.iter().map(|(_, v)| v)
+}
+
+impl ConstantTimeEq for Witness {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_m31_iter(self.output_wires.iter(), other.output_wires.iter())
+            & ct_eq_m31_iter(input_values(self), input_values(other))
+            & Choice::from((self.input_maps.wires() == other.input_maps.wires()) as u8)
+    }
+}
+
+// The statement is public, so only the values of the rows are wiped.
+impl Zeroize for Witness {
+    fn zeroize(&mut self) {
+        zeroize_m31(&mut self.output_wires);
+    }
+}
+
+impl Zeroize for Circuit {
+    fn zeroize(&mut self) {
+        self.output_wires.zeroize();
+    }
+}
+
+// A mapped witness is only read from its file, and is left as it is.
+impl Zeroize for Assignments {
+    fn zeroize(&mut self) {
+        match self {
+            Assignments::Values(values) => zeroize_m31(values),
+            Assignments::Mapped { appended, .. } => zeroize_m31(appended),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::field::FM31;
+    use crate::secure::{ct_eq_fm31, ct_eq_m31, zeroize_fm31};
+    use ark_ff::Zero;
+    use stwo_prover::core::fields::m31::M31;
+    use subtle::ConstantTimeEq;
+    use zeroize::Zeroize;
+
+    #[test]
+    fn test_secure() {
+        assert!(bool::from(ct_eq_m31(&M31::from(3), &M31::from(3))));
+        assert!(!bool::from(ct_eq_m31(&M31::from(3), &M31::from(4))));
+        assert!(bool::from(ct_eq_fm31(
+            &FM31::from(-1i64),
+            &FM31::from(2147483646u32)
+        )));
+        assert!(!bool::from(ct_eq_fm31(&FM31::from(1), &FM31::from(2))));
+
+        let mut circuit = Circuit::new();
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        circuit.mul(a, b);
+        let mut witness = circuit.witness();
+        assert!(bool::from(witness.ct_eq(&circuit.witness())));
+        assert!(bool::from(
+            circuit.output_wires.ct_eq(&circuit.output_wires)
+        ));
+
+        witness.output_wires[b.0] = M31::from(6);
+        assert!(!bool::from(witness.ct_eq(&circuit.witness())));
+        witness.zeroize();
+        assert!(witness.output_wires.iter().all(|v| v.is_zero()));
+        assert_eq!(witness.input_maps, circuit.input_maps);
+
+        circuit.zeroize();
+        assert!(circuit.output_wires.iter().all(|v| v.is_zero()));
+
+        let mut values = vec![FM31::from(7), FM31::from(8)];
+        zeroize_fm31(&mut values);
+        assert!(values.iter().all(|v| v.is_zero()));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::circuit::compressed::CompressedIndex;
+use crate::circuit::index::Witness;
+use crate::service::{JobStatus, ProverService, ServiceError};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Error, ErrorKind, Read, Result};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// The HTTP interface of a `ProverService`, with bincode bodies as in the files of `circle-plonk`:
+//
+// - `POST /indexes` with a `CompressedIndex`, as in the `index.bin` of `setup`, registers it and
+//   returns `{"id": ...}`;
+// - `POST /indexes/<id>/jobs` with a `Witness` queues a job and returns `{"job": ...}`, for an
+//   index registered before, with this service or in its `IndexRegistry`;
+// - `GET /jobs/<job>` returns `{"status": ...}`, one of `queued`, `running`, `done` and `failed`,
+//   with the `error` of a failed job;
+// - `GET /jobs/<job>/proof` returns the `PlonkProof` of a finished job and forgets the job;
+// - `GET /health` and `GET /metrics`, the latter in the Prometheus text format.
+//
+// With a token, every request but `GET /health` must carry it as `Authorization: Bearer <token>`.
+// Bodies above `max_body_size` are refused before they are read.
+//
+// Requests are handled one at a time, which only costs the time to read the bodies, as proving
+// happens on the workers of the service.
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpConfig {
+    pub max_body_size: usize,
+    pub token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_body_size: 256 << 20,
+            token: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn json_response(status: u16, value: serde_json::Value) -> HttpResponse {
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status: u16, message: &str) -> HttpResponse {
+    json_response(status, json!({ "error": message }))
+}
+
+fn service_error_response(err: ServiceError) -> HttpResponse {
+    let status = match err {
+        ServiceError::UnknownIndex => 404,
+        ServiceError::InvalidIndex | ServiceError::IncompatibleWitness => 422,
+        ServiceError::QueueFull => 503,
+    };
+    error_response(status, &err.to_string())
+}
+
+// `None` if the body is above the limit.
+fn read_body(request: &mut Request, max_body_size: usize) -> Result<Option<Vec<u8>>> {
+    if request.body_length().is_some_and(|len| len > max_body_size) {
+        return Ok(None);
+    }
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(max_body_size as u64 + 1)
+        .read_to_end(&mut body)?;
+    Ok(Some(body).filter(|body| body.len() <= max_body_size))
+}
+
+// The body as bincode, whose lengths cannot claim more than the body holds.
+fn decode_body<T: DeserializeOwned>(
+    request: &mut Request,
+    config: &HttpConfig,
+    what: &str,
+) -> std::result::Result<T, HttpResponse> {
+    let body = match read_body(request, config.max_body_size) {
+        Ok(Some(body)) => body,
+        Ok(None) => return Err(error_response(413, "the body is too large")),
+        Err(_) => return Err(error_response(400, "cannot read the body")),
+    };
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(body.len() as u64)
+        .deserialize(&body)
+        .map_err(|_| error_response(400, &format!("the body is not {}", what)))
+}
+
+// The tokens are compared through their hashes, so that the time taken does not tell how much of
+// a guess is right.
+fn is_authorized(request: &Request, token: &str) -> bool {
+    request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && header
+                .value
+                .as_str()
+                .strip_prefix("Bearer ")
+                .is_some_and(|given| {
+                    Sha256::digest(given.as_bytes()) == Sha256::digest(token.as_bytes())
+                })
+    })
+}
+
+fn handle(service: &ProverService, config: &HttpConfig, request: &mut Request) -> HttpResponse {
+    let url = request.url().trim_end_matches('/').to_string();
+    let path = url.split('/').skip(1).collect::<Vec<_>>();
+    let method = request.method().clone();
+
+    if let Some(token) = &config.token {
+        let is_health = method == Method::Get && path == ["health"];
+        if !is_health && !is_authorized(request, token) {
+            return error_response(401, "missing or wrong token")
+                .with_header(header("WWW-Authenticate", "Bearer"));
+        }
+    }
+
+    match (&method, path.as_slice()) {
+        (Method::Get, ["health"]) => Response::from_string("ok"),
+        (Method::Get, ["metrics"]) => Response::from_string(service.metrics())
+            .with_header(header("Content-Type", "text/plain; version=0.0.4")),
+        (Method::Post, ["indexes"]) => {
+            let index = match decode_body::<CompressedIndex>(request, config, "an index") {
+                Ok(index) => index,
+                Err(response) => return response,
+            };
+            let Ok(index) = index.expand() else {
+                return error_response(400, "the index is invalid");
+            };
+            match service.register_index(index) {
+                Ok(id) => json_response(201, json!({ "id": id })),
+                Err(err) => service_error_response(err),
+            }
+        }
+        (Method::Post, ["indexes", id, "jobs"]) => {
+            let witness = match decode_body::<Witness>(request, config, "a witness") {
+                Ok(witness) => witness,
+                Err(response) => return response,
+            };
+            match service.submit(id, witness) {
+                Ok(job) => json_response(202, json!({ "job": job })),
+                Err(err) => service_error_response(err),
+            }
+        }
+        (Method::Get, ["jobs", job]) => match service.status(job) {
+            None => error_response(404, "no such job"),
+            Some(JobStatus::Queued) => json_response(200, json!({ "status": "queued" })),
+            Some(JobStatus::Running) => json_response(200, json!({ "status": "running" })),
+            Some(JobStatus::Done(_)) => json_response(200, json!({ "status": "done" })),
+            Some(JobStatus::Failed(err)) => {
+                json_response(200, json!({ "status": "failed", "error": err }))
+            }
+        },
+        (Method::Get, ["jobs", job, "proof"]) => match service.take(job) {
+            Some(JobStatus::Done(proof)) => Response::from_data(proof)
+                .with_header(header("Content-Type", "application/octet-stream")),
+            Some(JobStatus::Failed(err)) => error_response(422, &err),
+            _ if service.status(job).is_some() => error_response(409, "the job is not done"),
+            _ => error_response(404, "no such job"),
+        },
+        _ => error_response(404, "no such endpoint"),
+    }
+}
+
+// Serves the service until the listener fails.
+pub fn serve(service: &ProverService, config: &HttpConfig, addr: &str) -> Result<()> {
+    let server = Server::http(addr).map_err(|err| Error::new(ErrorKind::Other, err))?;
+    for mut request in server.incoming_requests() {
+        let response = handle(service, config, &mut request);
+        if let Err(err) = request.respond(response) {
+            tracing::warn!("cannot respond: {}", err);
+        }
+    }
+    Ok(())
+}
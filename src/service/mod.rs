@@ -0,0 +1,545 @@
+use crate::circuit::compressed::CompressedIndex;
+use crate::circuit::index::{CircuitIndex, Witness};
+use crate::circuit::FastHashMap;
+use crate::metrics::Metrics;
+use crate::proof_system::registry::{fingerprint_id, IndexRegistry};
+use crate::proof_system::PlonkProof;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod http;
+
+// A prover daemon: indexes are registered once under the hex of their fingerprint, and jobs, each
+// a witness for a registered index, are queued and proved by a fixed number of worker threads.
+// A job is named by a random token, which is all it takes to fetch its proof. A finished proof is
+// kept, bincode-encoded as `PlonkProof`, until it is taken, or until it is older than
+// `finished_ttl` or `max_finished` newer jobs have finished. Indexes are kept compressed and
+// decoded straight into the trace by the worker that proves a job, and beyond `max_indexes` the
+// oldest registered one is forgotten.
+//
+// With an `IndexRegistry`, the registered indexes are also set up and kept there, by a worker as
+// the setup commits to the constant columns, and an index that is not registered with the
+// service is looked up in the registry, so that the clients of a restarted service, or of
+// another one sharing the registry, submit jobs without registering again.
+//
+// Each proof already uses the global rayon pool, so a few workers are enough to keep the machine
+// busy, and more only add to the memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceConfig {
+    pub workers: usize,
+    // the jobs and setups that can wait for a worker before new ones are refused
+    pub max_queued: usize,
+    pub finished_ttl: Duration,
+    pub max_finished: usize,
+    pub max_indexes: usize,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            max_queued: 64,
+            finished_ttl: Duration::from_secs(3600),
+            max_finished: 256,
+            max_indexes: 64,
+        }
+    }
+}
+
+impl ServiceConfig {
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+
+    pub fn with_finished_ttl(mut self, finished_ttl: Duration) -> Self {
+        self.finished_ttl = finished_ttl;
+        self
+    }
+
+    pub fn with_max_finished(mut self, max_finished: usize) -> Self {
+        self.max_finished = max_finished;
+        self
+    }
+
+    pub fn with_max_indexes(mut self, max_indexes: usize) -> Self {
+        self.max_indexes = max_indexes;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done(Vec<u8>),
+    Failed(String),
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done(_) | JobStatus::Failed(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServiceError {
+    UnknownIndex,
+    // the index is too large, or not padded as by `prove_circuit`
+    InvalidIndex,
+    IncompatibleWitness,
+    QueueFull,
+}
+
+impl Display for ServiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceError::UnknownIndex => write!(f, "no index is registered under this id"),
+            ServiceError::InvalidIndex => write!(f, "the index cannot be proved"),
+            ServiceError::IncompatibleWitness => write!(f, "the witness is not for this index"),
+            ServiceError::QueueFull => write!(f, "too many jobs are queued"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+struct Job {
+    id: String,
+    index: Arc<CompressedIndex>,
+    witness: Witness,
+}
+
+// What the workers take from the queue.
+enum Task {
+    Prove(Job),
+    Setup(Arc<IndexRegistry>, CircuitIndex),
+}
+
+// 128 bits from the randomness of the system, in hex.
+fn job_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("the system has no randomness");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Default)]
+struct Counters {
+    submitted: AtomicU64,
+    proved: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[derive(Default)]
+struct Jobs {
+    statuses: FastHashMap<String, JobStatus>,
+    // the finished jobs that are not taken yet, from the oldest
+    finished: VecDeque<(Instant, String)>,
+}
+
+impl Jobs {
+    fn remove(&mut self, id: &str) -> Option<JobStatus> {
+        self.finished.retain(|(_, finished)| finished != id);
+        self.statuses.remove(id)
+    }
+}
+
+struct Shared {
+    jobs: Mutex<Jobs>,
+    updated: Condvar,
+    counters: Counters,
+    finished_ttl: Duration,
+    max_finished: usize,
+}
+
+impl Shared {
+    fn set_status(&self, id: &str, status: JobStatus) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if status.is_finished() {
+            jobs.finished.push_back((Instant::now(), id.to_string()));
+        }
+        jobs.statuses.insert(id.to_string(), status);
+        // forgets the proofs that are too old or too many
+        while let Some((finished_at, id)) = jobs.finished.front().cloned() {
+            if finished_at.elapsed() <= self.finished_ttl
+                && jobs.finished.len() <= self.max_finished
+            {
+                break;
+            }
+            jobs.finished.pop_front();
+            jobs.statuses.remove(&id);
+        }
+        drop(jobs);
+        self.updated.notify_all();
+    }
+}
+
+// The indexes kept in memory, with the order they were added in.
+#[derive(Default)]
+struct Indexes {
+    map: FastHashMap<String, Arc<CompressedIndex>>,
+    order: VecDeque<String>,
+}
+
+pub struct ProverService {
+    indexes: RwLock<Indexes>,
+    max_indexes: usize,
+    registry: Option<Arc<IndexRegistry>>,
+    shared: Arc<Shared>,
+    sender: SyncSender<Task>,
+}
+
+fn prove(index: &CompressedIndex, witness: Witness) -> Result<Vec<u8>, String> {
+    let proof = PlonkProof::prove_compressed(index, &witness).map_err(|err| err.to_string())?;
+    bincode::serialize(&proof).map_err(|err| err.to_string())
+}
+
+fn run_job(shared: &Shared, job: Job) {
+    shared.set_status(&job.id, JobStatus::Running);
+    let result = catch_unwind(AssertUnwindSafe(|| prove(&job.index, job.witness)))
+        .unwrap_or_else(|_| Err("the prover panicked".to_string()));
+    let status = match result {
+        Ok(proof) => {
+            shared.counters.proved.fetch_add(1, Ordering::Relaxed);
+            JobStatus::Done(proof)
+        }
+        Err(err) => {
+            shared.counters.failed.fetch_add(1, Ordering::Relaxed);
+            JobStatus::Failed(err)
+        }
+    };
+    shared.set_status(&job.id, status);
+}
+
+fn run_worker(shared: &Shared, receiver: &Mutex<Receiver<Task>>) {
+    loop {
+        // the lock is only held while waiting, so that the workers take the tasks in turn
+        let Ok(task) = receiver.lock().unwrap().recv() else {
+            return;
+        };
+        match task {
+            Task::Prove(job) => run_job(shared, job),
+            Task::Setup(registry, index) => {
+                if catch_unwind(AssertUnwindSafe(|| registry.get_or_insert(index))).is_err() {
+                    tracing::warn!("the setup of a registered index panicked");
+                }
+            }
+        }
+    }
+}
+
+impl ProverService {
+    // The workers stop once the service is dropped and the queue is drained.
+    pub fn new(config: ServiceConfig) -> Self {
+        assert!(config.workers > 0 && config.max_indexes > 0);
+        let (sender, receiver) = sync_channel(config.max_queued);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let shared = Arc::new(Shared {
+            jobs: Mutex::new(Jobs::default()),
+            updated: Condvar::new(),
+            counters: Counters::default(),
+            finished_ttl: config.finished_ttl,
+            max_finished: config.max_finished,
+        });
+        for _ in 0..config.workers {
+            let shared = shared.clone();
+            let receiver = receiver.clone();
+            thread::spawn(move || run_worker(&shared, &receiver));
+        }
+
+        Self {
+            indexes: RwLock::new(Indexes::default()),
+            max_indexes: config.max_indexes,
+            registry: None,
+            shared,
+            sender,
+        }
+    }
+
+    pub fn with_registry(mut self, registry: IndexRegistry) -> Self {
+        self.registry = Some(Arc::new(registry));
+        self
+    }
+
+    // Registers a padded index, as written by `circle-plonk setup`, and returns its id, with its
+    // setup for the registry queued for a worker. Registering the same index again returns the
+    // same id.
+    pub fn register_index(&self, index: CircuitIndex) -> Result<String, ServiceError> {
+        if index.check_size().is_err() || !index.num_rows.is_power_of_two() {
+            return Err(ServiceError::InvalidIndex);
+        }
+        let id = fingerprint_id(&index.fingerprint());
+        if self.indexes.read().unwrap().map.contains_key(&id) {
+            return Ok(id);
+        }
+        let compressed = Arc::new(index.compress());
+        if let Some(registry) = &self.registry {
+            if registry.get(&id).is_none() {
+                self.sender
+                    .try_send(Task::Setup(registry.clone(), index))
+                    .map_err(|_| ServiceError::QueueFull)?;
+            }
+        }
+        self.insert_index(&id, compressed);
+        Ok(id)
+    }
+
+    fn insert_index(&self, id: &str, index: Arc<CompressedIndex>) {
+        let mut indexes = self.indexes.write().unwrap();
+        if indexes.map.insert(id.to_string(), index).is_none() {
+            indexes.order.push_back(id.to_string());
+        }
+        while indexes.order.len() > self.max_indexes {
+            let oldest = indexes.order.pop_front().unwrap();
+            indexes.map.remove(&oldest);
+        }
+    }
+
+    // The index registered under `id`, from the registry if the service does not have it yet.
+    fn index(&self, id: &str) -> Option<Arc<CompressedIndex>> {
+        if let Some(index) = self.indexes.read().unwrap().map.get(id) {
+            return Some(index.clone());
+        }
+        let entry = self.registry.as_ref()?.get(id)?;
+        let index = Arc::new(entry.index);
+        self.insert_index(id, index.clone());
+        Some(index)
+    }
+
+    pub fn has_index(&self, id: &str) -> bool {
+        self.index(id).is_some()
+    }
+
+    // Queues a job and returns its token.
+    pub fn submit(&self, index_id: &str, witness: Witness) -> Result<String, ServiceError> {
+        let index = self.index(index_id).ok_or(ServiceError::UnknownIndex)?;
+        if !index.is_compatible(&witness) {
+            return Err(ServiceError::IncompatibleWitness);
+        }
+
+        let id = job_token();
+        self.shared.set_status(&id, JobStatus::Queued);
+        let job = Job {
+            id: id.clone(),
+            index,
+            witness,
+        };
+        match self.sender.try_send(Task::Prove(job)) {
+            Ok(()) => {
+                self.shared
+                    .counters
+                    .submitted
+                    .fetch_add(1, Ordering::Relaxed);
+                Ok(id)
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.shared.jobs.lock().unwrap().remove(&id);
+                Err(ServiceError::QueueFull)
+            }
+        }
+    }
+
+    // The status of a job, without the proof of a finished one.
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        let jobs = self.shared.jobs.lock().unwrap();
+        jobs.statuses.get(id).map(|status| match status {
+            JobStatus::Done(_) => JobStatus::Done(vec![]),
+            status => status.clone(),
+        })
+    }
+
+    // Removes a finished job and returns its status, with the proof if it succeeded.
+    pub fn take(&self, id: &str) -> Option<JobStatus> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        if !jobs.statuses.get(id)?.is_finished() {
+            return None;
+        }
+        jobs.remove(id)
+    }
+
+    // Blocks until the job is finished, and then takes it.
+    pub fn wait(&self, id: &str) -> Option<JobStatus> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        loop {
+            if !jobs.statuses.get(id)?.is_finished() {
+                jobs = self.shared.updated.wait(jobs).unwrap();
+                continue;
+            }
+            return jobs.remove(id);
+        }
+    }
+
+    // The counters of the jobs and of the prover phases, in the Prometheus text format.
+    pub fn metrics(&self) -> String {
+        let counters = &self.shared.counters;
+        let queued = {
+            let jobs = self.shared.jobs.lock().unwrap();
+            jobs.statuses
+                .values()
+                .filter(|s| **s == JobStatus::Queued)
+                .count()
+        };
+
+        let mut out = String::new();
+        for (name, kind, value) in [
+            (
+                "circle_plonk_jobs_submitted_total",
+                "counter",
+                counters.submitted.load(Ordering::Relaxed),
+            ),
+            (
+                "circle_plonk_jobs_proved_total",
+                "counter",
+                counters.proved.load(Ordering::Relaxed),
+            ),
+            (
+                "circle_plonk_jobs_failed_total",
+                "counter",
+                counters.failed.load(Ordering::Relaxed),
+            ),
+            ("circle_plonk_jobs_queued", "gauge", queued as u64),
+        ] {
+            writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+            writeln!(out, "{} {}", name, value).unwrap();
+        }
+        out.push_str(&Metrics::global().to_prometheus());
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::{Circuit, Mode};
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::registry::IndexRegistry;
+    use crate::proof_system::{verify_plonk, PlonkProof};
+    use crate::service::{JobStatus, ProverService, ServiceConfig, ServiceError};
+    use ark_std::rand::SeedableRng;
+    use std::time::Duration;
+    use stwo_prover::core::backend::simd::m31::LOG_N_LANES;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_prover_service() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (index, witness) = circuit.split();
+
+        let service = ProverService::new(ServiceConfig::default().with_workers(2));
+        let id = service.register_index(index.clone()).unwrap();
+        assert_eq!(service.register_index(index).unwrap(), id);
+        assert!(service.has_index(&id));
+        assert_eq!(
+            service.submit("unknown", witness.clone()),
+            Err(ServiceError::UnknownIndex)
+        );
+        let mut wrong = witness.clone();
+        wrong.input_maps.pop();
+        assert_eq!(
+            service.submit(&id, wrong),
+            Err(ServiceError::IncompatibleWitness)
+        );
+
+        let job = service.submit(&id, witness.clone()).unwrap();
+        assert_eq!(job.len(), 32);
+        let Some(JobStatus::Done(proof)) = service.wait(&job) else {
+            panic!("the job failed");
+        };
+        assert_eq!(service.status(&job), None);
+        let proof: PlonkProof = bincode::deserialize(&proof).unwrap();
+        verify_plonk(proof.log_n_rows, &proof.inputs, proof.proof).unwrap();
+
+        // a witness that does not satisfy the constraints
+        let mut unsatisfied = witness;
+        unsatisfied.output_wires[0] = M31::from(1);
+        let other = service.submit(&id, unsatisfied).unwrap();
+        assert_ne!(other, job);
+        assert!(matches!(service.wait(&other), Some(JobStatus::Failed(_))));
+
+        let metrics = service.metrics();
+        assert!(metrics.contains("circle_plonk_jobs_submitted_total 2\n"));
+        assert!(metrics.contains("circle_plonk_jobs_proved_total 1\n"));
+        assert!(metrics.contains("circle_plonk_jobs_failed_total 1\n"));
+    }
+
+    #[test]
+    fn test_service_registry() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (index, witness) = circuit.split();
+
+        let dir = std::env::temp_dir().join(format!(
+            "circle-plonk-service-registry-{}",
+            std::process::id()
+        ));
+        let service =
+            ProverService::new(ServiceConfig::default()).with_registry(IndexRegistry::open(&dir));
+        let id = service.register_index(index).unwrap();
+        drop(service);
+
+        // the setup for the registry runs on a worker
+        while IndexRegistry::open(&dir).get(&id).is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // a restarted service proves for the index without it being registered again
+        let service =
+            ProverService::new(ServiceConfig::default()).with_registry(IndexRegistry::open(&dir));
+        assert!(service.has_index(&id));
+        let job = service.submit(&id, witness).unwrap();
+        assert!(matches!(service.wait(&job), Some(JobStatus::Done(_))));
+        assert!(!ProverService::new(ServiceConfig::default()).has_index(&id));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_service_limits() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(2);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        circuit.pad_minimal(LOG_N_LANES);
+        let (index, witness) = circuit.split();
+
+        let config = ServiceConfig::default()
+            .with_max_finished(1)
+            .with_max_indexes(1);
+        let service = ProverService::new(config);
+        let id = service.register_index(index.clone()).unwrap();
+        let first = service.submit(&id, witness.clone()).unwrap();
+        let second = service.submit(&id, witness.clone()).unwrap();
+
+        // the only worker finishes the first job before the second, which then evicts it
+        while !service.status(&second).is_some_and(|s| s.is_finished()) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(service.status(&first), None);
+        assert!(matches!(service.take(&second), Some(JobStatus::Done(_))));
+
+        // a larger index evicts the first one
+        let mut larger = Circuit::from_parts(index, witness);
+        larger.pad_to_log_size(larger.num_rows.ilog2() + 1);
+        let larger_id = service.register_index(larger.index()).unwrap();
+        assert!(service.has_index(&larger_id));
+        assert!(!service.has_index(&id));
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::OnceLock;
+
+// The vector extensions of the CPU, detected at runtime.
+//
+// The stwo backend is only as fast as the extensions it is compiled for, which takes building
+// with `RUSTFLAGS="-C target-cpu=native"`. The hot loops of this crate are instead compiled once
+// per level with `multiversion!` and run the best one the CPU has, so that a generic binary still
+// converts witnesses between arkworks and stwo at full speed, in both directions, and
+// `warn_if_not_native` points out the rest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    Scalar,
+    Neon,
+    Avx2,
+    Avx512,
+}
+
+impl SimdLevel {
+    pub fn detect() -> Self {
+        static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+        *LEVEL.get_or_init(|| {
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+                    return SimdLevel::Avx512;
+                }
+                if is_x86_feature_detected!("avx2") {
+                    return SimdLevel::Avx2;
+                }
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                if std::arch::is_aarch64_feature_detected!("neon") {
+                    return SimdLevel::Neon;
+                }
+            }
+            SimdLevel::Scalar
+        })
+    }
+
+    // The level that the binary, and so the stwo backend, is compiled for.
+    pub fn compiled() -> Self {
+        if cfg!(all(target_feature = "avx512f", target_feature = "avx512bw")) {
+            SimdLevel::Avx512
+        } else if cfg!(target_feature = "avx2") {
+            SimdLevel::Avx2
+        } else if cfg!(all(target_arch = "aarch64", target_feature = "neon")) {
+            SimdLevel::Neon
+        } else {
+            SimdLevel::Scalar
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SimdLevel::Scalar => "scalar",
+            SimdLevel::Neon => "neon",
+            SimdLevel::Avx2 => "avx2",
+            SimdLevel::Avx512 => "avx512",
+        }
+    }
+}
+
+// Warns when the CPU has wider vectors than the binary is compiled for, in which case the prover
+// runs several times slower than it could.
+pub fn warn_if_not_native() {
+    let (detected, compiled) = (SimdLevel::detect(), SimdLevel::compiled());
+    if detected > compiled {
+        tracing::warn!(
+            "the CPU supports {} but the prover is compiled for {}, build with \
+             RUSTFLAGS=\"-C target-cpu=native\" for full speed",
+            detected.as_str(),
+            compiled.as_str()
+        );
+    }
+}
+
+// Defines a function whose body is compiled for AVX-512 and AVX2 besides the baseline, and which
+// runs the best version that `SimdLevel::detect` allows. NEON is part of the aarch64 baseline, so
+// it needs no version of its own.
+macro_rules! multiversion {
+    (
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+    ) => {
+        $(#[$attr])*
+        $vis fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[inline(always)]
+            fn generic($($arg: $ty),*) $(-> $ret)? $body
+
+            #[cfg(target_arch = "x86_64")]
+            #[target_feature(enable = "avx512f,avx512bw")]
+            unsafe fn avx512($($arg: $ty),*) $(-> $ret)? {
+                generic($($arg),*)
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            #[target_feature(enable = "avx2")]
+            unsafe fn avx2($($arg: $ty),*) $(-> $ret)? {
+                generic($($arg),*)
+            }
+
+            match $crate::simd::SimdLevel::detect() {
+                // the CPU supports the extensions that the versions are compiled for
+                #[cfg(target_arch = "x86_64")]
+                $crate::simd::SimdLevel::Avx512 => unsafe { avx512($($arg),*) },
+                #[cfg(target_arch = "x86_64")]
+                $crate::simd::SimdLevel::Avx2 => unsafe { avx2($($arg),*) },
+                _ => generic($($arg),*),
+            }
+        }
+    };
+}
+
+pub(crate) use multiversion;
+
+#[cfg(test)]
+mod test {
+    use crate::simd::SimdLevel;
+
+    #[test]
+    fn test_simd_level() {
+        // whatever the binary is compiled for, the CPU that runs it supports
+        assert!(SimdLevel::detect() >= SimdLevel::compiled());
+        assert_eq!(SimdLevel::detect(), SimdLevel::detect());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::circuit::{Circuit, Mode, RowKind, Wire};
+use crate::field::FM31;
+use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+use ark_ff::{One, Zero};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::emulated_fp::EmulatedFpVar;
+use ark_relations::lc;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, Variable,
+};
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::{Rng, SeedableRng};
+use ark_std::UniformRand;
+use stwo_prover::core::fields::m31::{M31, P};
+
+pub mod mutate;
+
+// Random circuits, R1CS systems and mutations for fuzzing the satisfaction checks, the converter
+// and the prover and verifier, and circuits of a chosen size for benchmarks.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomCircuitConfig {
+    pub num_inputs: usize,
+    pub num_witnesses: usize,
+    pub num_gates: usize,
+    pub num_zero_tests: usize,
+    pub num_exposed: usize,
+}
+
+impl Default for RandomCircuitConfig {
+    fn default() -> Self {
+        Self {
+            num_inputs: 4,
+            num_witnesses: 8,
+            num_gates: 64,
+            num_zero_tests: 4,
+            num_exposed: 2,
+        }
+    }
+}
+
+pub fn random_m31<R: Rng>(prng: &mut R) -> M31 {
+    M31::from(prng.gen_range(0..P))
+}
+
+// A random gate DAG over random inputs and witnesses, with its correct assignment. The constant
+// one is at `Wire(1)`, as in circuits built by the builder or the R1CS converter.
+pub fn random_circuit<R: Rng>(prng: &mut R, config: &RandomCircuitConfig) -> Circuit {
+    let mut circuit = Circuit::new();
+    circuit.new_input(M31::one());
+
+    let mut wires = vec![Wire(1)];
+    for _ in 0..config.num_inputs {
+        wires.push(circuit.new_input(random_m31(prng)).wire());
+    }
+    for _ in 0..config.num_witnesses {
+        wires.push(circuit.new_witness(random_m31(prng)));
+    }
+
+    let mut gates = vec![];
+    for _ in 0..config.num_gates {
+        let a = wires[prng.gen_range(0..wires.len())];
+        let b = wires[prng.gen_range(0..wires.len())];
+        let wire = match prng.gen_range(0..4) {
+            0 => circuit.add(a, b),
+            1 => circuit.mul(a, b),
+            2 => circuit.mul_by_constant(a, random_m31(prng)),
+            _ => circuit.new_constant(random_m31(prng)),
+        };
+        if wire != Wire::ZERO {
+            wires.push(wire);
+            gates.push(wire);
+        }
+    }
+
+    for _ in 0..config.num_zero_tests {
+        let a = wires[prng.gen_range(0..wires.len())];
+        let a_neg = circuit.neg(a);
+        let zero = circuit.add(a, a_neg);
+        circuit.zero_test(zero);
+    }
+
+    for _ in 0..config.num_exposed.min(gates.len()) {
+        circuit.expose(gates[prng.gen_range(0..gates.len())]);
+    }
+
+    circuit
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomR1csConfig {
+    pub num_inputs: usize,
+    pub num_witnesses: usize,
+    pub num_constraints: usize,
+    // the most terms in each side of a product
+    pub max_terms: usize,
+}
+
+impl Default for RandomR1csConfig {
+    fn default() -> Self {
+        Self {
+            num_inputs: 2,
+            num_witnesses: 4,
+            num_constraints: 16,
+            max_terms: 3,
+        }
+    }
+}
+
+// A random R1CS system with a satisfying assignment: every constraint multiplies two random linear
+// combinations of the earlier variables into a fresh witness. The values are drawn from `seed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomR1cs {
+    pub config: RandomR1csConfig,
+    pub seed: u64,
+}
+
+fn random_lc<R: Rng>(
+    prng: &mut R,
+    variables: &[(Variable, FM31)],
+    max_terms: usize,
+) -> (LinearCombination<FM31>, FM31) {
+    let mut lc = lc!();
+    let mut value = FM31::zero();
+    for _ in 0..prng.gen_range(1..=max_terms.max(1)) {
+        let (variable, v) = variables[prng.gen_range(0..variables.len())];
+        let coef = FM31::rand(prng);
+        lc = lc + (coef, variable);
+        value += coef * v;
+    }
+    (lc, value)
+}
+
+impl ConstraintSynthesizer<FM31> for RandomR1cs {
+    fn generate_constraints(
+        self,
+        cs: ConstraintSystemRef<FM31>,
+    ) -> ark_relations::r1cs::Result<()> {
+        let mut prng = StdRng::seed_from_u64(self.seed);
+
+        let mut variables = vec![(Variable::One, FM31::one())];
+        for _ in 0..self.config.num_inputs {
+            let v = FM31::rand(&mut prng);
+            variables.push((cs.new_input_variable(|| Ok(v))?, v));
+        }
+        for _ in 0..self.config.num_witnesses {
+            let v = FM31::rand(&mut prng);
+            variables.push((cs.new_witness_variable(|| Ok(v))?, v));
+        }
+
+        for _ in 0..self.config.num_constraints {
+            let (a, a_value) = random_lc(&mut prng, &variables, self.config.max_terms);
+            let (b, b_value) = random_lc(&mut prng, &variables, self.config.max_terms);
+            let value = a_value * b_value;
+            let z = cs.new_witness_variable(|| Ok(value))?;
+            cs.enforce_constraint(a, b, lc!() + z)?;
+            variables.push((z, value));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BenchCircuitConfig {
+    pub num_emulated_muls: usize,
+    pub num_native_muls: usize,
+    pub num_native_adds: usize,
+}
+
+impl BenchCircuitConfig {
+    // Sets the number of native operations so that the converted circuit has `target_rows` rows
+    // or one less, keeping the ratio of multiplications to additions (half of each if both are
+    // zero). Every native operation takes two rows, one for its witness and one for its gate.
+    pub fn with_target_rows(self, target_rows: usize) -> Self {
+        let base = BenchCircuit {
+            config: Self {
+                num_native_muls: 0,
+                num_native_adds: 0,
+                ..self
+            },
+            seed: 0,
+        };
+        let base_rows = generate_circuit(base, Mode::INDEX)
+            .unwrap()
+            .circuit
+            .num_rows;
+        assert!(
+            base_rows <= target_rows,
+            "the emulated multiplications already take {} rows",
+            base_rows
+        );
+
+        let num_native = (target_rows - base_rows) / 2;
+        let (muls, adds) = (self.num_native_muls, self.num_native_adds);
+        let num_native_muls = if muls + adds == 0 {
+            num_native / 2
+        } else {
+            (num_native as u128 * muls as u128 / (muls + adds) as u128) as usize
+        };
+        Self {
+            num_native_muls,
+            num_native_adds: num_native - num_native_muls,
+            ..self
+        }
+    }
+}
+
+// A circuit of a given shape for benchmarks: a chain of multiplications of BN254 elements in
+// emulated arithmetic, as in `TestCircuit`, then a chain of native multiplications and additions
+// over M31, each with a fresh witness. The values are drawn from `seed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BenchCircuit {
+    pub config: BenchCircuitConfig,
+    pub seed: u64,
+}
+
+type Bn254Var = EmulatedFpVar<ark_bn254::Fr, FM31>;
+
+impl ConstraintSynthesizer<FM31> for BenchCircuit {
+    fn generate_constraints(
+        self,
+        cs: ConstraintSystemRef<FM31>,
+    ) -> ark_relations::r1cs::Result<()> {
+        let mut prng = StdRng::seed_from_u64(self.seed);
+
+        if self.config.num_emulated_muls > 0 {
+            let a = ark_bn254::Fr::rand(&mut prng);
+            let mut acc = Bn254Var::new_input(cs.clone(), || Ok(a))?;
+            let mut acc_value = a;
+            for _ in 0..self.config.num_emulated_muls {
+                let b = ark_bn254::Fr::rand(&mut prng);
+                acc = acc * Bn254Var::new_witness(cs.clone(), || Ok(b))?;
+                acc_value *= b;
+            }
+            let c = Bn254Var::new_witness(cs.clone(), || Ok(acc_value))?;
+            acc.enforce_equal(&c)?;
+        }
+
+        let x = FM31::rand(&mut prng);
+        let mut acc = cs.new_input_variable(|| Ok(x))?;
+        let mut acc_value = x;
+        let num_native = self.config.num_native_muls + self.config.num_native_adds;
+        for i in 0..num_native {
+            let w = FM31::rand(&mut prng);
+            let w_var = cs.new_witness_variable(|| Ok(w))?;
+            if i < self.config.num_native_muls {
+                acc_value *= w;
+                let z = cs.new_witness_variable(|| Ok(acc_value))?;
+                cs.enforce_constraint(lc!() + acc, lc!() + w_var, lc!() + z)?;
+                acc = z;
+            } else {
+                acc_value += w;
+                let z = cs.new_witness_variable(|| Ok(acc_value))?;
+                cs.enforce_constraint(lc!() + acc + w_var, lc!() + Variable::One, lc!() + z)?;
+                acc = z;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Changes that every satisfied circuit is guaranteed to fail, either in the row constraints or,
+// with overwhelming probability, in the logup sum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    // Adds a nonzero delta to the output of a gate row.
+    GateOutput { row: usize, delta: M31 },
+    // Increases the multiplicity of a row.
+    Mult { row: usize },
+    // Adds a nonzero delta to the claimed value of an entry of `input_maps`.
+    InputValue { index: usize, delta: M31 },
+}
+
+pub fn random_mutation<R: Rng>(prng: &mut R, circuit: &Circuit) -> Mutation {
+    let gates = circuit
+        .row_kinds()
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, kind)| kind == RowKind::Gate)
+        .map(|(row, _)| row)
+        .collect::<Vec<_>>();
+
+    let delta = M31::from(prng.gen_range(1..P));
+    loop {
+        match prng.gen_range(0..3) {
+            0 if !gates.is_empty() => {
+                return Mutation::GateOutput {
+                    row: gates[prng.gen_range(0..gates.len())],
+                    delta,
+                }
+            }
+            1 => {
+                return Mutation::Mult {
+                    row: prng.gen_range(0..circuit.num_rows),
+                }
+            }
+            2 if !circuit.input_maps.is_empty() => {
+                return Mutation::InputValue {
+                    index: prng.gen_range(0..circuit.input_maps.len()),
+                    delta,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn apply_mutation(circuit: &mut Circuit, mutation: Mutation) {
+    match mutation {
+        Mutation::GateOutput { row, delta } => circuit.output_wires[row] += delta,
+        Mutation::Mult { row } => circuit.mult[row] += 1,
+        Mutation::InputValue { index, delta } => {
+            let value = circuit.input_maps[index].1 + delta;
+            circuit.input_maps.set_value(index, value);
+        }
+    }
+}
+
+// Both checks a valid circuit passes: the row constraints and the logup sum against its own
+// statement.
+pub fn is_satisfied(circuit: &Circuit) -> bool {
+    circuit.is_constraint_satisfied() && circuit.is_logup_satisfied(&circuit.input_maps)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::proof_system::{prove_circuit, verify_plonk};
+    use crate::testing::{
+        apply_mutation, is_satisfied, random_circuit, random_mutation, BenchCircuit,
+        BenchCircuitConfig, RandomCircuitConfig, RandomR1cs, RandomR1csConfig,
+    };
+    use ark_ff::One;
+    use ark_std::rand::{Rng, SeedableRng};
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_random_circuits() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let config = RandomCircuitConfig::default();
+
+        for _ in 0..20 {
+            let circuit = random_circuit(&mut prng, &config);
+            assert!(is_satisfied(&circuit));
+
+            let index = circuit.index();
+            let (inputs, witnesses) = circuit.assignment();
+            assert_eq!(
+                index.evaluate(&inputs, &witnesses).unwrap(),
+                circuit.witness()
+            );
+
+            for _ in 0..5 {
+                let mut mutated = random_circuit(&mut prng, &config);
+                let mutation = random_mutation(&mut prng, &mutated);
+                apply_mutation(&mut mutated, mutation);
+                assert!(!is_satisfied(&mutated), "{:?}", mutation);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_circuit_proof() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = random_circuit(&mut prng, &RandomCircuitConfig::default());
+
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let log_n_rows = circuit.num_rows.ilog2();
+        verify_plonk(log_n_rows, &circuit.input_maps, proof).unwrap();
+
+        // a statement with a different input or output
+        let mut inputs = circuit.input_maps.clone();
+        let index = prng.gen_range(0..inputs.len());
+        inputs.set_value(index, inputs[index].1 + M31::one());
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        assert!(verify_plonk(log_n_rows, &inputs, proof).is_err());
+    }
+
+    #[test]
+    fn test_random_r1cs() {
+        let config = RandomR1csConfig::default();
+
+        for seed in 0..10 {
+            let circuit = generate_circuit(RandomR1cs { config, seed }, Mode::PROVE)
+                .unwrap()
+                .circuit;
+            assert!(is_satisfied(&circuit));
+            assert_eq!(circuit.validate(), Ok(()));
+
+            let index = generate_circuit(RandomR1cs { config, seed }, Mode::INDEX)
+                .unwrap()
+                .circuit;
+            assert_eq!(index.index(), circuit.index());
+        }
+    }
+
+    #[test]
+    fn test_bench_circuit() {
+        let config = BenchCircuitConfig {
+            num_native_muls: 1,
+            num_native_adds: 3,
+            ..Default::default()
+        }
+        .with_target_rows(1 << 10);
+        assert!(config.num_native_muls > 0);
+        assert!(config.num_native_muls < config.num_native_adds);
+
+        let circuit = generate_circuit(BenchCircuit { config, seed: 0 }, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(circuit.num_rows <= 1 << 10 && circuit.num_rows + 1 >= 1 << 10);
+        assert!(circuit.is_constraint_satisfied());
+
+        let index = generate_circuit(BenchCircuit { config, seed: 1 }, Mode::INDEX)
+            .unwrap()
+            .circuit;
+        assert_eq!(index.index(), circuit.index());
+
+        let config = BenchCircuitConfig {
+            num_emulated_muls: 1,
+            ..Default::default()
+        }
+        .with_target_rows(1 << 15);
+        let circuit = generate_circuit(BenchCircuit { config, seed: 0 }, Mode::PROVE)
+            .unwrap()
+            .circuit;
+        assert!(circuit.num_rows <= 1 << 15 && circuit.num_rows + 1 >= 1 << 15);
+        assert!(circuit.is_constraint_satisfied());
+    }
+}
@@ -0,0 +1,182 @@
+use ark_std::rand::Rng;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+// Mutations of the bytes of an encoded proof, which a sound verifier rejects one and all: a
+// mutation that is accepted is a part of the proof that the verifier does not check. The harness
+// is independent of the encoding and of the verifier, which is given as a closure from the bytes
+// to whether they verify.
+//
+// The test below checks a sample of the mutations of a proof on every run, and all of them with
+//
+//     CIRCLE_PLONK_SOAK=1 cargo test --release test_proof_mutations
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofMutation {
+    FlipBit { offset: usize, bit: u32 },
+    // keeps the first `len` bytes
+    Truncate { len: usize },
+    // removes `len` bytes from `offset` on
+    Remove { offset: usize, len: usize },
+}
+
+impl ProofMutation {
+    pub fn apply(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        match *self {
+            ProofMutation::FlipBit { offset, bit } => bytes[offset] ^= 1 << bit,
+            ProofMutation::Truncate { len } => bytes.truncate(len),
+            ProofMutation::Remove { offset, len } => {
+                bytes.drain(offset..(offset + len).min(bytes.len()));
+            }
+        }
+        bytes
+    }
+}
+
+// Every bit of every `stride`-th byte, the truncations to every `stride`-th length and the
+// removals of `stride` bytes from every `stride`-th byte on. A stride of one gives every flip and
+// every truncation of the proof.
+pub fn systematic_mutations(len: usize, stride: usize) -> impl Iterator<Item = ProofMutation> {
+    assert!(stride > 0);
+    let flips = (0..len)
+        .step_by(stride)
+        .flat_map(|offset| (0..8).map(move |bit| ProofMutation::FlipBit { offset, bit }));
+    let truncations = (0..len)
+        .step_by(stride)
+        .map(|len| ProofMutation::Truncate { len });
+    let removals = (0..len)
+        .step_by(stride)
+        .map(move |offset| ProofMutation::Remove {
+            offset,
+            len: stride,
+        });
+    flips.chain(truncations).chain(removals)
+}
+
+pub fn random_proof_mutation<R: Rng>(prng: &mut R, len: usize) -> ProofMutation {
+    assert!(len > 0);
+    match prng.gen_range(0..3) {
+        0 => ProofMutation::FlipBit {
+            offset: prng.gen_range(0..len),
+            bit: prng.gen_range(0..8),
+        },
+        1 => ProofMutation::Truncate {
+            len: prng.gen_range(0..len),
+        },
+        _ => {
+            let offset = prng.gen_range(0..len);
+            ProofMutation::Remove {
+                offset,
+                len: prng.gen_range(1..=len - offset),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MutationReport {
+    pub rejected: usize,
+    // the mutations for which the verifier panics instead of returning an error, which a prover
+    // can send to bring down whoever verifies its proofs
+    pub panicked: Vec<ProofMutation>,
+    // the mutations that the verifier accepts, each a check that it skips
+    pub accepted: Vec<ProofMutation>,
+}
+
+impl MutationReport {
+    // Whether the verifier returned a rejection for every mutation, without panicking.
+    pub fn all_rejected(&self) -> bool {
+        self.accepted.is_empty() && self.panicked.is_empty()
+    }
+}
+
+// Runs the verifier on every mutation of `bytes`, which must verify as they are. Panics of the
+// verifier are not rejections, and are listed apart. A verifier that decodes the bytes must bound
+// what their lengths can claim, as an allocation that fails aborts rather than panics.
+pub fn run_mutations<F>(
+    bytes: &[u8],
+    mutations: impl IntoIterator<Item = ProofMutation>,
+    mut verify: F,
+) -> MutationReport
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    assert!(
+        verify(bytes),
+        "the proof does not verify before any mutation"
+    );
+
+    let mut report = MutationReport::default();
+    for mutation in mutations {
+        let mutated = mutation.apply(bytes);
+        match catch_unwind(AssertUnwindSafe(|| verify(&mutated))) {
+            Ok(false) => report.rejected += 1,
+            Ok(true) => report.accepted.push(mutation),
+            Err(_) => report.panicked.push(mutation),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Circuit;
+    use crate::proof_system::{prove_circuit, verify_plonk};
+    use crate::testing::mutate::{
+        random_proof_mutation, run_mutations, systematic_mutations, ProofMutation,
+    };
+    use ark_ff::One;
+    use ark_std::rand::SeedableRng;
+    use bincode::Options;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::prover::StarkProof;
+    use stwo_prover::core::vcs::bws_sha256_merkle::BWSSha256MerkleHasher;
+
+    #[test]
+    fn test_proof_mutations() {
+        assert_eq!(
+            ProofMutation::Remove { offset: 1, len: 2 }.apply(&[1, 2, 3, 4]),
+            vec![1, 4]
+        );
+
+        let mut circuit = Circuit::new();
+        circuit.new_input(M31::one());
+        let a = circuit.new_input(M31::from(3)).wire();
+        let b = circuit.new_witness(M31::from(5));
+        let c = circuit.mul(a, b);
+        circuit.expose(c);
+        let (_, proof) = prove_circuit(&mut circuit).unwrap();
+        let bytes = bincode::serialize(&proof).unwrap();
+
+        // the statement is the verifier's own, and only the proof is mutated, decoded as by
+        // `bincode::deserialize` but with the lengths bounded by the size of the input
+        let log_n_rows = circuit.num_rows.ilog2();
+        let verify = |bytes: &[u8]| {
+            bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .with_limit(bytes.len() as u64)
+                .deserialize::<StarkProof<BWSSha256MerkleHasher>>(bytes)
+                .map_or(false, |proof| {
+                    verify_plonk(log_n_rows, &circuit.input_maps, proof).is_ok()
+                })
+        };
+
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let stride = if std::env::var("CIRCLE_PLONK_SOAK").is_ok() {
+            1
+        } else {
+            61
+        };
+        let mutations = systematic_mutations(bytes.len(), stride)
+            .chain((0..100).map(|_| random_proof_mutation(&mut prng, bytes.len())))
+            .collect::<Vec<_>>();
+        let report = run_mutations(&bytes, mutations, verify);
+        assert!(
+            report.panicked.is_empty(),
+            "panicked: {:?}",
+            report.panicked
+        );
+        assert!(report.all_rejected(), "accepted: {:?}", report.accepted);
+    }
+}
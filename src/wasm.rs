@@ -0,0 +1,69 @@
+use crate::circuit::serialization::m31_vec;
+use crate::proof_system::{verify_with_key, PlonkProof, VerificationKey};
+use std::error::Error;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+// Checks a proof from the same files as `circle-plonk verify`: the verification key and the proof
+// as written by `setup` and `prove`, and the values of the inputs as a JSON array.
+pub fn verify_bytes(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    public_json: &str,
+) -> Result<(), Box<dyn Error>> {
+    let key: VerificationKey = bincode::deserialize(vk_bytes)?;
+    let proof: PlonkProof = bincode::deserialize(proof_bytes)?;
+    let values = m31_vec::deserialize(&mut serde_json::Deserializer::from_str(public_json))?;
+
+    if proof.log_n_rows != key.log_n_rows {
+        return Err(format!(
+            "the proof has 2^{} rows, but the circuit has 2^{}",
+            proof.log_n_rows, key.log_n_rows
+        )
+        .into());
+    }
+    if !proof.is_num_rows_consistent() {
+        return Err("the proof claims more rows than its trace has".into());
+    }
+    verify_with_key(&key, &values, proof.proof)?;
+    Ok(())
+}
+
+// The entry point for JavaScript, for which a malformed key, proof or input is as invalid as a
+// wrong proof.
+#[wasm_bindgen]
+pub fn verify(vk_bytes: &[u8], proof_bytes: &[u8], public_json: &str) -> bool {
+    verify_bytes(vk_bytes, proof_bytes, public_json).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::circuit::Mode;
+    use crate::from_r1cs::r1cs_constraint_processor::generate_circuit;
+    use crate::from_r1cs::TestCircuit;
+    use crate::proof_system::{PlonkProof, VerificationKey};
+    use crate::wasm::{verify, verify_bytes};
+    use ark_std::rand::SeedableRng;
+
+    #[test]
+    fn test_wasm_verify() {
+        let mut prng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        let mut circuit = generate_circuit(TestCircuit::rand(&mut prng), Mode::PROVE)
+            .unwrap()
+            .circuit;
+        let proof = bincode::serialize(&PlonkProof::prove(&mut circuit).unwrap()).unwrap();
+        let vk = bincode::serialize(&VerificationKey::new(&circuit.index())).unwrap();
+        let values = circuit
+            .input_maps
+            .iter()
+            .map(|&(_, v)| v.0.to_string())
+            .collect::<Vec<_>>();
+        let public = format!("[{}]", values.join(","));
+
+        verify_bytes(&vk, &proof, &public).unwrap();
+        assert!(verify(&vk, &proof, &public));
+        assert!(!verify(&vk, &proof, "[]"));
+        assert!(!verify(&vk, &proof, "not json"));
+        assert!(!verify(&vk, &proof[..proof.len() / 2], &public));
+        assert!(!verify(&proof, &vk, &public));
+    }
+}